@@ -1,15 +1,34 @@
 use std::sync::Arc;
 
-use crate::device_manager::{check_scard_service_status, debug_list_hid_devices, debug_list_readers, DeviceManager, DeviceManagerImpl};
-use crate::fido::FidoModuleImpl;
-use crate::hsm::HsmModuleImpl;
-use crate::types::DeviceInfo;
+use crate::device_manager::{check_scard_service_status, debug_list_hid_devices, debug_list_readers, device_id_with, identify_device_with, DeviceManager, DeviceManagerImpl};
+use crate::error::{AppError, DeviceError};
+use crate::fido::{FidoModule, FidoModuleImpl};
+use crate::hsm::{HsmModule, HsmModuleImpl};
+use crate::types::{DeviceCapabilities, DeviceInfo, ScanScope};
 
 #[tauri::command]
 pub fn scan_devices(
     device_manager: tauri::State<'_, Arc<DeviceManagerImpl>>,
-) -> Result<Vec<DeviceInfo>, String> {
-    device_manager.scan_devices().map_err(|e| e.to_string())
+) -> Result<Vec<DeviceInfo>, AppError> {
+    Ok(device_manager.scan_devices()?)
+}
+
+#[tauri::command]
+pub fn set_scan_scope(
+    scope: ScanScope,
+    device_manager: tauri::State<'_, Arc<DeviceManagerImpl>>,
+) -> Result<(), AppError> {
+    device_manager.set_scan_scope(scope);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn set_hsm_reader_patterns(
+    patterns: Vec<String>,
+    device_manager: tauri::State<'_, Arc<DeviceManagerImpl>>,
+) -> Result<(), AppError> {
+    device_manager.set_hsm_reader_patterns(patterns);
+    Ok(())
 }
 
 #[tauri::command]
@@ -18,11 +37,11 @@ pub fn open_device(
     device_manager: tauri::State<'_, Arc<DeviceManagerImpl>>,
     fido: tauri::State<'_, Arc<FidoModuleImpl>>,
     hsm: tauri::State<'_, Arc<HsmModuleImpl>>,
-) -> Result<(), String> {
-    device_manager.open_device(&path).map_err(|e| e.to_string())?;
+) -> Result<(), AppError> {
+    device_manager.open_device(&path)?;
 
     // 根據裝置類型設定對應模組的路徑
-    let devices = device_manager.scan_devices().map_err(|e| e.to_string())?;
+    let devices = device_manager.scan_devices()?;
     if let Some(dev) = devices.iter().find(|d| d.path == path) {
         match dev.device_type {
             crate::types::DeviceType::PicoFido => fido.set_device_path(&path),
@@ -33,9 +52,66 @@ pub fn open_device(
     Ok(())
 }
 
+/// 開啟裝置並由掃描結果取得其資訊；不變更各模組目前使用的裝置
+fn lookup_device(device_manager: &DeviceManagerImpl, path: &str) -> Result<DeviceInfo, AppError> {
+    device_manager.open_device(path)?;
+    let device = device_manager
+        .scan_devices()?
+        .into_iter()
+        .find(|d| d.path == path)
+        .ok_or_else(|| DeviceError::NotFound(path.to_string()))?;
+    Ok(device)
+}
+
+/// 開啟裝置並一次回傳類型、韌體版本與模組能力
+#[tauri::command]
+pub fn identify_device(
+    path: String,
+    device_manager: tauri::State<'_, Arc<DeviceManagerImpl>>,
+    fido: tauri::State<'_, Arc<FidoModuleImpl>>,
+    hsm: tauri::State<'_, Arc<HsmModuleImpl>>,
+) -> Result<DeviceCapabilities, AppError> {
+    let device = lookup_device(&device_manager, &path)?;
+
+    match device.device_type {
+        crate::types::DeviceType::PicoFido => fido.set_device_path(&path),
+        crate::types::DeviceType::PicoHsm => hsm.set_device_path(&path),
+    }
+
+    Ok(identify_device_with(
+        &device,
+        || fido.get_info(),
+        || Ok((hsm.get_device_info()?, hsm.is_initialized()?)),
+    ))
+}
+
+/// 取得不隨插拔變動的裝置識別碼（資產盤點用）；不切換目前使用的裝置
+#[tauri::command]
+pub fn get_device_id(
+    path: String,
+    device_manager: tauri::State<'_, Arc<DeviceManagerImpl>>,
+    fido: tauri::State<'_, Arc<FidoModuleImpl>>,
+    hsm: tauri::State<'_, Arc<HsmModuleImpl>>,
+) -> Result<String, AppError> {
+    let device = lookup_device(&device_manager, &path)?;
+    device_id_with(
+        &device,
+        || fido.for_device(&path).get_info(),
+        || hsm.for_device(&path).get_device_id(),
+    )
+}
+
+#[tauri::command]
+pub fn close_device(
+    path: String,
+    device_manager: tauri::State<'_, Arc<DeviceManagerImpl>>,
+) -> Result<(), AppError> {
+    Ok(device_manager.close_device(&path)?)
+}
+
 /// 診斷用：列出所有 PC/SC 讀卡機及其 ATR（十六進位）+ HID 裝置
 #[tauri::command]
-pub fn list_all_readers() -> Result<Vec<String>, String> {
+pub fn list_all_readers() -> Result<Vec<String>, AppError> {
     let mut results = Vec::new();
 
     // HID 裝置
@@ -60,3 +136,9 @@ pub fn list_all_readers() -> Result<Vec<String>, String> {
 pub fn check_scard_service() -> String {
     check_scard_service_status()
 }
+
+/// 不需連接裝置的自我檢測，確認編解碼邏輯正常
+#[tauri::command]
+pub fn run_self_test() -> Vec<crate::diagnostics::TestResult> {
+    crate::diagnostics::self_test()
+}
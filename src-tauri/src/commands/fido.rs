@@ -1,135 +1,296 @@
 use std::sync::Arc;
+use std::time::Duration;
 
-use crate::fido::types::OathCredentialParams;
+use crate::fido::types::{OathCredentialParams, ResetPreparation, SetMinPinLengthParams, UvMethod};
 use crate::fido::{FidoModule, FidoModuleImpl};
-use crate::types::LedConfig;
+use crate::error::{AppError, FidoError};
+use crate::secret::SecretString;
+use crate::types::{FirmwareStatus, LedConfig, PingResult};
 
 #[tauri::command]
 pub fn fido_get_info(
+    refresh: Option<bool>,
     fido: tauri::State<'_, Arc<FidoModuleImpl>>,
-) -> Result<crate::fido::types::FidoDeviceInfo, String> {
-    fido.get_info().map_err(|e| e.to_string())
+) -> Result<crate::fido::types::FidoDeviceInfo, AppError> {
+    Ok(fido.cached_info(refresh.unwrap_or(false))?)
+}
+
+#[tauri::command]
+pub fn fido_ping(
+    fido: tauri::State<'_, Arc<FidoModuleImpl>>,
+) -> Result<PingResult, AppError> {
+    Ok(fido.ping()?)
+}
+
+#[tauri::command]
+pub fn fido_get_min_pin_length(
+    fido: tauri::State<'_, Arc<FidoModuleImpl>>,
+) -> Result<crate::fido::types::MinPinLengthInfo, AppError> {
+    Ok(fido.get_min_pin_length()?)
+}
+
+#[tauri::command]
+pub fn fido_firmware_status(
+    fido: tauri::State<'_, Arc<FidoModuleImpl>>,
+) -> Result<FirmwareStatus, AppError> {
+    Ok(fido.firmware_status()?)
 }
 
 #[tauri::command]
 pub fn fido_set_pin(
-    new_pin: String,
+    new_pin: SecretString,
     fido: tauri::State<'_, Arc<FidoModuleImpl>>,
-) -> Result<(), String> {
-    fido.set_pin(&new_pin).map_err(|e| e.to_string())
+) -> Result<(), AppError> {
+    Ok(fido.set_pin(&new_pin)?)
 }
 
 #[tauri::command]
 pub fn fido_change_pin(
-    old_pin: String,
-    new_pin: String,
+    old_pin: SecretString,
+    new_pin: SecretString,
+    fido: tauri::State<'_, Arc<FidoModuleImpl>>,
+) -> Result<(), AppError> {
+    Ok(fido.change_pin(&old_pin, &new_pin)?)
+}
+
+#[tauri::command]
+pub fn fido_get_creds_metadata(
+    pin: SecretString,
     fido: tauri::State<'_, Arc<FidoModuleImpl>>,
-) -> Result<(), String> {
-    fido.change_pin(&old_pin, &new_pin)
-        .map_err(|e| e.to_string())
+) -> Result<crate::fido::types::CredsMetadata, AppError> {
+    Ok(fido.get_creds_metadata(&pin)?)
 }
 
 #[tauri::command]
 pub fn fido_list_credentials(
-    pin: String,
+    pin: SecretString,
     fido: tauri::State<'_, Arc<FidoModuleImpl>>,
-) -> Result<Vec<crate::fido::types::FidoCredential>, String> {
-    fido.list_credentials(&pin).map_err(|e| e.to_string())
+) -> Result<Vec<crate::fido::types::FidoCredential>, AppError> {
+    Ok(fido.list_credentials(&pin)?)
 }
 
 #[tauri::command]
 pub fn fido_delete_credential(
-    pin: String,
+    pin: SecretString,
+    credential_id: Vec<u8>,
+    fido: tauri::State<'_, Arc<FidoModuleImpl>>,
+) -> Result<(), AppError> {
+    Ok(fido.delete_credential(&pin, &credential_id)?)
+}
+
+/// 刪除所有可發現憑證（不影響 PIN 設定），回傳刪除數量
+#[tauri::command]
+pub fn fido_delete_all_credentials(
+    pin: SecretString,
+    fido: tauri::State<'_, Arc<FidoModuleImpl>>,
+) -> Result<usize, AppError> {
+    Ok(fido.delete_all_credentials(&pin)?)
+}
+
+#[tauri::command]
+pub fn fido_assert_credential(
+    rp_id: String,
     credential_id: Vec<u8>,
+    pin: SecretString,
     fido: tauri::State<'_, Arc<FidoModuleImpl>>,
-) -> Result<(), String> {
-    fido.delete_credential(&pin, &credential_id)
-        .map_err(|e| e.to_string())
+) -> Result<crate::fido::types::AssertionCheck, AppError> {
+    Ok(fido.assert_credential(&rp_id, &credential_id, &pin)?)
 }
 
 #[tauri::command]
 pub fn fido_list_oath(
     fido: tauri::State<'_, Arc<FidoModuleImpl>>,
-) -> Result<Vec<crate::fido::types::OathCredential>, String> {
-    fido.list_oath_credentials().map_err(|e| e.to_string())
+) -> Result<Vec<crate::fido::types::OathCredential>, AppError> {
+    Ok(fido.list_oath_credentials()?)
+}
+
+#[tauri::command]
+pub fn fido_oath_slot_info(
+    fido: tauri::State<'_, Arc<FidoModuleImpl>>,
+) -> Result<crate::fido::types::OathSlotInfo, AppError> {
+    Ok(fido.oath_slot_info()?)
 }
 
 #[tauri::command]
 pub fn fido_calculate_oath(
     credential_id: String,
     fido: tauri::State<'_, Arc<FidoModuleImpl>>,
-) -> Result<String, String> {
-    fido.calculate_oath(&credential_id)
-        .map_err(|e| e.to_string())
+) -> Result<String, AppError> {
+    Ok(fido.calculate_oath(&credential_id)?)
 }
 
 #[tauri::command]
 pub fn fido_add_oath(
     credential: OathCredentialParams,
     fido: tauri::State<'_, Arc<FidoModuleImpl>>,
-) -> Result<(), String> {
-    fido.add_oath_credential(&credential)
-        .map_err(|e| e.to_string())
+) -> Result<(), AppError> {
+    Ok(fido.add_oath_credential(&credential)?)
 }
 
 #[tauri::command]
 pub fn fido_delete_oath(
     credential_id: String,
     fido: tauri::State<'_, Arc<FidoModuleImpl>>,
-) -> Result<(), String> {
-    fido.delete_oath_credential(&credential_id)
-        .map_err(|e| e.to_string())
+) -> Result<(), AppError> {
+    Ok(fido.delete_oath_credential(&credential_id)?)
 }
 
 #[tauri::command]
 pub fn fido_get_backup_words(
-    pin: String,
+    pin: SecretString,
     fido: tauri::State<'_, Arc<FidoModuleImpl>>,
-) -> Result<Vec<String>, String> {
-    fido.get_backup_words(&pin).map_err(|e| e.to_string())
+) -> Result<Vec<String>, AppError> {
+    Ok(fido.get_backup_words(&pin)?)
 }
 
 #[tauri::command]
 pub fn fido_restore_from_words(
-    pin: String,
+    pin: SecretString,
     words: Vec<String>,
     fido: tauri::State<'_, Arc<FidoModuleImpl>>,
-) -> Result<(), String> {
-    fido.restore_from_words(&pin, &words)
-        .map_err(|e| e.to_string())
+) -> Result<(), AppError> {
+    Ok(fido.restore_from_words(&pin, &words)?)
+}
+
+#[tauri::command]
+pub fn fido_reset_prepare(
+    pin: Option<SecretString>,
+    fido: tauri::State<'_, Arc<FidoModuleImpl>>,
+) -> Result<ResetPreparation, AppError> {
+    Ok(fido.reset_prepare(pin.as_deref().map(String::as_str))?)
 }
 
 #[tauri::command]
-pub fn fido_reset_device(
+pub fn fido_reset_confirm(
+    token: String,
     fido: tauri::State<'_, Arc<FidoModuleImpl>>,
-) -> Result<(), String> {
-    fido.reset_device().map_err(|e| e.to_string())
+) -> Result<(), AppError> {
+    Ok(fido.reset_confirm(&token)?)
 }
 
 #[tauri::command]
 pub fn fido_set_min_pin_length(
-    pin: String,
-    length: u8,
+    pin: SecretString,
+    params: SetMinPinLengthParams,
     fido: tauri::State<'_, Arc<FidoModuleImpl>>,
-) -> Result<(), String> {
-    fido.set_min_pin_length(&pin, length)
-        .map_err(|e| e.to_string())
+) -> Result<(), AppError> {
+    Ok(fido.set_min_pin_length(&pin, &params)?)
 }
 
 #[tauri::command]
 pub fn fido_toggle_enterprise_attestation(
-    pin: String,
+    pin: SecretString,
+    enable: bool,
+    fido: tauri::State<'_, Arc<FidoModuleImpl>>,
+) -> Result<(), AppError> {
+    Ok(fido.toggle_enterprise_attestation(&pin, enable)?)
+}
+
+#[tauri::command]
+pub fn fido_toggle_always_uv(
+    pin: SecretString,
     enable: bool,
     fido: tauri::State<'_, Arc<FidoModuleImpl>>,
-) -> Result<(), String> {
-    fido.toggle_enterprise_attestation(&pin, enable)
-        .map_err(|e| e.to_string())
+) -> Result<(), AppError> {
+    Ok(fido.toggle_always_uv(&pin, enable)?)
+}
+
+/// 診斷用：取得認證器的 keyAgreement 公鑰，預設使用 pinUvAuthProtocol 1
+#[tauri::command]
+pub fn fido_get_key_agreement(
+    protocol: Option<u8>,
+    fido: tauri::State<'_, Arc<FidoModuleImpl>>,
+) -> Result<crate::fido::types::KeyAgreementInfo, AppError> {
+    Ok(fido.get_key_agreement(protocol.unwrap_or(1))?)
+}
+
+/// 診斷用：以測試 RP 執行一次 MakeCredential + GetAssertion，需觸碰裝置兩次
+#[tauri::command]
+pub fn fido_self_test_registration(
+    fido: tauri::State<'_, Arc<FidoModuleImpl>>,
+) -> Result<crate::fido::types::SelfTestResult, AppError> {
+    Ok(fido.self_test_registration()?)
 }
 
 #[tauri::command]
 pub fn fido_set_led_config(
     config: LedConfig,
     fido: tauri::State<'_, Arc<FidoModuleImpl>>,
-) -> Result<(), String> {
-    fido.set_led_config(&config).map_err(|e| e.to_string())
+) -> Result<(), AppError> {
+    Ok(fido.set_led_config(&config)?)
+}
+
+// === largeBlobs ===
+
+#[tauri::command]
+pub fn fido_read_large_blob(
+    fido: tauri::State<'_, Arc<FidoModuleImpl>>,
+) -> Result<Vec<u8>, AppError> {
+    Ok(fido.read_large_blob()?)
+}
+
+#[tauri::command]
+pub fn fido_write_large_blob(
+    pin: SecretString,
+    data: Vec<u8>,
+    fido: tauri::State<'_, Arc<FidoModuleImpl>>,
+) -> Result<(), AppError> {
+    Ok(fido.write_large_blob(&pin, &data)?)
+}
+
+// === 進階模式 ===
+
+/// 設定取得 pinUvAuthToken 時偏好的驗證方式（PIN 或裝置內建 UV）
+#[tauri::command]
+pub fn fido_set_uv_method(
+    method: UvMethod,
+    fido: tauri::State<'_, Arc<FidoModuleImpl>>,
+) -> Result<(), AppError> {
+    fido.set_uv_method(method);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn fido_set_advanced_mode(
+    enabled: bool,
+    fido: tauri::State<'_, Arc<FidoModuleImpl>>,
+) -> Result<(), AppError> {
+    fido.set_advanced_mode(enabled);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn fido_set_oath_aid(
+    aid: Option<Vec<u8>>,
+    fido: tauri::State<'_, Arc<FidoModuleImpl>>,
+) -> Result<(), AppError> {
+    Ok(fido.set_oath_aid(aid.unwrap_or_else(|| crate::fido::oath::YKOATH_AID.to_vec()))?)
+}
+
+#[tauri::command]
+pub fn fido_cancel(
+    fido: tauri::State<'_, Arc<FidoModuleImpl>>,
+) -> Result<(), AppError> {
+    Ok(fido.cancel()?)
+}
+
+#[tauri::command]
+pub fn fido_set_read_timeout(
+    seconds: u64,
+    fido: tauri::State<'_, Arc<FidoModuleImpl>>,
+) -> Result<(), AppError> {
+    fido.set_read_timeout(Duration::from_secs(seconds));
+    Ok(())
+}
+
+#[tauri::command]
+pub fn fido_transmit_cbor(
+    command_byte: u8,
+    cbor_params: Vec<u8>,
+    fido: tauri::State<'_, Arc<FidoModuleImpl>>,
+) -> Result<Vec<u8>, AppError> {
+    if !fido.advanced_mode() {
+        return Err(FidoError::AdvancedModeDisabled.into());
+    }
+    Ok(fido.transmit_cbor(command_byte, &cbor_params)?)
 }
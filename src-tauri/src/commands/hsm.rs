@@ -1,145 +1,230 @@
 use std::sync::Arc;
+use std::time::Duration;
 
-use crate::hsm::types::{DkekStatus, HsmCertInfo, HsmDeviceInfo, HsmKeyInfo, HsmOptionType, HsmOptions, KeyObjectType};
+use crate::error::AppError;
+use crate::secret::{SecretString, Zeroizing};
+use crate::hsm::cert_bundle;
+use crate::hsm::types::{AesMode, CardShareMode, CertBundleFormat, DebugReport, DkekStatus, HsmCertInfo, HsmDeviceInfo, HsmKeyInfo, HsmOptionType, HsmOptions, InitializeOptions, KeyObjectType, KeySpec, MemInfo, ObjectRef, SupportedAlgos, UnwrapStatus, WrappedKey};
 use crate::hsm::{HsmModule, HsmModuleImpl};
-use crate::types::LedConfig;
+use crate::types::{FirmwareStatus, LedConfig, PingResult};
 
 // === 初始化 ===
 
 #[tauri::command]
 pub fn hsm_initialize(
-    pin: String,
-    so_pin: String,
+    pin: SecretString,
+    so_pin: SecretString,
     dkek_shares: u8,
+    options: Option<InitializeOptions>,
     hsm: tauri::State<'_, Arc<HsmModuleImpl>>,
-) -> Result<(), String> {
-    hsm.initialize(&pin, &so_pin, dkek_shares)
-        .map_err(|e| e.to_string())
+) -> Result<(), AppError> {
+    Ok(hsm.initialize_with_options(&pin, &so_pin, dkek_shares, &options.unwrap_or_default())?)
 }
 
 // === PIN 管理 ===
 
 #[tauri::command]
 pub fn hsm_verify_pin(
-    pin: String,
+    pin: SecretString,
     hsm: tauri::State<'_, Arc<HsmModuleImpl>>,
-) -> Result<(), String> {
-    hsm.verify_pin(&pin).map_err(|e| e.to_string())
+) -> Result<(), AppError> {
+    Ok(hsm.verify_pin(&pin)?)
 }
 
 #[tauri::command]
 pub fn hsm_change_pin(
-    old_pin: String,
-    new_pin: String,
+    old_pin: SecretString,
+    new_pin: SecretString,
     hsm: tauri::State<'_, Arc<HsmModuleImpl>>,
-) -> Result<(), String> {
-    hsm.change_pin(&old_pin, &new_pin)
-        .map_err(|e| e.to_string())
+) -> Result<(), AppError> {
+    Ok(hsm.change_pin(&old_pin, &new_pin)?)
 }
 
 #[tauri::command]
 pub fn hsm_change_so_pin(
-    old_so_pin: String,
-    new_so_pin: String,
+    old_so_pin: SecretString,
+    new_so_pin: SecretString,
+    force: Option<bool>,
     hsm: tauri::State<'_, Arc<HsmModuleImpl>>,
-) -> Result<(), String> {
-    hsm.change_so_pin(&old_so_pin, &new_so_pin)
-        .map_err(|e| e.to_string())
+) -> Result<(), AppError> {
+    Ok(hsm.change_so_pin(&old_so_pin, &new_so_pin, force.unwrap_or(false))?)
 }
 
 #[tauri::command]
 pub fn hsm_unblock_pin(
-    so_pin: String,
-    new_pin: String,
+    so_pin: SecretString,
+    new_pin: SecretString,
+    force: Option<bool>,
     hsm: tauri::State<'_, Arc<HsmModuleImpl>>,
-) -> Result<(), String> {
-    hsm.unblock_pin(&so_pin, &new_pin)
-        .map_err(|e| e.to_string())
+) -> Result<(), AppError> {
+    Ok(hsm.unblock_pin(&so_pin, &new_pin, force.unwrap_or(false))?)
+}
+
+#[tauri::command]
+pub fn hsm_get_pin_retries(
+    hsm: tauri::State<'_, Arc<HsmModuleImpl>>,
+) -> Result<Option<u8>, AppError> {
+    Ok(hsm.get_pin_retries()?)
+}
+
+#[tauri::command]
+pub fn hsm_ping(
+    hsm: tauri::State<'_, Arc<HsmModuleImpl>>,
+) -> Result<PingResult, AppError> {
+    Ok(hsm.ping()?)
+}
+
+#[tauri::command]
+pub fn hsm_get_so_pin_retries(
+    hsm: tauri::State<'_, Arc<HsmModuleImpl>>,
+) -> Result<Option<u8>, AppError> {
+    Ok(hsm.get_so_pin_retries()?)
 }
 
 // === 金鑰管理 ===
 
 #[tauri::command]
 pub fn hsm_list_keys(
-    pin: String,
+    pin: SecretString,
     hsm: tauri::State<'_, Arc<HsmModuleImpl>>,
-) -> Result<Vec<HsmKeyInfo>, String> {
-    hsm.list_keys(&pin).map_err(|e| e.to_string())
+) -> Result<Vec<HsmKeyInfo>, AppError> {
+    Ok(hsm.list_keys(&pin)?)
+}
+
+#[tauri::command]
+pub fn hsm_next_free_key_id(
+    pin: SecretString,
+    hsm: tauri::State<'_, Arc<HsmModuleImpl>>,
+) -> Result<u8, AppError> {
+    Ok(hsm.next_free_key_id(&pin)?)
 }
 
 #[tauri::command]
 pub fn hsm_generate_rsa_key(
-    pin: String,
+    pin: SecretString,
     bits: u16,
-    id: u8,
+    id: Option<u8>,
     label: String,
     hsm: tauri::State<'_, Arc<HsmModuleImpl>>,
-) -> Result<HsmKeyInfo, String> {
-    hsm.generate_rsa_key(&pin, bits, id, &label)
-        .map_err(|e| e.to_string())
+) -> Result<HsmKeyInfo, AppError> {
+    Ok(hsm.generate_rsa_key(&pin, bits, id, &label)?)
 }
 
 #[tauri::command]
 pub fn hsm_generate_ec_key(
-    pin: String,
+    pin: SecretString,
     curve: String,
-    id: u8,
+    id: Option<u8>,
     label: String,
     hsm: tauri::State<'_, Arc<HsmModuleImpl>>,
-) -> Result<HsmKeyInfo, String> {
-    hsm.generate_ec_key(&pin, &curve, id, &label)
-        .map_err(|e| e.to_string())
+) -> Result<HsmKeyInfo, AppError> {
+    Ok(hsm.generate_ec_key(&pin, &curve, id, &label)?)
 }
 
 #[tauri::command]
 pub fn hsm_generate_aes_key(
-    pin: String,
+    pin: SecretString,
     bits: u16,
-    id: u8,
+    id: Option<u8>,
+    label: String,
+    mode: Option<AesMode>,
     hsm: tauri::State<'_, Arc<HsmModuleImpl>>,
-) -> Result<HsmKeyInfo, String> {
-    hsm.generate_aes_key(&pin, bits, id)
-        .map_err(|e| e.to_string())
+) -> Result<HsmKeyInfo, AppError> {
+    Ok(hsm.generate_aes_key(&pin, bits, id, &label, mode.unwrap_or_default())?)
+}
+
+#[tauri::command]
+pub fn hsm_generate_keys(
+    pin: SecretString,
+    specs: Vec<KeySpec>,
+    hsm: tauri::State<'_, Arc<HsmModuleImpl>>,
+) -> Result<Vec<HsmKeyInfo>, AppError> {
+    Ok(hsm.generate_keys(&pin, &specs)?)
 }
 
 #[tauri::command]
 pub fn hsm_delete_key(
-    pin: String,
+    pin: SecretString,
     id: u8,
     key_type: KeyObjectType,
     hsm: tauri::State<'_, Arc<HsmModuleImpl>>,
-) -> Result<(), String> {
-    hsm.delete_key(&pin, id, key_type)
-        .map_err(|e| e.to_string())
+) -> Result<(), AppError> {
+    Ok(hsm.delete_key(&pin, id, key_type)?)
+}
+
+#[tauri::command]
+pub fn hsm_delete_key_pair(
+    pin: SecretString,
+    id: u8,
+    delete_certificate: bool,
+    hsm: tauri::State<'_, Arc<HsmModuleImpl>>,
+) -> Result<(), AppError> {
+    Ok(hsm.delete_key_pair(&pin, id, delete_certificate)?)
+}
+
+#[tauri::command]
+pub fn hsm_set_key_label(
+    pin: SecretString,
+    id: u8,
+    label: String,
+    hsm: tauri::State<'_, Arc<HsmModuleImpl>>,
+) -> Result<(), AppError> {
+    Ok(hsm.set_key_label(&pin, id, &label)?)
+}
+
+#[tauri::command]
+pub fn hsm_list_all_objects(
+    pin: SecretString,
+    hsm: tauri::State<'_, Arc<HsmModuleImpl>>,
+) -> Result<Vec<ObjectRef>, AppError> {
+    Ok(hsm.list_all_objects(&pin)?)
+}
+
+#[tauri::command]
+pub fn hsm_delete_orphaned_objects(
+    pin: SecretString,
+    hsm: tauri::State<'_, Arc<HsmModuleImpl>>,
+) -> Result<Vec<ObjectRef>, AppError> {
+    Ok(hsm.delete_orphaned_objects(&pin)?)
 }
 
 // === 憑證管理 ===
 
 #[tauri::command]
 pub fn hsm_list_certificates(
-    pin: String,
+    pin: SecretString,
     hsm: tauri::State<'_, Arc<HsmModuleImpl>>,
-) -> Result<Vec<HsmCertInfo>, String> {
-    hsm.list_certificates(&pin).map_err(|e| e.to_string())
+) -> Result<Vec<HsmCertInfo>, AppError> {
+    Ok(hsm.list_certificates(&pin)?)
 }
 
 #[tauri::command]
 pub fn hsm_import_certificate(
-    pin: String,
+    pin: SecretString,
     id: u8,
     cert_data: Vec<u8>,
+    force: Option<bool>,
     hsm: tauri::State<'_, Arc<HsmModuleImpl>>,
-) -> Result<(), String> {
-    hsm.import_certificate(&pin, id, &cert_data)
-        .map_err(|e| e.to_string())
+) -> Result<(), AppError> {
+    Ok(hsm.import_certificate(&pin, id, &cert_data, force.unwrap_or(false))?)
 }
 
 #[tauri::command]
 pub fn hsm_export_certificate(
     id: u8,
     hsm: tauri::State<'_, Arc<HsmModuleImpl>>,
-) -> Result<Vec<u8>, String> {
-    hsm.export_certificate(id).map_err(|e| e.to_string())
+) -> Result<Vec<u8>, AppError> {
+    Ok(hsm.export_certificate(id)?)
+}
+
+#[tauri::command]
+pub fn hsm_export_certificate_chain(
+    id: u8,
+    format: Option<CertBundleFormat>,
+    hsm: tauri::State<'_, Arc<HsmModuleImpl>>,
+) -> Result<Vec<u8>, AppError> {
+    let chain = hsm.export_certificate_chain(id)?;
+    Ok(cert_bundle::bundle(&chain, format.unwrap_or_default()))
 }
 
 // === DKEK 備份還原 ===
@@ -148,9 +233,32 @@ pub fn hsm_export_certificate(
 pub fn hsm_create_dkek_share(
     password: String,
     hsm: tauri::State<'_, Arc<HsmModuleImpl>>,
-) -> Result<Vec<u8>, String> {
-    hsm.create_dkek_share(&password)
-        .map_err(|e| e.to_string())
+) -> Result<Vec<u8>, AppError> {
+    Ok(hsm.create_dkek_share(&password)?)
+}
+
+/// 以目前的 SO-PIN 重新初始化裝置，清除所有金鑰與憑證
+#[tauri::command]
+pub fn hsm_factory_reset(
+    so_pin: SecretString,
+    new_pin: SecretString,
+    new_so_pin: SecretString,
+    dkek_shares: u8,
+    confirmation: String,
+    hsm: tauri::State<'_, Arc<HsmModuleImpl>>,
+) -> Result<HsmDeviceInfo, AppError> {
+    Ok(hsm.factory_reset(&so_pin, &new_pin, &new_so_pin, dkek_shares, &confirmation)?)
+}
+
+/// 變更 SO-PIN 並建立新的 DKEK 份額，失敗時回報 SO-PIN 最終狀態
+#[tauri::command]
+pub fn hsm_rotate_security(
+    old_so_pin: SecretString,
+    new_so_pin: SecretString,
+    new_dkek_password: String,
+    hsm: tauri::State<'_, Arc<HsmModuleImpl>>,
+) -> Result<Vec<u8>, AppError> {
+    Ok(hsm.rotate_security(&old_so_pin, &new_so_pin, &new_dkek_password)?)
 }
 
 #[tauri::command]
@@ -158,29 +266,49 @@ pub fn hsm_import_dkek_share(
     share_data: Vec<u8>,
     password: String,
     hsm: tauri::State<'_, Arc<HsmModuleImpl>>,
-) -> Result<DkekStatus, String> {
-    hsm.import_dkek_share(&share_data, &password)
-        .map_err(|e| e.to_string())
+) -> Result<DkekStatus, AppError> {
+    Ok(hsm.import_dkek_share(&share_data, &password)?)
 }
 
 #[tauri::command]
 pub fn hsm_wrap_key(
-    pin: String,
+    pin: SecretString,
     key_ref: u8,
     hsm: tauri::State<'_, Arc<HsmModuleImpl>>,
-) -> Result<Vec<u8>, String> {
-    hsm.wrap_key(&pin, key_ref).map_err(|e| e.to_string())
+) -> Result<WrappedKey, AppError> {
+    Ok(hsm.wrap_key(&pin, key_ref)?)
 }
 
 #[tauri::command]
 pub fn hsm_unwrap_key(
-    pin: String,
+    pin: SecretString,
     key_ref: u8,
     wrapped: Vec<u8>,
     hsm: tauri::State<'_, Arc<HsmModuleImpl>>,
-) -> Result<(), String> {
-    hsm.unwrap_key(&pin, key_ref, &wrapped)
-        .map_err(|e| e.to_string())
+) -> Result<UnwrapStatus, AppError> {
+    Ok(hsm.unwrap_key(&pin, key_ref, &wrapped)?)
+}
+
+#[tauri::command]
+pub fn hsm_import_key_with_cert(
+    pin: SecretString,
+    key_ref: u8,
+    wrapped: Vec<u8>,
+    cert_der: Vec<u8>,
+    hsm: tauri::State<'_, Arc<HsmModuleImpl>>,
+) -> Result<UnwrapStatus, AppError> {
+    Ok(hsm.import_key_with_cert(&pin, key_ref, &wrapped, &cert_der)?)
+}
+
+#[tauri::command]
+pub fn hsm_import_private_key(
+    pin: SecretString,
+    id: u8,
+    pkcs8_der: Zeroizing<Vec<u8>>,
+    label: String,
+    hsm: tauri::State<'_, Arc<HsmModuleImpl>>,
+) -> Result<HsmKeyInfo, AppError> {
+    Ok(hsm.import_private_key(&pin, id, &pkcs8_der, &label)?)
 }
 
 // === 裝置選項與組態 ===
@@ -188,8 +316,8 @@ pub fn hsm_unwrap_key(
 #[tauri::command]
 pub fn hsm_get_options(
     hsm: tauri::State<'_, Arc<HsmModuleImpl>>,
-) -> Result<HsmOptions, String> {
-    hsm.get_options().map_err(|e| e.to_string())
+) -> Result<HsmOptions, AppError> {
+    Ok(hsm.get_options()?)
 }
 
 #[tauri::command]
@@ -197,44 +325,109 @@ pub fn hsm_set_option(
     option: HsmOptionType,
     enabled: bool,
     hsm: tauri::State<'_, Arc<HsmModuleImpl>>,
-) -> Result<(), String> {
-    hsm.set_option(option, enabled).map_err(|e| e.to_string())
+) -> Result<(), AppError> {
+    Ok(hsm.set_option(option, enabled)?)
 }
 
 #[tauri::command]
 pub fn hsm_set_datetime(
     hsm: tauri::State<'_, Arc<HsmModuleImpl>>,
-) -> Result<(), String> {
-    hsm.set_datetime().map_err(|e| e.to_string())
+) -> Result<(), AppError> {
+    Ok(hsm.set_datetime()?)
 }
 
 #[tauri::command]
 pub fn hsm_get_device_info(
+    refresh: Option<bool>,
+    hsm: tauri::State<'_, Arc<HsmModuleImpl>>,
+) -> Result<HsmDeviceInfo, AppError> {
+    Ok(hsm.cached_device_info(refresh.unwrap_or(false))?)
+}
+
+#[tauri::command]
+pub fn hsm_firmware_status(
+    hsm: tauri::State<'_, Arc<HsmModuleImpl>>,
+) -> Result<FirmwareStatus, AppError> {
+    Ok(hsm.firmware_status()?)
+}
+
+#[tauri::command]
+pub fn hsm_get_memory(
+    hsm: tauri::State<'_, Arc<HsmModuleImpl>>,
+) -> Result<MemInfo, AppError> {
+    Ok(hsm.get_memory()?)
+}
+
+/// 讀取韌體故障紀錄；韌體未提供時回傳 NotSupported
+#[tauri::command]
+pub fn hsm_read_fault_log(
     hsm: tauri::State<'_, Arc<HsmModuleImpl>>,
-) -> Result<HsmDeviceInfo, String> {
-    hsm.get_device_info().map_err(|e| e.to_string())
+) -> Result<Vec<String>, AppError> {
+    Ok(hsm.read_fault_log()?)
+}
+
+#[tauri::command]
+pub fn hsm_clear_fault_log(
+    hsm: tauri::State<'_, Arc<HsmModuleImpl>>,
+) -> Result<(), AppError> {
+    Ok(hsm.clear_fault_log()?)
+}
+
+/// 查詢韌體支援的 RSA 長度、EC 曲線與 AES 長度
+#[tauri::command]
+pub fn hsm_get_supported_algorithms(
+    hsm: tauri::State<'_, Arc<HsmModuleImpl>>,
+) -> Result<SupportedAlgos, AppError> {
+    Ok(hsm.get_supported_algorithms()?)
 }
 
 #[tauri::command]
 pub fn hsm_enable_secure_lock(
     hsm: tauri::State<'_, Arc<HsmModuleImpl>>,
-) -> Result<(), String> {
-    hsm.enable_secure_lock().map_err(|e| e.to_string())
+) -> Result<(), AppError> {
+    Ok(hsm.enable_secure_lock()?)
 }
 
 #[tauri::command]
 pub fn hsm_disable_secure_lock(
     hsm: tauri::State<'_, Arc<HsmModuleImpl>>,
-) -> Result<(), String> {
-    hsm.disable_secure_lock().map_err(|e| e.to_string())
+) -> Result<(), AppError> {
+    Ok(hsm.disable_secure_lock()?)
 }
 
 #[tauri::command]
 pub fn hsm_set_led_config(
     config: LedConfig,
     hsm: tauri::State<'_, Arc<HsmModuleImpl>>,
-) -> Result<(), String> {
-    hsm.set_led_config(&config).map_err(|e| e.to_string())
+) -> Result<(), AppError> {
+    Ok(hsm.set_led_config(&config)?)
+}
+
+#[tauri::command]
+pub fn hsm_set_share_mode(
+    mode: CardShareMode,
+    hsm: tauri::State<'_, Arc<HsmModuleImpl>>,
+) -> Result<(), AppError> {
+    hsm.set_share_mode(mode);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn hsm_set_transmit_timeout(
+    seconds: u64,
+    hsm: tauri::State<'_, Arc<HsmModuleImpl>>,
+) -> Result<(), AppError> {
+    hsm.set_transmit_timeout(Duration::from_secs(seconds));
+    Ok(())
+}
+
+/// 設定 SELECT 使用的 applet AID；未指定時還原為 SC-HSM 預設 AID
+#[tauri::command]
+pub fn hsm_set_applet_aid(
+    aid: Option<Vec<u8>>,
+    hsm: tauri::State<'_, Arc<HsmModuleImpl>>,
+) -> Result<(), AppError> {
+    Ok(hsm.set_applet_aid(aid.unwrap_or_else(|| crate::hsm::SC_HSM_AID.to_vec()))?)
 }
 
 // === 診斷 ===
@@ -242,6 +435,13 @@ pub fn hsm_set_led_config(
 #[tauri::command]
 pub fn hsm_debug_device_raw(
     hsm: tauri::State<'_, Arc<HsmModuleImpl>>,
-) -> Result<Vec<String>, String> {
-    hsm.debug_device_raw().map_err(|e| e.to_string())
+) -> Result<Vec<String>, AppError> {
+    Ok(hsm.debug_device_raw()?)
+}
+
+#[tauri::command]
+pub fn hsm_debug_device_raw_structured(
+    hsm: tauri::State<'_, Arc<HsmModuleImpl>>,
+) -> Result<DebugReport, AppError> {
+    Ok(hsm.debug_device_raw_structured()?)
 }
@@ -0,0 +1,165 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Condvar, Mutex};
+
+/// 單一裝置的鎖；不可重入，鎖由 `DeviceGuard` 擁有而非執行緒，
+/// 逾時後仍在背景傳送的執行緒持有 guard 時，其他呼叫一律等待
+#[derive(Default)]
+struct DeviceLock {
+    held: Mutex<bool>,
+    released: Condvar,
+}
+
+impl DeviceLock {
+    fn enter(&self) {
+        let mut held = self.held.lock().unwrap_or_else(|e| e.into_inner());
+        while *held {
+            held = self.released.wait(held).unwrap_or_else(|e| e.into_inner());
+        }
+        *held = true;
+    }
+
+    fn leave(&self) {
+        let mut held = self.held.lock().unwrap_or_else(|e| e.into_inner());
+        *held = false;
+        self.released.notify_one();
+    }
+}
+
+/// 持有裝置鎖的憑證，drop 時釋放；可隨連線物件移交給其他執行緒。
+/// 持有期間的巢狀交換須沿用此 guard，再次取得同一裝置的鎖會永久等待
+pub struct DeviceGuard {
+    lock: Arc<DeviceLock>,
+}
+
+impl Drop for DeviceGuard {
+    fn drop(&mut self) {
+        self.lock.leave();
+    }
+}
+
+/// 以裝置路徑為鍵的互斥鎖表：同一裝置的交換依序執行，不同裝置可平行
+#[derive(Default)]
+pub struct DeviceLocks {
+    locks: Mutex<HashMap<String, Arc<DeviceLock>>>,
+}
+
+impl DeviceLocks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 取得（必要時建立）指定裝置的鎖
+    fn lock_for(&self, path: &str) -> Arc<DeviceLock> {
+        let mut locks = self.locks.lock().unwrap_or_else(|e| e.into_inner());
+        Arc::clone(locks.entry(path.to_string()).or_default())
+    }
+
+    /// 取得指定裝置的鎖並持有到回傳的 guard 被 drop，用於橫跨整個連線的多個交換
+    pub fn acquire(&self, path: &str) -> DeviceGuard {
+        let lock = self.lock_for(path);
+        lock.enter();
+        DeviceGuard { lock }
+    }
+
+    /// 持有指定裝置的鎖執行 `f`；先前持有者 panic 時仍可繼續使用
+    pub fn with_lock<T, F: FnOnce() -> T>(&self, path: &str, f: F) -> T {
+        let _guard = self.acquire(path);
+        f()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    // === 裝置鎖測試 ===
+
+    #[test]
+    fn test_same_device_never_overlaps() {
+        let locks = Arc::new(DeviceLocks::new());
+        let active = Arc::new(AtomicUsize::new(0));
+        let max_seen = Arc::new(AtomicUsize::new(0));
+
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                let (locks, active, max_seen) =
+                    (Arc::clone(&locks), Arc::clone(&active), Arc::clone(&max_seen));
+                std::thread::spawn(move || {
+                    for _ in 0..20 {
+                        locks.with_lock("reader0", || {
+                            let now = active.fetch_add(1, Ordering::SeqCst) + 1;
+                            max_seen.fetch_max(now, Ordering::SeqCst);
+                            std::thread::yield_now();
+                            active.fetch_sub(1, Ordering::SeqCst);
+                        });
+                    }
+                })
+            })
+            .collect();
+        for h in handles {
+            h.join().unwrap();
+        }
+        assert_eq!(max_seen.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_different_devices_run_in_parallel() {
+        let locks = Arc::new(DeviceLocks::new());
+        let (entered_tx, entered_rx) = mpsc::channel();
+        let (release_tx, release_rx) = mpsc::channel::<()>();
+
+        let holder = {
+            let locks = Arc::clone(&locks);
+            std::thread::spawn(move || {
+                locks.with_lock("reader0", || {
+                    entered_tx.send(()).unwrap();
+                    release_rx.recv_timeout(Duration::from_secs(5)).unwrap();
+                });
+            })
+        };
+        entered_rx.recv_timeout(Duration::from_secs(5)).unwrap();
+
+        // reader0 被持有時，reader1 仍可立即取得
+        let (done_tx, done_rx) = mpsc::channel();
+        let other = {
+            let locks = Arc::clone(&locks);
+            std::thread::spawn(move || locks.with_lock("reader1", || done_tx.send(()).unwrap()))
+        };
+        assert!(done_rx.recv_timeout(Duration::from_secs(5)).is_ok());
+
+        release_tx.send(()).unwrap();
+        holder.join().unwrap();
+        other.join().unwrap();
+    }
+
+    #[test]
+    fn test_guard_holds_lock_until_dropped() {
+        let locks = Arc::new(DeviceLocks::new());
+        let guard = locks.acquire("reader0");
+
+        let (done_tx, done_rx) = mpsc::channel();
+        let waiter = {
+            let locks = Arc::clone(&locks);
+            std::thread::spawn(move || locks.with_lock("reader0", || done_tx.send(()).unwrap()))
+        };
+        assert!(done_rx.recv_timeout(Duration::from_millis(50)).is_err());
+
+        // guard 移交給其他執行緒後於該處釋放
+        std::thread::spawn(move || drop(guard)).join().unwrap();
+        assert!(done_rx.recv_timeout(Duration::from_secs(5)).is_ok());
+        waiter.join().unwrap();
+    }
+
+    #[test]
+    fn test_lock_survives_panic() {
+        let locks = Arc::new(DeviceLocks::new());
+        let panicking = Arc::clone(&locks);
+        let result = std::thread::spawn(move || panicking.with_lock("reader0", || panic!("boom")))
+            .join();
+        assert!(result.is_err());
+        assert_eq!(locks.with_lock("reader0", || 42), 42);
+    }
+}
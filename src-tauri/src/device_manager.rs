@@ -1,11 +1,16 @@
-use std::collections::HashSet;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::ffi::CString;
+use std::sync::mpsc;
 use std::sync::{Arc, Mutex};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use tauri::Emitter;
 
-use crate::error::DeviceError;
-use crate::types::{DeviceInfo, DeviceType};
+use crate::error::{AppError, DeviceError, FidoError, HsmError};
+use crate::fido::types::FidoDeviceInfo;
+use crate::hsm::types::HsmDeviceInfo;
+use crate::types::{DeviceAvailability, DeviceCapabilities, DeviceInfo, DeviceType, ScanScope};
 
 // Pico-FIDO HID 裝置識別
 // 預設 VID/PID (Raspberry Pi Foundation)
@@ -18,6 +23,81 @@ const NITROKEY_FIDO_PID: u16 = 0x42B2;
 // SmartCard-HSM 歷史位元組中的應用識別字串 "THSM"
 const HSM_ATR_MARKER: &[u8] = &[0x54, 0x48, 0x53, 0x4D]; // "THSM"
 
+// 無法讀取 ATR 時，讀卡機名稱包含以下任一字串（不分大小寫）即視為 Pico-HSM
+const DEFAULT_HSM_READER_PATTERNS: &[&str] = &["HSM"];
+
+// ATR 快取有效時間：涵蓋連續掃描與 2 秒輪詢，拔插後最多延遲此時間反映
+const ATR_CACHE_TTL: Duration = Duration::from_secs(5);
+
+// 單一讀卡機連線並讀取 ATR 的時限；逾時的讀卡機不阻擋其餘讀卡機的掃描結果
+const READER_PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// 以讀卡機名稱為鍵的 ATR 快取，避免每次掃描都重新連線未變更的讀卡機
+struct AtrCache {
+    ttl: Duration,
+    entries: HashMap<String, (Instant, Vec<u8>)>,
+}
+
+impl AtrCache {
+    fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: HashMap::new(),
+        }
+    }
+
+    /// 取得未過期的 ATR
+    fn get(&self, reader: &str, now: Instant) -> Option<Vec<u8>> {
+        self.entries
+            .get(reader)
+            .filter(|(stored, _)| now.saturating_duration_since(*stored) < self.ttl)
+            .map(|(_, atr)| atr.clone())
+    }
+
+    fn insert(&mut self, reader: String, atr: Vec<u8>, now: Instant) {
+        self.entries.insert(reader, (now, atr));
+    }
+
+    /// 移除已不在列表中的讀卡機及過期項目
+    fn retain_readers(&mut self, readers: &[String], now: Instant) {
+        let ttl = self.ttl;
+        self.entries.retain(|name, (stored, _)| {
+            readers.contains(name) && now.saturating_duration_since(*stored) < ttl
+        });
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+/// 以裝置路徑為鍵的 HID 裝置資訊快取（序號 + 韌體版本），
+/// 只在出現新路徑或同一路徑的序號改變時重新讀取
+#[derive(Default)]
+struct HidInfoCache {
+    entries: HashMap<String, (String, String)>,
+}
+
+impl HidInfoCache {
+    /// 取得快取的韌體版本；未命中時以 `read` 讀取並寫入
+    fn firmware_version<F: FnOnce() -> String>(&mut self, path: &str, serial: &str, read: F) -> String {
+        match self.entries.get(path) {
+            Some((cached_serial, version)) if cached_serial == serial => version.clone(),
+            _ => {
+                let version = read();
+                self.entries
+                    .insert(path.to_string(), (serial.to_string(), version.clone()));
+                version
+            }
+        }
+    }
+
+    /// 移除已拔除的裝置
+    fn retain_paths(&mut self, paths: &[String]) {
+        self.entries.retain(|path, _| paths.contains(path));
+    }
+}
+
 /// DeviceManager trait — 裝置偵測、開啟與關閉
 pub trait DeviceManager {
     /// 掃描所有已連接的 Pico 裝置
@@ -28,15 +108,133 @@ pub trait DeviceManager {
     fn close_device(&self, path: &str) -> Result<(), DeviceError>;
 }
 
+/// 已開啟裝置所持有的連線資源
+pub enum DeviceHandle {
+    /// Pico-FIDO 的 HID 連線
+    Hid(hidapi::HidDevice),
+    /// Pico-HSM 的 PC/SC context 與卡片連線
+    Pcsc {
+        context: pcsc::Context,
+        card: Option<pcsc::Card>,
+    },
+}
+
+impl DeviceHandle {
+    /// 釋放連線資源：PC/SC 卡片先斷線再釋放 context，HID 於 drop 時關閉
+    fn close(self) {
+        match self {
+            DeviceHandle::Hid(device) => drop(device),
+            DeviceHandle::Pcsc { context, card } => {
+                if let Some(card) = card {
+                    let _ = card.disconnect(pcsc::Disposition::LeaveCard);
+                }
+                let _ = context.release();
+            }
+        }
+    }
+}
+
 /// DeviceManager 實作
 pub struct DeviceManagerImpl {
-    opened_devices: Mutex<HashSet<String>>,
+    /// 已開啟的裝置路徑，對應其（可選的）連線資源
+    opened_devices: Mutex<HashMap<String, Option<DeviceHandle>>>,
+    /// 讀卡機 ATR 快取
+    atr_cache: Mutex<AtrCache>,
+    /// HID 裝置韌體版本快取
+    hid_info_cache: Mutex<HidInfoCache>,
+    /// 掃描範圍
+    scan_scope: Mutex<ScanScope>,
+    /// 以讀卡機名稱推斷 Pico-HSM 的比對字串
+    hsm_reader_patterns: Mutex<Vec<String>>,
+    /// 探測執行緒尚未結束的讀卡機（逾時後仍持有連線）
+    probes_in_flight: Arc<Mutex<HashSet<String>>>,
+}
+
+impl Default for DeviceManagerImpl {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl DeviceManagerImpl {
     pub fn new() -> Self {
         Self {
-            opened_devices: Mutex::new(HashSet::new()),
+            opened_devices: Mutex::new(HashMap::new()),
+            atr_cache: Mutex::new(AtrCache::new(ATR_CACHE_TTL)),
+            hid_info_cache: Mutex::new(HidInfoCache::default()),
+            scan_scope: Mutex::new(ScanScope::default()),
+            hsm_reader_patterns: Mutex::new(
+                DEFAULT_HSM_READER_PATTERNS.iter().map(|p| p.to_string()).collect(),
+            ),
+            probes_in_flight: Arc::new(Mutex::new(HashSet::new())),
+        }
+    }
+
+    /// 以指定掃描範圍建立
+    pub fn with_scan_scope(scope: ScanScope) -> Self {
+        let dm = Self::new();
+        dm.set_scan_scope(scope);
+        dm
+    }
+
+    /// 設定掃描範圍
+    pub fn set_scan_scope(&self, scope: ScanScope) {
+        if let Ok(mut s) = self.scan_scope.lock() {
+            *s = scope;
+        }
+    }
+
+    /// 取得目前的掃描範圍
+    pub fn scan_scope(&self) -> ScanScope {
+        self.scan_scope.lock().map(|s| *s).unwrap_or_default()
+    }
+
+    /// 設定以讀卡機名稱推斷 Pico-HSM 的比對字串，空白項目會被忽略
+    pub fn set_hsm_reader_patterns(&self, patterns: Vec<String>) {
+        if let Ok(mut p) = self.hsm_reader_patterns.lock() {
+            *p = patterns
+                .into_iter()
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+                .collect();
+        }
+    }
+
+    /// 取得目前的讀卡機名稱比對字串
+    pub fn hsm_reader_patterns(&self) -> Vec<String> {
+        self.hsm_reader_patterns
+            .lock()
+            .map(|p| p.clone())
+            .unwrap_or_default()
+    }
+
+    /// 清除 ATR 快取，下次掃描時重新連線讀取（讀卡機狀態變更時呼叫）
+    pub fn invalidate_atr_cache(&self) {
+        if let Ok(mut cache) = self.atr_cache.lock() {
+            cache.clear();
+        }
+    }
+
+    fn cached_atr(&self, reader: &str, now: Instant) -> Option<Vec<u8>> {
+        self.atr_cache.lock().ok()?.get(reader, now)
+    }
+
+    /// 指定路徑是否已開啟
+    pub fn is_open(&self, path: &str) -> bool {
+        self.opened_devices
+            .lock()
+            .map(|opened| opened.contains_key(path))
+            .unwrap_or(false)
+    }
+
+    /// 關閉所有已開啟的裝置
+    pub fn close_all(&self) {
+        if let Ok(mut opened) = self.opened_devices.lock() {
+            for (_, handle) in opened.drain() {
+                if let Some(handle) = handle {
+                    handle.close();
+                }
+            }
         }
     }
 
@@ -46,7 +244,12 @@ impl DeviceManagerImpl {
             DeviceError::OpenFailed(format!("HID API 初始化失敗: {e}"))
         })?;
 
-        let devices = api
+        let mut cache = self
+            .hid_info_cache
+            .lock()
+            .map_err(|_| DeviceError::OpenFailed("內部鎖定錯誤".into()))?;
+
+        let devices: Vec<DeviceInfo> = api
             .device_list()
             .filter(|dev| {
                 let vid = dev.vendor_id();
@@ -60,94 +263,157 @@ impl DeviceManagerImpl {
                     .serial_number()
                     .unwrap_or("")
                     .to_string();
-                let firmware_version = read_hid_firmware_version(&api, dev);
+                let firmware_version = cache.firmware_version(&path, &serial, || {
+                    firmware_version_from_release(dev.release_number())
+                });
+                let capabilities =
+                    scan_capabilities(DeviceType::PicoFido, &path, &firmware_version);
 
                 DeviceInfo {
                     device_type: DeviceType::PicoFido,
                     serial,
                     firmware_version,
                     path,
+                    availability: DeviceAvailability::Available,
+                    verified: true,
+                    capabilities,
                 }
             })
             .collect();
 
+        let paths: Vec<String> = devices.iter().map(|d| d.path.clone()).collect();
+        cache.retain_paths(&paths);
+
         Ok(devices)
     }
 
     /// 透過 pcsc 掃描 CCID 裝置，篩選 Pico-HSM
     fn scan_ccid_devices(&self) -> Result<Vec<DeviceInfo>, DeviceError> {
-        let ctx = pcsc::Context::establish(pcsc::Scope::User).map_err(|e| {
-            DeviceError::OpenFailed(format!("PC/SC context 建立失敗: {e}"))
-        })?;
+        let shared = crate::pcsc_context::shared();
+        let ctx = match shared.get() {
+            Ok(ctx) => ctx,
+            Err(e) if pcsc_has_no_readers(&e) => return Ok(Vec::new()),
+            Err(e) => {
+                return Err(DeviceError::OpenFailed(format!("PC/SC context 建立失敗: {e}")));
+            }
+        };
 
-        let readers_buf_len = ctx.list_readers_len().map_err(|e| {
-            DeviceError::OpenFailed(format!("無法取得讀卡機列表長度: {e}"))
-        })?;
+        let readers_buf_len = match ctx.list_readers_len() {
+            Ok(len) => len,
+            Err(e) => {
+                shared.report_error(&e);
+                if pcsc_has_no_readers(&e) {
+                    return Ok(Vec::new());
+                }
+                return Err(DeviceError::OpenFailed(format!("無法取得讀卡機列表長度: {e}")));
+            }
+        };
 
         let mut readers_buf = vec![0u8; readers_buf_len];
         let readers = match ctx.list_readers(&mut readers_buf) {
             Ok(r) => r,
-            Err(pcsc::Error::NoReadersAvailable) => return Ok(Vec::new()),
+            Err(e) if pcsc_has_no_readers(&e) => {
+                shared.report_error(&e);
+                return Ok(Vec::new());
+            }
             Err(e) => {
+                shared.report_error(&e);
                 return Err(DeviceError::OpenFailed(format!(
                     "無法列出讀卡機: {e}"
                 )));
             }
         };
 
-        let mut devices = Vec::new();
+        let now = Instant::now();
+        let names: Vec<String> = readers
+            .map(|r| r.to_string_lossy().into_owned())
+            .collect();
+        if let Ok(mut cache) = self.atr_cache.lock() {
+            cache.retain_readers(&names, now);
+        }
+
+        let patterns = self.hsm_reader_patterns();
+        // 未命中快取的讀卡機平行連線，每個讀卡機最多等待 READER_PROBE_TIMEOUT
+        let cached: Vec<(String, Option<Vec<u8>>)> = names
+            .into_iter()
+            .map(|name| {
+                let atr = self.cached_atr(&name, now);
+                (name, atr)
+            })
+            .collect();
+        let uncached = cached
+            .iter()
+            .filter(|(_, atr)| atr.is_none())
+            .map(|(name, _)| name.clone())
+            .collect();
+        let probe_ctx = ctx.clone();
+        let mut probed: HashMap<String, Option<ReaderProbe>> =
+            probe_readers(uncached, READER_PROBE_TIMEOUT, &self.probes_in_flight, move |name| {
+                probe_reader(&probe_ctx, name)
+            })
+            .into_iter()
+            .collect();
 
-        for reader in readers {
-            let card = match ctx.connect(reader, pcsc::ShareMode::Shared, pcsc::Protocols::ANY) {
-                Ok(c) => c,
-                Err(_) => continue, // 無法連線的讀卡機跳過
-            };
+        // 每個讀卡機的裝置，最後去除重複
+        let mut found = Vec::new();
 
-            // 取得 ATR
-            let mut atr_buf = [0u8; pcsc::MAX_ATR_SIZE];
-            let atr_len = match card_atr(&card, &mut atr_buf) {
-                Some(len) => len,
-                None => continue,
+        for (name, cached_atr) in cached {
+            let atr = match cached_atr {
+                Some(atr) => atr,
+                None => match probed.remove(&name).flatten() {
+                    Some(ReaderProbe::Atr(atr)) => {
+                        if let Ok(mut cache) = self.atr_cache.lock() {
+                            cache.insert(name.clone(), atr.clone(), now);
+                        }
+                        atr
+                    }
+                    Some(ReaderProbe::ConnectFailed(e)) => {
+                        log::debug!("connect to reader {name:?} failed: {e}");
+                        shared.report_error(&e);
+                        // 被佔用或暫時無回應的 Pico-HSM 仍列出（無法讀取 ATR，以讀卡機名稱判斷）；
+                        // 其餘無法連線的讀卡機跳過。連線失敗不寫入快取
+                        if let Some(availability) = classify_connect_error(&e) {
+                            if reader_name_suggests_hsm(&name, &patterns) {
+                                found.push(inferred_hsm_device(name, availability));
+                            }
+                        }
+                        continue;
+                    }
+                    // ATR 讀取失敗或連線逾時同樣以讀卡機名稱後備判斷
+                    Some(ReaderProbe::AtrUnreadable) | None => {
+                        log::debug!("reading ATR from {name:?} failed or timed out");
+                        if reader_name_suggests_hsm(&name, &patterns) {
+                            found.push(inferred_hsm_device(name, DeviceAvailability::Unresponsive));
+                        }
+                        continue;
+                    }
+                },
             };
-            let atr = &atr_buf[..atr_len];
 
             // 比對 SmartCard-HSM ATR：搜尋歷史位元組中的 "THSM" 標識
-            if !atr_contains_marker(atr, HSM_ATR_MARKER) {
+            if !atr_contains_marker(&atr, HSM_ATR_MARKER) {
                 continue;
             }
 
-            let path = reader.to_string_lossy().into_owned();
-            let (firmware_version, serial) = read_hsm_info_from_atr(atr);
-
-            devices.push(DeviceInfo {
-                device_type: DeviceType::PicoHsm,
-                serial,
-                firmware_version,
-                path,
-            });
+            // 輪詢期間只讀 ATR、不送 SELECT：SELECT 會重設其他程式在裝置上已驗證的 PIN 狀態。
+            // 選項欄等需 SELECT 取得的資訊留待明確識別裝置時再讀取
+            let mut device = confirmed_hsm_device(name, &atr);
+            device.capabilities =
+                scan_capabilities(DeviceType::PicoHsm, &device.path, &device.firmware_version);
+            found.push(device);
         }
 
-        Ok(devices)
+        Ok(dedup_ccid_devices(found))
     }
 }
 
 impl DeviceManager for DeviceManagerImpl {
     fn scan_devices(&self) -> Result<Vec<DeviceInfo>, DeviceError> {
-        let mut all_devices = Vec::new();
-
-        // 掃描 HID (Pico-FIDO)
-        match self.scan_hid_devices() {
-            Ok(devs) => all_devices.extend(devs),
-            Err(_) => { /* HID 不可用時靜默跳過 */ }
-        }
-
-        // 掃描 CCID (Pico-HSM)
-        match self.scan_ccid_devices() {
-            Ok(devs) => all_devices.extend(devs),
-            Err(_) => { /* PC/SC 不可用時靜默跳過 */ }
-        }
-
-        Ok(all_devices)
+        Ok(scan_in_scope(
+            self.scan_scope(),
+            || self.scan_hid_devices(),
+            || self.scan_ccid_devices(),
+        ))
     }
 
     fn open_device(&self, path: &str) -> Result<(), DeviceError> {
@@ -157,17 +423,21 @@ impl DeviceManager for DeviceManagerImpl {
             .map_err(|_| DeviceError::OpenFailed("內部鎖定錯誤".into()))?;
 
         // 已開啟的裝置直接成功回傳（允許重複選擇）
-        if opened.contains(path) {
+        if opened.contains_key(path) {
             return Ok(());
         }
 
         // 驗證裝置路徑存在（嘗試掃描確認）
         let all = self.scan_devices()?;
-        if !all.iter().any(|d| d.path == path) {
-            return Err(DeviceError::NotFound(path.to_string()));
+        match all.iter().find(|d| d.path == path) {
+            None => return Err(DeviceError::NotFound(path.to_string())),
+            Some(dev) if dev.availability == DeviceAvailability::Busy => {
+                return Err(DeviceError::DeviceBusy);
+            }
+            Some(_) => {}
         }
 
-        opened.insert(path.to_string());
+        opened.insert(path.to_string(), None);
         Ok(())
     }
 
@@ -177,38 +447,301 @@ impl DeviceManager for DeviceManagerImpl {
             .lock()
             .map_err(|_| DeviceError::OpenFailed("內部鎖定錯誤".into()))?;
 
-        if !opened.remove(path) {
-            return Err(DeviceError::NotFound(path.to_string()));
+        match opened.remove(path) {
+            Some(Some(handle)) => handle.close(),
+            Some(None) => {}
+            None => return Err(DeviceError::NotFound(path.to_string())),
         }
 
         Ok(())
     }
 }
 
-/// 嘗試從 HID 裝置讀取韌體版本（透過 CTAP GetInfo）。
-/// 若無法取得則回傳 "unknown"。
-fn read_hid_firmware_version(api: &hidapi::HidApi, dev: &hidapi::DeviceInfo) -> String {
-    // 嘗試開啟裝置並送出 CTAP authenticatorGetInfo (0x04)
-    let device = match api.open_path(dev.path()) {
-        Ok(d) => d,
-        Err(_) => return "unknown".to_string(),
-    };
+impl Drop for DeviceManagerImpl {
+    fn drop(&mut self) {
+        self.close_all();
+    }
+}
 
-    // CTAP HID init + GetInfo 需要完整 CTAPHID 封包，
-    // 此處簡化：使用 product_string 或 release_number 作為版本資訊
-    let version = dev.release_number();
-    if version > 0 {
-        let major = (version >> 8) & 0xFF;
-        let minor = version & 0xFF;
-        // 確保 device 被使用以避免 unused 警告
-        drop(device);
+/// 由 HID 描述的 release_number (bcdDevice) 取得韌體版本，不需開啟裝置
+fn firmware_version_from_release(release: u16) -> String {
+    if release > 0 {
+        let major = (release >> 8) & 0xFF;
+        let minor = release & 0xFF;
         format!("{major}.{minor}")
     } else {
-        drop(device);
         "unknown".to_string()
     }
 }
 
+/// 依掃描範圍呼叫 HID / CCID 掃描器並合併結果；不可用的掃描器靜默跳過
+fn scan_in_scope<H, C>(scope: ScanScope, scan_hid: H, scan_ccid: C) -> Vec<DeviceInfo>
+where
+    H: FnOnce() -> Result<Vec<DeviceInfo>, DeviceError>,
+    C: FnOnce() -> Result<Vec<DeviceInfo>, DeviceError>,
+{
+    log::debug!("scan start: {scope:?}");
+    let mut all_devices = Vec::new();
+
+    // 掃描 HID (Pico-FIDO)
+    if scope.includes_hid() {
+        match scan_hid() {
+            Ok(devs) => all_devices.extend(devs),
+            Err(e) => log::warn!("HID scan failed: {e}"),
+        }
+    }
+
+    // 掃描 CCID (Pico-HSM)
+    if scope.includes_ccid() {
+        match scan_ccid() {
+            Ok(devs) => all_devices.extend(devs),
+            Err(e) => log::warn!("CCID scan failed: {e}"),
+        }
+    }
+
+    log::debug!("scan end: {} device(s)", all_devices.len());
+    all_devices
+}
+
+/// 依裝置類型查詢對應模組並組出能力摘要。
+/// 查詢失敗時不視為錯誤：沿用掃描得到的韌體版本，模組欄位留空。
+pub fn identify_device_with<F, H>(device: &DeviceInfo, fido_info: F, hsm_info: H) -> DeviceCapabilities
+where
+    F: FnOnce() -> Result<FidoDeviceInfo, FidoError>,
+    H: FnOnce() -> Result<(HsmDeviceInfo, bool), HsmError>,
+{
+    let mut caps = base_capabilities(device.device_type.clone(), &device.path, &device.firmware_version);
+    match device.device_type {
+        DeviceType::PicoFido => {
+            if let Ok(info) = fido_info() {
+                caps.firmware_version = info.firmware_version.clone();
+                caps.pin_set = Some(info.pin_set);
+                caps.cred_mgmt = Some(info.supports_cred_mgmt());
+            }
+        }
+        DeviceType::PicoHsm => {
+            if let Ok((info, initialized)) = hsm_info() {
+                caps.firmware_version = info.firmware_version;
+                caps.initialized = Some(initialized);
+                caps.free_memory = Some(info.free_memory);
+                caps.features = Some(info.features);
+            }
+        }
+    }
+    caps
+}
+
+/// 依裝置類型取得穩定的裝置識別碼，不隨重新插拔後改變的路徑或讀卡機名稱變動：
+/// FIDO 為 `fido:<AAGUID>[:<序號>]`（無序號時只能識別型號），HSM 為 `hsm:<裝置認證憑證 CHR>`
+pub fn device_id_with<F, H>(device: &DeviceInfo, fido_info: F, hsm_id: H) -> Result<String, AppError>
+where
+    F: FnOnce() -> Result<FidoDeviceInfo, FidoError>,
+    H: FnOnce() -> Result<String, HsmError>,
+{
+    match device.device_type {
+        DeviceType::PicoFido => {
+            let info = fido_info()?;
+            // GetInfo 未回報序號時改用 USB 描述的序號
+            let serial = info
+                .serial_number
+                .as_deref()
+                .filter(|s| !s.is_empty())
+                .or(Some(device.serial.as_str()).filter(|s| !s.is_empty()));
+            Ok(format_fido_device_id(&info.aaguid, serial))
+        }
+        DeviceType::PicoHsm => Ok(format!("hsm:{}", hsm_id()?)),
+    }
+}
+
+fn format_fido_device_id(aaguid: &str, serial: Option<&str>) -> String {
+    match serial {
+        Some(serial) => format!("fido:{aaguid}:{serial}"),
+        None => format!("fido:{aaguid}"),
+    }
+}
+
+/// 只含識別資訊、其餘欄位未知的能力摘要
+fn base_capabilities(device_type: DeviceType, path: &str, firmware_version: &str) -> DeviceCapabilities {
+    DeviceCapabilities {
+        device_type,
+        path: path.to_string(),
+        firmware_version: firmware_version.to_string(),
+        pin_set: None,
+        cred_mgmt: None,
+        initialized: None,
+        free_memory: None,
+        features: None,
+    }
+}
+
+/// 由掃描時已知的韌體版本（HID release_number 或 ATR）建立能力摘要；版本未知時為 None
+fn scan_capabilities(
+    device_type: DeviceType,
+    path: &str,
+    firmware_version: &str,
+) -> Option<DeviceCapabilities> {
+    (firmware_version != "unknown").then(|| base_capabilities(device_type, path, firmware_version))
+}
+
+/// 沒有讀卡機，或 PC/SC 服務未執行（例如 Windows 在最後一個讀卡機拔除後停止服務）。
+/// 兩者皆代表目前沒有 CCID 裝置，而非掃描失敗
+fn pcsc_has_no_readers(err: &pcsc::Error) -> bool {
+    matches!(err, pcsc::Error::NoReadersAvailable | pcsc::Error::NoService)
+}
+
+/// 將讀卡機連線錯誤分類為裝置可用狀態。
+/// 回傳 `None` 表示該讀卡機不應列出（例如沒有插卡）。
+fn classify_connect_error(err: &pcsc::Error) -> Option<DeviceAvailability> {
+    match err {
+        pcsc::Error::SharingViolation => Some(DeviceAvailability::Busy),
+        pcsc::Error::RemovedCard
+        | pcsc::Error::ResetCard
+        | pcsc::Error::UnresponsiveCard
+        | pcsc::Error::UnpoweredCard => Some(DeviceAvailability::Unresponsive),
+        _ => None,
+    }
+}
+
+/// 從讀卡機名稱判斷是否為 Pico-HSM（無法讀取 ATR 時的後備判斷）
+fn reader_name_suggests_hsm(name: &str, patterns: &[String]) -> bool {
+    let name = name.to_lowercase();
+    patterns
+        .iter()
+        .any(|p| !p.is_empty() && name.contains(&p.to_lowercase()))
+}
+
+/// 未能確認 ATR、僅由讀卡機名稱推斷的 Pico-HSM
+fn inferred_hsm_device(name: String, availability: DeviceAvailability) -> DeviceInfo {
+    DeviceInfo {
+        device_type: DeviceType::PicoHsm,
+        serial: String::new(),
+        firmware_version: "unknown".to_string(),
+        path: name,
+        availability,
+        verified: false,
+        capabilities: None,
+    }
+}
+
+/// ATR 含 SmartCard-HSM 標記、已確認的 Pico-HSM
+fn confirmed_hsm_device(name: String, atr: &[u8]) -> DeviceInfo {
+    let (firmware_version, serial) = read_hsm_info_from_atr(atr);
+    DeviceInfo {
+        device_type: DeviceType::PicoHsm,
+        serial,
+        firmware_version,
+        path: name,
+        availability: DeviceAvailability::Available,
+        verified: true,
+        capabilities: None,
+    }
+}
+
+/// 去除同一實體裝置經多個讀卡機重複出現的項目（Windows 上 CCID 介面可能被列出兩次）。
+/// 僅以自卡片讀到的序號判斷，保留最先列出的讀卡機；相同型號與韌體的裝置 ATR 完全相同，
+/// 因此沒有序號的裝置一律保留
+fn dedup_ccid_devices(found: Vec<DeviceInfo>) -> Vec<DeviceInfo> {
+    let mut seen: HashMap<String, String> = HashMap::new();
+    let mut devices = Vec::with_capacity(found.len());
+    for device in found {
+        if !device.serial.is_empty() {
+            match seen.entry(device.serial.clone()) {
+                Entry::Occupied(first) => {
+                    log::info!(
+                        "reader {:?} exposes the same device as {:?}, skipping",
+                        device.path,
+                        first.get()
+                    );
+                    continue;
+                }
+                Entry::Vacant(slot) => {
+                    slot.insert(device.path.clone());
+                }
+            }
+        }
+        devices.push(device);
+    }
+    devices
+}
+
+/// 單一讀卡機的連線結果
+enum ReaderProbe {
+    Atr(Vec<u8>),
+    ConnectFailed(pcsc::Error),
+    AtrUnreadable,
+}
+
+/// 連線讀卡機並讀取 ATR
+fn probe_reader(ctx: &pcsc::Context, name: &str) -> ReaderProbe {
+    let Ok(reader) = CString::new(name) else {
+        return ReaderProbe::AtrUnreadable;
+    };
+    let card = match ctx.connect(&reader, pcsc::ShareMode::Shared, pcsc::Protocols::ANY) {
+        Ok(card) => card,
+        Err(e) => return ReaderProbe::ConnectFailed(e),
+    };
+    let mut atr_buf = [0u8; pcsc::MAX_ATR_SIZE];
+    match card_atr(&card, &mut atr_buf) {
+        Some(len) => ReaderProbe::Atr(atr_buf[..len].to_vec()),
+        None => ReaderProbe::AtrUnreadable,
+    }
+}
+
+/// 在各自的執行緒中同時探測每個讀卡機，依輸入順序回傳結果。
+/// 所有探測同時開始，因此每個讀卡機各自最多等待 `timeout`；逾時者結果為 None，
+/// 其執行緒結束後的結果直接丟棄。
+/// 逾時的執行緒仍持有該讀卡機的連線，記錄於 `in_flight` 直到結束；
+/// 期間的掃描不再對同一讀卡機開新的探測，結果同樣為 None
+fn probe_readers<R, P>(
+    names: Vec<String>,
+    timeout: Duration,
+    in_flight: &Arc<Mutex<HashSet<String>>>,
+    probe: P,
+) -> Vec<(String, Option<R>)>
+where
+    R: Send + 'static,
+    P: Fn(&str) -> R + Send + Sync + 'static,
+{
+    let probe = Arc::new(probe);
+    let (tx, rx) = mpsc::channel();
+    for (index, name) in names.iter().enumerate() {
+        let started = in_flight
+            .lock()
+            .map(|mut pending| pending.insert(name.clone()))
+            .unwrap_or(true);
+        if !started {
+            log::debug!("previous probe of reader {name:?} is still running, skipping");
+            continue;
+        }
+        let (tx, probe, name) = (tx.clone(), Arc::clone(&probe), name.clone());
+        let in_flight = Arc::clone(in_flight);
+        std::thread::spawn(move || {
+            let result = probe(&name);
+            if let Ok(mut pending) = in_flight.lock() {
+                pending.remove(&name);
+            }
+            let _ = tx.send((index, result));
+        });
+    }
+    drop(tx);
+
+    let mut results: Vec<Option<R>> = names.iter().map(|_| None).collect();
+    let deadline = Instant::now() + timeout;
+    while let Some(remaining) = deadline.checked_duration_since(Instant::now()) {
+        match rx.recv_timeout(remaining) {
+            Ok((index, result)) => results[index] = Some(result),
+            // 逾時，或所有探測皆已回報
+            Err(_) => break,
+        }
+    }
+    for (name, result) in names.iter().zip(&results) {
+        if result.is_none() {
+            log::warn!("reader {name:?} did not respond within {timeout:?}, skipping");
+        }
+    }
+    names.into_iter().zip(results).collect()
+}
+
 /// 檢查 ATR 是否包含指定的標記位元組序列
 fn atr_contains_marker(atr: &[u8], marker: &[u8]) -> bool {
     atr.windows(marker.len()).any(|w| w == marker)
@@ -374,28 +907,225 @@ pub fn check_scard_service_status() -> String {
     }
 }
 
-/// 比較兩個裝置列表是否有變更（以 path 為比較基準）
+/// 比較兩個裝置列表是否有變更（以 path 與可用狀態為比較基準）
 fn devices_changed(previous: &[DeviceInfo], current: &[DeviceInfo]) -> bool {
     if previous.len() != current.len() {
         return true;
     }
-    let prev_paths: HashSet<&str> = previous.iter().map(|d| d.path.as_str()).collect();
-    let curr_paths: HashSet<&str> = current.iter().map(|d| d.path.as_str()).collect();
-    prev_paths != curr_paths
+    let prev: HashSet<(&str, &DeviceAvailability)> =
+        previous.iter().map(|d| (d.path.as_str(), &d.availability)).collect();
+    let curr: HashSet<(&str, &DeviceAvailability)> =
+        current.iter().map(|d| (d.path.as_str(), &d.availability)).collect();
+    prev != curr
 }
 
-/// 啟動背景裝置輪詢，偵測裝置插入與拔除。
-/// 每 2 秒掃描一次，若裝置列表有變更則透過 Tauri 事件 `"device-changed"` 通知前端。
+/// HID 輪詢間隔（亦為不支援 PC/SC 事件時的 CCID 輪詢間隔）
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+/// 掃描連續失敗時的最長輪詢間隔
+const MAX_POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+/// CCID 掃描失敗時的重試退避：每次連續失敗間隔加倍（上限 `MAX_POLL_INTERVAL`），
+/// 成功一次即恢復為 `POLL_INTERVAL`，避免 PC/SC 暫時無法使用時持續以全速重試。
+/// HID 輪詢不受影響
+#[derive(Debug, Default)]
+struct PollBackoff {
+    failures: u32,
+}
+
+impl PollBackoff {
+    fn interval(&self) -> Duration {
+        POLL_INTERVAL
+            .saturating_mul(2u32.saturating_pow(self.failures))
+            .min(MAX_POLL_INTERVAL)
+    }
+
+    fn record_failure(&mut self) {
+        self.failures = self.failures.saturating_add(1);
+    }
+
+    fn record_success(&mut self) {
+        self.failures = 0;
+    }
+}
+
+/// PC/SC 事件監看執行緒傳回的通知
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PcscEvent {
+    /// 讀卡機插拔或卡片狀態變更
+    Changed,
+    /// 平台不支援 PnP 通知或監看失敗，需改回輪詢
+    Unsupported,
+}
+
+/// 比對目前監看中的讀卡機與最新的讀卡機列表，回傳 (新增, 移除) 的名稱
+fn reader_list_diff(watched: &[String], listed: &[String]) -> (Vec<String>, Vec<String>) {
+    let added = listed
+        .iter()
+        .filter(|name| !watched.contains(name))
+        .cloned()
+        .collect();
+    let removed = watched
+        .iter()
+        .filter(|name| !listed.contains(name))
+        .cloned()
+        .collect();
+    (added, removed)
+}
+
+/// `get_status_change` 回傳後，判斷是否有任一讀卡機（含 PnP 通知）狀態變更
+fn reader_states_changed<I: IntoIterator<Item = pcsc::State>>(event_states: I) -> bool {
+    event_states
+        .into_iter()
+        .any(|state| state.contains(pcsc::State::CHANGED))
+}
+
+/// PnP 通知讀卡機回報 UNKNOWN 代表平台不支援讀卡機插拔事件
+fn pnp_notification_unsupported(event_state: pcsc::State) -> bool {
+    event_state.intersects(pcsc::State::UNKNOWN)
+}
+
+/// 以 `SCardGetStatusChange` + PnP 通知監看讀卡機插拔與卡片變更，
+/// 有變更時送出 `PcscEvent::Changed`。無法使用時送出 `Unsupported` 並結束。
+fn watch_pcsc_events(tx: mpsc::Sender<PcscEvent>) {
+    let ctx = match pcsc::Context::establish(pcsc::Scope::User) {
+        Ok(ctx) => ctx,
+        Err(e) => {
+            log::warn!("PC/SC event watcher unavailable: {e}");
+            let _ = tx.send(PcscEvent::Unsupported);
+            return;
+        }
+    };
+
+    // 第 0 筆固定為 PnP 通知
+    let mut states = vec![pcsc::ReaderState::new(
+        pcsc::PNP_NOTIFICATION(),
+        pcsc::State::UNAWARE,
+    )];
+
+    loop {
+        // 同步監看中的讀卡機與目前的讀卡機列表
+        let listed: Vec<String> = match ctx.list_readers_owned() {
+            Ok(names) => names
+                .iter()
+                .map(|n| n.to_string_lossy().into_owned())
+                .collect(),
+            Err(pcsc::Error::NoReadersAvailable) => Vec::new(),
+            Err(e) => {
+                log::warn!("PC/SC event watcher failed to list readers: {e}");
+                let _ = tx.send(PcscEvent::Unsupported);
+                return;
+            }
+        };
+        let watched: Vec<String> = states[1..]
+            .iter()
+            .map(|rs| rs.name().to_string_lossy().into_owned())
+            .collect();
+        let (added, removed) = reader_list_diff(&watched, &listed);
+        states.retain(|rs| !removed.contains(&rs.name().to_string_lossy().into_owned()));
+        for name in added {
+            if let Ok(cname) = CString::new(name) {
+                states.push(pcsc::ReaderState::new(cname, pcsc::State::UNAWARE));
+            }
+        }
+        for rs in &mut states {
+            rs.sync_current_state();
+        }
+
+        match ctx.get_status_change(None, &mut states) {
+            Ok(()) => {}
+            Err(pcsc::Error::Timeout) => continue,
+            Err(e) => {
+                log::warn!("PC/SC status change failed, falling back to polling: {e}");
+                let _ = tx.send(PcscEvent::Unsupported);
+                return;
+            }
+        }
+
+        if pnp_notification_unsupported(states[0].event_state()) {
+            let _ = tx.send(PcscEvent::Unsupported);
+            return;
+        }
+
+        if reader_states_changed(states.iter().map(|rs| rs.event_state()))
+            && tx.send(PcscEvent::Changed).is_err()
+        {
+            return;
+        }
+    }
+}
+
+/// 啟動背景裝置監看，偵測裝置插入與拔除。
+/// CCID (Pico-HSM) 以 PC/SC 狀態變更事件即時偵測，平台不支援時改為每 2 秒輪詢；
+/// hidapi 未提供 hotplug 回呼，HID (Pico-FIDO) 維持每 2 秒輪詢；CCID 掃描失敗時重試間隔逐次加倍。
+/// 裝置列表有變更時透過 Tauri 事件 `"device-changed"` 通知前端。
 pub fn start_device_polling(app: tauri::AppHandle, device_manager: Arc<DeviceManagerImpl>) {
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || watch_pcsc_events(tx));
+
     std::thread::spawn(move || {
         let mut previous_devices: Vec<DeviceInfo> = Vec::new();
+        let mut ccid_devices: Vec<DeviceInfo> = Vec::new();
+        let mut hid_devices: Vec<DeviceInfo> = Vec::new();
+        let mut pcsc_events = true;
+        // CCID 掃描失敗後，即使沒有 PC/SC 事件也需在下次輪詢重試
+        let mut ccid_stale = false;
+        let mut backoff = PollBackoff::default();
+        // CCID 掃描失敗後，下次允許重試的時間
+        let mut ccid_retry_at: Option<Instant> = None;
         loop {
-            std::thread::sleep(Duration::from_secs(2));
-            if let Ok(current) = device_manager.scan_devices() {
-                if devices_changed(&previous_devices, &current) {
-                    let _ = app.emit("device-changed", &current);
-                    previous_devices = current;
+            let rescan_ccid = match rx.recv_timeout(POLL_INTERVAL) {
+                Ok(PcscEvent::Changed) => {
+                    // 卡片狀態已變更，快取的 ATR 可能過時
+                    device_manager.invalidate_atr_cache();
+                    true
+                }
+                Ok(PcscEvent::Unsupported) | Err(mpsc::RecvTimeoutError::Disconnected) => {
+                    pcsc_events = false;
+                    true
+                }
+                Err(mpsc::RecvTimeoutError::Timeout) => !pcsc_events || ccid_stale,
+            };
+            let backing_off = ccid_retry_at.is_some_and(|at| Instant::now() < at);
+            let scope = device_manager.scan_scope();
+            // 掃描失敗時沿用上次的結果，避免裝置列表因暫時性錯誤閃爍
+            if rescan_ccid && !backing_off {
+                let scanned = if scope.includes_ccid() {
+                    device_manager.scan_ccid_devices()
+                } else {
+                    Ok(Vec::new())
+                };
+                ccid_stale = scanned.is_err();
+                match scanned {
+                    Ok(devices) => {
+                        ccid_devices = devices;
+                        backoff.record_success();
+                        ccid_retry_at = None;
+                    }
+                    Err(e) => {
+                        backoff.record_failure();
+                        ccid_retry_at = Some(Instant::now() + backoff.interval());
+                        log::warn!("CCID scan failed, retrying in {:?}: {e}", backoff.interval());
+                    }
                 }
+            } else if rescan_ccid {
+                // 退避期間收到的事件留待下次重試時處理
+                ccid_stale = true;
+            }
+            let scanned = if scope.includes_hid() {
+                device_manager.scan_hid_devices()
+            } else {
+                Ok(Vec::new())
+            };
+            match scanned {
+                Ok(devices) => hid_devices = devices,
+                Err(e) => log::warn!("HID scan failed: {e}"),
+            }
+
+            let mut current = hid_devices.clone();
+            current.extend(ccid_devices.iter().cloned());
+            if devices_changed(&previous_devices, &current) {
+                let _ = app.emit("device-changed", &current);
+                previous_devices = current;
             }
         }
     });
@@ -404,6 +1134,7 @@ pub fn start_device_polling(app: tauri::AppHandle, device_manager: Arc<DeviceMan
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::hsm::types::DeviceFeatures;
 
     #[test]
     fn test_device_manager_new() {
@@ -446,12 +1177,65 @@ mod tests {
         assert_eq!(NITROKEY_FIDO_PID, 0x42B2);
     }
 
+    #[test]
+    fn test_close_device_removes_opened_path() {
+        let dm = DeviceManagerImpl::new();
+        dm.opened_devices
+            .lock()
+            .unwrap()
+            .insert("/dev/hid0".to_string(), None);
+        assert!(dm.is_open("/dev/hid0"));
+
+        dm.close_device("/dev/hid0").unwrap();
+        assert!(!dm.is_open("/dev/hid0"));
+    }
+
+    #[test]
+    fn test_close_device_twice_returns_not_found() {
+        let dm = DeviceManagerImpl::new();
+        dm.opened_devices
+            .lock()
+            .unwrap()
+            .insert("/dev/hid0".to_string(), None);
+
+        dm.close_device("/dev/hid0").unwrap();
+        assert!(matches!(
+            dm.close_device("/dev/hid0"),
+            Err(DeviceError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_close_device_unknown_path() {
+        let dm = DeviceManagerImpl::new();
+        assert!(matches!(
+            dm.close_device("/dev/missing"),
+            Err(DeviceError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_close_all_clears_every_device() {
+        let dm = DeviceManagerImpl::new();
+        {
+            let mut opened = dm.opened_devices.lock().unwrap();
+            opened.insert("/dev/hid0".to_string(), None);
+            opened.insert("reader0".to_string(), None);
+        }
+        dm.close_all();
+        assert!(!dm.is_open("/dev/hid0"));
+        assert!(!dm.is_open("reader0"));
+    }
+
     fn make_device(path: &str, device_type: DeviceType) -> DeviceInfo {
         DeviceInfo {
             device_type,
             serial: String::new(),
             firmware_version: "1.0".to_string(),
             path: path.to_string(),
+            availability: DeviceAvailability::Available,
+            verified: true,
+            capabilities: None,
         }
     }
 
@@ -492,6 +1276,242 @@ mod tests {
         assert!(devices_changed(&a, &b));
     }
 
+    #[test]
+    fn test_devices_changed_availability() {
+        let a = vec![make_device("reader0", DeviceType::PicoHsm)];
+        let mut b = a.clone();
+        b[0].availability = DeviceAvailability::Busy;
+        assert!(devices_changed(&a, &b));
+    }
+
+    // === PC/SC 狀態變更測試 ===
+
+    #[test]
+    fn test_reader_list_diff_added_and_removed() {
+        let watched = vec!["reader0".to_string(), "reader1".to_string()];
+        let listed = vec!["reader1".to_string(), "reader2".to_string()];
+        let (added, removed) = reader_list_diff(&watched, &listed);
+        assert_eq!(added, vec!["reader2".to_string()]);
+        assert_eq!(removed, vec!["reader0".to_string()]);
+    }
+
+    #[test]
+    fn test_poll_backoff_grows_and_caps() {
+        let mut backoff = PollBackoff::default();
+        assert_eq!(backoff.interval(), POLL_INTERVAL);
+        let intervals: Vec<u64> = (0..6)
+            .map(|_| {
+                backoff.record_failure();
+                backoff.interval().as_secs()
+            })
+            .collect();
+        assert_eq!(intervals, vec![4, 8, 16, 30, 30, 30]);
+    }
+
+    #[test]
+    fn test_poll_backoff_resets_on_success() {
+        let mut backoff = PollBackoff::default();
+        for _ in 0..3 {
+            backoff.record_failure();
+        }
+        backoff.record_success();
+        assert_eq!(backoff.interval(), POLL_INTERVAL);
+        backoff.record_failure();
+        assert_eq!(backoff.interval(), POLL_INTERVAL * 2);
+    }
+
+    #[test]
+    fn test_poll_backoff_survives_many_failures() {
+        let mut backoff = PollBackoff { failures: u32::MAX - 1 };
+        backoff.record_failure();
+        backoff.record_failure();
+        assert_eq!(backoff.interval(), MAX_POLL_INTERVAL);
+    }
+
+    #[test]
+    fn test_reader_list_diff_unchanged() {
+        let names = vec!["reader0".to_string()];
+        let (added, removed) = reader_list_diff(&names, &names);
+        assert!(added.is_empty());
+        assert!(removed.is_empty());
+    }
+
+    #[test]
+    fn test_reader_states_changed() {
+        assert!(!reader_states_changed([
+            pcsc::State::PRESENT,
+            pcsc::State::EMPTY,
+        ]));
+        assert!(reader_states_changed([
+            pcsc::State::PRESENT,
+            pcsc::State::CHANGED | pcsc::State::EMPTY,
+        ]));
+        assert!(!reader_states_changed(Vec::new()));
+    }
+
+    #[test]
+    fn test_pnp_notification_unsupported() {
+        assert!(pnp_notification_unsupported(pcsc::State::UNKNOWN));
+        assert!(pnp_notification_unsupported(
+            pcsc::State::UNKNOWN | pcsc::State::CHANGED
+        ));
+        assert!(!pnp_notification_unsupported(pcsc::State::CHANGED));
+    }
+
+    // === 讀卡機連線錯誤分類測試 ===
+
+    #[test]
+    fn test_no_readers_and_no_service_are_empty_scans() {
+        assert!(pcsc_has_no_readers(&pcsc::Error::NoReadersAvailable));
+        assert!(pcsc_has_no_readers(&pcsc::Error::NoService));
+        assert!(!pcsc_has_no_readers(&pcsc::Error::ServiceStopped));
+        assert!(!pcsc_has_no_readers(&pcsc::Error::InvalidHandle));
+    }
+
+    #[test]
+    fn test_classify_connect_error_sharing_violation_is_busy() {
+        assert_eq!(
+            classify_connect_error(&pcsc::Error::SharingViolation),
+            Some(DeviceAvailability::Busy)
+        );
+    }
+
+    #[test]
+    fn test_classify_connect_error_card_state_is_unresponsive() {
+        for err in [
+            pcsc::Error::RemovedCard,
+            pcsc::Error::ResetCard,
+            pcsc::Error::UnresponsiveCard,
+            pcsc::Error::UnpoweredCard,
+        ] {
+            assert_eq!(
+                classify_connect_error(&err),
+                Some(DeviceAvailability::Unresponsive)
+            );
+        }
+    }
+
+    #[test]
+    fn test_classify_connect_error_other_is_skipped() {
+        assert_eq!(classify_connect_error(&pcsc::Error::NoSmartcard), None);
+        assert_eq!(classify_connect_error(&pcsc::Error::UnknownReader), None);
+    }
+
+    fn default_patterns() -> Vec<String> {
+        DEFAULT_HSM_READER_PATTERNS.iter().map(|p| p.to_string()).collect()
+    }
+
+    #[test]
+    fn test_reader_name_suggests_hsm() {
+        let patterns = default_patterns();
+        assert!(reader_name_suggests_hsm("Pol Henarejos Pico HSM 0", &patterns));
+        assert!(reader_name_suggests_hsm("SmartCard-HSM [CCID Interface] 00 00", &patterns));
+        assert!(!reader_name_suggests_hsm("Generic USB Smart Card Reader 0", &patterns));
+    }
+
+    #[test]
+    fn test_reader_name_suggests_hsm_custom_patterns() {
+        let patterns = vec!["pico key".to_string()];
+        assert!(reader_name_suggests_hsm("Pol Henarejos Pico Key 0", &patterns));
+        assert!(!reader_name_suggests_hsm("Pol Henarejos Pico HSM 0", &patterns));
+        assert!(!reader_name_suggests_hsm("Pol Henarejos Pico HSM 0", &[]));
+        assert!(!reader_name_suggests_hsm("Any Reader", &[String::new()]));
+    }
+
+    #[test]
+    fn test_set_hsm_reader_patterns_drops_blank_entries() {
+        let dm = DeviceManagerImpl::new();
+        assert_eq!(dm.hsm_reader_patterns(), default_patterns());
+        dm.set_hsm_reader_patterns(vec![" Pico Key ".to_string(), "  ".to_string()]);
+        assert_eq!(dm.hsm_reader_patterns(), vec!["Pico Key".to_string()]);
+    }
+
+    #[test]
+    fn test_inferred_hsm_device() {
+        let dev = inferred_hsm_device("Pico HSM 0".to_string(), DeviceAvailability::Busy);
+        assert_eq!(dev.device_type, DeviceType::PicoHsm);
+        assert_eq!(dev.path, "Pico HSM 0");
+        assert_eq!(dev.firmware_version, "unknown");
+        assert_eq!(dev.availability, DeviceAvailability::Busy);
+        assert!(!dev.verified);
+    }
+
+    #[test]
+    fn test_confirmed_hsm_device_is_verified() {
+        let atr = [
+            0x3B, 0xFE, 0x18, 0x00, 0x00, 0x81, 0x31, 0xFE, 0x45, 0x80,
+            0x31, 0x81, 0x54, 0x48, 0x53, 0x4D, 0x31, 0x73, 0x80, 0x21,
+            0x03, 0x05, 0x07, 0xFA,
+        ];
+        let dev = confirmed_hsm_device("Pico HSM 0".to_string(), &atr);
+        assert_eq!(dev.device_type, DeviceType::PicoHsm);
+        assert_eq!(dev.availability, DeviceAvailability::Available);
+        assert_eq!(dev.firmware_version, "3.5");
+        assert!(dev.verified);
+    }
+
+    // === CCID 重複讀卡機測試 ===
+
+    const PICO_HSM_ATR: [u8; 24] = [
+        0x3B, 0xFE, 0x18, 0x00, 0x00, 0x81, 0x31, 0xFE, 0x45, 0x80,
+        0x31, 0x81, 0x54, 0x48, 0x53, 0x4D, 0x31, 0x73, 0x80, 0x21,
+        0x03, 0x05, 0x07, 0xFA,
+    ];
+
+    fn ccid_entry(name: &str, serial: &str) -> DeviceInfo {
+        DeviceInfo {
+            serial: serial.to_string(),
+            ..confirmed_hsm_device(name.to_string(), &PICO_HSM_ATR)
+        }
+    }
+
+    #[test]
+    fn test_dedup_ccid_same_serial_keeps_first_reader() {
+        let devices = dedup_ccid_devices(vec![
+            ccid_entry("Pico HSM 0", "ESPICOHSM01"),
+            ccid_entry("Pico HSM 1", "ESPICOHSM01"),
+        ]);
+        assert_eq!(devices.len(), 1);
+        assert_eq!(devices[0].path, "Pico HSM 0");
+    }
+
+    #[test]
+    fn test_dedup_ccid_identical_atr_without_serial_kept() {
+        // 兩台相同的 Pico-HSM：ATR 相同且掃描時讀不到序號
+        let devices = dedup_ccid_devices(vec![
+            confirmed_hsm_device("Pico HSM 0".to_string(), &PICO_HSM_ATR),
+            confirmed_hsm_device("Pico HSM 1".to_string(), &PICO_HSM_ATR),
+        ]);
+        let paths: Vec<&str> = devices.iter().map(|d| d.path.as_str()).collect();
+        assert_eq!(paths, vec!["Pico HSM 0", "Pico HSM 1"]);
+    }
+
+    #[test]
+    fn test_dedup_ccid_distinct_serials_kept() {
+        let devices = dedup_ccid_devices(vec![
+            ccid_entry("Pico HSM 0", "ESPICOHSM01"),
+            ccid_entry("Pico HSM 1", "ESPICOHSM02"),
+        ]);
+        assert_eq!(devices.len(), 2);
+    }
+
+    #[test]
+    fn test_dedup_ccid_keeps_inferred_devices() {
+        let devices = dedup_ccid_devices(vec![
+            ccid_entry("Pico HSM 0", "ESPICOHSM01"),
+            inferred_hsm_device("Pico HSM 1".to_string(), DeviceAvailability::Busy),
+            inferred_hsm_device("Pico HSM 2".to_string(), DeviceAvailability::Busy),
+        ]);
+        assert_eq!(devices.len(), 3);
+    }
+
+    #[test]
+    fn test_device_info_verified_defaults_to_false() {
+        let json = r#"{"device_type":"PicoHsm","serial":"","firmware_version":"unknown","path":"r0"}"#;
+        let dev: DeviceInfo = serde_json::from_str(json).unwrap();
+        assert!(!dev.verified);
+    }
+
     #[test]
     fn test_devices_changed_order_independent() {
         let a = vec![
@@ -504,4 +1524,462 @@ mod tests {
         ];
         assert!(!devices_changed(&a, &b));
     }
+
+    // === ATR 快取測試 ===
+
+    #[test]
+    fn test_atr_cache_hit_within_ttl() {
+        let mut cache = AtrCache::new(Duration::from_secs(5));
+        let t0 = Instant::now();
+        cache.insert("Reader A".to_string(), vec![0x3B, 0x01], t0);
+        assert_eq!(
+            cache.get("Reader A", t0 + Duration::from_secs(4)),
+            Some(vec![0x3B, 0x01])
+        );
+        assert_eq!(cache.get("Reader B", t0), None);
+    }
+
+    #[test]
+    fn test_atr_cache_expires_after_ttl() {
+        let mut cache = AtrCache::new(Duration::from_secs(5));
+        let t0 = Instant::now();
+        cache.insert("Reader A".to_string(), vec![0x3B], t0);
+        assert_eq!(cache.get("Reader A", t0 + Duration::from_secs(5)), None);
+    }
+
+    #[test]
+    fn test_atr_cache_insert_refreshes_timestamp() {
+        let mut cache = AtrCache::new(Duration::from_secs(5));
+        let t0 = Instant::now();
+        cache.insert("Reader A".to_string(), vec![0x01], t0);
+        cache.insert("Reader A".to_string(), vec![0x02], t0 + Duration::from_secs(4));
+        assert_eq!(
+            cache.get("Reader A", t0 + Duration::from_secs(8)),
+            Some(vec![0x02])
+        );
+    }
+
+    #[test]
+    fn test_atr_cache_retain_drops_removed_and_expired_readers() {
+        let mut cache = AtrCache::new(Duration::from_secs(5));
+        let t0 = Instant::now();
+        cache.insert("Reader A".to_string(), vec![0x01], t0);
+        cache.insert("Reader B".to_string(), vec![0x02], t0);
+        cache.insert("Reader C".to_string(), vec![0x03], t0 + Duration::from_secs(4));
+
+        let listed = vec!["Reader B".to_string(), "Reader C".to_string()];
+        cache.retain_readers(&listed, t0 + Duration::from_secs(6));
+
+        assert_eq!(cache.entries.len(), 1);
+        assert!(cache.entries.contains_key("Reader C"));
+    }
+
+    #[test]
+    fn test_invalidate_atr_cache() {
+        let dm = DeviceManagerImpl::new();
+        let now = Instant::now();
+        dm.atr_cache
+            .lock()
+            .unwrap()
+            .insert("Reader A".to_string(), vec![0x3B], now);
+        assert!(dm.cached_atr("Reader A", now).is_some());
+        dm.invalidate_atr_cache();
+        assert!(dm.cached_atr("Reader A", now).is_none());
+    }
+
+    // === 讀卡機探測時限測試 ===
+
+    fn reader_names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    fn no_probes_in_flight() -> Arc<Mutex<HashSet<String>>> {
+        Arc::new(Mutex::new(HashSet::new()))
+    }
+
+    #[test]
+    fn test_probe_readers_keeps_order() {
+        let results = probe_readers(
+            reader_names(&["Reader A", "Reader B", "Reader C"]),
+            Duration::from_secs(5),
+            &no_probes_in_flight(),
+            |name| name.len(),
+        );
+        assert_eq!(
+            results,
+            vec![
+                ("Reader A".to_string(), Some(8)),
+                ("Reader B".to_string(), Some(8)),
+                ("Reader C".to_string(), Some(8)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_probe_readers_slow_reader_does_not_block_others() {
+        let start = Instant::now();
+        let results = probe_readers(
+            reader_names(&["Stuck Reader", "Pico HSM"]),
+            Duration::from_millis(100),
+            &no_probes_in_flight(),
+            |name| {
+                if name == "Stuck Reader" {
+                    std::thread::sleep(Duration::from_secs(2));
+                }
+                name.to_string()
+            },
+        );
+        assert!(start.elapsed() < Duration::from_secs(1));
+        assert_eq!(results[0], ("Stuck Reader".to_string(), None));
+        assert_eq!(results[1], ("Pico HSM".to_string(), Some("Pico HSM".to_string())));
+    }
+
+    #[test]
+    fn test_probe_readers_time_box_is_per_reader_not_cumulative() {
+        // 四個各需 100ms 的讀卡機同時探測，依序探測會超過 300ms 的時限
+        let results = probe_readers(
+            reader_names(&["A", "B", "C", "D"]),
+            Duration::from_millis(300),
+            &no_probes_in_flight(),
+            |_| std::thread::sleep(Duration::from_millis(100)),
+        );
+        assert!(results.iter().all(|(_, result)| result.is_some()));
+    }
+
+    #[test]
+    fn test_probe_readers_skips_reader_with_probe_in_flight() {
+        let in_flight = no_probes_in_flight();
+        let names = reader_names(&["Stuck Reader"]);
+        let probes = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counter = Arc::clone(&probes);
+        let slow_probe = move |_: &str| {
+            counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            std::thread::sleep(Duration::from_millis(300));
+        };
+        let timeout = Duration::from_millis(50);
+        let first = probe_readers(names.clone(), timeout, &in_flight, slow_probe.clone());
+        assert_eq!(first[0].1, None);
+        assert!(in_flight.lock().unwrap().contains("Stuck Reader"));
+
+        // 前一次的探測仍在執行，不重複連線
+        let second = probe_readers(names.clone(), timeout, &in_flight, slow_probe.clone());
+        assert_eq!(second[0].1, None);
+        assert_eq!(probes.load(std::sync::atomic::Ordering::SeqCst), 1);
+
+        // 執行緒結束後即可再次探測
+        std::thread::sleep(Duration::from_millis(400));
+        assert!(in_flight.lock().unwrap().is_empty());
+        let third = probe_readers(names, Duration::from_secs(1), &in_flight, slow_probe);
+        assert_eq!(third[0].1, Some(()));
+        assert_eq!(probes.load(std::sync::atomic::Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_probe_readers_empty_returns_immediately() {
+        let start = Instant::now();
+        let results =
+            probe_readers(Vec::new(), Duration::from_secs(5), &no_probes_in_flight(), |_| ());
+        assert!(results.is_empty());
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    // === HID 裝置資訊快取測試 ===
+
+    #[test]
+    fn test_firmware_version_from_release() {
+        assert_eq!(firmware_version_from_release(0x0602), "6.2");
+        assert_eq!(firmware_version_from_release(0), "unknown");
+    }
+
+    #[test]
+    fn test_hid_info_cache_reads_once_per_path() {
+        let mut cache = HidInfoCache::default();
+        let mut reads = 0;
+        for _ in 0..3 {
+            let version = cache.firmware_version("/dev/hidraw0", "ABC", || {
+                reads += 1;
+                "6.2".to_string()
+            });
+            assert_eq!(version, "6.2");
+        }
+        assert_eq!(reads, 1);
+    }
+
+    #[test]
+    fn test_hid_info_cache_rereads_new_path_or_serial() {
+        let mut cache = HidInfoCache::default();
+        cache.firmware_version("/dev/hidraw0", "ABC", || "6.2".to_string());
+        assert_eq!(
+            cache.firmware_version("/dev/hidraw1", "ABC", || "6.4".to_string()),
+            "6.4"
+        );
+        // 同一路徑換了另一支裝置
+        assert_eq!(
+            cache.firmware_version("/dev/hidraw0", "XYZ", || "5.0".to_string()),
+            "5.0"
+        );
+    }
+
+    #[test]
+    fn test_hid_info_cache_retain_paths() {
+        let mut cache = HidInfoCache::default();
+        cache.firmware_version("/dev/hidraw0", "A", || "1.0".to_string());
+        cache.firmware_version("/dev/hidraw1", "B", || "1.0".to_string());
+        cache.retain_paths(&["/dev/hidraw1".to_string()]);
+        assert_eq!(cache.entries.len(), 1);
+        assert!(cache.entries.contains_key("/dev/hidraw1"));
+    }
+
+    // === 裝置識別測試 ===
+
+    fn fido_info() -> FidoDeviceInfo {
+        FidoDeviceInfo {
+            versions: vec![],
+            extensions: vec![],
+            aaguid: String::new(),
+            model_name: None,
+            firmware_version: "6.2".to_string(),
+            serial_number: None,
+            pin_set: true,
+            pin_retries: 8,
+            options: HashMap::from([("credMgmt".to_string(), true)]),
+            capabilities: Default::default(),
+            min_pin_length: None,
+            force_pin_change: false,
+            pin_uv_auth_protocols: vec![],
+        }
+    }
+
+    fn hsm_info() -> HsmDeviceInfo {
+        HsmDeviceInfo {
+            firmware_version: "5.0".to_string(),
+            serial_number: String::new(),
+            free_memory: 4096,
+            used_memory: 0,
+            total_memory: 0,
+            file_count: 0,
+            available_ram: 0,
+            dkek_shares_total: None,
+            dkek_shares_missing: None,
+            life_cycle: None,
+            features: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_identify_fido_device() {
+        let device = make_device("/dev/hidraw0", DeviceType::PicoFido);
+        let caps = identify_device_with(
+            &device,
+            || Ok(fido_info()),
+            || panic!("HSM 查詢不應被呼叫"),
+        );
+        assert_eq!(caps.device_type, DeviceType::PicoFido);
+        assert_eq!(caps.firmware_version, "6.2");
+        assert_eq!(caps.pin_set, Some(true));
+        assert_eq!(caps.cred_mgmt, Some(true));
+        assert_eq!(caps.initialized, None);
+    }
+
+    #[test]
+    fn test_identify_hsm_device() {
+        let device = make_device("Reader 0", DeviceType::PicoHsm);
+        let caps = identify_device_with(
+            &device,
+            || panic!("FIDO 查詢不應被呼叫"),
+            || Ok((hsm_info(), false)),
+        );
+        assert_eq!(caps.device_type, DeviceType::PicoHsm);
+        assert_eq!(caps.firmware_version, "5.0");
+        assert_eq!(caps.initialized, Some(false));
+        assert_eq!(caps.free_memory, Some(4096));
+        assert_eq!(caps.features, Some(DeviceFeatures::default()));
+        assert_eq!(caps.pin_set, None);
+    }
+
+    // === 裝置識別碼測試 ===
+
+    #[test]
+    fn test_device_id_fido_uses_aaguid_and_usb_serial() {
+        let mut device = make_device("/dev/hidraw0", DeviceType::PicoFido);
+        device.serial = "E66130B4A3A1".to_string();
+        let id = device_id_with(
+            &device,
+            || {
+                let mut info = fido_info();
+                info.aaguid = "89b19028-256b-4025-8872-255358d950e4".to_string();
+                Ok(info)
+            },
+            || panic!("HSM 查詢不應被呼叫"),
+        )
+        .unwrap();
+        assert_eq!(id, "fido:89b19028-256b-4025-8872-255358d950e4:E66130B4A3A1");
+    }
+
+    #[test]
+    fn test_device_id_fido_prefers_get_info_serial() {
+        let mut device = make_device("/dev/hidraw0", DeviceType::PicoFido);
+        device.serial = "USB".to_string();
+        let mut info = fido_info();
+        info.aaguid = "00010203-0405-0607-0809-0a0b0c0d0e0f".to_string();
+        info.serial_number = Some("CTAP-1".to_string());
+        let id = device_id_with(&device, || Ok(info), || panic!("HSM 查詢不應被呼叫")).unwrap();
+        assert_eq!(id, "fido:00010203-0405-0607-0809-0a0b0c0d0e0f:CTAP-1");
+    }
+
+    #[test]
+    fn test_device_id_fido_without_serial() {
+        assert_eq!(format_fido_device_id("aaguid", None), "fido:aaguid");
+    }
+
+    #[test]
+    fn test_device_id_hsm_uses_chr() {
+        let device = make_device("Reader 0", DeviceType::PicoHsm);
+        let id = device_id_with(
+            &device,
+            || panic!("FIDO 查詢不應被呼叫"),
+            || Ok("ESPICOHSMTR00001".to_string()),
+        )
+        .unwrap();
+        assert_eq!(id, "hsm:ESPICOHSMTR00001");
+    }
+
+    #[test]
+    fn test_device_id_propagates_query_error() {
+        let device = make_device("Reader 0", DeviceType::PicoHsm);
+        let result = device_id_with(&device, || panic!("FIDO 查詢不應被呼叫"), || Err(HsmError::Timeout));
+        assert!(matches!(result, Err(AppError::Hsm(HsmError::Timeout))));
+    }
+
+    // === 掃描時能力摘要測試 ===
+
+    #[test]
+    fn test_scan_capabilities_from_release_number() {
+        let version = firmware_version_from_release(0x0602);
+        let caps = scan_capabilities(DeviceType::PicoFido, "/dev/hidraw0", &version).unwrap();
+        assert_eq!(caps.device_type, DeviceType::PicoFido);
+        assert_eq!(caps.path, "/dev/hidraw0");
+        assert_eq!(caps.firmware_version, "6.2");
+        assert_eq!(caps.pin_set, None);
+        assert_eq!(caps.features, None);
+    }
+
+    #[test]
+    fn test_scan_capabilities_unknown_version() {
+        let version = firmware_version_from_release(0);
+        assert_eq!(scan_capabilities(DeviceType::PicoFido, "/dev/hidraw0", &version), None);
+    }
+
+    #[test]
+    fn test_scan_capabilities_hsm_omits_select_only_fields() {
+        // 輪詢不送 SELECT，選項欄與初始化狀態需待明確識別時才取得
+        let caps = scan_capabilities(DeviceType::PicoHsm, "Pico HSM 0", "5.4").unwrap();
+        assert_eq!(caps.device_type, DeviceType::PicoHsm);
+        assert_eq!(caps.firmware_version, "5.4");
+        assert_eq!(caps.features, None);
+        assert_eq!(caps.initialized, None);
+    }
+
+    #[test]
+    fn test_identify_falls_back_on_query_failure() {
+        let device = make_device("Reader 0", DeviceType::PicoHsm);
+        let caps = identify_device_with(
+            &device,
+            || panic!("FIDO 查詢不應被呼叫"),
+            || Err(HsmError::CommunicationError("x".to_string())),
+        );
+        assert_eq!(caps.firmware_version, device.firmware_version);
+        assert_eq!(caps.initialized, None);
+        assert_eq!(caps.free_memory, None);
+    }
+
+    // === 掃描範圍測試 ===
+
+    #[test]
+    fn test_scan_scope_predicates() {
+        assert!(ScanScope::Both.includes_hid() && ScanScope::Both.includes_ccid());
+        assert!(ScanScope::HidOnly.includes_hid() && !ScanScope::HidOnly.includes_ccid());
+        assert!(!ScanScope::CcidOnly.includes_hid() && ScanScope::CcidOnly.includes_ccid());
+        assert_eq!(ScanScope::default(), ScanScope::Both);
+    }
+
+    fn scanned_types(scope: ScanScope) -> Vec<DeviceType> {
+        scan_in_scope(
+            scope,
+            || Ok(vec![make_device("hid", DeviceType::PicoFido)]),
+            || Ok(vec![make_device("ccid", DeviceType::PicoHsm)]),
+        )
+        .into_iter()
+        .map(|d| d.device_type)
+        .collect()
+    }
+
+    #[test]
+    fn test_scan_in_scope_invokes_only_intended_scanner() {
+        assert_eq!(scanned_types(ScanScope::Both), vec![DeviceType::PicoFido, DeviceType::PicoHsm]);
+        assert_eq!(scanned_types(ScanScope::HidOnly), vec![DeviceType::PicoFido]);
+        assert_eq!(scanned_types(ScanScope::CcidOnly), vec![DeviceType::PicoHsm]);
+
+        scan_in_scope(ScanScope::HidOnly, || Ok(vec![]), || panic!("不應掃描 CCID"));
+        scan_in_scope(ScanScope::CcidOnly, || panic!("不應掃描 HID"), || Ok(vec![]));
+    }
+
+    #[test]
+    fn test_scan_in_scope_skips_failed_scanner() {
+        let devices = scan_in_scope(
+            ScanScope::Both,
+            || Err(DeviceError::OpenFailed("HID".to_string())),
+            || Ok(vec![make_device("ccid", DeviceType::PicoHsm)]),
+        );
+        assert_eq!(devices.len(), 1);
+    }
+
+    #[test]
+    fn test_set_scan_scope() {
+        let dm = DeviceManagerImpl::with_scan_scope(ScanScope::CcidOnly);
+        assert_eq!(dm.scan_scope(), ScanScope::CcidOnly);
+        dm.set_scan_scope(ScanScope::Both);
+        assert_eq!(dm.scan_scope(), ScanScope::Both);
+    }
+
+    // === 日誌測試 ===
+
+    struct CaptureLogger {
+        records: Mutex<Vec<(log::Level, String)>>,
+    }
+
+    impl log::Log for CaptureLogger {
+        fn enabled(&self, _metadata: &log::Metadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &log::Record) {
+            if let Ok(mut records) = self.records.lock() {
+                records.push((record.level(), record.args().to_string()));
+            }
+        }
+
+        fn flush(&self) {}
+    }
+
+    static CAPTURE: CaptureLogger = CaptureLogger {
+        records: Mutex::new(Vec::new()),
+    };
+
+    #[test]
+    fn test_failed_scan_emits_warn_record() {
+        let _ = log::set_logger(&CAPTURE);
+        log::set_max_level(log::LevelFilter::Trace);
+
+        scan_in_scope(
+            ScanScope::HidOnly,
+            || Err(DeviceError::OpenFailed("capture-marker".to_string())),
+            || Ok(vec![]),
+        );
+
+        let records = CAPTURE.records.lock().unwrap();
+        assert!(records
+            .iter()
+            .any(|(level, msg)| *level == log::Level::Warn && msg.contains("capture-marker")));
+    }
 }
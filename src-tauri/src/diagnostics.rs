@@ -0,0 +1,295 @@
+use serde::{Deserialize, Serialize};
+
+use crate::error::HsmError;
+use crate::fido::cbor::{CborCodec, CborCodecImpl};
+use crate::fido::types::{
+    AuthConfigSubCommand, ClientPinSubCommand, CredMgmtSubCommand, CtapCommand,
+};
+use crate::fido::FidoModuleImpl;
+use crate::hsm::apdu::{ApduCodec, ApduCodecImpl};
+use crate::hsm::types::{ApduCommand, ApduResponse};
+use crate::hsm::HsmModuleImpl;
+
+/// 單項自我檢測結果
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TestResult {
+    pub name: String,
+    pub passed: bool,
+    /// 失敗時的說明
+    pub detail: Option<String>,
+}
+
+impl TestResult {
+    fn from_check(name: &str, result: Result<(), String>) -> Self {
+        Self {
+            name: name.to_string(),
+            passed: result.is_ok(),
+            detail: result.err(),
+        }
+    }
+}
+
+/// 不需連接裝置的自我檢測：APDU / CBOR 編解碼、狀態碼對應與 PIN 驗證邊界
+pub fn self_test() -> Vec<TestResult> {
+    let mut results = Vec::new();
+    results.extend(apdu_round_trips());
+    results.extend(cbor_command_encodes());
+    results.extend(status_mappings());
+    results.extend(pin_validators());
+    results
+}
+
+// === APDU ===
+
+fn apdu(data: Option<Vec<u8>>, le: Option<u16>) -> ApduCommand {
+    ApduCommand {
+        cla: 0x80,
+        ins: 0x64,
+        p1: 0x01,
+        p2: 0x02,
+        data,
+        le,
+    }
+}
+
+/// 依 ISO 7816-4 將編碼後的指令解析回 ApduCommand（僅供自我檢測比對）
+fn parse_command(raw: &[u8]) -> Option<ApduCommand> {
+    let (header, body) = raw.split_at_checked(4)?;
+    let short_le = |b: u8| if b == 0 { 256 } else { u16::from(b) };
+    let (data, le) = match body {
+        [] => (None, None),
+        [le] => (None, Some(short_le(*le))),
+        [0x00, hi, lo] => (None, Some(u16::from_be_bytes([*hi, *lo]))),
+        [0x00, hi, lo, rest @ ..] => {
+            let lc = usize::from(u16::from_be_bytes([*hi, *lo]));
+            match rest.len() {
+                n if n == lc => (Some(rest.to_vec()), None),
+                n if n == lc + 2 => (
+                    Some(rest[..lc].to_vec()),
+                    Some(u16::from_be_bytes([rest[lc], rest[lc + 1]])),
+                ),
+                _ => return None,
+            }
+        }
+        [lc, rest @ ..] => {
+            let lc = usize::from(*lc);
+            match rest.len() {
+                n if n == lc => (Some(rest.to_vec()), None),
+                n if n == lc + 1 => (Some(rest[..lc].to_vec()), Some(short_le(rest[lc]))),
+                _ => return None,
+            }
+        }
+    };
+    Some(ApduCommand {
+        cla: header[0],
+        ins: header[1],
+        p1: header[2],
+        p2: header[3],
+        data,
+        le,
+    })
+}
+
+fn apdu_round_trips() -> Vec<TestResult> {
+    let codec = ApduCodecImpl::new();
+    let cases = [
+        ("APDU case 1", apdu(None, None)),
+        ("APDU case 2", apdu(None, Some(256))),
+        ("APDU case 3", apdu(Some(vec![0x01, 0x02, 0x03]), None)),
+        ("APDU case 4", apdu(Some(vec![0xAA; 255]), Some(16))),
+        ("APDU extended case 2", apdu(None, Some(1024))),
+        ("APDU extended case 3", apdu(Some(vec![0x5A; 300]), None)),
+        ("APDU extended case 4", apdu(Some(vec![0x5A; 300]), Some(512))),
+    ];
+
+    let mut results: Vec<TestResult> = cases
+        .into_iter()
+        .map(|(name, cmd)| {
+            let encoded = codec.encode_apdu(&cmd);
+            let check = match parse_command(&encoded) {
+                Some(decoded) if decoded == cmd => Ok(()),
+                Some(decoded) => Err(format!("解碼結果不符: {decoded:?}")),
+                None => Err(format!("無法解析編碼結果 ({} bytes)", encoded.len())),
+            };
+            TestResult::from_check(name, check)
+        })
+        .collect();
+
+    let expected = ApduResponse {
+        data: vec![0x01, 0x02],
+        sw1: 0x90,
+        sw2: 0x00,
+    };
+    let check = match codec.decode_apdu_response(&[0x01, 0x02, 0x90, 0x00]) {
+        Ok(resp) if resp == expected => Ok(()),
+        Ok(resp) => Err(format!("解碼結果不符: {resp:?}")),
+        Err(e) => Err(e.to_string()),
+    };
+    results.push(TestResult::from_check("APDU response decode", check));
+
+    let check = match codec.decode_apdu_response(&[0x90]) {
+        Err(_) => Ok(()),
+        Ok(resp) => Err(format!("不完整回應應被拒絕: {resp:?}")),
+    };
+    results.push(TestResult::from_check("APDU truncated response", check));
+
+    results
+}
+
+// === CBOR ===
+
+fn cbor_command_encodes() -> Vec<TestResult> {
+    let codec = CborCodecImpl::new();
+    let cases = [
+        ("CTAP GetInfo", CtapCommand::GetInfo, 0x04),
+        ("CTAP MakeCredential", CtapCommand::MakeCredential, 0x01),
+        ("CTAP GetAssertion", CtapCommand::GetAssertion, 0x02),
+        ("CTAP Reset", CtapCommand::Reset, 0x07),
+        ("CTAP Selection", CtapCommand::Selection, 0x0B),
+        (
+            "CTAP ClientPin",
+            CtapCommand::ClientPin(ClientPinSubCommand::GetRetries),
+            0x06,
+        ),
+        (
+            "CTAP CredentialManagement",
+            CtapCommand::CredentialManagement(CredMgmtSubCommand::GetCredsMetadata),
+            0x0A,
+        ),
+        (
+            "CTAP AuthenticatorConfig",
+            CtapCommand::AuthenticatorConfig(AuthConfigSubCommand::ToggleAlwaysUv),
+            0x0D,
+        ),
+    ];
+
+    cases
+        .into_iter()
+        .map(|(name, cmd, expected)| {
+            let check = match codec.encode_ctap_command(&cmd) {
+                Ok(bytes) if bytes.first() == Some(&expected) => Ok(()),
+                Ok(bytes) => Err(format!(
+                    "指令碼應為 0x{expected:02X}，實際為 {:02X?}",
+                    bytes.first()
+                )),
+                Err(e) => Err(e.to_string()),
+            };
+            TestResult::from_check(name, check)
+        })
+        .collect()
+}
+
+// === 狀態碼對應 ===
+
+/// 判斷 status_to_error 結果是否符合預期
+type StatusCheck = fn(&Option<HsmError>) -> bool;
+
+fn status_mappings() -> Vec<TestResult> {
+    let codec = ApduCodecImpl::new();
+    let cases: [(u8, u8, StatusCheck); 9] = [
+        (0x90, 0x00, |e| e.is_none()),
+        (0x61, 0x10, |e| e.is_none()),
+        (0x63, 0xC3, |e| matches!(e, Some(HsmError::PinInvalid(3)))),
+        (0x69, 0x83, |e| matches!(e, Some(HsmError::PinLocked))),
+        (0x69, 0x82, |e| matches!(e, Some(HsmError::SoPinInvalid))),
+        (0x6A, 0x82, |e| matches!(e, Some(HsmError::KeyNotFound(_)))),
+        (0x6A, 0x88, |e| matches!(e, Some(HsmError::KeyNotFound(_)))),
+        (0x6D, 0x00, |e| matches!(e, Some(HsmError::NotSupported))),
+        (0x6F, 0x00, |e| matches!(e, Some(HsmError::StatusError(0x6F, 0x00)))),
+    ];
+
+    cases
+        .into_iter()
+        .map(|(sw1, sw2, expected)| {
+            let mapped = codec.status_to_error(sw1, sw2);
+            let check = if expected(&mapped) {
+                Ok(())
+            } else {
+                Err(format!("對應結果不符: {mapped:?}"))
+            };
+            TestResult::from_check(&format!("SW {sw1:02X}{sw2:02X}"), check)
+        })
+        .collect()
+}
+
+// === PIN 驗證邊界 ===
+
+fn expect(valid: bool, result_ok: bool) -> Result<(), String> {
+    match (valid, result_ok) {
+        (true, false) => Err("應接受但被拒絕".to_string()),
+        (false, true) => Err("應拒絕但被接受".to_string()),
+        _ => Ok(()),
+    }
+}
+
+fn pin_validators() -> Vec<TestResult> {
+    let mut results = Vec::new();
+
+    for (len, valid) in [(5, false), (6, true), (16, true), (17, false)] {
+        let pin = "1".repeat(len);
+        results.push(TestResult::from_check(
+            &format!("HSM PIN length {len}"),
+            expect(valid, HsmModuleImpl::validate_pin(&pin).is_ok()),
+        ));
+    }
+
+    for (so_pin, valid) in [
+        ("3537363231383830", true),
+        ("353736323138383", false),
+        ("353736323138383G", false),
+    ] {
+        results.push(TestResult::from_check(
+            &format!("HSM SO-PIN {so_pin:?}"),
+            expect(valid, HsmModuleImpl::validate_so_pin(so_pin).is_ok()),
+        ));
+    }
+
+    for (len, valid) in [(3, false), (4, true), (63, true), (64, false)] {
+        let pin = "1".repeat(len);
+        results.push(TestResult::from_check(
+            &format!("FIDO PIN length {len}"),
+            expect(valid, FidoModuleImpl::validate_pin(&pin).is_ok()),
+        ));
+    }
+
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // === 自我檢測測試 ===
+
+    #[test]
+    fn test_self_test_all_pass() {
+        let results = self_test();
+        let failed: Vec<&TestResult> = results.iter().filter(|r| !r.passed).collect();
+        assert!(failed.is_empty(), "failed checks: {failed:?}");
+    }
+
+    #[test]
+    fn test_self_test_covers_each_area() {
+        let results = self_test();
+        for prefix in ["APDU extended", "CTAP", "SW ", "HSM PIN", "HSM SO-PIN", "FIDO PIN"] {
+            assert!(
+                results.iter().any(|r| r.name.starts_with(prefix)),
+                "missing {prefix}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_command_rejects_bad_length() {
+        assert!(parse_command(&[0x00, 0xA4]).is_none());
+        assert!(parse_command(&[0x00, 0xA4, 0x04, 0x00, 0x05, 0x01]).is_none());
+    }
+
+    #[test]
+    fn test_expect_reports_mismatch() {
+        assert!(expect(true, true).is_ok());
+        assert!(expect(false, false).is_ok());
+        assert!(expect(true, false).is_err());
+        assert!(expect(false, true).is_err());
+    }
+}
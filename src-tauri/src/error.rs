@@ -1,4 +1,7 @@
-use serde::Serialize;
+use serde::ser::{Error as _, SerializeStruct};
+use serde::{Serialize, Serializer};
+
+use crate::hsm::types::{HsmKeyInfo, ImportStep, RotationStep};
 
 /// 裝置管理錯誤
 #[derive(Debug, thiserror::Error, Serialize)]
@@ -34,12 +37,39 @@ pub enum FidoError {
     #[error("PIN 已鎖定，裝置需要重設")]
     PinLocked,
 
+    #[error("連續 PIN 錯誤次數過多，請拔除並重新插入裝置後再試")]
+    PinAuthBlocked,
+
     #[error("PIN 長度不符合規範 (需 4-63 位元組)")]
     PinLengthInvalid,
 
+    #[error("裝置要求先變更 PIN，請完成變更後再進行其他操作")]
+    PinChangeRequired,
+
     #[error("裝置不支援此功能")]
     NotSupported,
 
+    #[error("此功能需先啟用進階模式")]
+    AdvancedModeDisabled,
+
+    #[error("largeBlob 資料完整性檢查失敗")]
+    LargeBlobIntegrity,
+
+    #[error("裝置拒絕重設：請拔除並重新插入裝置，於 10 秒內執行重設並觸碰裝置確認")]
+    ResetRequiresReinsert,
+
+    #[error("重設確認碼無效，請重新開始重設流程")]
+    ResetTokenInvalid,
+
+    #[error("重設確認碼已過期，請重新開始重設流程")]
+    ResetTokenExpired,
+
+    #[error("找不到指定的憑證")]
+    CredentialNotFound,
+
+    #[error("AID 長度無效 (需 5-16 位元組): {0}")]
+    InvalidAid(usize),
+
     #[error("裝置通訊錯誤: {0}")]
     CommunicationError(String),
 
@@ -48,6 +78,13 @@ pub enum FidoError {
 
     #[error("CBOR 編解碼錯誤: {0}")]
     CborError(String),
+
+    #[error("刪除憑證時失敗（已刪除 {deleted}/{total} 個）: {error}")]
+    BulkDeleteAborted {
+        deleted: usize,
+        total: usize,
+        error: Box<FidoError>,
+    },
 }
 
 /// HSM (APDU) 模組錯誤
@@ -68,32 +105,106 @@ pub enum HsmError {
     #[error("SO-PIN 已鎖定，裝置需要重新初始化")]
     SoPinLocked,
 
-    #[error("PIN 格式不符合規範 (需 6-16 字元)")]
+    #[error("SO-PIN 僅剩最後一次嘗試機會，輸入錯誤將鎖定裝置")]
+    SoPinLastAttempt,
+
+    #[error("PIN 格式不符合規範 (需 6-16 位元組)")]
     PinFormatInvalid,
 
     #[error("SO-PIN 格式不符合規範 (需 16 個十六進位字元)")]
     SoPinFormatInvalid,
 
+    #[error("初始化參數無效: {0}")]
+    InvalidInitializeOption(String),
+
+    #[error("DKEK 份額數無效 (需 0-{max}): {shares}")]
+    InvalidDkekShares { shares: u8, max: u8 },
+
+    #[error("未確認清除裝置，已取消重設")]
+    FactoryResetNotConfirmed,
+
     #[error("金鑰未找到: ID={0}")]
     KeyNotFound(u8),
 
+    #[error("沒有可用的金鑰 ID（1-255 皆已使用）")]
+    NoFreeKeySlot,
+
+    #[error("金鑰 ID 無效或為保留值: {0}")]
+    InvalidKeyId(u8),
+
+    #[error("AID 長度無效 (需 5-16 位元組): {0}")]
+    InvalidAid(usize),
+
+    #[error("私鑰資料無效: {0}")]
+    InvalidPrivateKey(String),
+
+    #[error("金鑰標籤長度無效 (需 1-255 位元組): {0}")]
+    InvalidKeyLabel(usize),
+
     #[error("憑證未找到: ID={0}")]
     CertificateNotFound(u8),
 
+    #[error("憑證格式無效: {0}")]
+    InvalidCertificate(String),
+
+    #[error("憑證公鑰與金鑰不符: ID={0}")]
+    CertificateKeyMismatch(u8),
+
+    #[error("無法取得金鑰的公鑰以比對憑證: ID={0}")]
+    CertificateKeyUnverified(u8),
+
     #[error("DKEK 尚未初始化")]
     DkekNotInitialized,
 
+    #[error("DKEK 檢查值不符（包裝金鑰: {wrapped}，裝置: {device}）")]
+    DkekMismatch { wrapped: String, device: String },
+
     #[error("裝置未初始化")]
     DeviceNotInitialized,
 
     #[error("裝置通訊錯誤: {0}")]
     CommunicationError(String),
 
+    #[error("卡片已重設，目前的連線已失效")]
+    CardReset,
+
+    #[error("無法取得讀卡機「{0}」的獨佔存取，請關閉其他正在使用此裝置的程式（例如瀏覽器的安全金鑰提示）後重試")]
+    ExclusiveAccessDenied(String),
+
     #[error("操作逾時")]
     Timeout,
 
     #[error("不支援的操作")]
     NotSupported,
+
+    #[error("批次操作於第 {} 項失敗（已完成 {} 項）: {error}", failed_index + 1, completed.len())]
+    BatchAborted {
+        completed: Vec<HsmKeyInfo>,
+        failed_index: usize,
+        error: Box<HsmError>,
+    },
+
+    #[error(
+        "安全性輪替於 {step:?} 步驟失敗，SO-PIN {}: {error}",
+        if *so_pin_changed { "已變更為新值" } else { "維持原值" }
+    )]
+    RotationFailed {
+        step: RotationStep,
+        /// 失敗後裝置上的 SO-PIN 是否已是新值（還原失敗時為 true）
+        so_pin_changed: bool,
+        error: Box<HsmError>,
+    },
+
+    #[error(
+        "金鑰匯入於 {step:?} 步驟失敗，金鑰{}: {error}",
+        if *key_imported { "仍留在裝置上" } else { "未匯入" }
+    )]
+    ImportFailed {
+        step: ImportStep,
+        /// 失敗後裝置上是否留有已解包的金鑰（移除失敗時為 true）
+        key_imported: bool,
+        error: Box<HsmError>,
+    },
 }
 
 /// CBOR 編解碼錯誤
@@ -121,3 +232,149 @@ pub enum ApduError {
     #[error("非預期的狀態碼: SW=0x{0:02X}{1:02X}")]
     UnexpectedStatus(u8, u8),
 }
+
+/// 指令層統一錯誤：序列化為 `domain`（來源模組）、`kind`（變體名稱）、
+/// `message`（Display 文字）與 `error`（原始錯誤，保留重試次數等欄位），例如
+/// `{ "domain": "Fido", "kind": "PinInvalid", "message": "PIN 錯誤...", "error": { "PinInvalid": 2 } }`
+#[derive(Debug, thiserror::Error)]
+pub enum AppError {
+    #[error(transparent)]
+    Device(#[from] DeviceError),
+
+    #[error(transparent)]
+    Fido(#[from] FidoError),
+
+    #[error(transparent)]
+    Hsm(#[from] HsmError),
+
+    #[error(transparent)]
+    Cbor(#[from] CborError),
+
+    #[error(transparent)]
+    Apdu(#[from] ApduError),
+}
+
+impl Serialize for AppError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let (domain, error) = match self {
+            AppError::Device(e) => ("Device", serde_json::to_value(e)),
+            AppError::Fido(e) => ("Fido", serde_json::to_value(e)),
+            AppError::Hsm(e) => ("Hsm", serde_json::to_value(e)),
+            AppError::Cbor(e) => ("Cbor", serde_json::to_value(e)),
+            AppError::Apdu(e) => ("Apdu", serde_json::to_value(e)),
+        };
+        let error = error.map_err(S::Error::custom)?;
+        let mut state = serializer.serialize_struct("AppError", 4)?;
+        state.serialize_field("domain", domain)?;
+        state.serialize_field("kind", &variant_kind(&error))?;
+        state.serialize_field("message", &self.to_string())?;
+        state.serialize_field("error", &error)?;
+        state.end()
+    }
+}
+
+/// 取得序列化後錯誤的變體名稱：單元變體為字串，帶資料的變體為單一鍵物件
+fn variant_kind(error: &serde_json::Value) -> String {
+    match error {
+        serde_json::Value::String(name) => name.clone(),
+        serde_json::Value::Object(map) => map.keys().next().cloned().unwrap_or_default(),
+        _ => String::new(),
+    }
+}
+
+/// Hex / Base64 編解碼錯誤
+#[derive(Debug, thiserror::Error, Serialize)]
+pub enum EncodingError {
+    #[error("十六進位字串長度必須為偶數 (長度: {0})")]
+    OddHexLength(usize),
+
+    #[error("無效的十六進位字元 {ch:?} (位置: {position})")]
+    InvalidHexChar { position: usize, ch: char },
+
+    #[error("無效的 Base64 字元 {ch:?} (位置: {position})")]
+    InvalidBase64Char { position: usize, ch: char },
+
+    #[error("Base64 資料長度無效 (長度: {0})")]
+    InvalidBase64Length(usize),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // === 序列化測試 ===
+
+    #[test]
+    fn test_fido_pin_invalid_serializes_retry_count() {
+        let json = serde_json::to_value(FidoError::PinInvalid(2)).unwrap();
+        assert_eq!(json, serde_json::json!({ "PinInvalid": 2 }));
+    }
+
+    #[test]
+    fn test_fido_variants_are_discriminable() {
+        let locked = serde_json::to_value(FidoError::PinLocked).unwrap();
+        let ctap = serde_json::to_value(FidoError::CtapError(0x31)).unwrap();
+        assert_eq!(locked, serde_json::json!("PinLocked"));
+        assert_eq!(ctap, serde_json::json!({ "CtapError": 0x31 }));
+    }
+
+    #[test]
+    fn test_hsm_pin_invalid_serializes_retry_count() {
+        let json = serde_json::to_value(HsmError::PinInvalid(3)).unwrap();
+        assert_eq!(json, serde_json::json!({ "PinInvalid": 3 }));
+    }
+
+    #[test]
+    fn test_hsm_unit_variant_serializes_as_name() {
+        let json = serde_json::to_value(HsmError::PinLocked).unwrap();
+        assert_eq!(json, serde_json::json!("PinLocked"));
+    }
+
+    // === AppError 測試 ===
+
+    #[test]
+    fn test_app_error_tags_each_domain() {
+        let cases = [
+            (AppError::from(DeviceError::Timeout), "Device", serde_json::json!("Timeout")),
+            (
+                AppError::from(FidoError::PinInvalid(2)),
+                "Fido",
+                serde_json::json!({ "PinInvalid": 2 }),
+            ),
+            (AppError::from(HsmError::PinLocked), "Hsm", serde_json::json!("PinLocked")),
+            (
+                AppError::from(CborError::UnexpectedFormat),
+                "Cbor",
+                serde_json::json!("UnexpectedFormat"),
+            ),
+            (
+                AppError::from(ApduError::IncompleteResponse(1)),
+                "Apdu",
+                serde_json::json!({ "IncompleteResponse": 1 }),
+            ),
+        ];
+        for (error, domain, inner) in cases {
+            let json = serde_json::to_value(&error).unwrap();
+            assert_eq!(json["domain"], domain);
+            assert_eq!(json["error"], inner);
+        }
+    }
+
+    #[test]
+    fn test_app_error_serializes_kind_and_message() {
+        let error = AppError::from(HsmError::SoPinLocked);
+        let json = serde_json::to_value(&error).unwrap();
+        assert_eq!(json["kind"], "SoPinLocked");
+        assert_eq!(json["message"], HsmError::SoPinLocked.to_string());
+
+        let json = serde_json::to_value(AppError::from(FidoError::PinInvalid(2))).unwrap();
+        assert_eq!(json["kind"], "PinInvalid");
+        assert_eq!(json["message"], FidoError::PinInvalid(2).to_string());
+    }
+
+    #[test]
+    fn test_app_error_display_is_transparent() {
+        let error = AppError::from(HsmError::KeyNotFound(3));
+        assert_eq!(error.to_string(), HsmError::KeyNotFound(3).to_string());
+    }
+}
@@ -0,0 +1,68 @@
+/// 已知的 AAGUID 與型號名稱（小寫、含連字號的 UUID 格式）
+const KNOWN_AAGUIDS: &[(&str, &str)] = &[
+    ("89fb94b7-06c9-3673-9b7e-30526d968145", "Pico-FIDO"),
+    ("ec99db19-cd1f-4c06-a2a9-940f17a6a30b", "Nitrokey 3"),
+];
+
+/// 將 16 位元組 AAGUID 格式化為標準 UUID 字串（8-4-4-4-12，小寫）；
+/// 長度不符時回傳原始十六進位以便除錯
+pub fn format(bytes: &[u8]) -> String {
+    let hex = crate::util::hex_encode(bytes);
+    if bytes.len() != 16 {
+        return hex;
+    }
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+/// 依 AAGUID 查詢型號名稱；比對不分大小寫
+pub fn model_name(aaguid: &str) -> Option<&'static str> {
+    KNOWN_AAGUIDS
+        .iter()
+        .find(|(known, _)| known.eq_ignore_ascii_case(aaguid))
+        .map(|&(_, name)| name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // === AAGUID 測試 ===
+
+    #[test]
+    fn test_format_sixteen_bytes_as_uuid() {
+        let bytes: Vec<u8> = (0..16).map(|i| i * 0x11).collect();
+        assert_eq!(format(&bytes), "00112233-4455-6677-8899-aabbccddeeff");
+    }
+
+    #[test]
+    fn test_format_wrong_length_falls_back_to_hex() {
+        assert_eq!(format(&[0xAB, 0xCD]), "abcd");
+        assert_eq!(format(&[]), "");
+    }
+
+    #[test]
+    fn test_known_aaguid_resolves_to_model_name() {
+        let pico = [
+            0x89, 0xFB, 0x94, 0xB7, 0x06, 0xC9, 0x36, 0x73, 0x9B, 0x7E, 0x30, 0x52, 0x6D, 0x96,
+            0x81, 0x45,
+        ];
+        assert_eq!(model_name(&format(&pico)), Some("Pico-FIDO"));
+        assert_eq!(model_name("EC99DB19-CD1F-4C06-A2A9-940F17A6A30B"), Some("Nitrokey 3"));
+        assert_eq!(model_name("00000000-0000-0000-0000-000000000000"), None);
+    }
+
+    #[test]
+    fn test_known_aaguids_are_canonical() {
+        for (aaguid, _) in KNOWN_AAGUIDS {
+            assert_eq!(aaguid.len(), 36);
+            assert_eq!(*aaguid, aaguid.to_ascii_lowercase());
+        }
+    }
+}
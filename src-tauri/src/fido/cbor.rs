@@ -1,5 +1,15 @@
+use std::collections::BTreeMap;
+
+use serde_cbor::Value;
+
 use crate::error::{CborError, FidoError};
-use crate::fido::types::{CtapCommand, CtapResponse};
+use crate::fido::aaguid;
+use crate::fido::pin_protocol::PinUvAuthToken;
+use crate::fido::types::{
+    AuthConfigSubCommand, ClientPinSubCommand, CredMgmtSubCommand, CredsMetadata, CtapCommand,
+    CtapResponse, FidoCredential, FidoDeviceInfo, KeyAgreementInfo, PinUvPermissions,
+    SetMinPinLengthParams,
+};
 
 /// CTAP 指令的 CBOR 編解碼器 trait
 pub trait CborCodec {
@@ -26,34 +36,27 @@ impl Default for CborCodecImpl {
 
 impl CborCodec for CborCodecImpl {
     fn encode_ctap_command(&self, cmd: &CtapCommand) -> Result<Vec<u8>, CborError> {
-        let (cmd_byte, params) = match cmd {
-            CtapCommand::GetInfo => (0x04u8, None),
-            CtapCommand::MakeCredential => (0x01, None),
-            CtapCommand::GetAssertion => (0x02, None),
-            CtapCommand::Reset => (0x07, None),
-            CtapCommand::Selection => (0x0B, None),
+        // 帶子指令的指令只編入 subCommand；需要其他參數的請求使用各自的編碼函式
+        match cmd {
+            CtapCommand::GetInfo => Ok(vec![0x04]),
+            CtapCommand::MakeCredential => Ok(vec![0x01]),
+            CtapCommand::GetAssertion => Ok(vec![0x02]),
+            CtapCommand::Reset => Ok(vec![0x07]),
+            CtapCommand::Selection => Ok(vec![0x0B]),
             CtapCommand::ClientPin(sub) => {
-                let cbor = serde_cbor::to_vec(sub)
-                    .map_err(|e| CborError::EncodingError(e.to_string()))?;
-                (0x06, Some(cbor))
+                let map = BTreeMap::from([(
+                    Value::Integer(0x02),
+                    Value::Integer(client_pin_code(sub).into()),
+                )]);
+                encode_params(0x06, map)
             }
             CtapCommand::CredentialManagement(sub) => {
-                let cbor = serde_cbor::to_vec(sub)
-                    .map_err(|e| CborError::EncodingError(e.to_string()))?;
-                (0x0A, Some(cbor))
+                encode_cred_mgmt_command(cred_mgmt_code(sub), None, None)
             }
             CtapCommand::AuthenticatorConfig(sub) => {
-                let cbor = serde_cbor::to_vec(sub)
-                    .map_err(|e| CborError::EncodingError(e.to_string()))?;
-                (0x0D, Some(cbor))
+                encode_config_command(config_code(sub), None, None)
             }
-        };
-
-        let mut buf = vec![cmd_byte];
-        if let Some(params) = params {
-            buf.extend_from_slice(&params);
         }
-        Ok(buf)
     }
 
     fn decode_ctap_response(&self, data: &[u8]) -> Result<CtapResponse, CborError> {
@@ -89,20 +92,781 @@ impl CborCodec for CborCodecImpl {
     }
 }
 
+/// authenticatorConfig 子指令碼（CTAP 2.1 §6.11）
+fn config_code(sub: &AuthConfigSubCommand) -> u8 {
+    match sub {
+        AuthConfigSubCommand::EnableEnterpriseAttestation => 0x01,
+        AuthConfigSubCommand::ToggleAlwaysUv => 0x02,
+        AuthConfigSubCommand::SetMinPinLength => 0x03,
+    }
+}
+
+/// 建立 setMinPINLength 的 subCommandParams map，未指定的欄位不編入
+pub fn set_min_pin_length_sub_params(params: &SetMinPinLengthParams) -> Value {
+    let mut map = BTreeMap::new();
+    if let Some(length) = params.new_min_pin_length {
+        map.insert(Value::Integer(0x01), Value::Integer(length.into()));
+    }
+    if !params.min_pin_length_rp_ids.is_empty() {
+        let rp_ids = params
+            .min_pin_length_rp_ids
+            .iter()
+            .map(|id| Value::Text(id.clone()))
+            .collect();
+        map.insert(Value::Integer(0x02), Value::Array(rp_ids));
+    }
+    if params.force_change_pin {
+        map.insert(Value::Integer(0x03), Value::Bool(true));
+    }
+    Value::Map(map)
+}
+
+/// 編碼 authenticatorConfig(setMinPINLength) 指令（指令碼 0x0D + CBOR 參數）
+pub fn encode_set_min_pin_length(
+    params: &SetMinPinLengthParams,
+    token: Option<&PinUvAuthToken>,
+) -> Result<Vec<u8>, CborError> {
+    let sub_params = set_min_pin_length_sub_params(params);
+    let sub_params = match &sub_params {
+        Value::Map(m) if m.is_empty() => None,
+        _ => Some(sub_params),
+    };
+    encode_config_command(config_code(&AuthConfigSubCommand::SetMinPinLength), sub_params, token)
+}
+
+/// 編碼 authenticatorConfig(toggleAlwaysUv) 指令，此子指令沒有 subCommandParams
+pub fn encode_toggle_always_uv(token: Option<&PinUvAuthToken>) -> Result<Vec<u8>, CborError> {
+    encode_config_command(config_code(&AuthConfigSubCommand::ToggleAlwaysUv), None, token)
+}
+
+/// 編碼 authenticatorConfig(enableEnterpriseAttestation) 指令，此子指令沒有 subCommandParams
+pub fn encode_enable_enterprise_attestation(
+    token: Option<&PinUvAuthToken>,
+) -> Result<Vec<u8>, CborError> {
+    let sub_command = config_code(&AuthConfigSubCommand::EnableEnterpriseAttestation);
+    encode_config_command(sub_command, None, token)
+}
+
+/// 組出 authenticatorConfig 指令（指令碼 0x0D + CBOR 參數）
+/// 參數 map: 0x01=subCommand, 0x02=subCommandParams, 0x03=pinUvAuthProtocol, 0x04=pinUvAuthParam
+/// pinUvAuthParam = authenticate(token, 32×0xFF || 0x0D || subCommand || subCommandParams)
+fn encode_config_command(
+    sub_command: u8,
+    sub_params: Option<Value>,
+    token: Option<&PinUvAuthToken>,
+) -> Result<Vec<u8>, CborError> {
+    let mut message = vec![0xFF; 32];
+    message.extend_from_slice(&[0x0D, sub_command]);
+    encode_sub_command(0x0D, sub_command, sub_params, message, token)
+}
+
+/// 組出帶 subCommand 的指令（credentialManagement / authenticatorConfig 共用的 map 配置）
+/// 參數 map: 0x01=subCommand, 0x02=subCommandParams, 0x03=pinUvAuthProtocol, 0x04=pinUvAuthParam；
+/// `message` 為 subCommandParams 之前的驗證訊息前綴
+fn encode_sub_command(
+    command: u8,
+    sub_command: u8,
+    sub_params: Option<Value>,
+    mut message: Vec<u8>,
+    token: Option<&PinUvAuthToken>,
+) -> Result<Vec<u8>, CborError> {
+    let mut map = BTreeMap::new();
+    map.insert(Value::Integer(0x01), Value::Integer(sub_command.into()));
+    if let Some(sub_params) = sub_params {
+        message.extend_from_slice(
+            &serde_cbor::to_vec(&sub_params).map_err(|e| CborError::EncodingError(e.to_string()))?,
+        );
+        map.insert(Value::Integer(0x02), sub_params);
+    }
+    if let Some(token) = token {
+        let auth = token.authenticate(&message);
+        map.insert(Value::Integer(0x03), Value::Integer(auth.protocol.into()));
+        map.insert(Value::Integer(0x04), Value::Bytes(auth.param));
+    }
+    encode_params(command, map)
+}
+
+/// 將參數 map 序列化並加上指令碼
+fn encode_params(command: u8, map: BTreeMap<Value, Value>) -> Result<Vec<u8>, CborError> {
+    let cbor = serde_cbor::to_vec(&Value::Map(map))
+        .map_err(|e| CborError::EncodingError(e.to_string()))?;
+    let mut buf = vec![command];
+    buf.extend_from_slice(&cbor);
+    Ok(buf)
+}
+
+// === credentialManagement ===
+
+/// credentialManagement 子指令碼（CTAP 2.1 §6.8）
+fn cred_mgmt_code(sub: &CredMgmtSubCommand) -> u8 {
+    match sub {
+        CredMgmtSubCommand::GetCredsMetadata => 0x01,
+        CredMgmtSubCommand::EnumerateRPsBegin => 0x02,
+        CredMgmtSubCommand::EnumerateRPsNext => 0x03,
+        CredMgmtSubCommand::EnumerateCredentialsBegin => 0x04,
+        CredMgmtSubCommand::EnumerateCredentialsNext => 0x05,
+        CredMgmtSubCommand::DeleteCredential => 0x06,
+    }
+}
+
+/// 組出 credentialManagement 指令（指令碼 0x0A + CBOR 參數）
+/// pinUvAuthParam = authenticate(token, subCommand || subCommandParams)
+fn encode_cred_mgmt_command(
+    sub_command: u8,
+    sub_params: Option<Value>,
+    token: Option<&PinUvAuthToken>,
+) -> Result<Vec<u8>, CborError> {
+    encode_sub_command(0x0A, sub_command, sub_params, vec![sub_command], token)
+}
+
+/// 編碼 credentialManagement(getCredsMetadata) 指令
+pub fn encode_get_creds_metadata(token: &PinUvAuthToken) -> Result<Vec<u8>, CborError> {
+    let sub_command = cred_mgmt_code(&CredMgmtSubCommand::GetCredsMetadata);
+    encode_cred_mgmt_command(sub_command, None, Some(token))
+}
+
+/// 編碼 credentialManagement(enumerateRPsBegin) 指令
+pub fn encode_enumerate_rps_begin(token: &PinUvAuthToken) -> Result<Vec<u8>, CborError> {
+    let sub_command = cred_mgmt_code(&CredMgmtSubCommand::EnumerateRPsBegin);
+    encode_cred_mgmt_command(sub_command, None, Some(token))
+}
+
+/// 編碼 credentialManagement(enumerateCredentialsBegin) 指令
+/// subCommandParams: 0x01=rpIDHash
+pub fn encode_enumerate_credentials_begin(
+    rp_id_hash: &[u8],
+    token: &PinUvAuthToken,
+) -> Result<Vec<u8>, CborError> {
+    let sub_command = cred_mgmt_code(&CredMgmtSubCommand::EnumerateCredentialsBegin);
+    let sub_params = BTreeMap::from([(Value::Integer(0x01), Value::Bytes(rp_id_hash.to_vec()))]);
+    encode_cred_mgmt_command(sub_command, Some(Value::Map(sub_params)), Some(token))
+}
+
+/// 編碼 credentialManagement(deleteCredential) 指令
+/// subCommandParams: 0x02=credentialID (PublicKeyCredentialDescriptor)
+pub fn encode_delete_credential(
+    credential_id: &[u8],
+    token: &PinUvAuthToken,
+) -> Result<Vec<u8>, CborError> {
+    let sub_command = cred_mgmt_code(&CredMgmtSubCommand::DeleteCredential);
+    let sub_params = BTreeMap::from([(Value::Integer(0x02), credential_descriptor(credential_id))]);
+    encode_cred_mgmt_command(sub_command, Some(Value::Map(sub_params)), Some(token))
+}
+
+/// 建立 PublicKeyCredentialDescriptor：{"id": credentialId, "type": "public-key"}
+fn credential_descriptor(credential_id: &[u8]) -> Value {
+    let mut descriptor = BTreeMap::new();
+    descriptor.insert(Value::Text("id".to_string()), Value::Bytes(credential_id.to_vec()));
+    descriptor.insert(Value::Text("type".to_string()), Value::Text("public-key".to_string()));
+    Value::Map(descriptor)
+}
+
+/// 編碼 enumerateRPsGetNextRP / enumerateCredentialsGetNextCredential；延續 Begin 的狀態，不需驗證
+pub fn encode_enumerate_next(sub: &CredMgmtSubCommand) -> Result<Vec<u8>, CborError> {
+    encode_cred_mgmt_command(cred_mgmt_code(sub), None, None)
+}
+
+/// enumerateRPsBegin/Next 回應中的 RP
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnumeratedRp {
+    pub rp_id: String,
+    pub rp_name: Option<String>,
+    pub rp_id_hash: Vec<u8>,
+    /// RP 總數，僅 enumerateRPsBegin 回應帶有
+    pub total_rps: Option<u32>,
+}
+
+/// 解碼 credentialManagement enumerateRPsBegin/Next 回應（不含狀態碼）
+/// 0x03=rp (PublicKeyCredentialRpEntity), 0x04=rpIDHash, 0x05=totalRPs
+pub fn decode_enumerated_rp(payload: &[u8]) -> Result<EnumeratedRp, CborError> {
+    let map = decode_response_map(payload, "enumerateRPs")?;
+    let rp = match map.get(&Value::Integer(0x03)) {
+        Some(Value::Map(rp)) => rp,
+        _ => return Err(CborError::DecodingError("回應缺少 rp".to_string())),
+    };
+    let text = |key: &str| match rp.get(&Value::Text(key.to_string())) {
+        Some(Value::Text(t)) => Some(t.clone()),
+        _ => None,
+    };
+    let rp_id = text("id").ok_or_else(|| CborError::DecodingError("rp 缺少 id".to_string()))?;
+    let rp_id_hash = match map.get(&Value::Integer(0x04)) {
+        Some(Value::Bytes(hash)) if hash.len() == 32 => hash.clone(),
+        _ => return Err(CborError::DecodingError("回應缺少 rpIDHash".to_string())),
+    };
+    Ok(EnumeratedRp {
+        rp_id,
+        rp_name: text("name"),
+        rp_id_hash,
+        total_rps: optional_count(&map, 0x05),
+    })
+}
+
+/// 解碼 enumerateCredentialsBegin 回應的 0x09=totalCredentials
+pub fn decode_total_credentials(payload: &[u8]) -> Result<Option<u32>, CborError> {
+    Ok(optional_count(&decode_response_map(payload, "enumerateCredentials")?, 0x09))
+}
+
+fn optional_count(map: &BTreeMap<Value, Value>, key: i128) -> Option<u32> {
+    match map.get(&Value::Integer(key)) {
+        Some(Value::Integer(n)) => u32::try_from(*n).ok(),
+        _ => None,
+    }
+}
+
+/// 解碼 authenticatorGetInfo 回應的 CBOR map（不含狀態碼）
+/// 0x01=versions, 0x02=extensions, 0x03=aaguid, 0x04=options, 0x06=pinUvAuthProtocols,
+/// 0x0C=forcePINChange, 0x0D=minPINLength, 0x0E=firmwareVersion
+pub fn decode_get_info(payload: &[u8]) -> Result<FidoDeviceInfo, CborError> {
+    let map = match serde_cbor::from_slice(payload)
+        .map_err(|e| CborError::DecodingError(e.to_string()))?
+    {
+        Value::Map(map) => map,
+        _ => return Err(CborError::DecodingError("GetInfo 回應不是 CBOR map".to_string())),
+    };
+
+    let text_array = |key: i128| -> Vec<String> {
+        match map.get(&Value::Integer(key)) {
+            Some(Value::Array(items)) => items
+                .iter()
+                .filter_map(|v| match v {
+                    Value::Text(t) => Some(t.clone()),
+                    _ => None,
+                })
+                .collect(),
+            _ => Vec::new(),
+        }
+    };
+
+    let versions = text_array(0x01);
+    let extensions = text_array(0x02);
+    let aaguid = match map.get(&Value::Integer(0x03)) {
+        Some(Value::Bytes(b)) => aaguid::format(b),
+        _ => String::new(),
+    };
+    let options = match map.get(&Value::Integer(0x04)) {
+        Some(Value::Map(opts)) => opts
+            .iter()
+            .filter_map(|(k, v)| match (k, v) {
+                (Value::Text(name), Value::Bool(enabled)) => Some((name.clone(), *enabled)),
+                _ => None,
+            })
+            .collect(),
+        _ => Default::default(),
+    };
+    let firmware_version = match map.get(&Value::Integer(0x0E)) {
+        Some(Value::Integer(v)) if (0..=0xFFFF).contains(v) => {
+            format!("{}.{}", (*v >> 8) & 0xFF, *v & 0xFF)
+        }
+        Some(Value::Integer(v)) => v.to_string(),
+        _ => "unknown".to_string(),
+    };
+    let force_pin_change = matches!(map.get(&Value::Integer(0x0C)), Some(Value::Bool(true)));
+    let min_pin_length = match map.get(&Value::Integer(0x0D)) {
+        Some(Value::Integer(n)) => u8::try_from(*n).ok(),
+        _ => None,
+    };
+    let pin_uv_auth_protocols = match map.get(&Value::Integer(0x06)) {
+        Some(Value::Array(items)) => items
+            .iter()
+            .filter_map(|v| match v {
+                Value::Integer(n) => u8::try_from(*n).ok(),
+                _ => None,
+            })
+            .collect(),
+        _ => Vec::new(),
+    };
+
+    let model_name = aaguid::model_name(&aaguid).map(str::to_string);
+    let mut info = FidoDeviceInfo {
+        versions,
+        extensions,
+        aaguid,
+        model_name,
+        firmware_version,
+        serial_number: None,
+        pin_set: false,
+        pin_retries: 0,
+        options,
+        capabilities: Default::default(),
+        min_pin_length,
+        force_pin_change,
+        pin_uv_auth_protocols,
+    };
+    info.pin_set = info.option_enabled("clientPin");
+    info.capabilities = info.compute_capabilities();
+    Ok(info)
+}
+
+/// 解碼 credentialManagement getCredsMetadata 回應（不含狀態碼）
+/// 0x01=existingResidentCredentialsCount, 0x02=maxPossibleRemainingResidentCredentialsCount
+pub fn decode_creds_metadata(payload: &[u8]) -> Result<CredsMetadata, CborError> {
+    let map = match serde_cbor::from_slice(payload)
+        .map_err(|e| CborError::DecodingError(e.to_string()))?
+    {
+        Value::Map(map) => map,
+        _ => return Err(CborError::DecodingError("metadata 回應不是 CBOR map".to_string())),
+    };
+
+    let count = |key: i128| match map.get(&Value::Integer(key)) {
+        Some(Value::Integer(n)) => u32::try_from(*n)
+            .map_err(|_| CborError::DecodingError(format!("metadata 欄位 {key} 超出範圍"))),
+        _ => Err(CborError::DecodingError(format!("metadata 回應缺少欄位 {key}"))),
+    };
+
+    Ok(CredsMetadata {
+        existing_resident_credentials_count: count(0x01)?,
+        max_possible_remaining_resident_credentials_count: count(0x02)?,
+    })
+}
+
+/// clientPIN 子指令碼（CTAP 2.1 §6.5.5）
+fn client_pin_code(sub: &ClientPinSubCommand) -> u8 {
+    match sub {
+        ClientPinSubCommand::GetRetries => 0x01,
+        ClientPinSubCommand::GetKeyAgreement => 0x02,
+        ClientPinSubCommand::SetPin => 0x03,
+        ClientPinSubCommand::ChangePin => 0x04,
+        ClientPinSubCommand::GetPinToken => 0x05,
+        ClientPinSubCommand::GetPinUvAuthTokenUsingUvWithPermissions => 0x06,
+        ClientPinSubCommand::GetPinUvAuthTokenUsingPinWithPermissions => 0x09,
+    }
+}
+
+/// 建立 clientPIN 參數 map 的共同欄位：0x01=pinUvAuthProtocol, 0x02=subCommand
+fn client_pin_params(protocol: u8, sub: ClientPinSubCommand) -> BTreeMap<Value, Value> {
+    BTreeMap::from([
+        (Value::Integer(0x01), Value::Integer(protocol.into())),
+        (Value::Integer(0x02), Value::Integer(client_pin_code(&sub).into())),
+    ])
+}
+
+/// 編碼 clientPIN(getKeyAgreement) 指令（指令碼 0x06 + CBOR 參數）
+/// 參數 map: 0x01=pinUvAuthProtocol, 0x02=subCommand
+pub fn encode_get_key_agreement(protocol: u8) -> Result<Vec<u8>, CborError> {
+    encode_params(0x06, client_pin_params(protocol, ClientPinSubCommand::GetKeyAgreement))
+}
+
+/// 建立平台端的 keyAgreement COSE_Key（EC2、ECDH-ES+HKDF-256、P-256）
+pub fn platform_key_agreement(x: &[u8], y: &[u8]) -> Value {
+    let mut key = BTreeMap::new();
+    key.insert(Value::Integer(1), Value::Integer(2));
+    key.insert(Value::Integer(3), Value::Integer(-25));
+    key.insert(Value::Integer(-1), Value::Integer(1));
+    key.insert(Value::Integer(-2), Value::Bytes(x.to_vec()));
+    key.insert(Value::Integer(-3), Value::Bytes(y.to_vec()));
+    Value::Map(key)
+}
+
+/// 編碼 clientPIN(setPIN) 指令
+/// 參數 map: 0x01=pinUvAuthProtocol, 0x02=subCommand, 0x03=keyAgreement, 0x04=pinUvAuthParam,
+/// 0x05=newPinEnc
+pub fn encode_set_pin(
+    protocol: u8,
+    key_agreement: Value,
+    new_pin_enc: &[u8],
+    pin_uv_auth_param: &[u8],
+) -> Result<Vec<u8>, CborError> {
+    let mut map = client_pin_params(protocol, ClientPinSubCommand::SetPin);
+    map.insert(Value::Integer(0x03), key_agreement);
+    map.insert(Value::Integer(0x04), Value::Bytes(pin_uv_auth_param.to_vec()));
+    map.insert(Value::Integer(0x05), Value::Bytes(new_pin_enc.to_vec()));
+    encode_params(0x06, map)
+}
+
+/// 編碼 clientPIN(changePIN) 指令
+/// 參數 map: 0x01=pinUvAuthProtocol, 0x02=subCommand, 0x03=keyAgreement, 0x04=pinUvAuthParam,
+/// 0x05=newPinEnc, 0x06=pinHashEnc
+pub fn encode_change_pin(
+    protocol: u8,
+    key_agreement: Value,
+    new_pin_enc: &[u8],
+    pin_hash_enc: &[u8],
+    pin_uv_auth_param: &[u8],
+) -> Result<Vec<u8>, CborError> {
+    let mut map = client_pin_params(protocol, ClientPinSubCommand::ChangePin);
+    map.insert(Value::Integer(0x03), key_agreement);
+    map.insert(Value::Integer(0x04), Value::Bytes(pin_uv_auth_param.to_vec()));
+    map.insert(Value::Integer(0x05), Value::Bytes(new_pin_enc.to_vec()));
+    map.insert(Value::Integer(0x06), Value::Bytes(pin_hash_enc.to_vec()));
+    encode_params(0x06, map)
+}
+
+/// 編碼 clientPIN(getPinToken) 指令，供不支援 pinUvAuthToken 的 CTAP 2.0 裝置使用
+/// 參數 map: 0x01=pinUvAuthProtocol, 0x02=subCommand, 0x03=keyAgreement, 0x06=pinHashEnc
+pub fn encode_get_pin_token(
+    protocol: u8,
+    key_agreement: Value,
+    pin_hash_enc: &[u8],
+) -> Result<Vec<u8>, CborError> {
+    let mut map = client_pin_params(protocol, ClientPinSubCommand::GetPinToken);
+    map.insert(Value::Integer(0x03), key_agreement);
+    map.insert(Value::Integer(0x06), Value::Bytes(pin_hash_enc.to_vec()));
+    encode_params(0x06, map)
+}
+
+/// 編碼 clientPIN(getPinUvAuthTokenUsingPinWithPermissions) 指令
+/// 參數 map: 0x01=pinUvAuthProtocol, 0x02=subCommand, 0x03=keyAgreement, 0x06=pinHashEnc,
+/// 0x09=permissions, 0x0A=rpId（選用）。權限不可為空
+pub fn encode_get_token_using_pin(
+    protocol: u8,
+    key_agreement: Value,
+    pin_hash_enc: &[u8],
+    permissions: PinUvPermissions,
+    rp_id: Option<&str>,
+) -> Result<Vec<u8>, CborError> {
+    let sub = ClientPinSubCommand::GetPinUvAuthTokenUsingPinWithPermissions;
+    let mut map = token_with_permissions_params(protocol, sub, key_agreement, permissions, rp_id)?;
+    map.insert(Value::Integer(0x06), Value::Bytes(pin_hash_enc.to_vec()));
+    encode_params(0x06, map)
+}
+
+/// 編碼 clientPIN(getPinUvAuthTokenUsingUvWithPermissions) 指令，以裝置內建驗證取得 token
+/// 參數 map: 0x01=pinUvAuthProtocol, 0x02=subCommand, 0x03=keyAgreement, 0x09=permissions,
+/// 0x0A=rpId（選用）。權限不可為空
+pub fn encode_get_token_using_uv(
+    protocol: u8,
+    key_agreement: Value,
+    permissions: PinUvPermissions,
+    rp_id: Option<&str>,
+) -> Result<Vec<u8>, CborError> {
+    let sub = ClientPinSubCommand::GetPinUvAuthTokenUsingUvWithPermissions;
+    let map = token_with_permissions_params(protocol, sub, key_agreement, permissions, rp_id)?;
+    encode_params(0x06, map)
+}
+
+/// 以 PIN 或 UV 取得含權限 token 的共同參數：keyAgreement、permissions 與選用的 rpId
+fn token_with_permissions_params(
+    protocol: u8,
+    sub: ClientPinSubCommand,
+    key_agreement: Value,
+    permissions: PinUvPermissions,
+    rp_id: Option<&str>,
+) -> Result<BTreeMap<Value, Value>, CborError> {
+    if permissions == PinUvPermissions::default() {
+        return Err(CborError::EncodingError("pinUvAuthToken 權限不可為空".to_string()));
+    }
+
+    let mut map = client_pin_params(protocol, sub);
+    map.insert(Value::Integer(0x03), key_agreement);
+    map.insert(Value::Integer(0x09), Value::Integer(permissions.0.into()));
+    if let Some(rp_id) = rp_id {
+        map.insert(Value::Integer(0x0A), Value::Text(rp_id.to_string()));
+    }
+    Ok(map)
+}
+
+/// 解碼 clientPIN 取得 token 的回應（不含狀態碼），回傳 0x02 的加密 pinUvAuthToken
+pub fn decode_pin_token(payload: &[u8]) -> Result<Vec<u8>, CborError> {
+    match decode_response_map(payload, "clientPIN")?.remove(&Value::Integer(0x02)) {
+        Some(Value::Bytes(token)) if !token.is_empty() => Ok(token),
+        _ => Err(CborError::DecodingError("回應缺少 pinUvAuthToken".to_string())),
+    }
+}
+
+/// 解碼 clientPIN getKeyAgreement 回應（不含狀態碼）
+/// 0x01=keyAgreement (COSE_Key: 1=kty, 3=alg, -1=crv, -2=x, -3=y)
+pub fn decode_key_agreement(payload: &[u8], protocol: u8) -> Result<KeyAgreementInfo, CborError> {
+    let map = match serde_cbor::from_slice(payload)
+        .map_err(|e| CborError::DecodingError(e.to_string()))?
+    {
+        Value::Map(map) => map,
+        _ => return Err(CborError::DecodingError("keyAgreement 回應不是 CBOR map".to_string())),
+    };
+
+    let cose_key = match map.get(&Value::Integer(0x01)) {
+        Some(Value::Map(key)) => key,
+        _ => return Err(CborError::DecodingError("回應缺少 keyAgreement".to_string())),
+    };
+
+    let int_field = |key: i128| match cose_key.get(&Value::Integer(key)) {
+        Some(Value::Integer(n)) => i64::try_from(*n)
+            .map_err(|_| CborError::DecodingError(format!("COSE_Key 欄位 {key} 超出範圍"))),
+        _ => Err(CborError::DecodingError(format!("COSE_Key 缺少欄位 {key}"))),
+    };
+    let coord = |key: i128| match cose_key.get(&Value::Integer(key)) {
+        Some(Value::Bytes(b)) => Ok(crate::util::hex_encode(b)),
+        _ => Err(CborError::DecodingError(format!("COSE_Key 缺少座標 {key}"))),
+    };
+
+    Ok(KeyAgreementInfo {
+        pin_uv_auth_protocol: protocol,
+        kty: int_field(1)?,
+        alg: int_field(3)?,
+        crv: int_field(-1)?,
+        x: coord(-2)?,
+        y: coord(-3)?,
+    })
+}
+
+// === 測試註冊（MakeCredential / GetAssertion） ===
+
+/// authenticatorData 旗標：UP（使用者已觸碰）
+const AUTH_DATA_FLAG_UP: u8 = 0x01;
+/// authenticatorData 旗標：AT（含 attestedCredentialData）
+const AUTH_DATA_FLAG_AT: u8 = 0x40;
+/// COSE 演算法 ES256
+const COSE_ALG_ES256: i128 = -7;
+
+/// 依 WebAuthn 規格組出 clientDataJSON 並計算其 SHA-256（即 clientDataHash）
+/// `ceremony` 為 "webauthn.create" 或 "webauthn.get"；challenge 以無補齊 base64url 表示
+pub fn client_data_hash(ceremony: &str, challenge: &[u8], rp_id: &str) -> [u8; 32] {
+    use sha2::{Digest, Sha256};
+
+    let challenge = crate::util::base64_encode(challenge)
+        .trim_end_matches('=')
+        .replace('+', "-")
+        .replace('/', "_");
+    let client_data = format!(
+        r#"{{"type":"{ceremony}","challenge":"{challenge}","origin":"https://{rp_id}","crossOrigin":false}}"#
+    );
+    Sha256::digest(client_data.as_bytes()).into()
+}
+
+/// 編碼 authenticatorMakeCredential 指令（指令碼 0x01 + CBOR 參數）
+/// 參數 map: 0x01=clientDataHash, 0x02=rp, 0x03=user, 0x04=pubKeyCredParams
+/// 未帶 options，使用認證器預設（非駐留憑證）
+pub fn encode_make_credential(
+    client_data_hash: &[u8; 32],
+    rp_id: &str,
+    user_id: &[u8],
+) -> Result<Vec<u8>, CborError> {
+    let text = |s: &str| Value::Text(s.to_string());
+
+    let mut rp = BTreeMap::new();
+    rp.insert(text("id"), text(rp_id));
+    rp.insert(text("name"), text(rp_id));
+
+    let mut user = BTreeMap::new();
+    user.insert(text("id"), Value::Bytes(user_id.to_vec()));
+    user.insert(text("name"), text("self-test"));
+
+    let mut es256 = BTreeMap::new();
+    es256.insert(text("alg"), Value::Integer(COSE_ALG_ES256));
+    es256.insert(text("type"), text("public-key"));
+
+    let mut map = BTreeMap::new();
+    map.insert(Value::Integer(0x01), Value::Bytes(client_data_hash.to_vec()));
+    map.insert(Value::Integer(0x02), Value::Map(rp));
+    map.insert(Value::Integer(0x03), Value::Map(user));
+    map.insert(Value::Integer(0x04), Value::Array(vec![Value::Map(es256)]));
+
+    let cbor = serde_cbor::to_vec(&Value::Map(map))
+        .map_err(|e| CborError::EncodingError(e.to_string()))?;
+    let mut buf = vec![0x01];
+    buf.extend_from_slice(&cbor);
+    Ok(buf)
+}
+
+/// 編碼 authenticatorGetAssertion 指令（指令碼 0x02 + CBOR 參數）
+/// 參數 map: 0x01=rpId, 0x02=clientDataHash, 0x03=allowList,
+/// 0x06=pinUvAuthParam (authenticate(token, clientDataHash)), 0x07=pinUvAuthProtocol
+pub fn encode_get_assertion(
+    rp_id: &str,
+    client_data_hash: &[u8; 32],
+    credential_id: &[u8],
+    token: Option<&PinUvAuthToken>,
+) -> Result<Vec<u8>, CborError> {
+    let mut map = BTreeMap::new();
+    map.insert(Value::Integer(0x01), Value::Text(rp_id.to_string()));
+    map.insert(Value::Integer(0x02), Value::Bytes(client_data_hash.to_vec()));
+    map.insert(Value::Integer(0x03), Value::Array(vec![credential_descriptor(credential_id)]));
+    if let Some(token) = token {
+        let auth = token.authenticate(client_data_hash);
+        map.insert(Value::Integer(0x06), Value::Bytes(auth.param));
+        map.insert(Value::Integer(0x07), Value::Integer(auth.protocol.into()));
+    }
+
+    let cbor = serde_cbor::to_vec(&Value::Map(map))
+        .map_err(|e| CborError::EncodingError(e.to_string()))?;
+    let mut buf = vec![0x02];
+    buf.extend_from_slice(&cbor);
+    Ok(buf)
+}
+
+/// 取出回應 map 中 0x02 的 authenticatorData，並確認 rpIdHash 與 UP 旗標
+/// 回傳 (authData, flags, signCount)
+fn checked_auth_data<'a>(
+    map: &'a BTreeMap<Value, Value>,
+    rp_id: &str,
+) -> Result<(&'a [u8], u8, u32), CborError> {
+    use sha2::{Digest, Sha256};
+
+    let auth_data = match map.get(&Value::Integer(0x02)) {
+        Some(Value::Bytes(b)) if b.len() >= 37 => b.as_slice(),
+        _ => return Err(CborError::DecodingError("回應缺少 authenticatorData".to_string())),
+    };
+    if auth_data[..32] != Sha256::digest(rp_id.as_bytes())[..] {
+        return Err(CborError::DecodingError("rpIdHash 與 RP ID 不符".to_string()));
+    }
+    let flags = auth_data[32];
+    if flags & AUTH_DATA_FLAG_UP == 0 {
+        return Err(CborError::DecodingError("authenticatorData 未設定 UP 旗標".to_string()));
+    }
+    let sign_count = u32::from_be_bytes([auth_data[33], auth_data[34], auth_data[35], auth_data[36]]);
+    Ok((auth_data, flags, sign_count))
+}
+
+fn decode_response_map(payload: &[u8], what: &str) -> Result<BTreeMap<Value, Value>, CborError> {
+    match serde_cbor::from_slice(payload).map_err(|e| CborError::DecodingError(e.to_string()))? {
+        Value::Map(map) => Ok(map),
+        _ => Err(CborError::DecodingError(format!("{what} 回應不是 CBOR map"))),
+    }
+}
+
+/// 解碼 authenticatorMakeCredential 回應（不含狀態碼），回傳 (attestation fmt, credentialId)
+/// 0x01=fmt, 0x02=authData (rpIdHash 32 + flags 1 + signCount 4 + aaguid 16 + credIdLen 2 + credId ...)
+pub fn decode_make_credential(payload: &[u8], rp_id: &str) -> Result<(String, Vec<u8>), CborError> {
+    let map = decode_response_map(payload, "makeCredential")?;
+
+    let fmt = match map.get(&Value::Integer(0x01)) {
+        Some(Value::Text(t)) => t.clone(),
+        _ => return Err(CborError::DecodingError("回應缺少 attestation fmt".to_string())),
+    };
+    let (auth_data, flags, _) = checked_auth_data(&map, rp_id)?;
+    if flags & AUTH_DATA_FLAG_AT == 0 {
+        return Err(CborError::DecodingError("authenticatorData 缺少 attestedCredentialData".to_string()));
+    }
+
+    let cred_len_at = 37 + 16;
+    let cred_id = auth_data
+        .get(cred_len_at..cred_len_at + 2)
+        .map(|len| usize::from(u16::from_be_bytes([len[0], len[1]])))
+        .and_then(|len| auth_data.get(cred_len_at + 2..cred_len_at + 2 + len))
+        .ok_or_else(|| CborError::DecodingError("attestedCredentialData 不完整".to_string()))?;
+    Ok((fmt, cred_id.to_vec()))
+}
+
+/// 解碼後的 authenticatorGetAssertion 回應
+#[derive(Debug, Clone, PartialEq)]
+pub struct AssertionResponse {
+    pub auth_data: Vec<u8>,
+    pub signature: Vec<u8>,
+    pub flags: u8,
+    pub sign_count: u32,
+}
+
+/// 解碼 authenticatorGetAssertion 回應（不含狀態碼）
+/// 0x02=authData, 0x03=signature
+pub fn decode_assertion(payload: &[u8], rp_id: &str) -> Result<AssertionResponse, CborError> {
+    let map = decode_response_map(payload, "getAssertion")?;
+    let (auth_data, flags, sign_count) = checked_auth_data(&map, rp_id)?;
+    match map.get(&Value::Integer(0x03)) {
+        Some(Value::Bytes(sig)) if !sig.is_empty() => Ok(AssertionResponse {
+            auth_data: auth_data.to_vec(),
+            signature: sig.clone(),
+            flags,
+            sign_count,
+        }),
+        _ => Err(CborError::DecodingError("回應缺少簽章".to_string())),
+    }
+}
+
+/// 解碼 authenticatorGetAssertion 回應（不含狀態碼），回傳 signCount
+pub fn decode_get_assertion(payload: &[u8], rp_id: &str) -> Result<u32, CborError> {
+    decode_assertion(payload, rp_id).map(|assertion| assertion.sign_count)
+}
+
+/// 解碼 credentialManagement enumerateCredentialsBegin/Next 回應（不含狀態碼）
+/// 0x06=user, 0x07=credentialID, 0x08=publicKey, 0x0A=credProtect；
+/// RP 資訊由先前的 enumerateRPs 取得
+pub fn decode_enumerated_credential(
+    payload: &[u8],
+    rp_id: &str,
+    rp_name: Option<String>,
+) -> Result<FidoCredential, CborError> {
+    let map = match serde_cbor::from_slice(payload)
+        .map_err(|e| CborError::DecodingError(e.to_string()))?
+    {
+        Value::Map(map) => map,
+        _ => return Err(CborError::DecodingError("憑證回應不是 CBOR map".to_string())),
+    };
+
+    let text_field = |m: &BTreeMap<Value, Value>, key: &str| {
+        match m.get(&Value::Text(key.to_string())) {
+            Some(Value::Text(t)) => Some(t.clone()),
+            _ => None,
+        }
+    };
+
+    let credential_id = match map.get(&Value::Integer(0x07)) {
+        Some(Value::Map(desc)) => match desc.get(&Value::Text("id".to_string())) {
+            Some(Value::Bytes(id)) => id.clone(),
+            _ => return Err(CborError::DecodingError("credentialID 缺少 id".to_string())),
+        },
+        _ => return Err(CborError::DecodingError("回應缺少 credentialID".to_string())),
+    };
+
+    let (user_name, user_display_name) = match map.get(&Value::Integer(0x06)) {
+        Some(Value::Map(user)) => (text_field(user, "name"), text_field(user, "displayName")),
+        _ => (None, None),
+    };
+
+    let cred_protect = match map.get(&Value::Integer(0x0A)) {
+        Some(Value::Integer(level)) => u8::try_from(*level).ok(),
+        _ => None,
+    };
+
+    let public_key = match map.get(&Value::Integer(0x08)) {
+        Some(Value::Map(cose_key)) => es256_public_key(cose_key),
+        _ => None,
+    };
+
+    Ok(FidoCredential {
+        credential_id,
+        rp_id: rp_id.to_string(),
+        rp_name,
+        user_name,
+        user_display_name,
+        creation_time: None,
+        cred_protect,
+        public_key,
+    })
+}
+
+/// 將 EC2 / P-256 的 COSE_Key（1=kty 2, -1=crv 1, -2=x, -3=y）轉為未壓縮 SEC1 點
+fn es256_public_key(cose_key: &BTreeMap<Value, Value>) -> Option<Vec<u8>> {
+    let field = |key: i128| cose_key.get(&Value::Integer(key));
+    if field(1) != Some(&Value::Integer(2)) || field(-1) != Some(&Value::Integer(1)) {
+        return None;
+    }
+    match (field(-2), field(-3)) {
+        (Some(Value::Bytes(x)), Some(Value::Bytes(y))) if x.len() == 32 && y.len() == 32 => {
+            Some([&[0x04][..], x, y].concat())
+        }
+        _ => None,
+    }
+}
+
 /// 將 CTAP 錯誤碼轉換為 FidoError
+/// 0x32 (CTAP2_ERR_PIN_BLOCKED): 重試次數用盡，需重設裝置
+/// 0x34 (CTAP2_ERR_PIN_AUTH_BLOCKED): 本次上電錯誤過多，重新插入即可再試
+/// 0x37 (CTAP2_ERR_PIN_POLICY_VIOLATION): 裝置設定 forcePINChange，需先變更 PIN
 pub fn ctap_error_to_fido_error(code: u8) -> FidoError {
     match code {
         0x31 => FidoError::PinInvalid(0),
         0x32 => FidoError::PinLocked,
         0x33 => FidoError::PinLengthInvalid,
+        0x34 => FidoError::PinAuthBlocked,
         0x36 => FidoError::PinInvalid(0), // PIN auth invalid
+        0x37 => FidoError::PinChangeRequired,
         _ => FidoError::CtapError(code),
     }
 }
 
+/// 將 authenticatorReset 的 CTAP 錯誤碼轉換為 FidoError
+/// 0x46 (CTAP2_ERR_NOT_ALLOWED): 已超過插入後約 10 秒的重設時限
+/// 0x3A (CTAP2_ERR_USER_ACTION_TIMEOUT): 未在時限內觸碰裝置
+pub fn reset_error_to_fido_error(code: u8) -> FidoError {
+    match code {
+        0x46 | 0x3A => FidoError::ResetRequiresReinsert,
+        _ => ctap_error_to_fido_error(code),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::fido::pin_protocol::PinUvAuthProtocol;
     use crate::fido::types::{
         AuthConfigSubCommand, ClientPinSubCommand, CredMgmtSubCommand,
     };
@@ -111,6 +875,10 @@ mod tests {
         CborCodecImpl::new()
     }
 
+    fn sample_token(protocol: PinUvAuthProtocol) -> PinUvAuthToken {
+        PinUvAuthToken::new(protocol, crate::secret::Zeroizing::new(vec![0x3C; 32]))
+    }
+
     // === encode_ctap_command 測試 ===
 
     #[test]
@@ -157,6 +925,28 @@ mod tests {
         assert!(result.len() > 1);
     }
 
+    #[test]
+    fn test_encode_client_pin_sub_command_as_integer_map() {
+        let cmd = CtapCommand::ClientPin(ClientPinSubCommand::GetRetries);
+        let result = codec().encode_ctap_command(&cmd).unwrap();
+        let map: BTreeMap<Value, Value> = serde_cbor::from_slice(&result[1..]).unwrap();
+        assert_eq!(map, BTreeMap::from([(Value::Integer(0x02), Value::Integer(0x01))]));
+    }
+
+    #[test]
+    fn test_client_pin_codes() {
+        assert_eq!(client_pin_code(&ClientPinSubCommand::ChangePin), 0x04);
+        assert_eq!(client_pin_code(&ClientPinSubCommand::GetPinToken), 0x05);
+        assert_eq!(
+            client_pin_code(&ClientPinSubCommand::GetPinUvAuthTokenUsingUvWithPermissions),
+            0x06
+        );
+        assert_eq!(
+            client_pin_code(&ClientPinSubCommand::GetPinUvAuthTokenUsingPinWithPermissions),
+            0x09
+        );
+    }
+
     #[test]
     fn test_encode_credential_management_has_correct_prefix() {
         let cmd = CtapCommand::CredentialManagement(CredMgmtSubCommand::GetCredsMetadata);
@@ -172,6 +962,768 @@ mod tests {
         let result = codec().encode_ctap_command(&cmd).unwrap();
         assert_eq!(result[0], 0x0D);
         assert!(result.len() > 1);
+        let map = decode_config_params(&result);
+        assert_eq!(map, BTreeMap::from([(Value::Integer(0x01), Value::Integer(0x01))]));
+    }
+
+    // === setMinPINLength 參數測試 ===
+
+    fn decode_config_params(encoded: &[u8]) -> BTreeMap<Value, Value> {
+        assert_eq!(encoded[0], 0x0D);
+        match serde_cbor::from_slice(&encoded[1..]).unwrap() {
+            Value::Map(map) => map,
+            other => panic!("expected map, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_set_min_pin_length_sub_params_length_only() {
+        let params = SetMinPinLengthParams {
+            new_min_pin_length: Some(8),
+            ..Default::default()
+        };
+        let mut expected = BTreeMap::new();
+        expected.insert(Value::Integer(0x01), Value::Integer(8));
+        assert_eq!(set_min_pin_length_sub_params(&params), Value::Map(expected));
+    }
+
+    #[test]
+    fn test_set_min_pin_length_sub_params_all_fields() {
+        let params = SetMinPinLengthParams {
+            new_min_pin_length: Some(6),
+            min_pin_length_rp_ids: vec!["example.com".to_string(), "login.example".to_string()],
+            force_change_pin: true,
+        };
+        let mut expected = BTreeMap::new();
+        expected.insert(Value::Integer(0x01), Value::Integer(6));
+        expected.insert(
+            Value::Integer(0x02),
+            Value::Array(vec![
+                Value::Text("example.com".to_string()),
+                Value::Text("login.example".to_string()),
+            ]),
+        );
+        expected.insert(Value::Integer(0x03), Value::Bool(true));
+        assert_eq!(set_min_pin_length_sub_params(&params), Value::Map(expected));
+    }
+
+    #[test]
+    fn test_set_min_pin_length_sub_params_force_change_only() {
+        let params = SetMinPinLengthParams {
+            force_change_pin: true,
+            ..Default::default()
+        };
+        let mut expected = BTreeMap::new();
+        expected.insert(Value::Integer(0x03), Value::Bool(true));
+        assert_eq!(set_min_pin_length_sub_params(&params), Value::Map(expected));
+    }
+
+    #[test]
+    fn test_encode_set_min_pin_length_without_params_or_auth() {
+        let encoded =
+            encode_set_min_pin_length(&SetMinPinLengthParams::default(), None).unwrap();
+        let map = decode_config_params(&encoded);
+        assert_eq!(map.len(), 1);
+        assert_eq!(map[&Value::Integer(0x01)], Value::Integer(0x03));
+    }
+
+    #[test]
+    fn test_encode_set_min_pin_length_with_auth() {
+        let params = SetMinPinLengthParams {
+            new_min_pin_length: Some(10),
+            min_pin_length_rp_ids: vec!["example.com".to_string()],
+            force_change_pin: false,
+        };
+        let token = sample_token(PinUvAuthProtocol::Two);
+        let encoded = encode_set_min_pin_length(&params, Some(&token)).unwrap();
+        let map = decode_config_params(&encoded);
+        assert_eq!(map[&Value::Integer(0x01)], Value::Integer(0x03));
+        assert_eq!(map[&Value::Integer(0x02)], set_min_pin_length_sub_params(&params));
+        assert_eq!(map[&Value::Integer(0x03)], Value::Integer(2));
+
+        // 32×0xFF || 0x0D || subCommand || subCommandParams
+        let mut message = vec![0xFF; 32];
+        message.extend_from_slice(&[0x0D, 0x03]);
+        message.extend(serde_cbor::to_vec(&set_min_pin_length_sub_params(&params)).unwrap());
+        let expected = token.authenticate(&message).param;
+        assert_eq!(expected.len(), 32);
+        assert_eq!(map[&Value::Integer(0x04)], Value::Bytes(expected));
+    }
+
+    #[test]
+    fn test_encode_enable_enterprise_attestation_with_auth() {
+        let token = sample_token(PinUvAuthProtocol::Two);
+        let encoded = encode_enable_enterprise_attestation(Some(&token)).unwrap();
+        let map = decode_config_params(&encoded);
+        assert_eq!(map[&Value::Integer(0x01)], Value::Integer(0x01));
+        assert!(!map.contains_key(&Value::Integer(0x02)));
+        let mut message = vec![0xFF; 32];
+        message.extend_from_slice(&[0x0D, 0x01]);
+        assert_eq!(map[&Value::Integer(0x04)], Value::Bytes(token.authenticate(&message).param));
+    }
+
+    // === credentialManagement 編碼測試 ===
+
+    #[test]
+    fn test_encode_get_creds_metadata_authenticates_sub_command() {
+        let token = sample_token(PinUvAuthProtocol::One);
+        let encoded = encode_get_creds_metadata(&token).unwrap();
+        assert_eq!(encoded[0], 0x0A);
+        let map: BTreeMap<Value, Value> = serde_cbor::from_slice(&encoded[1..]).unwrap();
+        assert_eq!(map.len(), 3);
+        assert_eq!(map[&Value::Integer(0x01)], Value::Integer(0x01));
+        assert_eq!(map[&Value::Integer(0x03)], Value::Integer(1));
+        // 協定 1 的 pinUvAuthParam 為 HMAC 前 16 位元組
+        assert_eq!(map[&Value::Integer(0x04)], Value::Bytes(token.authenticate(&[0x01]).param));
+        assert_eq!(token.authenticate(&[0x01]).param.len(), 16);
+    }
+
+    // === getKeyAgreement 測試 ===
+
+    fn sample_key_agreement() -> Vec<u8> {
+        let mut cose = BTreeMap::new();
+        cose.insert(Value::Integer(1), Value::Integer(2));
+        cose.insert(Value::Integer(3), Value::Integer(-25));
+        cose.insert(Value::Integer(-1), Value::Integer(1));
+        cose.insert(Value::Integer(-2), Value::Bytes(vec![0x11; 32]));
+        cose.insert(Value::Integer(-3), Value::Bytes(vec![0xAB; 32]));
+        let mut map = BTreeMap::new();
+        map.insert(Value::Integer(0x01), Value::Map(cose));
+        serde_cbor::to_vec(&Value::Map(map)).unwrap()
+    }
+
+    #[test]
+    fn test_encode_get_key_agreement() {
+        let encoded = encode_get_key_agreement(2).unwrap();
+        assert_eq!(encoded[0], 0x06);
+        let map: BTreeMap<Value, Value> = serde_cbor::from_slice(&encoded[1..]).unwrap();
+        assert_eq!(map[&Value::Integer(0x01)], Value::Integer(2));
+        assert_eq!(map[&Value::Integer(0x02)], Value::Integer(0x02));
+    }
+
+    fn encode_token_request(
+        permissions: PinUvPermissions,
+        rp_id: Option<&str>,
+    ) -> BTreeMap<Value, Value> {
+        let key = platform_key_agreement(&[0x11; 32], &[0x22; 32]);
+        let encoded = encode_get_token_using_pin(2, key, &[0x5A; 16], permissions, rp_id).unwrap();
+        assert_eq!(encoded[0], 0x06);
+        serde_cbor::from_slice(&encoded[1..]).unwrap()
+    }
+
+    #[test]
+    fn test_encode_get_token_using_pin_cred_mgmt_permission_only() {
+        let permissions = CredMgmtSubCommand::EnumerateRPsBegin.permissions();
+        let map = encode_token_request(permissions, None);
+        assert_eq!(map[&Value::Integer(0x01)], Value::Integer(2));
+        assert_eq!(map[&Value::Integer(0x02)], Value::Integer(0x09));
+        assert_eq!(map[&Value::Integer(0x06)], Value::Bytes(vec![0x5A; 16]));
+        assert_eq!(map[&Value::Integer(0x09)], Value::Integer(0x04));
+        assert!(!map.contains_key(&Value::Integer(0x0A)));
+        let Value::Map(key) = &map[&Value::Integer(0x03)] else {
+            panic!("keyAgreement 不是 COSE_Key");
+        };
+        assert_eq!(key[&Value::Integer(3)], Value::Integer(-25));
+        assert_eq!(key[&Value::Integer(-2)], Value::Bytes(vec![0x11; 32]));
+    }
+
+    #[test]
+    fn test_encode_get_token_using_pin_combined_permissions_with_rp_id() {
+        let permissions = PinUvPermissions::GET_ASSERTION | PinUvPermissions::LARGE_BLOB_WRITE;
+        assert!(permissions.contains(PinUvPermissions::GET_ASSERTION));
+        assert!(!permissions.contains(PinUvPermissions::CREDENTIAL_MANAGEMENT));
+        let map = encode_token_request(permissions, Some("example.com"));
+        assert_eq!(map[&Value::Integer(0x09)], Value::Integer(0x12));
+        assert_eq!(map[&Value::Integer(0x0A)], Value::Text("example.com".to_string()));
+        assert_eq!(
+            encode_token_request(AuthConfigSubCommand::ToggleAlwaysUv.permissions(), None)
+                [&Value::Integer(0x09)],
+            Value::Integer(0x20)
+        );
+    }
+
+    #[test]
+    fn test_encode_get_token_using_pin_rejects_empty_permissions() {
+        let encode = |permissions| {
+            let key = platform_key_agreement(&[0x11; 32], &[0x22; 32]);
+            encode_get_token_using_pin(2, key, &[0; 16], permissions, None)
+        };
+        assert!(encode(PinUvPermissions::default()).is_err());
+        // rpId 為選用參數，未指定時由認證器自行處理
+        assert!(encode(PinUvPermissions::GET_ASSERTION).is_ok());
+    }
+
+    #[test]
+    fn test_encode_get_token_using_uv() {
+        let key = platform_key_agreement(&[0x11; 32], &[0x22; 32]);
+        let permissions = PinUvPermissions::CREDENTIAL_MANAGEMENT;
+        let encoded = encode_get_token_using_uv(2, key, permissions, Some("example.com")).unwrap();
+        assert_eq!(encoded[0], 0x06);
+        let map: BTreeMap<Value, Value> = serde_cbor::from_slice(&encoded[1..]).unwrap();
+        assert_eq!(map[&Value::Integer(0x02)], Value::Integer(0x06));
+        assert_eq!(map[&Value::Integer(0x09)], Value::Integer(0x04));
+        assert_eq!(map[&Value::Integer(0x0A)], Value::Text("example.com".to_string()));
+        // UV 不需要 pinHashEnc
+        assert!(!map.contains_key(&Value::Integer(0x06)));
+        assert!(map.contains_key(&Value::Integer(0x03)));
+
+        let key = platform_key_agreement(&[0x11; 32], &[0x22; 32]);
+        assert!(encode_get_token_using_uv(2, key, PinUvPermissions::default(), None).is_err());
+    }
+
+    #[test]
+    fn test_encode_get_pin_token() {
+        let key = platform_key_agreement(&[0x11; 32], &[0x22; 32]);
+        let encoded = encode_get_pin_token(1, key, &[0x5A; 16]).unwrap();
+        assert_eq!(encoded[0], 0x06);
+        let map: BTreeMap<Value, Value> = serde_cbor::from_slice(&encoded[1..]).unwrap();
+        assert_eq!(map.len(), 4);
+        assert_eq!(map[&Value::Integer(0x01)], Value::Integer(1));
+        assert_eq!(map[&Value::Integer(0x02)], Value::Integer(0x05));
+        assert_eq!(map[&Value::Integer(0x06)], Value::Bytes(vec![0x5A; 16]));
+    }
+
+    #[test]
+    fn test_encode_set_pin() {
+        let key = platform_key_agreement(&[0x11; 32], &[0x22; 32]);
+        let encoded = encode_set_pin(2, key.clone(), &[0x5A; 80], &[0xA5; 32]).unwrap();
+        assert_eq!(encoded[0], 0x06);
+        let map: BTreeMap<Value, Value> = serde_cbor::from_slice(&encoded[1..]).unwrap();
+        assert_eq!(map.len(), 5);
+        assert_eq!(map[&Value::Integer(0x01)], Value::Integer(2));
+        assert_eq!(map[&Value::Integer(0x02)], Value::Integer(0x03));
+        assert_eq!(map[&Value::Integer(0x03)], key);
+        assert_eq!(map[&Value::Integer(0x04)], Value::Bytes(vec![0xA5; 32]));
+        assert_eq!(map[&Value::Integer(0x05)], Value::Bytes(vec![0x5A; 80]));
+    }
+
+    #[test]
+    fn test_encode_change_pin() {
+        let key = platform_key_agreement(&[0x11; 32], &[0x22; 32]);
+        let encoded = encode_change_pin(1, key, &[0x5A; 64], &[0x3C; 16], &[0xA5; 16]).unwrap();
+        assert_eq!(encoded[0], 0x06);
+        let map: BTreeMap<Value, Value> = serde_cbor::from_slice(&encoded[1..]).unwrap();
+        assert_eq!(map.len(), 6);
+        assert_eq!(map[&Value::Integer(0x02)], Value::Integer(0x04));
+        assert_eq!(map[&Value::Integer(0x04)], Value::Bytes(vec![0xA5; 16]));
+        assert_eq!(map[&Value::Integer(0x05)], Value::Bytes(vec![0x5A; 64]));
+        assert_eq!(map[&Value::Integer(0x06)], Value::Bytes(vec![0x3C; 16]));
+    }
+
+    #[test]
+    fn test_decode_pin_token() {
+        let mut map = BTreeMap::new();
+        map.insert(Value::Integer(0x02), Value::Bytes(vec![0x77; 48]));
+        let payload = serde_cbor::to_vec(&Value::Map(map)).unwrap();
+        assert_eq!(decode_pin_token(&payload).unwrap(), vec![0x77; 48]);
+        let empty = serde_cbor::to_vec(&Value::Map(BTreeMap::new())).unwrap();
+        assert!(decode_pin_token(&empty).is_err());
+    }
+
+    #[test]
+    fn test_decode_key_agreement() {
+        let info = decode_key_agreement(&sample_key_agreement(), 1).unwrap();
+        assert_eq!(info.pin_uv_auth_protocol, 1);
+        assert_eq!(info.kty, 2);
+        assert_eq!(info.alg, -25);
+        assert_eq!(info.crv, 1);
+        assert_eq!(info.x, "11".repeat(32));
+        assert_eq!(info.y, "ab".repeat(32));
+    }
+
+    #[test]
+    fn test_decode_key_agreement_missing_key() {
+        let payload = serde_cbor::to_vec(&Value::Map(BTreeMap::new())).unwrap();
+        assert!(decode_key_agreement(&payload, 1).is_err());
+    }
+
+    // === 測試註冊編碼測試 ===
+
+    fn sample_auth_data(rp_id: &str, flags: u8, cred_id: &[u8]) -> Vec<u8> {
+        use sha2::{Digest, Sha256};
+
+        let mut data = Sha256::digest(rp_id.as_bytes()).to_vec();
+        data.push(flags);
+        data.extend_from_slice(&7u32.to_be_bytes());
+        if flags & AUTH_DATA_FLAG_AT != 0 {
+            data.extend_from_slice(&[0u8; 16]);
+            data.extend_from_slice(&(cred_id.len() as u16).to_be_bytes());
+            data.extend_from_slice(cred_id);
+        }
+        data
+    }
+
+    #[test]
+    fn test_client_data_hash_matches_client_data_json() {
+        use sha2::{Digest, Sha256};
+
+        let expected: [u8; 32] = Sha256::digest(
+            br#"{"type":"webauthn.create","challenge":"-_8","origin":"https://example.test","crossOrigin":false}"#,
+        )
+        .into();
+        assert_eq!(client_data_hash("webauthn.create", &[0xFB, 0xFF], "example.test"), expected);
+        assert_ne!(
+            client_data_hash("webauthn.get", &[0xFB, 0xFF], "example.test"),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_encode_make_credential_params() {
+        let hash = [0x5A; 32];
+        let encoded = encode_make_credential(&hash, "example.test", &[1, 2, 3]).unwrap();
+        assert_eq!(encoded[0], 0x01);
+        let map: BTreeMap<Value, Value> = serde_cbor::from_slice(&encoded[1..]).unwrap();
+        assert_eq!(map[&Value::Integer(0x01)], Value::Bytes(hash.to_vec()));
+
+        let Value::Map(rp) = &map[&Value::Integer(0x02)] else { panic!("rp 不是 map") };
+        assert_eq!(rp[&Value::Text("id".into())], Value::Text("example.test".into()));
+        let Value::Map(user) = &map[&Value::Integer(0x03)] else { panic!("user 不是 map") };
+        assert_eq!(user[&Value::Text("id".into())], Value::Bytes(vec![1, 2, 3]));
+        let Value::Array(params) = &map[&Value::Integer(0x04)] else { panic!("params 不是 array") };
+        let Value::Map(alg) = &params[0] else { panic!("param 不是 map") };
+        assert_eq!(alg[&Value::Text("alg".into())], Value::Integer(-7));
+        assert_eq!(alg[&Value::Text("type".into())], Value::Text("public-key".into()));
+        assert!(!map.contains_key(&Value::Integer(0x07)));
+    }
+
+    #[test]
+    fn test_encode_get_assertion_params() {
+        let hash = [0xA5; 32];
+        let encoded = encode_get_assertion("example.test", &hash, &[9, 9], None).unwrap();
+        assert_eq!(encoded[0], 0x02);
+        let map: BTreeMap<Value, Value> = serde_cbor::from_slice(&encoded[1..]).unwrap();
+        assert_eq!(map[&Value::Integer(0x01)], Value::Text("example.test".into()));
+        assert_eq!(map[&Value::Integer(0x02)], Value::Bytes(hash.to_vec()));
+        let Value::Array(allow) = &map[&Value::Integer(0x03)] else { panic!("allowList 不是 array") };
+        let Value::Map(desc) = &allow[0] else { panic!("descriptor 不是 map") };
+        assert_eq!(desc[&Value::Text("id".into())], Value::Bytes(vec![9, 9]));
+    }
+
+    #[test]
+    fn test_encode_get_assertion_allow_list_has_single_public_key_descriptor() {
+        let encoded = encode_get_assertion("example.com", &[0; 32], &[0xCA, 0xFE], None).unwrap();
+        let map: BTreeMap<Value, Value> = serde_cbor::from_slice(&encoded[1..]).unwrap();
+        assert_eq!(map.len(), 3);
+        let Value::Array(allow) = &map[&Value::Integer(0x03)] else { panic!("allowList 不是 array") };
+        assert_eq!(allow.len(), 1);
+        let Value::Map(desc) = &allow[0] else { panic!("descriptor 不是 map") };
+        assert_eq!(desc.len(), 2);
+        assert_eq!(desc[&Value::Text("id".into())], Value::Bytes(vec![0xCA, 0xFE]));
+        assert_eq!(desc[&Value::Text("type".into())], Value::Text("public-key".into()));
+    }
+
+    #[test]
+    fn test_encode_get_assertion_authenticates_client_data_hash() {
+        let token = sample_token(PinUvAuthProtocol::Two);
+        let hash = [0x42; 32];
+        let encoded = encode_get_assertion("example.com", &hash, &[1], Some(&token)).unwrap();
+        let map: BTreeMap<Value, Value> = serde_cbor::from_slice(&encoded[1..]).unwrap();
+        assert_eq!(map[&Value::Integer(0x06)], Value::Bytes(token.authenticate(&hash).param));
+        assert_eq!(map[&Value::Integer(0x07)], Value::Integer(2));
+    }
+
+    #[test]
+    fn test_client_data_hash_for_get_ceremony() {
+        assert_eq!(
+            crate::util::hex_encode(&client_data_hash("webauthn.get", &[0; 32], "example.com")),
+            "255e6e8baea1fcaa46da36633ec0b59e7ca901c0a25fcc32cf1db81d508ecdb3"
+        );
+    }
+
+    #[test]
+    fn test_decode_make_credential_extracts_credential_id() {
+        let mut map = BTreeMap::new();
+        map.insert(Value::Integer(0x01), Value::Text("packed".into()));
+        map.insert(
+            Value::Integer(0x02),
+            Value::Bytes(sample_auth_data("example.test", 0x41, &[0xC1, 0xC2, 0xC3])),
+        );
+        map.insert(Value::Integer(0x03), Value::Map(BTreeMap::new()));
+        let payload = serde_cbor::to_vec(&Value::Map(map)).unwrap();
+
+        let (fmt, cred_id) = decode_make_credential(&payload, "example.test").unwrap();
+        assert_eq!(fmt, "packed");
+        assert_eq!(cred_id, vec![0xC1, 0xC2, 0xC3]);
+        assert!(decode_make_credential(&payload, "other.test").is_err());
+    }
+
+    #[test]
+    fn test_decode_get_assertion_requires_signature() {
+        let mut map = BTreeMap::new();
+        map.insert(Value::Integer(0x02), Value::Bytes(sample_auth_data("example.test", 0x01, &[])));
+        let unsigned = serde_cbor::to_vec(&Value::Map(map.clone())).unwrap();
+        assert!(decode_get_assertion(&unsigned, "example.test").is_err());
+
+        map.insert(Value::Integer(0x03), Value::Bytes(vec![0x30, 0x44]));
+        let signed = serde_cbor::to_vec(&Value::Map(map)).unwrap();
+        assert_eq!(decode_get_assertion(&signed, "example.test").unwrap(), 7);
+    }
+
+    #[test]
+    fn test_decode_assertion_returns_signed_parts() {
+        let auth_data = sample_auth_data("example.test", 0x05, &[]);
+        let mut map = BTreeMap::new();
+        map.insert(Value::Integer(0x02), Value::Bytes(auth_data.clone()));
+        map.insert(Value::Integer(0x03), Value::Bytes(vec![0x30, 0x44]));
+        let payload = serde_cbor::to_vec(&Value::Map(map)).unwrap();
+
+        let assertion = decode_assertion(&payload, "example.test").unwrap();
+        assert_eq!(assertion.auth_data, auth_data);
+        assert_eq!(assertion.signature, vec![0x30, 0x44]);
+        assert_eq!(assertion.flags, 0x05);
+        assert_eq!(assertion.sign_count, 7);
+    }
+
+    // === toggleAlwaysUv 編碼測試 ===
+
+    #[test]
+    fn test_encode_toggle_always_uv_without_auth() {
+        let encoded = encode_toggle_always_uv(None).unwrap();
+        let map = decode_config_params(&encoded);
+        assert_eq!(map.len(), 1);
+        assert_eq!(map[&Value::Integer(0x01)], Value::Integer(0x02));
+    }
+
+    #[test]
+    fn test_encode_toggle_always_uv_with_auth() {
+        let token = sample_token(PinUvAuthProtocol::One);
+        let encoded = encode_toggle_always_uv(Some(&token)).unwrap();
+        let map = decode_config_params(&encoded);
+        assert_eq!(map[&Value::Integer(0x01)], Value::Integer(0x02));
+        assert!(!map.contains_key(&Value::Integer(0x02)));
+        assert_eq!(map[&Value::Integer(0x03)], Value::Integer(1));
+        let mut message = vec![0xFF; 32];
+        message.extend_from_slice(&[0x0D, 0x02]);
+        assert_eq!(map[&Value::Integer(0x04)], Value::Bytes(token.authenticate(&message).param));
+    }
+
+    // === GetInfo 解碼測試 ===
+
+    fn sample_get_info(options: &[(&str, bool)], extensions: &[&str]) -> Vec<u8> {
+        let mut map = BTreeMap::new();
+        map.insert(
+            Value::Integer(0x01),
+            Value::Array(vec![
+                Value::Text("FIDO_2_0".to_string()),
+                Value::Text("FIDO_2_1".to_string()),
+            ]),
+        );
+        map.insert(
+            Value::Integer(0x02),
+            Value::Array(extensions.iter().map(|e| Value::Text(e.to_string())).collect()),
+        );
+        map.insert(Value::Integer(0x03), Value::Bytes((0u8..16).collect()));
+        map.insert(
+            Value::Integer(0x04),
+            Value::Map(
+                options
+                    .iter()
+                    .map(|(k, v)| (Value::Text(k.to_string()), Value::Bool(*v)))
+                    .collect(),
+            ),
+        );
+        map.insert(Value::Integer(0x0E), Value::Integer(0x0604));
+        serde_cbor::to_vec(&Value::Map(map)).unwrap()
+    }
+
+    #[test]
+    fn test_decode_get_info_fields() {
+        let payload = sample_get_info(&[("clientPin", true), ("rk", true)], &["hmac-secret"]);
+        let info = decode_get_info(&payload).unwrap();
+        assert_eq!(info.versions, vec!["FIDO_2_0", "FIDO_2_1"]);
+        assert_eq!(info.extensions, vec!["hmac-secret"]);
+        assert_eq!(info.aaguid, "00010203-0405-0607-0809-0a0b0c0d0e0f");
+        assert_eq!(info.model_name, None);
+        assert_eq!(info.firmware_version, "6.4");
+        assert!(info.pin_set);
+        assert_eq!(info.options.get("rk"), Some(&true));
+    }
+
+    #[test]
+    fn test_decode_get_info_min_pin_length_and_force_change() {
+        let payload = sample_get_info(&[("setMinPINLength", true)], &[]);
+        let Value::Map(mut map) = serde_cbor::from_slice(&payload).unwrap() else {
+            panic!("GetInfo 不是 map");
+        };
+        map.insert(Value::Integer(0x0C), Value::Bool(true));
+        map.insert(Value::Integer(0x0D), Value::Integer(8));
+        let info = decode_get_info(&serde_cbor::to_vec(&Value::Map(map)).unwrap()).unwrap();
+        assert_eq!(info.min_pin_length, Some(8));
+        assert!(info.force_pin_change);
+    }
+
+    #[test]
+    fn test_decode_get_info_without_min_pin_length() {
+        let info = decode_get_info(&sample_get_info(&[], &[])).unwrap();
+        assert_eq!(info.min_pin_length, None);
+        assert!(!info.force_pin_change);
+        assert!(info.pin_uv_auth_protocols.is_empty());
+    }
+
+    #[test]
+    fn test_decode_get_info_pin_uv_auth_protocols() {
+        let Value::Map(mut map) = serde_cbor::from_slice(&sample_get_info(&[], &[])).unwrap() else {
+            panic!("GetInfo 不是 map");
+        };
+        map.insert(
+            Value::Integer(0x06),
+            Value::Array(vec![Value::Integer(2), Value::Integer(1), Value::Text("x".into())]),
+        );
+        let info = decode_get_info(&serde_cbor::to_vec(&Value::Map(map)).unwrap()).unwrap();
+        assert_eq!(info.pin_uv_auth_protocols, vec![2, 1]);
+    }
+
+    #[test]
+    fn test_decode_get_info_force_pin_change_false_or_malformed() {
+        for value in [Value::Bool(false), Value::Integer(1)] {
+            let payload = sample_get_info(&[], &[]);
+            let Value::Map(mut map) = serde_cbor::from_slice(&payload).unwrap() else {
+                panic!("GetInfo 不是 map");
+            };
+            map.insert(Value::Integer(0x0C), value);
+            let info = decode_get_info(&serde_cbor::to_vec(&Value::Map(map)).unwrap()).unwrap();
+            assert!(!info.force_pin_change);
+        }
+    }
+
+    #[test]
+    fn test_decode_get_info_rejects_non_map() {
+        assert!(decode_get_info(&[0x80]).is_err());
+    }
+
+    #[test]
+    fn test_capabilities_full_featured() {
+        let payload = sample_get_info(
+            &[("clientPin", false), ("credMgmt", true), ("authnrCfg", true), ("largeBlobs", true)],
+            &["hmac-secret", "credProtect", "largeBlobKey"],
+        );
+        let info = decode_get_info(&payload).unwrap();
+        assert!(!info.pin_set);
+        assert_eq!(
+            info.capabilities,
+            crate::fido::types::FidoCapabilities {
+                client_pin: true,
+                cred_mgmt: true,
+                authenticator_config: true,
+                large_blobs: true,
+                hmac_secret: true,
+                cred_protect: true,
+            }
+        );
+    }
+
+    #[test]
+    fn test_capabilities_cred_mgmt_preview() {
+        let payload = sample_get_info(&[("credentialMgmtPreview", true)], &[]);
+        let info = decode_get_info(&payload).unwrap();
+        assert!(info.supports_cred_mgmt());
+        assert!(!info.supports_large_blobs());
+        assert!(!info.capabilities.client_pin);
+    }
+
+    #[test]
+    fn test_capabilities_disabled_options() {
+        let payload = sample_get_info(&[("credMgmt", false), ("largeBlobs", false)], &[]);
+        let info = decode_get_info(&payload).unwrap();
+        assert!(!info.supports_cred_mgmt());
+        assert!(!info.supports_large_blobs());
+        assert!(!info.supports_hmac_secret());
+        assert!(!info.supports_cred_protect());
+    }
+
+    // === 憑證列舉解碼測試 ===
+
+    fn sample_credential(cred_protect: Option<i128>) -> Vec<u8> {
+        let mut user = BTreeMap::new();
+        user.insert(Value::Text("id".to_string()), Value::Bytes(vec![0x01]));
+        user.insert(Value::Text("name".to_string()), Value::Text("alice".to_string()));
+        user.insert(Value::Text("displayName".to_string()), Value::Text("Alice".to_string()));
+        let mut desc = BTreeMap::new();
+        desc.insert(Value::Text("id".to_string()), Value::Bytes(vec![0xCA, 0xFE]));
+        desc.insert(Value::Text("type".to_string()), Value::Text("public-key".to_string()));
+
+        let mut map = BTreeMap::new();
+        map.insert(Value::Integer(0x06), Value::Map(user));
+        map.insert(Value::Integer(0x07), Value::Map(desc));
+        if let Some(level) = cred_protect {
+            map.insert(Value::Integer(0x0A), Value::Integer(level));
+        }
+        serde_cbor::to_vec(&Value::Map(map)).unwrap()
+    }
+
+    #[test]
+    fn test_decode_enumerated_credential_with_cred_protect() {
+        let payload = sample_credential(Some(3));
+        let cred =
+            decode_enumerated_credential(&payload, "example.com", Some("Example".to_string()))
+                .unwrap();
+        assert_eq!(cred.credential_id, vec![0xCA, 0xFE]);
+        assert_eq!(cred.rp_id, "example.com");
+        assert_eq!(cred.rp_name.as_deref(), Some("Example"));
+        assert_eq!(cred.user_name.as_deref(), Some("alice"));
+        assert_eq!(cred.user_display_name.as_deref(), Some("Alice"));
+        assert_eq!(cred.cred_protect, Some(3));
+    }
+
+    #[test]
+    fn test_decode_enumerated_credential_without_cred_protect() {
+        let payload = sample_credential(None);
+        let cred = decode_enumerated_credential(&payload, "example.com", None).unwrap();
+        assert_eq!(cred.cred_protect, None);
+    }
+
+    #[test]
+    fn test_decode_enumerated_credential_out_of_range_cred_protect() {
+        let payload = sample_credential(Some(300));
+        let cred = decode_enumerated_credential(&payload, "example.com", None).unwrap();
+        assert_eq!(cred.cred_protect, None);
+    }
+
+    #[test]
+    fn test_decode_enumerated_credential_es256_public_key() {
+        let mut cose_key = BTreeMap::new();
+        cose_key.insert(Value::Integer(1), Value::Integer(2));
+        cose_key.insert(Value::Integer(3), Value::Integer(-7));
+        cose_key.insert(Value::Integer(-1), Value::Integer(1));
+        cose_key.insert(Value::Integer(-2), Value::Bytes(vec![0x11; 32]));
+        cose_key.insert(Value::Integer(-3), Value::Bytes(vec![0x22; 32]));
+        let Value::Map(mut map) = serde_cbor::from_slice(&sample_credential(None)).unwrap() else {
+            panic!("憑證不是 map");
+        };
+        map.insert(Value::Integer(0x08), Value::Map(cose_key.clone()));
+        let payload = serde_cbor::to_vec(&Value::Map(map.clone())).unwrap();
+
+        let cred = decode_enumerated_credential(&payload, "example.com", None).unwrap();
+        let public_key = cred.public_key.unwrap();
+        assert_eq!(public_key.len(), 65);
+        assert_eq!(public_key[0], 0x04);
+        assert_eq!(&public_key[1..33], &[0x11; 32]);
+        assert_eq!(&public_key[33..], &[0x22; 32]);
+
+        // Ed25519（OKP）等非 P-256 金鑰不帶入
+        cose_key.insert(Value::Integer(1), Value::Integer(1));
+        map.insert(Value::Integer(0x08), Value::Map(cose_key));
+        let payload = serde_cbor::to_vec(&Value::Map(map)).unwrap();
+        assert_eq!(decode_enumerated_credential(&payload, "example.com", None).unwrap().public_key, None);
+    }
+
+    fn sample_rp(total: Option<i128>) -> Vec<u8> {
+        let mut rp = BTreeMap::new();
+        rp.insert(Value::Text("id".to_string()), Value::Text("example.com".to_string()));
+        rp.insert(Value::Text("name".to_string()), Value::Text("Example".to_string()));
+        let mut map = BTreeMap::new();
+        map.insert(Value::Integer(0x03), Value::Map(rp));
+        map.insert(Value::Integer(0x04), Value::Bytes(vec![0xAB; 32]));
+        if let Some(total) = total {
+            map.insert(Value::Integer(0x05), Value::Integer(total));
+        }
+        serde_cbor::to_vec(&Value::Map(map)).unwrap()
+    }
+
+    #[test]
+    fn test_decode_enumerated_rp() {
+        let rp = decode_enumerated_rp(&sample_rp(Some(2))).unwrap();
+        assert_eq!(rp.rp_id, "example.com");
+        assert_eq!(rp.rp_name.as_deref(), Some("Example"));
+        assert_eq!(rp.rp_id_hash, vec![0xAB; 32]);
+        assert_eq!(rp.total_rps, Some(2));
+        assert_eq!(decode_enumerated_rp(&sample_rp(None)).unwrap().total_rps, None);
+        assert!(decode_enumerated_rp(&[0xA0]).is_err());
+    }
+
+    #[test]
+    fn test_decode_total_credentials() {
+        let Value::Map(mut map) = serde_cbor::from_slice(&sample_credential(None)).unwrap() else {
+            panic!("憑證不是 map");
+        };
+        assert_eq!(decode_total_credentials(&sample_credential(None)).unwrap(), None);
+        map.insert(Value::Integer(0x09), Value::Integer(4));
+        let payload = serde_cbor::to_vec(&Value::Map(map)).unwrap();
+        assert_eq!(decode_total_credentials(&payload).unwrap(), Some(4));
+    }
+
+    #[test]
+    fn test_encode_enumerate_credentials_begin_authenticates_rp_id_hash() {
+        let token = sample_token(PinUvAuthProtocol::Two);
+        let encoded = encode_enumerate_credentials_begin(&[0xAB; 32], &token).unwrap();
+        assert_eq!(encoded[0], 0x0A);
+        let map: BTreeMap<Value, Value> = serde_cbor::from_slice(&encoded[1..]).unwrap();
+        assert_eq!(map[&Value::Integer(0x01)], Value::Integer(0x04));
+        let sub_params = &map[&Value::Integer(0x02)];
+        let mut message = vec![0x04];
+        message.extend(serde_cbor::to_vec(sub_params).unwrap());
+        assert_eq!(map[&Value::Integer(0x04)], Value::Bytes(token.authenticate(&message).param));
+    }
+
+    #[test]
+    fn test_encode_delete_credential_sends_descriptor_and_auth() {
+        let token = sample_token(PinUvAuthProtocol::Two);
+        let encoded = encode_delete_credential(&[0xCA, 0xFE], &token).unwrap();
+        assert_eq!(encoded[0], 0x0A);
+        let map: BTreeMap<Value, Value> = serde_cbor::from_slice(&encoded[1..]).unwrap();
+        assert_eq!(map.len(), 4);
+        assert_eq!(map[&Value::Integer(0x01)], Value::Integer(0x06));
+        let Value::Map(sub_params) = &map[&Value::Integer(0x02)] else {
+            panic!("subCommandParams 不是 map");
+        };
+        let Value::Map(desc) = &sub_params[&Value::Integer(0x02)] else {
+            panic!("credentialID 不是 descriptor");
+        };
+        assert_eq!(desc[&Value::Text("id".into())], Value::Bytes(vec![0xCA, 0xFE]));
+        assert_eq!(desc[&Value::Text("type".into())], Value::Text("public-key".into()));
+        assert_eq!(map[&Value::Integer(0x03)], Value::Integer(2));
+
+        let mut message = vec![0x06];
+        message.extend(serde_cbor::to_vec(&map[&Value::Integer(0x02)]).unwrap());
+        assert_eq!(map[&Value::Integer(0x04)], Value::Bytes(token.authenticate(&message).param));
+    }
+
+    #[test]
+    fn test_encode_enumerate_next_has_no_auth() {
+        let encoded = encode_enumerate_next(&CredMgmtSubCommand::EnumerateRPsNext).unwrap();
+        let map: BTreeMap<Value, Value> = serde_cbor::from_slice(&encoded[1..]).unwrap();
+        assert_eq!(map, BTreeMap::from([(Value::Integer(0x01), Value::Integer(0x03))]));
+        let token = sample_token(PinUvAuthProtocol::One);
+        let encoded = encode_enumerate_rps_begin(&token).unwrap();
+        let map: BTreeMap<Value, Value> = serde_cbor::from_slice(&encoded[1..]).unwrap();
+        assert_eq!(map[&Value::Integer(0x01)], Value::Integer(0x02));
+        assert_eq!(map[&Value::Integer(0x04)], Value::Bytes(token.authenticate(&[0x02]).param));
+    }
+
+    #[test]
+    fn test_decode_enumerated_credential_missing_id() {
+        let payload = serde_cbor::to_vec(&Value::Map(BTreeMap::new())).unwrap();
+        assert!(decode_enumerated_credential(&payload, "example.com", None).is_err());
+    }
+
+    #[test]
+    fn test_decode_creds_metadata() {
+        let mut map = BTreeMap::new();
+        map.insert(Value::Integer(0x01), Value::Integer(3));
+        map.insert(Value::Integer(0x02), Value::Integer(47));
+        let payload = serde_cbor::to_vec(&Value::Map(map)).unwrap();
+        let metadata = decode_creds_metadata(&payload).unwrap();
+        assert_eq!(metadata.existing_resident_credentials_count, 3);
+        assert_eq!(metadata.max_possible_remaining_resident_credentials_count, 47);
+        assert_eq!(metadata.total_slots(), 50);
+    }
+
+    #[test]
+    fn test_decode_creds_metadata_missing_field() {
+        let mut map = BTreeMap::new();
+        map.insert(Value::Integer(0x01), Value::Integer(3));
+        let payload = serde_cbor::to_vec(&Value::Map(map)).unwrap();
+        assert!(decode_creds_metadata(&payload).is_err());
+    }
+
+    #[test]
+    fn test_decode_creds_metadata_negative_count() {
+        let mut map = BTreeMap::new();
+        map.insert(Value::Integer(0x01), Value::Integer(-1));
+        map.insert(Value::Integer(0x02), Value::Integer(10));
+        let payload = serde_cbor::to_vec(&Value::Map(map)).unwrap();
+        assert!(decode_creds_metadata(&payload).is_err());
     }
 
     // === decode_ctap_response 測試 ===
@@ -224,6 +1776,13 @@ mod tests {
         assert!(matches!(err, FidoError::PinLocked));
     }
 
+    #[test]
+    fn test_ctap_error_pin_auth_blocked_is_recoverable() {
+        let err = ctap_error_to_fido_error(0x34);
+        assert!(matches!(err, FidoError::PinAuthBlocked));
+        assert!(!matches!(ctap_error_to_fido_error(0x32), FidoError::PinAuthBlocked));
+    }
+
     #[test]
     fn test_ctap_error_pin_length_invalid() {
         let err = ctap_error_to_fido_error(0x33);
@@ -236,9 +1795,36 @@ mod tests {
         assert!(matches!(err, FidoError::PinInvalid(0)));
     }
 
+    #[test]
+    fn test_ctap_error_pin_policy_violation_requires_change() {
+        let err = ctap_error_to_fido_error(0x37);
+        assert!(matches!(err, FidoError::PinChangeRequired));
+    }
+
     #[test]
     fn test_ctap_error_unknown_code() {
         let err = ctap_error_to_fido_error(0x99);
         assert!(matches!(err, FidoError::CtapError(0x99)));
     }
+
+    #[test]
+    fn test_reset_error_not_allowed() {
+        let err = reset_error_to_fido_error(0x46);
+        assert!(matches!(err, FidoError::ResetRequiresReinsert));
+    }
+
+    #[test]
+    fn test_reset_error_user_action_timeout() {
+        let err = reset_error_to_fido_error(0x3A);
+        assert!(matches!(err, FidoError::ResetRequiresReinsert));
+    }
+
+    #[test]
+    fn test_reset_error_falls_back_to_generic_mapping() {
+        assert!(matches!(reset_error_to_fido_error(0x32), FidoError::PinLocked));
+        assert!(matches!(
+            reset_error_to_fido_error(0x99),
+            FidoError::CtapError(0x99)
+        ));
+    }
 }
@@ -0,0 +1,426 @@
+use std::time::{Duration, Instant};
+
+use crate::error::FidoError;
+use crate::fido::types::KeepaliveStatus;
+
+/// USB HID 報告大小（不含 report ID）
+pub const HID_REPORT_SIZE: usize = 64;
+/// CTAPHID_CBOR 指令碼
+pub const CTAPHID_CBOR: u8 = 0x10;
+/// CTAPHID_MSG 指令碼（資料為 ISO 7816-4 APDU，回應含 SW1 SW2）
+pub const CTAPHID_MSG: u8 = 0x03;
+
+/// CTAPHID_KEEPALIVE 指令碼（裝置處理中或等待使用者觸碰）
+pub const CTAPHID_KEEPALIVE: u8 = 0x3B;
+/// CTAPHID_CANCEL 指令碼（中止等待使用者觸碰的請求）
+pub const CTAPHID_CANCEL: u8 = 0x11;
+/// CTAPHID_INIT 指令碼（向裝置配置 channel）
+pub const CTAPHID_INIT: u8 = 0x06;
+/// CTAPHID_ERROR 指令碼（資料為一位元組錯誤碼）
+pub const CTAPHID_ERROR: u8 = 0x3F;
+/// 配置 channel 前使用的廣播 CID
+pub const BROADCAST_CID: u32 = 0xFFFF_FFFF;
+
+/// 初始化封包可承載的資料量: CID(4) + CMD(1) + BCNT(2) 之後的空間
+const INIT_DATA_SIZE: usize = HID_REPORT_SIZE - 7;
+/// 接續封包可承載的資料量: CID(4) + SEQ(1) 之後的空間
+const CONT_DATA_SIZE: usize = HID_REPORT_SIZE - 5;
+/// 接續封包序號上限 (0x00-0x7F)
+const MAX_CONT_PACKETS: usize = 128;
+
+/// 單一 CTAPHID 訊息可承載的最大資料量
+pub const MAX_MESSAGE_SIZE: usize = INIT_DATA_SIZE + MAX_CONT_PACKETS * CONT_DATA_SIZE;
+
+/// 組出 CTAPHID_CBOR 的 payload：CTAP 指令碼 + CBOR 參數
+pub fn cbor_request(command_byte: u8, cbor_params: &[u8]) -> Vec<u8> {
+    let mut payload = Vec::with_capacity(1 + cbor_params.len());
+    payload.push(command_byte);
+    payload.extend_from_slice(cbor_params);
+    payload
+}
+
+/// 將訊息切割為 CTAPHID 封包（初始化封包 + 接續封包），不足部分補 0
+pub fn frame_message(
+    cid: u32,
+    cmd: u8,
+    payload: &[u8],
+) -> Result<Vec<[u8; HID_REPORT_SIZE]>, FidoError> {
+    if payload.len() > MAX_MESSAGE_SIZE {
+        return Err(FidoError::CommunicationError(format!(
+            "CTAPHID 訊息過長（{} 位元組，上限 {MAX_MESSAGE_SIZE}）",
+            payload.len()
+        )));
+    }
+
+    let cid_bytes = cid.to_be_bytes();
+    let len = payload.len() as u16;
+    let split = payload.len().min(INIT_DATA_SIZE);
+
+    let mut init = [0u8; HID_REPORT_SIZE];
+    init[..4].copy_from_slice(&cid_bytes);
+    init[4] = cmd | 0x80;
+    init[5..7].copy_from_slice(&len.to_be_bytes());
+    init[7..7 + split].copy_from_slice(&payload[..split]);
+
+    let mut packets = vec![init];
+    for (seq, chunk) in payload[split..].chunks(CONT_DATA_SIZE).enumerate() {
+        let mut cont = [0u8; HID_REPORT_SIZE];
+        cont[..4].copy_from_slice(&cid_bytes);
+        cont[4] = seq as u8;
+        cont[5..5 + chunk.len()].copy_from_slice(chunk);
+        packets.push(cont);
+    }
+    Ok(packets)
+}
+
+/// 組出 CTAPHID_CANCEL 封包（無資料）
+pub fn cancel_frame(cid: u32) -> [u8; HID_REPORT_SIZE] {
+    let mut report = [0u8; HID_REPORT_SIZE];
+    report[..4].copy_from_slice(&cid.to_be_bytes());
+    report[4] = CTAPHID_CANCEL | 0x80;
+    report
+}
+
+/// 解析 KEEPALIVE 初始化封包的狀態位元組；非 KEEPALIVE 封包回傳 None
+pub fn parse_keepalive(report: &[u8; HID_REPORT_SIZE]) -> Option<KeepaliveStatus> {
+    if report[4] != CTAPHID_KEEPALIVE | 0x80 {
+        return None;
+    }
+    let len = u16::from_be_bytes([report[5], report[6]]);
+    if len < 1 {
+        return None;
+    }
+    Some(KeepaliveStatus::from(report[7]))
+}
+
+/// 解析 CTAPHID_INIT 回應：nonce(8) + 配置的 CID(4) + 版本與能力旗標；nonce 不符時回傳錯誤
+pub fn parse_init_response(nonce: &[u8; 8], data: &[u8]) -> Result<u32, FidoError> {
+    match data.get(..12) {
+        Some(head) if head[..8] == nonce[..] => {
+            Ok(u32::from_be_bytes([head[8], head[9], head[10], head[11]]))
+        }
+        _ => Err(FidoError::CommunicationError("CTAPHID_INIT 回應無效".to_string())),
+    }
+}
+
+/// 檢查回應的指令碼；CTAPHID_ERROR 或其他非預期的指令碼轉為錯誤
+pub fn expect_response(expected: u8, response: (u8, Vec<u8>)) -> Result<Vec<u8>, FidoError> {
+    match response {
+        (cmd, data) if cmd == expected => Ok(data),
+        (CTAPHID_ERROR, data) => Err(FidoError::CommunicationError(format!(
+            "CTAPHID 錯誤碼: 0x{:02X}",
+            data.first().copied().unwrap_or(0)
+        ))),
+        (cmd, _) => Err(FidoError::CommunicationError(format!(
+            "非預期的 CTAPHID 回應指令: 0x{cmd:02X}"
+        ))),
+    }
+}
+
+/// CTAPHID 傳輸層，抽象化 hidapi 以便測試
+pub trait HidTransport {
+    fn write_report(&self, report: &[u8]) -> Result<(), FidoError>;
+    /// 讀取一個 HID 報告；逾時未收到資料時回傳 0
+    fn read_report(&self, buf: &mut [u8], timeout: Duration) -> Result<usize, FidoError>;
+}
+
+impl HidTransport for hidapi::HidDevice {
+    fn write_report(&self, report: &[u8]) -> Result<(), FidoError> {
+        // 第一個位元組為 report ID (0)
+        let mut buf = Vec::with_capacity(report.len() + 1);
+        buf.push(0x00);
+        buf.extend_from_slice(report);
+        self.write(&buf)
+            .map(|_| ())
+            .map_err(|e| FidoError::CommunicationError(format!("HID 寫入失敗: {e}")))
+    }
+
+    fn read_report(&self, buf: &mut [u8], timeout: Duration) -> Result<usize, FidoError> {
+        let millis = i32::try_from(timeout.as_millis()).unwrap_or(i32::MAX);
+        self.read_timeout(buf, millis)
+            .map_err(|e| FidoError::CommunicationError(format!("HID 讀取失敗: {e}")))
+    }
+}
+
+/// 讀取一個完整的 CTAPHID 回應訊息，回傳 (指令碼, 資料)
+/// 每個封包須在 `timeout` 內抵達，收到 KEEPALIVE 時重新計時並回呼 `on_keepalive`；
+/// 逾時回傳 `FidoError::Timeout`
+pub fn read_message<T: HidTransport + ?Sized>(
+    dev: &T,
+    cid: u32,
+    timeout: Duration,
+    on_keepalive: &mut dyn FnMut(KeepaliveStatus),
+) -> Result<(u8, Vec<u8>), FidoError> {
+    let cid_bytes = cid.to_be_bytes();
+    let mut deadline = Instant::now() + timeout;
+    let mut report = [0u8; HID_REPORT_SIZE];
+
+    let read_packet = |deadline: Instant, report: &mut [u8; HID_REPORT_SIZE]| loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(FidoError::Timeout);
+        }
+        let n = dev.read_report(report, remaining)?;
+        if n == 0 {
+            return Err(FidoError::Timeout);
+        }
+        // 忽略其他 channel 的封包
+        if report[..4] == cid_bytes {
+            return Ok(());
+        }
+    };
+
+    // 初始化封包（略過 KEEPALIVE）
+    let (cmd, total) = loop {
+        read_packet(deadline, &mut report)?;
+        let cmd = report[4] & 0x7F;
+        if report[4] & 0x80 == 0 {
+            return Err(FidoError::CommunicationError("CTAPHID 封包順序錯誤".to_string()));
+        }
+        if let Some(status) = parse_keepalive(&report) {
+            on_keepalive(status);
+            deadline = Instant::now() + timeout;
+            continue;
+        }
+        break (cmd, u16::from_be_bytes([report[5], report[6]]) as usize);
+    };
+
+    let mut payload = Vec::with_capacity(total);
+    payload.extend_from_slice(&report[7..7 + total.min(INIT_DATA_SIZE)]);
+
+    // 接續封包
+    let mut seq = 0u8;
+    while payload.len() < total {
+        read_packet(deadline, &mut report)?;
+        if report[4] != seq {
+            return Err(FidoError::CommunicationError("CTAPHID 封包順序錯誤".to_string()));
+        }
+        let take = (total - payload.len()).min(CONT_DATA_SIZE);
+        payload.extend_from_slice(&report[5..5 + take]);
+        seq = seq.wrapping_add(1);
+    }
+
+    Ok((cmd, payload))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::collections::VecDeque;
+
+    /// 依序回傳預錄封包的傳輸層；封包用盡後模擬裝置無回應
+    struct StubTransport {
+        packets: RefCell<VecDeque<[u8; HID_REPORT_SIZE]>>,
+    }
+
+    impl StubTransport {
+        fn new(packets: Vec<[u8; HID_REPORT_SIZE]>) -> Self {
+            Self {
+                packets: RefCell::new(packets.into()),
+            }
+        }
+    }
+
+    impl HidTransport for StubTransport {
+        fn write_report(&self, _report: &[u8]) -> Result<(), FidoError> {
+            Ok(())
+        }
+
+        fn read_report(&self, buf: &mut [u8], _timeout: Duration) -> Result<usize, FidoError> {
+            match self.packets.borrow_mut().pop_front() {
+                Some(packet) => {
+                    buf[..HID_REPORT_SIZE].copy_from_slice(&packet);
+                    Ok(HID_REPORT_SIZE)
+                }
+                None => Ok(0),
+            }
+        }
+    }
+
+    /// 模擬卡住的裝置：等到逾時才回傳 0
+    struct SlowTransport;
+
+    impl HidTransport for SlowTransport {
+        fn write_report(&self, _report: &[u8]) -> Result<(), FidoError> {
+            Ok(())
+        }
+
+        fn read_report(&self, _buf: &mut [u8], timeout: Duration) -> Result<usize, FidoError> {
+            std::thread::sleep(timeout);
+            Ok(0)
+        }
+    }
+
+    // === 回應讀取測試 ===
+
+    #[test]
+    fn test_read_message_reassembles_frames() {
+        let payload: Vec<u8> = (0..100u8).collect();
+        let packets = frame_message(7, CTAPHID_CBOR, &payload).unwrap();
+        let dev = StubTransport::new(packets);
+        let (cmd, data) = read_message(&dev, 7, Duration::from_secs(1), &mut |_| {}).unwrap();
+        assert_eq!(cmd, CTAPHID_CBOR);
+        assert_eq!(data, payload);
+    }
+
+    #[test]
+    fn test_read_message_skips_keepalive_and_other_channels() {
+        let mut packets = frame_message(7, CTAPHID_KEEPALIVE, &[0x02]).unwrap();
+        packets.extend(frame_message(9, CTAPHID_CBOR, &[0xEE]).unwrap());
+        packets.extend(frame_message(7, CTAPHID_CBOR, &[0x00]).unwrap());
+        let dev = StubTransport::new(packets);
+        let (cmd, data) = read_message(&dev, 7, Duration::from_secs(1), &mut |_| {}).unwrap();
+        assert_eq!(cmd, CTAPHID_CBOR);
+        assert_eq!(data, vec![0x00]);
+    }
+
+    #[test]
+    fn test_parse_init_response_checks_nonce() {
+        let nonce = [1, 2, 3, 4, 5, 6, 7, 8];
+        let data = [&nonce[..], &[0x00, 0x00, 0x00, 0x2A, 0x02, 0x05, 0x02, 0x00, 0x05]].concat();
+        assert_eq!(parse_init_response(&nonce, &data).unwrap(), 0x2A);
+        let mut other = data.clone();
+        other[0] = 0xFF;
+        assert!(parse_init_response(&nonce, &other).is_err());
+        assert!(parse_init_response(&nonce, &data[..11]).is_err());
+    }
+
+    #[test]
+    fn test_expect_response_maps_ctaphid_error() {
+        assert_eq!(expect_response(CTAPHID_CBOR, (CTAPHID_CBOR, vec![0x00])).unwrap(), vec![0x00]);
+        assert!(matches!(
+            expect_response(CTAPHID_CBOR, (CTAPHID_ERROR, vec![0x06])),
+            Err(FidoError::CommunicationError(msg)) if msg.contains("0x06")
+        ));
+        assert!(expect_response(CTAPHID_CBOR, (CTAPHID_INIT, vec![])).is_err());
+    }
+
+    #[test]
+    fn test_read_message_times_out_on_slow_transport() {
+        let result = read_message(&SlowTransport, 7, Duration::from_millis(20), &mut |_| {});
+        assert!(matches!(result, Err(FidoError::Timeout)));
+    }
+
+    #[test]
+    fn test_read_message_times_out_mid_message() {
+        // 只有初始化封包，接續封包遲遲未到
+        let payload = vec![0u8; 100];
+        let mut packets = frame_message(7, CTAPHID_CBOR, &payload).unwrap();
+        packets.truncate(1);
+        let dev = StubTransport::new(packets);
+        assert!(matches!(
+            read_message(&dev, 7, Duration::from_millis(20), &mut |_| {}),
+            Err(FidoError::Timeout)
+        ));
+    }
+
+    #[test]
+    fn test_read_message_rejects_out_of_order_sequence() {
+        let payload = vec![0u8; 200];
+        let mut packets = frame_message(7, CTAPHID_CBOR, &payload).unwrap();
+        packets.swap(1, 2);
+        let dev = StubTransport::new(packets);
+        assert!(matches!(
+            read_message(&dev, 7, Duration::from_secs(1), &mut |_| {}),
+            Err(FidoError::CommunicationError(_))
+        ));
+    }
+
+    #[test]
+    fn test_read_message_reports_keepalive_status() {
+        let mut packets = frame_message(7, CTAPHID_KEEPALIVE, &[0x01]).unwrap();
+        packets.extend(frame_message(7, CTAPHID_KEEPALIVE, &[0x02]).unwrap());
+        packets.extend(frame_message(7, CTAPHID_CBOR, &[0x00]).unwrap());
+        let dev = StubTransport::new(packets);
+        let mut seen = Vec::new();
+        read_message(&dev, 7, Duration::from_secs(1), &mut |s| seen.push(s)).unwrap();
+        assert_eq!(seen, vec![KeepaliveStatus::Processing, KeepaliveStatus::UpNeeded]);
+    }
+
+    // === KEEPALIVE / CANCEL 測試 ===
+
+    #[test]
+    fn test_parse_keepalive_status() {
+        let up_needed = frame_message(7, CTAPHID_KEEPALIVE, &[0x02]).unwrap();
+        assert_eq!(parse_keepalive(&up_needed[0]), Some(KeepaliveStatus::UpNeeded));
+        let unknown = frame_message(7, CTAPHID_KEEPALIVE, &[0x7F]).unwrap();
+        assert_eq!(parse_keepalive(&unknown[0]), Some(KeepaliveStatus::Unknown(0x7F)));
+    }
+
+    #[test]
+    fn test_parse_keepalive_rejects_other_frames() {
+        let cbor = frame_message(7, CTAPHID_CBOR, &[0x02]).unwrap();
+        assert_eq!(parse_keepalive(&cbor[0]), None);
+        let empty = frame_message(7, CTAPHID_KEEPALIVE, &[]).unwrap();
+        assert_eq!(parse_keepalive(&empty[0]), None);
+    }
+
+    #[test]
+    fn test_cancel_frame() {
+        let frame = cancel_frame(0x0102_0304);
+        assert_eq!(&frame[..7], &[0x01, 0x02, 0x03, 0x04, 0x91, 0x00, 0x00]);
+        assert!(frame[7..].iter().all(|&b| b == 0));
+        assert_eq!(frame, frame_message(0x0102_0304, CTAPHID_CANCEL, &[]).unwrap()[0]);
+    }
+
+    // === 封包切割測試 ===
+
+    #[test]
+    fn test_cbor_request_prefixes_command_byte() {
+        assert_eq!(cbor_request(0x41, &[0xA1, 0x01, 0x02]), vec![0x41, 0xA1, 0x01, 0x02]);
+        assert_eq!(cbor_request(0x04, &[]), vec![0x04]);
+    }
+
+    #[test]
+    fn test_frame_single_packet() {
+        let packets = frame_message(0x0102_0304, CTAPHID_CBOR, &[0x04]).unwrap();
+        assert_eq!(packets.len(), 1);
+        let p = &packets[0];
+        assert_eq!(&p[..4], &[0x01, 0x02, 0x03, 0x04]);
+        assert_eq!(p[4], 0x90);
+        assert_eq!(&p[5..7], &[0x00, 0x01]);
+        assert_eq!(p[7], 0x04);
+        assert!(p[8..].iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn test_frame_arbitrary_command_byte() {
+        // 任意（含廠商自訂）CTAP 指令碼皆原樣放在 payload 第一個位元組
+        for command_byte in [0x00, 0x0C, 0x41, 0xC0, 0xFF] {
+            let payload = cbor_request(command_byte, &[0xA0]);
+            let packets = frame_message(0xFFFF_FFFF, CTAPHID_CBOR, &payload).unwrap();
+            assert_eq!(packets[0][7], command_byte);
+            assert_eq!(packets[0][8], 0xA0);
+        }
+    }
+
+    #[test]
+    fn test_frame_continuation_packets() {
+        let payload: Vec<u8> = (0..200u16).map(|i| i as u8).collect();
+        let packets = frame_message(1, CTAPHID_CBOR, &payload).unwrap();
+        // 57 + 59 + 59 + 25
+        assert_eq!(packets.len(), 4);
+        assert_eq!(&packets[0][5..7], &[0x00, 200]);
+        assert_eq!(&packets[0][7..], &payload[..57]);
+        assert_eq!(packets[1][4], 0);
+        assert_eq!(&packets[1][5..], &payload[57..116]);
+        assert_eq!(packets[2][4], 1);
+        assert_eq!(packets[3][4], 2);
+        assert_eq!(&packets[3][5..30], &payload[175..]);
+        assert!(packets[3][30..].iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn test_frame_rejects_oversized_message() {
+        let payload = vec![0u8; MAX_MESSAGE_SIZE + 1];
+        assert!(matches!(
+            frame_message(1, CTAPHID_CBOR, &payload),
+            Err(FidoError::CommunicationError(_))
+        ));
+        assert_eq!(
+            frame_message(1, CTAPHID_CBOR, &vec![0u8; MAX_MESSAGE_SIZE]).unwrap().len(),
+            1 + MAX_CONT_PACKETS
+        );
+    }
+}
@@ -0,0 +1,232 @@
+use std::collections::BTreeMap;
+
+use serde_cbor::Value;
+use sha2::{Digest, Sha256};
+
+use crate::error::{CborError, FidoError};
+use crate::fido::pin_protocol::PinUvAuthToken;
+
+/// authenticatorLargeBlobs 指令碼
+pub const CTAP_LARGE_BLOBS: u8 = 0x0C;
+/// 完整性檢查值長度（SHA-256 前 16 位元組）
+pub const INTEGRITY_SIZE: usize = 16;
+/// 每次 get/set 的分段長度：預設 maxMsgSize (1024) 扣除 64 位元組的協定開銷
+pub const DEFAULT_FRAGMENT_SIZE: usize = 1024 - 64;
+
+/// 計算 largeBlob 陣列的完整性檢查值：LEFT(SHA-256(array), 16)
+fn integrity_check(array: &[u8]) -> [u8; INTEGRITY_SIZE] {
+    let digest = Sha256::digest(array);
+    let mut out = [0u8; INTEGRITY_SIZE];
+    out.copy_from_slice(&digest[..INTEGRITY_SIZE]);
+    out
+}
+
+/// 在序列化的 largeBlob 陣列後附加完整性檢查值
+pub fn append_integrity(array: &[u8]) -> Vec<u8> {
+    let mut blob = Vec::with_capacity(array.len() + INTEGRITY_SIZE);
+    blob.extend_from_slice(array);
+    blob.extend_from_slice(&integrity_check(array));
+    blob
+}
+
+/// 驗證完整性檢查值並回傳去除檢查值後的陣列
+pub fn verify_integrity(blob: &[u8]) -> Result<&[u8], FidoError> {
+    if blob.len() < 1 + INTEGRITY_SIZE {
+        return Err(FidoError::LargeBlobIntegrity);
+    }
+    let (array, check) = blob.split_at(blob.len() - INTEGRITY_SIZE);
+    if integrity_check(array) != check {
+        return Err(FidoError::LargeBlobIntegrity);
+    }
+    Ok(array)
+}
+
+fn encode_request(map: BTreeMap<Value, Value>) -> Result<Vec<u8>, CborError> {
+    let cbor = serde_cbor::to_vec(&Value::Map(map))
+        .map_err(|e| CborError::EncodingError(e.to_string()))?;
+    let mut buf = vec![CTAP_LARGE_BLOBS];
+    buf.extend_from_slice(&cbor);
+    Ok(buf)
+}
+
+/// 建立 get 請求：0x01=get (讀取長度), 0x03=offset
+pub fn get_request(offset: usize, length: usize) -> Result<Vec<u8>, CborError> {
+    let mut map = BTreeMap::new();
+    map.insert(Value::Integer(0x01), Value::Integer(length as i128));
+    map.insert(Value::Integer(0x03), Value::Integer(offset as i128));
+    encode_request(map)
+}
+
+/// set 分段的驗證訊息：32×0xFF || 0x0C 0x00 || uint32LE(offset) || SHA-256(分段資料)
+fn set_auth_message(offset: usize, fragment: &[u8]) -> Result<Vec<u8>, CborError> {
+    let offset = u32::try_from(offset)
+        .map_err(|_| CborError::EncodingError("largeBlob offset 超出範圍".to_string()))?;
+    let mut message = vec![0xFF; 32];
+    message.extend_from_slice(&[CTAP_LARGE_BLOBS, 0x00]);
+    message.extend_from_slice(&offset.to_le_bytes());
+    message.extend_from_slice(&Sha256::digest(fragment));
+    Ok(message)
+}
+
+/// 將含完整性檢查值的 blob 切割為多個 set 請求
+/// 0x02=set (分段資料), 0x03=offset；第一段另帶 0x04=length (總長度)；
+/// 有 token 時每段帶 0x05=pinUvAuthParam, 0x06=pinUvAuthProtocol
+pub fn set_requests(
+    blob: &[u8],
+    fragment_size: usize,
+    token: Option<&PinUvAuthToken>,
+) -> Result<Vec<Vec<u8>>, CborError> {
+    if fragment_size == 0 {
+        return Err(CborError::EncodingError("分段長度不可為 0".to_string()));
+    }
+    blob.chunks(fragment_size)
+        .enumerate()
+        .map(|(i, fragment)| {
+            let offset = i * fragment_size;
+            let mut map = BTreeMap::new();
+            map.insert(Value::Integer(0x02), Value::Bytes(fragment.to_vec()));
+            map.insert(Value::Integer(0x03), Value::Integer(offset as i128));
+            if offset == 0 {
+                map.insert(Value::Integer(0x04), Value::Integer(blob.len() as i128));
+            }
+            if let Some(token) = token {
+                let auth = token.authenticate(&set_auth_message(offset, fragment)?);
+                map.insert(Value::Integer(0x05), Value::Bytes(auth.param));
+                map.insert(Value::Integer(0x06), Value::Integer(auth.protocol.into()));
+            }
+            encode_request(map)
+        })
+        .collect()
+}
+
+/// 解析 get 回應（不含狀態碼），取出 0x01=config 分段資料
+pub fn parse_get_response(payload: &[u8]) -> Result<Vec<u8>, CborError> {
+    match serde_cbor::from_slice(payload).map_err(|e| CborError::DecodingError(e.to_string()))? {
+        Value::Map(map) => match map.get(&Value::Integer(0x01)) {
+            Some(Value::Bytes(fragment)) => Ok(fragment.clone()),
+            _ => Err(CborError::DecodingError("largeBlobs 回應缺少 config".to_string())),
+        },
+        _ => Err(CborError::DecodingError("largeBlobs 回應不是 CBOR map".to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode_request(req: &[u8]) -> BTreeMap<Value, Value> {
+        assert_eq!(req[0], CTAP_LARGE_BLOBS);
+        match serde_cbor::from_slice(&req[1..]).unwrap() {
+            Value::Map(map) => map,
+            other => panic!("expected map, got {other:?}"),
+        }
+    }
+
+    // === 完整性檢查測試 ===
+
+    #[test]
+    fn test_empty_array_integrity_matches_spec() {
+        // CTAP 2.1 規範的初始值: 0x80 || 76be8b528d0075f7aae98d6fa57a6d3c
+        let blob = append_integrity(&[0x80]);
+        assert_eq!(
+            blob,
+            vec![
+                0x80, 0x76, 0xBE, 0x8B, 0x52, 0x8D, 0x00, 0x75, 0xF7, 0xAA, 0xE9, 0x8D, 0x6F,
+                0xA5, 0x7A, 0x6D, 0x3C
+            ]
+        );
+    }
+
+    #[test]
+    fn test_verify_integrity_round_trip() {
+        let array = [0x81, 0x43, 0x01, 0x02, 0x03];
+        let blob = append_integrity(&array);
+        assert_eq!(verify_integrity(&blob).unwrap(), &array);
+    }
+
+    #[test]
+    fn test_verify_integrity_rejects_tampered_data() {
+        let mut blob = append_integrity(&[0x81, 0x01]);
+        blob[1] ^= 0xFF;
+        assert!(matches!(verify_integrity(&blob), Err(FidoError::LargeBlobIntegrity)));
+    }
+
+    #[test]
+    fn test_verify_integrity_rejects_short_blob() {
+        assert!(matches!(
+            verify_integrity(&[0u8; INTEGRITY_SIZE]),
+            Err(FidoError::LargeBlobIntegrity)
+        ));
+    }
+
+    // === 分段測試 ===
+
+    #[test]
+    fn test_get_request_encodes_offset_and_length() {
+        let map = decode_request(&get_request(960, DEFAULT_FRAGMENT_SIZE).unwrap());
+        assert_eq!(map[&Value::Integer(0x01)], Value::Integer(960));
+        assert_eq!(map[&Value::Integer(0x03)], Value::Integer(960));
+        assert_eq!(map.len(), 2);
+    }
+
+    #[test]
+    fn test_set_requests_chunk_offsets() {
+        let blob: Vec<u8> = (0..25u8).collect();
+        let requests = set_requests(&blob, 10, None).unwrap();
+        assert_eq!(requests.len(), 3);
+
+        let first = decode_request(&requests[0]);
+        assert_eq!(first[&Value::Integer(0x02)], Value::Bytes(blob[..10].to_vec()));
+        assert_eq!(first[&Value::Integer(0x03)], Value::Integer(0));
+        assert_eq!(first[&Value::Integer(0x04)], Value::Integer(25));
+
+        let second = decode_request(&requests[1]);
+        assert_eq!(second[&Value::Integer(0x03)], Value::Integer(10));
+        assert!(!second.contains_key(&Value::Integer(0x04)));
+
+        let last = decode_request(&requests[2]);
+        assert_eq!(last[&Value::Integer(0x02)], Value::Bytes(blob[20..].to_vec()));
+        assert_eq!(last[&Value::Integer(0x03)], Value::Integer(20));
+    }
+
+    #[test]
+    fn test_set_requests_single_fragment() {
+        let blob = append_integrity(&[0x80]);
+        let requests = set_requests(&blob, DEFAULT_FRAGMENT_SIZE, None).unwrap();
+        assert_eq!(requests.len(), 1);
+        let map = decode_request(&requests[0]);
+        assert_eq!(map[&Value::Integer(0x04)], Value::Integer(17));
+        assert!(!map.contains_key(&Value::Integer(0x05)));
+    }
+
+    #[test]
+    fn test_set_requests_authenticate_each_fragment() {
+        use crate::fido::pin_protocol::PinUvAuthProtocol;
+        use crate::secret::Zeroizing;
+
+        let token = PinUvAuthToken::new(PinUvAuthProtocol::Two, Zeroizing::new(vec![0x21; 32]));
+        let blob: Vec<u8> = (0..15u8).collect();
+        let requests = set_requests(&blob, 10, Some(&token)).unwrap();
+
+        let second = decode_request(&requests[1]);
+        let mut message = vec![0xFF; 32];
+        message.extend_from_slice(&[0x0C, 0x00, 10, 0, 0, 0]);
+        message.extend_from_slice(&Sha256::digest(&blob[10..]));
+        assert_eq!(second[&Value::Integer(0x05)], Value::Bytes(token.authenticate(&message).param));
+        assert_eq!(second[&Value::Integer(0x06)], Value::Integer(2));
+    }
+
+    #[test]
+    fn test_set_requests_rejects_zero_fragment() {
+        assert!(set_requests(&[0x80], 0, None).is_err());
+    }
+
+    #[test]
+    fn test_parse_get_response() {
+        let mut map = BTreeMap::new();
+        map.insert(Value::Integer(0x01), Value::Bytes(vec![0x80, 0x01]));
+        let payload = serde_cbor::to_vec(&Value::Map(map)).unwrap();
+        assert_eq!(parse_get_response(&payload).unwrap(), vec![0x80, 0x01]);
+        assert!(parse_get_response(&[0xA0]).is_err());
+    }
+}
@@ -1,11 +1,52 @@
+pub mod aaguid;
 pub mod cbor;
+pub mod ctaphid;
+pub mod large_blob;
+pub mod oath;
+pub mod pin_protocol;
 pub mod types;
 
+use std::ffi::CString;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::device_lock::{DeviceGuard, DeviceLocks};
+use crate::info_cache::InfoCache;
 use crate::error::FidoError;
+use crate::fido::ctaphid::HidTransport;
+use crate::fido::pin_protocol::{
+    random_bytes, EphemeralKey, PinUvAuthProtocol, PinUvAuthToken, SharedSecret,
+};
 use crate::fido::types::{
-    FidoCredential, FidoDeviceInfo, OathCredential, OathCredentialParams,
+    AssertionCheck, ClientPinSubCommand, CredsMetadata, FidoCredential, FidoDeviceInfo, KeepaliveStatus, KeyAgreementInfo, MinPinLengthInfo, OathCredential,
+    OathCredentialParams, OathSlotInfo, PinUvPermissions, ResetPreparation, SelfTestResult,
+    SetMinPinLengthParams, UvMethod,
 };
-use crate::types::LedConfig;
+use crate::types::{DeviceType, FirmwareStatus, LedConfig, PingResult};
+
+/// 預設的 CTAPHID 讀取逾時；涵蓋等待使用者觸碰的時間
+const DEFAULT_READ_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// 裝置資訊（GetInfo）快取的有效期限
+const DEVICE_INFO_TTL: Duration = Duration::from_secs(5);
+
+/// 重設確認碼的有效期限
+const RESET_TOKEN_TTL: Duration = Duration::from_secs(30);
+
+/// 測試註冊使用的 RP ID；憑證為非駐留，不會佔用裝置空間
+const SELF_TEST_RP_ID: &str = "self-test.picokeys.local";
+
+/// 裝置未回報 minPINLength 時的 CTAP 預設值
+const CTAP_DEFAULT_MIN_PIN_LENGTH: u8 = 4;
+
+/// authenticatorData 旗標：UV（已驗證使用者）
+const AUTH_DATA_FLAG_UV: u8 = 0x04;
+
+/// CTAP2_ERR_NO_CREDENTIALS：裝置沒有可列舉的憑證
+const CTAP2_ERR_NO_CREDENTIALS: u8 = 0x2E;
+
+/// KEEPALIVE 狀態回呼（由前端事件橋接）
+pub type KeepaliveHandler = Box<dyn Fn(KeepaliveStatus) + Send + Sync>;
 
 /// FIDO 模組 trait — 封裝所有 CTAP 2.1 協定操作
 pub trait FidoModule {
@@ -15,41 +56,259 @@ pub trait FidoModule {
     fn change_pin(&self, old_pin: &str, new_pin: &str) -> Result<(), FidoError>;
 
     // 憑證管理
+    fn get_creds_metadata(&self, pin: &str) -> Result<CredsMetadata, FidoError>;
     fn list_credentials(&self, pin: &str) -> Result<Vec<FidoCredential>, FidoError>;
     fn delete_credential(&self, pin: &str, credential_id: &[u8]) -> Result<(), FidoError>;
+    /// 列舉後逐一刪除所有可發現憑證，回傳刪除數量；中途失敗時以 `BulkDeleteAborted` 回報已刪除數量
+    fn delete_all_credentials(&self, pin: &str) -> Result<usize, FidoError>;
+    /// 以指定憑證執行一次 GetAssertion，並以列舉取得的公鑰驗證簽章
+    fn assert_credential(
+        &self,
+        rp_id: &str,
+        credential_id: &[u8],
+        pin: &str,
+    ) -> Result<AssertionCheck, FidoError>;
 
     // 裝置資訊
     fn get_info(&self) -> Result<FidoDeviceInfo, FidoError>;
+    fn firmware_status(&self) -> Result<FirmwareStatus, FidoError>;
+    /// 連線測試：只送出 GetInfo，不需要 PIN
+    fn ping(&self) -> Result<PingResult, FidoError>;
 
     // 認證器組態
-    fn set_min_pin_length(&self, pin: &str, length: u8) -> Result<(), FidoError>;
+    fn set_min_pin_length(&self, pin: &str, params: &SetMinPinLengthParams) -> Result<(), FidoError>;
+    /// 重新讀取 GetInfo，回傳目前的 PIN 最小長度與是否強制變更 PIN
+    fn get_min_pin_length(&self) -> Result<MinPinLengthInfo, FidoError>;
     fn toggle_enterprise_attestation(&self, pin: &str, enable: bool) -> Result<(), FidoError>;
+    fn toggle_always_uv(&self, pin: &str, enable: bool) -> Result<(), FidoError>;
+
+    // 診斷
+    fn get_key_agreement(&self, protocol: u8) -> Result<KeyAgreementInfo, FidoError>;
+    fn self_test_registration(&self) -> Result<SelfTestResult, FidoError>;
 
     // OATH
     fn list_oath_credentials(&self) -> Result<Vec<OathCredential>, FidoError>;
     fn add_oath_credential(&self, credential: &OathCredentialParams) -> Result<(), FidoError>;
     fn calculate_oath(&self, credential_id: &str) -> Result<String, FidoError>;
     fn delete_oath_credential(&self, credential_id: &str) -> Result<(), FidoError>;
+    fn oath_slot_info(&self) -> Result<OathSlotInfo, FidoError>;
 
     // 備份與重設
     fn get_backup_words(&self, pin: &str) -> Result<Vec<String>, FidoError>;
     fn restore_from_words(&self, pin: &str, words: &[String]) -> Result<(), FidoError>;
     fn reset_device(&self) -> Result<(), FidoError>;
+    /// 重設第一階段：發出一次性確認碼，提供 PIN 時一併回報將遺失的憑證數
+    fn reset_prepare(&self, pin: Option<&str>) -> Result<ResetPreparation, FidoError>;
+    /// 重設第二階段：確認碼有效才送出 authenticatorReset
+    fn reset_confirm(&self, token: &str) -> Result<(), FidoError>;
 
     // LED 設定
     fn set_led_config(&self, config: &LedConfig) -> Result<(), FidoError>;
+
+    // largeBlobs
+    fn read_large_blob(&self) -> Result<Vec<u8>, FidoError>;
+    fn write_large_blob(&self, pin: &str, data: &[u8]) -> Result<(), FidoError>;
+
+    // 進階：原始 CTAP 指令
+    fn transmit_cbor(&self, command_byte: u8, cbor_params: &[u8]) -> Result<Vec<u8>, FidoError>;
 }
 
 /// FidoModule 的實作，透過 USB HID 與 Pico-FIDO 裝置通訊
 pub struct FidoModuleImpl {
     device_path: std::sync::Mutex<String>,
+    advanced_mode: std::sync::Mutex<bool>,
+    /// 取得 pinUvAuthToken 時偏好的驗證方式
+    uv_method: std::sync::Mutex<UvMethod>,
+    read_timeout: std::sync::Mutex<Duration>,
+    /// 等待回應中的 CTAPHID channel，供 CANCEL 使用
+    pending_cid: std::sync::Mutex<Option<u32>>,
+    keepalive_handler: std::sync::Mutex<Option<KeepaliveHandler>>,
+    /// 尚未確認的重設確認碼與發出時間
+    pending_reset: std::sync::Mutex<Option<(String, Instant)>>,
+    /// 序列化同一裝置上的 CTAPHID 交換，避免並行請求的封包交錯；`for_device` 建立的模組共用同一份
+    device_locks: Arc<DeviceLocks>,
+    device_info_cache: InfoCache<FidoDeviceInfo>,
+    /// OATH SELECT 使用的 applet AID（Nitrokey 或自訂韌體可能不同）
+    oath_aid: std::sync::Mutex<Vec<u8>>,
 }
 
 impl FidoModuleImpl {
     pub fn new(device_path: String) -> Self {
         Self {
             device_path: std::sync::Mutex::new(device_path),
+            advanced_mode: std::sync::Mutex::new(false),
+            uv_method: std::sync::Mutex::new(UvMethod::default()),
+            read_timeout: std::sync::Mutex::new(DEFAULT_READ_TIMEOUT),
+            pending_cid: std::sync::Mutex::new(None),
+            keepalive_handler: std::sync::Mutex::new(None),
+            pending_reset: std::sync::Mutex::new(None),
+            device_locks: Arc::new(DeviceLocks::new()),
+            device_info_cache: InfoCache::new(DEVICE_INFO_TTL),
+            oath_aid: std::sync::Mutex::new(oath::YKOATH_AID.to_vec()),
+        }
+    }
+
+    /// 以相同設定與裝置鎖建立指向 `path` 的模組，查詢其他裝置時不變更目前使用的裝置
+    pub fn for_device(&self, path: &str) -> Self {
+        let module = Self::new(path.to_string());
+        module.set_advanced_mode(self.advanced_mode());
+        module.set_uv_method(self.uv_method());
+        module.set_read_timeout(self.read_timeout());
+        // AID 已於設定時驗證過
+        let _ = module.set_oath_aid(self.oath_aid());
+        Self { device_locks: Arc::clone(&self.device_locks), ..module }
+    }
+
+    /// 設定 OATH SELECT 使用的 applet AID，長度需為 5-16 位元組
+    pub fn set_oath_aid(&self, aid: Vec<u8>) -> Result<(), FidoError> {
+        crate::util::validate_aid(&aid).map_err(FidoError::InvalidAid)?;
+        if let Ok(mut a) = self.oath_aid.lock() {
+            *a = aid;
+        }
+        Ok(())
+    }
+
+    /// 取得目前的 OATH applet AID
+    pub fn oath_aid(&self) -> Vec<u8> {
+        self.oath_aid
+            .lock()
+            .map(|a| a.clone())
+            .unwrap_or_else(|_| oath::YKOATH_AID.to_vec())
+    }
+
+    /// 以目前設定的 AID 建立 OATH SELECT APDU
+    pub fn oath_select_command(&self) -> Vec<u8> {
+        oath::select_command(&self.oath_aid())
+    }
+
+    /// 設定 KEEPALIVE 狀態回呼
+    pub fn set_keepalive_handler(&self, handler: KeepaliveHandler) {
+        if let Ok(mut h) = self.keepalive_handler.lock() {
+            *h = Some(handler);
+        }
+    }
+
+    /// 讀取指定 channel 的 CTAPHID 回應，期間轉發 KEEPALIVE 狀態並允許 `cancel` 中止
+    pub fn read_response<T: HidTransport + ?Sized>(
+        &self,
+        dev: &T,
+        cid: u32,
+    ) -> Result<(u8, Vec<u8>), FidoError> {
+        if let Ok(mut pending) = self.pending_cid.lock() {
+            *pending = Some(cid);
+        }
+        let result = crate::fido::ctaphid::read_message(dev, cid, self.read_timeout(), &mut |status| {
+            if let Ok(Some(handler)) = self.keepalive_handler.lock().as_deref() {
+                handler(status);
+            }
+        });
+        if let Ok(mut pending) = self.pending_cid.lock() {
+            *pending = None;
+        }
+        result
+    }
+
+    /// 送出一個 CTAPHID 請求並讀取回應，同一裝置一次只允許一個交換。
+    /// 等待使用者觸碰期間仍持有鎖；`cancel` 不需取得鎖，可中止等待並釋放
+    pub fn transact<T: HidTransport + ?Sized>(
+        &self,
+        dev: &T,
+        cid: u32,
+        cmd: u8,
+        payload: &[u8],
+    ) -> Result<(u8, Vec<u8>), FidoError> {
+        let guard = self.device_locks.acquire(&self.get_device_path());
+        self.transact_held(&guard, dev, cid, cmd, payload)
+    }
+
+    /// 在呼叫端已持有的裝置鎖下送出請求並讀取回應；鎖不可重入，巢狀交換須沿用同一個 guard
+    fn transact_held<T: HidTransport + ?Sized>(
+        &self,
+        _guard: &DeviceGuard,
+        dev: &T,
+        cid: u32,
+        cmd: u8,
+        payload: &[u8],
+    ) -> Result<(u8, Vec<u8>), FidoError> {
+        for packet in &crate::fido::ctaphid::frame_message(cid, cmd, payload)? {
+            dev.write_report(packet)?;
         }
+        self.read_response(dev, cid)
+    }
+
+    /// 對等待中的請求送出 CTAPHID_CANCEL；沒有等待中的請求時不做任何事
+    pub fn cancel(&self) -> Result<(), FidoError> {
+        let cid = match self.pending_cid.lock().ok().and_then(|pending| *pending) {
+            Some(cid) => cid,
+            None => return Ok(()),
+        };
+        let path = CString::new(self.get_device_path())
+            .map_err(|_| FidoError::CommunicationError("裝置路徑無效".to_string()))?;
+        let api = hidapi::HidApi::new()
+            .map_err(|e| FidoError::CommunicationError(format!("HID API 初始化失敗: {e}")))?;
+        let device = api
+            .open_path(&path)
+            .map_err(|e| FidoError::CommunicationError(format!("無法開啟裝置: {e}")))?;
+        device.write_report(&crate::fido::ctaphid::cancel_frame(cid))
+    }
+
+    /// 設定 CTAPHID 回應的讀取逾時（收到 KEEPALIVE 時重新計時）
+    pub fn set_read_timeout(&self, timeout: Duration) {
+        if let Ok(mut t) = self.read_timeout.lock() {
+            *t = timeout;
+        }
+    }
+
+    /// 取得目前的 CTAPHID 讀取逾時
+    pub fn read_timeout(&self) -> Duration {
+        self.read_timeout.lock().map(|t| *t).unwrap_or(DEFAULT_READ_TIMEOUT)
+    }
+
+    /// 啟用或停用進階模式（允許原始 CTAP 指令）
+    pub fn set_advanced_mode(&self, enabled: bool) {
+        if let Ok(mut m) = self.advanced_mode.lock() {
+            *m = enabled;
+        }
+    }
+
+    /// 是否已啟用進階模式
+    pub fn advanced_mode(&self) -> bool {
+        self.advanced_mode.lock().map(|m| *m).unwrap_or(false)
+    }
+
+    /// 設定取得 pinUvAuthToken 時偏好的驗證方式
+    pub fn set_uv_method(&self, method: UvMethod) {
+        if let Ok(mut m) = self.uv_method.lock() {
+            *m = method;
+        }
+    }
+
+    /// 目前偏好的驗證方式
+    pub fn uv_method(&self) -> UvMethod {
+        self.uv_method.lock().map(|m| *m).unwrap_or_default()
+    }
+
+    /// 依裝置 options 選擇取得 token 的 ClientPin 子指令：偏好 UV 且裝置支援內建驗證時使用 UV，
+    /// 否則支援 CTAP 2.1 時以 PIN 取得含 permissions 的 token，舊裝置使用 getPinToken
+    pub fn token_subcommand(info: &FidoDeviceInfo, preference: UvMethod) -> ClientPinSubCommand {
+        if preference == UvMethod::Uv && info.supports_builtin_uv() {
+            ClientPinSubCommand::GetPinUvAuthTokenUsingUvWithPermissions
+        } else if info.supports_pin_uv_auth_token() {
+            ClientPinSubCommand::GetPinUvAuthTokenUsingPinWithPermissions
+        } else {
+            ClientPinSubCommand::GetPinToken
+        }
+    }
+
+    /// 取得 GetInfo 結果；快取有效時不與裝置通訊，`refresh` 為 true 時強制重新讀取
+    pub fn cached_info(&self, refresh: bool) -> Result<FidoDeviceInfo, FidoError> {
+        self.device_info_cache
+            .get_or_fetch(&self.get_device_path(), refresh, || self.get_info())
+    }
+
+    /// 清除裝置資訊快取（裝置列表變更時呼叫）
+    pub fn invalidate_info_cache(&self) {
+        self.device_info_cache.invalidate_all();
     }
 
     /// 設定目前使用的裝置路徑
@@ -57,6 +316,10 @@ impl FidoModuleImpl {
         if let Ok(mut p) = self.device_path.lock() {
             *p = path.to_string();
         }
+        // 確認碼只對發出時的裝置有效
+        if let Ok(mut pending) = self.pending_reset.lock() {
+            *pending = None;
+        }
     }
 
     /// 取得目前裝置路徑
@@ -65,27 +328,353 @@ impl FidoModuleImpl {
     }
 
     /// 驗證 PIN 長度是否符合 CTAP 2.1 規範（4-63 位元組）
-    fn validate_pin(pin: &str) -> Result<(), FidoError> {
-        let len = pin.as_bytes().len();
-        if len < 4 || len > 63 {
+    pub fn validate_pin(pin: &str) -> Result<(), FidoError> {
+        if !(4..=63).contains(&pin.len()) {
             return Err(FidoError::PinLengthInvalid);
         }
         Ok(())
     }
 
-    /// 傳送 CTAP 指令至裝置並讀取回應
-    fn send_ctap_command(&self, _data: &[u8]) -> Result<Vec<u8>, FidoError> {
+    /// 裝置設定 forcePINChange 時，除變更 PIN 外的 PIN 操作都會被拒絕
+    fn check_forced_pin_change(info: &FidoDeviceInfo) -> Result<(), FidoError> {
+        if info.force_pin_change {
+            return Err(FidoError::PinChangeRequired);
+        }
+        Ok(())
+    }
+
+    /// 在需要 PIN 的操作前確認沒有待完成的強制變更；
+    /// 無法取得 GetInfo 時交由後續指令回報錯誤
+    fn ensure_pin_change_not_required(&self) -> Result<(), FidoError> {
+        match self.cached_info(false) {
+            Ok(info) => Self::check_forced_pin_change(&info),
+            Err(_) => Ok(()),
+        }
+    }
+
+    /// 以 enumerateRPsBegin/Next 取得所有 RP，再逐一以 enumerateCredentialsBegin/Next 列舉憑證；
+    /// `send` 傳送指令並回傳狀態碼之後的資料。RP 須先全部讀完，Begin 會重設裝置的列舉狀態
+    fn enumerate_credentials(
+        token: &PinUvAuthToken,
+        mut send: impl FnMut(&[u8]) -> Result<Vec<u8>, FidoError>,
+    ) -> Result<Vec<FidoCredential>, FidoError> {
+        use crate::fido::cbor::{
+            decode_enumerated_credential, decode_enumerated_rp, decode_total_credentials,
+            encode_enumerate_credentials_begin, encode_enumerate_next, encode_enumerate_rps_begin,
+        };
+        use crate::fido::types::CredMgmtSubCommand;
+        let cbor_err = |e: crate::error::CborError| FidoError::CborError(e.to_string());
+
+        let first = match send(&encode_enumerate_rps_begin(token).map_err(cbor_err)?) {
+            Err(FidoError::CtapError(CTAP2_ERR_NO_CREDENTIALS)) => return Ok(Vec::new()),
+            payload => decode_enumerated_rp(&payload?).map_err(cbor_err)?,
+        };
+        let total_rps = first.total_rps.unwrap_or(1);
+        let mut rps = vec![first];
+        for _ in 1..total_rps {
+            let request = encode_enumerate_next(&CredMgmtSubCommand::EnumerateRPsNext);
+            rps.push(decode_enumerated_rp(&send(&request.map_err(cbor_err)?)?).map_err(cbor_err)?);
+        }
+
+        let mut credentials = Vec::new();
+        for rp in rps {
+            let request = encode_enumerate_credentials_begin(&rp.rp_id_hash, token);
+            let payload = match send(&request.map_err(cbor_err)?) {
+                Err(FidoError::CtapError(CTAP2_ERR_NO_CREDENTIALS)) => continue,
+                payload => payload?,
+            };
+            let total = decode_total_credentials(&payload).map_err(cbor_err)?.unwrap_or(1);
+            let decode = |payload: &[u8]| {
+                decode_enumerated_credential(payload, &rp.rp_id, rp.rp_name.clone())
+                    .map_err(cbor_err)
+            };
+            credentials.push(decode(&payload)?);
+            for _ in 1..total {
+                let request = encode_enumerate_next(&CredMgmtSubCommand::EnumerateCredentialsNext);
+                credentials.push(decode(&send(&request.map_err(cbor_err)?)?)?);
+            }
+        }
+        Ok(credentials)
+    }
+
+    /// 依序刪除列舉出的憑證；任一刪除失敗即停止，避免在 PIN token 失效後繼續嘗試
+    fn run_delete_all<L, D>(list: L, mut delete: D) -> Result<usize, FidoError>
+    where
+        L: FnOnce() -> Result<Vec<FidoCredential>, FidoError>,
+        D: FnMut(&[u8]) -> Result<(), FidoError>,
+    {
+        let credentials = list()?;
+        let total = credentials.len();
+        for (deleted, credential) in credentials.iter().enumerate() {
+            if let Err(error) = delete(&credential.credential_id) {
+                return Err(FidoError::BulkDeleteAborted { deleted, total, error: Box::new(error) });
+            }
+        }
+        Ok(total)
+    }
+
+    /// 以具 cm 權限的 token 傳送 credentialManagement deleteCredential；
+    /// 呼叫端需先驗證 PIN 與裝置支援
+    fn send_delete_credential(
+        &self,
+        credential_id: &[u8],
+        token: &PinUvAuthToken,
+    ) -> Result<(), FidoError> {
+        if credential_id.is_empty() {
+            return Err(FidoError::CommunicationError(
+                "憑證 ID 不可為空".to_string(),
+            ));
+        }
+
+        let encoded = crate::fido::cbor::encode_delete_credential(credential_id, token)
+            .map_err(|e| FidoError::CborError(e.to_string()))?;
+        self.send_ctap_checked(&encoded)?;
+        Ok(())
+    }
+
+    /// 以 `get_info` 的 GetInfo 結果建立連線測試結果
+    fn ping_with(get_info: impl FnOnce() -> Result<FidoDeviceInfo, FidoError>) -> PingResult {
+        PingResult::from_outcome(get_info().map(|info| {
+            (info.firmware_version != "unknown").then_some(info.firmware_version)
+        }))
+    }
+
+    /// 產生重設確認碼；僅用於防止誤觸，不作為安全憑證
+    fn new_reset_token() -> String {
+        use std::hash::{BuildHasher, Hasher};
+
+        let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or_default();
+        hasher.write_u128(nanos);
+        format!("{:016x}", hasher.finish())
+    }
+
+    /// 產生隨機 challenge
+    fn random_challenge() -> Result<[u8; 32], FidoError> {
+        random_bytes()
+    }
+
+    /// assertion 簽章涵蓋 authenticatorData || clientDataHash
+    fn assertion_signature_valid(
+        public_key: &[u8], assertion: &crate::fido::cbor::AssertionResponse, client_data_hash: &[u8; 32],
+    ) -> bool {
+        use p256::ecdsa::signature::Verifier;
+        use p256::ecdsa::{Signature, VerifyingKey};
+
+        let (Ok(key), Ok(signature)) = (
+            VerifyingKey::from_sec1_bytes(public_key),
+            Signature::from_der(&assertion.signature),
+        ) else {
+            return false;
+        };
+        let signed = [assertion.auth_data.as_slice(), client_data_hash].concat();
+        key.verify(&signed, &signature).is_ok()
+    }
+
+    /// 檢查確認碼是否與待確認的重設相符且未過期
+    fn check_reset_token(
+        pending: Option<(String, Instant)>, token: &str, now: Instant,
+    ) -> Result<(), FidoError> {
+        match pending {
+            Some((expected, _)) if expected != token => Err(FidoError::ResetTokenInvalid),
+            Some((_, issued)) if now.saturating_duration_since(issued) > RESET_TOKEN_TTL => {
+                Err(FidoError::ResetTokenExpired)
+            }
+            Some(_) => Ok(()),
+            None => Err(FidoError::ResetTokenInvalid),
+        }
+    }
+
+    /// 取得認證器的 keyAgreement，並以新的暫時金鑰協商共享密鑰
+    fn establish_shared_secret(
+        &self,
+        protocol: PinUvAuthProtocol,
+    ) -> Result<(EphemeralKey, SharedSecret), FidoError> {
+        let key_agreement = self.get_key_agreement(protocol.version())?;
+        let coordinate = |hex: &str| {
+            crate::util::hex_decode(hex).map_err(|e| FidoError::CborError(e.to_string()))
+        };
+        let (x, y) = (coordinate(&key_agreement.x)?, coordinate(&key_agreement.y)?);
+        let peer = [&[0x04][..], &x, &y].concat();
+        let platform_key = EphemeralKey::generate()?;
+        let shared = platform_key.agree(protocol, &peer)?;
+        Ok((platform_key, shared))
+    }
+
+    /// 取得 pinUvAuthToken：協商共享密鑰、依 `token_subcommand` 送出請求並解密回傳的 token。
+    /// 以 UV 取得時不使用 PIN；CTAP 2.1 的子指令綁定 `permissions`（與選用的 `rp_id`），
+    /// CTAP 2.0 裝置使用未綁定權限的 getPinToken
+    fn acquire_pin_uv_auth_token(
+        &self,
+        pin: &str,
+        permissions: PinUvPermissions,
+        rp_id: Option<&str>,
+    ) -> Result<PinUvAuthToken, FidoError> {
+        use crate::fido::cbor::{
+            decode_pin_token, encode_get_pin_token, encode_get_token_using_pin,
+            encode_get_token_using_uv, platform_key_agreement,
+        };
+        use crate::fido::pin_protocol::pin_hash;
+        let cbor_err = |e: crate::error::CborError| FidoError::CborError(e.to_string());
+
+        let info = self.cached_info(false)?;
+        let protocol = PinUvAuthProtocol::preferred(&info.pin_uv_auth_protocols);
+        let (platform_key, shared) = self.establish_shared_secret(protocol)?;
+        let (x, y) = platform_key.coordinates();
+        let key_agreement = platform_key_agreement(x, y);
+        let version = protocol.version();
+
+        let encoded = match Self::token_subcommand(&info, self.uv_method()) {
+            ClientPinSubCommand::GetPinUvAuthTokenUsingUvWithPermissions => {
+                encode_get_token_using_uv(version, key_agreement, permissions, rp_id)
+            }
+            ClientPinSubCommand::GetPinUvAuthTokenUsingPinWithPermissions => {
+                let pin_hash_enc = shared.encrypt(&pin_hash(pin))?;
+                let pin_hash_enc = pin_hash_enc.as_slice();
+                encode_get_token_using_pin(version, key_agreement, pin_hash_enc, permissions, rp_id)
+            }
+            _ => {
+                let pin_hash_enc = shared.encrypt(&pin_hash(pin))?;
+                encode_get_pin_token(version, key_agreement, &pin_hash_enc)
+            }
+        }
+        .map_err(cbor_err)?;
+        let payload = self.send_ctap_checked(&encoded)?;
+        let encrypted = decode_pin_token(&payload).map_err(cbor_err)?;
+        Ok(PinUvAuthToken::new(protocol, shared.decrypt(&encrypted)?))
+    }
+
+    /// 確認認證器支援憑證管理，避免送出裝置不認得的指令
+    fn require_cred_mgmt(&self) -> Result<(), FidoError> {
+        if self.get_info()?.supports_cred_mgmt() {
+            Ok(())
+        } else {
+            Err(FidoError::NotSupported)
+        }
+    }
+
+    /// 傳送 CTAP 指令並檢查狀態碼，回傳狀態碼之後的 CBOR 資料
+    fn send_ctap_checked(&self, data: &[u8]) -> Result<Vec<u8>, FidoError> {
+        // 只記錄指令碼與長度，參數可能含 pinUvAuthParam
+        log::debug!(
+            "CTAP > cmd=0x{:02X} ({} bytes)",
+            data.first().copied().unwrap_or(0),
+            data.len()
+        );
+        let response_bytes = self.send_ctap_command(data).inspect_err(|e| {
+            log::warn!("CTAP exchange failed: {e}");
+        })?;
+        log::debug!(
+            "CTAP < status=0x{:02X} ({} bytes)",
+            response_bytes.first().copied().unwrap_or(0xFF),
+            response_bytes.len()
+        );
+        match response_bytes.split_first() {
+            Some((0x00, payload)) => Ok(payload.to_vec()),
+            Some((&code, _)) => Err(crate::fido::cbor::ctap_error_to_fido_error(code)),
+            None => Err(FidoError::CommunicationError(
+                "非預期的回應格式".to_string(),
+            )),
+        }
+    }
+
+    /// 確認認證器支援 largeBlobs
+    fn require_large_blobs(&self) -> Result<(), FidoError> {
+        if self.get_info()?.supports_large_blobs() {
+            Ok(())
+        } else {
+            Err(FidoError::NotSupported)
+        }
+    }
+
+    /// 傳送 CTAP 指令至裝置並讀取回應（含狀態碼）
+    fn send_ctap_command(&self, data: &[u8]) -> Result<Vec<u8>, FidoError> {
+        self.ctap_exchange(&self.open_device()?, data)
+    }
+
+    /// 選擇 OATH applet 後傳送 YKOATH 指令，回傳去除狀態碼的回應資料
+    fn send_oath_command(&self, apdu: &[u8]) -> Result<Vec<u8>, FidoError> {
+        self.oath_exchange(&self.open_device()?, apdu)
+    }
+
+    /// 開啟目前選擇的 HID 裝置
+    fn open_device(&self) -> Result<hidapi::HidDevice, FidoError> {
         let device_path = self.get_device_path();
         if device_path.is_empty() {
             return Err(FidoError::CommunicationError(
                 "尚未選擇裝置。請先從左側選擇一個 Pico-FIDO 裝置。".to_string(),
             ));
         }
-        // HID CTAP 通訊需要完整的 CTAPHID 協議實作
-        // 目前回傳裝置路徑以供除錯
-        Err(FidoError::CommunicationError(format!(
-            "CTAP HID 通訊尚未實作（裝置: {device_path}）"
-        )))
+        let path = CString::new(device_path)
+            .map_err(|_| FidoError::CommunicationError("裝置路徑無效".to_string()))?;
+        let api = hidapi::HidApi::new()
+            .map_err(|e| FidoError::CommunicationError(format!("HID API 初始化失敗: {e}")))?;
+        api.open_path(&path)
+            .map_err(|e| FidoError::CommunicationError(format!("無法開啟裝置: {e}")))
+    }
+
+    /// 以 CTAPHID_INIT 配置 channel 後執行 `exchange`；
+    /// 期間持有裝置鎖，避免其他請求搶先使用剛配置的 channel
+    fn with_channel<T: HidTransport + ?Sized, R>(
+        &self,
+        dev: &T,
+        exchange: impl FnOnce(&DeviceGuard, u32) -> Result<R, FidoError>,
+    ) -> Result<R, FidoError> {
+        use crate::fido::ctaphid::{self, BROADCAST_CID, CTAPHID_INIT};
+
+        let nonce = random_bytes::<8>()?;
+        let guard = self.device_locks.acquire(&self.get_device_path());
+        let init = self.transact_held(&guard, dev, BROADCAST_CID, CTAPHID_INIT, &nonce)?;
+        let init = ctaphid::expect_response(CTAPHID_INIT, init)?;
+        exchange(&guard, ctaphid::parse_init_response(&nonce, &init)?)
+    }
+
+    /// 以 CTAPHID_INIT 配置 channel 後送出 CTAPHID_CBOR 請求
+    pub fn ctap_exchange<T: HidTransport + ?Sized>(
+        &self,
+        dev: &T,
+        data: &[u8],
+    ) -> Result<Vec<u8>, FidoError> {
+        use crate::fido::ctaphid::{self, CTAPHID_CBOR};
+
+        self.with_channel(dev, |guard, cid| {
+            let response = self.transact_held(guard, dev, cid, CTAPHID_CBOR, data)?;
+            ctaphid::expect_response(CTAPHID_CBOR, response)
+        })
+    }
+
+    /// 在同一 channel 上以 CTAPHID_MSG 送出 OATH SELECT（使用設定的 AID）與 YKOATH 指令；
+    /// 狀態碼為 61xx 時以 SEND REMAINING 取得其餘資料
+    pub fn oath_exchange<T: HidTransport + ?Sized>(
+        &self,
+        dev: &T,
+        apdu: &[u8],
+    ) -> Result<Vec<u8>, FidoError> {
+        use crate::fido::ctaphid::{self, CTAPHID_MSG};
+
+        self.with_channel(dev, |guard, cid| {
+            let msg = |apdu: &[u8]| {
+                let response = self.transact_held(guard, dev, cid, CTAPHID_MSG, apdu)?;
+                ctaphid::expect_response(CTAPHID_MSG, response)
+            };
+            let (_, sw) = oath::split_status(&msg(&self.oath_select_command())?)?;
+            oath::check_status(sw)?;
+
+            let mut data = Vec::new();
+            let mut response = msg(apdu)?;
+            loop {
+                let (chunk, sw) = oath::split_status(&response)?;
+                data.extend_from_slice(chunk);
+                if sw >> 8 != 0x61 {
+                    oath::check_status(sw)?;
+                    return Ok(data);
+                }
+                response = msg(&oath::send_remaining_command())?;
+            }
+        })
     }
 }
 
@@ -124,97 +713,90 @@ impl FidoModule for FidoModuleImpl {
     fn set_pin(&self, new_pin: &str) -> Result<(), FidoError> {
         Self::validate_pin(new_pin)?;
 
-        use crate::fido::cbor::{CborCodec, CborCodecImpl};
-        use crate::fido::types::{ClientPinSubCommand, CtapCommand};
-
-        let codec = CborCodecImpl::new();
-        let cmd = CtapCommand::ClientPin(ClientPinSubCommand::SetPin);
-        let encoded = codec
-            .encode_ctap_command(&cmd)
-            .map_err(|e| FidoError::CborError(e.to_string()))?;
-
-        let response_bytes = self.send_ctap_command(&encoded)?;
-
-        let response = codec
-            .decode_ctap_response(&response_bytes)
-            .map_err(|e| FidoError::CborError(e.to_string()))?;
-
-        match response {
-            crate::fido::types::CtapResponse::Success => Ok(()),
-            crate::fido::types::CtapResponse::Error(code) => {
-                Err(crate::fido::cbor::ctap_error_to_fido_error(code))
-            }
-            _ => Err(FidoError::CommunicationError(
-                "非預期的回應格式".to_string(),
-            )),
-        }
+        use crate::fido::cbor::{encode_set_pin, platform_key_agreement};
+        use crate::fido::pin_protocol::padded_pin;
+        let cbor_err = |e: crate::error::CborError| FidoError::CborError(e.to_string());
+
+        let info = self.cached_info(false)?;
+        let protocol = PinUvAuthProtocol::preferred(&info.pin_uv_auth_protocols);
+        let (platform_key, shared) = self.establish_shared_secret(protocol)?;
+        let (x, y) = platform_key.coordinates();
+        let new_pin_enc = shared.encrypt(&padded_pin(new_pin))?;
+        let pin_uv_auth_param = shared.authenticate(&new_pin_enc);
+        let encoded = encode_set_pin(
+            protocol.version(),
+            platform_key_agreement(x, y),
+            &new_pin_enc,
+            &pin_uv_auth_param,
+        )
+        .map_err(cbor_err)?;
+        self.send_ctap_checked(&encoded)?;
+        // 設定後 clientPin 選項改變，需重新讀取 GetInfo
+        self.invalidate_info_cache();
+        Ok(())
     }
 
-    fn change_pin(&self, _old_pin: &str, new_pin: &str) -> Result<(), FidoError> {
+    fn change_pin(&self, old_pin: &str, new_pin: &str) -> Result<(), FidoError> {
+        Self::validate_pin(old_pin)?;
         Self::validate_pin(new_pin)?;
 
-        // NOTE: _old_pin will be used for CTAP ClientPin ChangePin protocol
-        // (HMAC-SHA-256 with shared secret) once real HID communication is wired up.
-
-        use crate::fido::cbor::{CborCodec, CborCodecImpl};
-        use crate::fido::types::{ClientPinSubCommand, CtapCommand};
-
-        let codec = CborCodecImpl::new();
-        let cmd = CtapCommand::ClientPin(ClientPinSubCommand::ChangePin);
-        let encoded = codec
-            .encode_ctap_command(&cmd)
-            .map_err(|e| FidoError::CborError(e.to_string()))?;
-
-        let response_bytes = self.send_ctap_command(&encoded)?;
-
-        let response = codec
-            .decode_ctap_response(&response_bytes)
-            .map_err(|e| FidoError::CborError(e.to_string()))?;
-
-        match response {
-            crate::fido::types::CtapResponse::Success => Ok(()),
-            crate::fido::types::CtapResponse::Error(code) => {
-                Err(crate::fido::cbor::ctap_error_to_fido_error(code))
-            }
-            _ => Err(FidoError::CommunicationError(
-                "非預期的回應格式".to_string(),
-            )),
-        }
+        use crate::fido::cbor::{encode_change_pin, platform_key_agreement};
+        use crate::fido::pin_protocol::{padded_pin, pin_hash};
+        let cbor_err = |e: crate::error::CborError| FidoError::CborError(e.to_string());
+
+        let info = self.cached_info(false)?;
+        let protocol = PinUvAuthProtocol::preferred(&info.pin_uv_auth_protocols);
+        let (platform_key, shared) = self.establish_shared_secret(protocol)?;
+        let (x, y) = platform_key.coordinates();
+        let new_pin_enc = shared.encrypt(&padded_pin(new_pin))?;
+        let pin_hash_enc = shared.encrypt(&pin_hash(old_pin))?;
+        // pinUvAuthParam 涵蓋 newPinEnc || pinHashEnc
+        let signed = [new_pin_enc.as_slice(), &pin_hash_enc].concat();
+        let pin_uv_auth_param = shared.authenticate(&signed);
+        let encoded = encode_change_pin(
+            protocol.version(),
+            platform_key_agreement(x, y),
+            &new_pin_enc,
+            &pin_hash_enc,
+            &pin_uv_auth_param,
+        )
+        .map_err(cbor_err)?;
+        self.send_ctap_checked(&encoded)?;
+        // 變更後 forcePINChange 會被清除，需重新讀取 GetInfo
+        self.invalidate_info_cache();
+        Ok(())
     }
 
     // === 6.3: FIDO 憑證管理 ===
 
-    fn list_credentials(&self, pin: &str) -> Result<Vec<FidoCredential>, FidoError> {
+    fn get_creds_metadata(&self, pin: &str) -> Result<CredsMetadata, FidoError> {
         Self::validate_pin(pin)?;
+        self.ensure_pin_change_not_required()?;
+        self.require_cred_mgmt()?;
 
-        use crate::fido::cbor::{CborCodec, CborCodecImpl};
-        use crate::fido::types::{CredMgmtSubCommand, CtapCommand, CtapResponse};
-
-        let codec = CborCodecImpl::new();
-
-        // 使用 CTAP credentialManagement 子指令列舉所有 RP 的憑證
-        let cmd = CtapCommand::CredentialManagement(CredMgmtSubCommand::EnumerateRPsBegin);
-        let encoded = codec
-            .encode_ctap_command(&cmd)
+        let token =
+            self.acquire_pin_uv_auth_token(pin, PinUvPermissions::CREDENTIAL_MANAGEMENT, None)?;
+        let encoded = crate::fido::cbor::encode_get_creds_metadata(&token)
             .map_err(|e| FidoError::CborError(e.to_string()))?;
 
-        let response_bytes = self.send_ctap_command(&encoded)?;
+        let payload = self.send_ctap_checked(&encoded)?;
+        crate::fido::cbor::decode_creds_metadata(&payload)
+            .map_err(|e| FidoError::CborError(e.to_string()))
+    }
 
-        let response = codec
-            .decode_ctap_response(&response_bytes)
-            .map_err(|e| FidoError::CborError(e.to_string()))?;
+    fn list_credentials(&self, pin: &str) -> Result<Vec<FidoCredential>, FidoError> {
+        Self::validate_pin(pin)?;
+        self.ensure_pin_change_not_required()?;
+        self.require_cred_mgmt()?;
 
-        match response {
-            CtapResponse::CredentialManagement(cred_resp) => Ok(cred_resp.credentials),
-            CtapResponse::Error(code) => Err(crate::fido::cbor::ctap_error_to_fido_error(code)),
-            _ => Err(FidoError::CommunicationError(
-                "非預期的回應格式".to_string(),
-            )),
-        }
+        let token =
+            self.acquire_pin_uv_auth_token(pin, PinUvPermissions::CREDENTIAL_MANAGEMENT, None)?;
+        Self::enumerate_credentials(&token, |request| self.send_ctap_checked(request))
     }
 
     fn delete_credential(&self, pin: &str, credential_id: &[u8]) -> Result<(), FidoError> {
         Self::validate_pin(pin)?;
+        self.ensure_pin_change_not_required()?;
 
         if credential_id.is_empty() {
             return Err(FidoError::CommunicationError(
@@ -222,35 +804,74 @@ impl FidoModule for FidoModuleImpl {
             ));
         }
 
-        use crate::fido::cbor::{CborCodec, CborCodecImpl};
-        use crate::fido::types::{CredMgmtSubCommand, CtapCommand, CtapResponse};
-
-        let codec = CborCodecImpl::new();
-        let cmd = CtapCommand::CredentialManagement(CredMgmtSubCommand::DeleteCredential);
-        let encoded = codec
-            .encode_ctap_command(&cmd)
-            .map_err(|e| FidoError::CborError(e.to_string()))?;
-
-        let response_bytes = self.send_ctap_command(&encoded)?;
+        self.require_cred_mgmt()?;
+        let token =
+            self.acquire_pin_uv_auth_token(pin, PinUvPermissions::CREDENTIAL_MANAGEMENT, None)?;
+        self.send_delete_credential(credential_id, &token)
+    }
 
-        let response = codec
-            .decode_ctap_response(&response_bytes)
-            .map_err(|e| FidoError::CborError(e.to_string()))?;
+    fn delete_all_credentials(&self, pin: &str) -> Result<usize, FidoError> {
+        Self::validate_pin(pin)?;
+        self.ensure_pin_change_not_required()?;
+        self.require_cred_mgmt()?;
+
+        // 列舉與每次刪除共用同一個 cm 權限的 pinUvAuthToken，整個流程只驗證一次 PIN
+        let token =
+            self.acquire_pin_uv_auth_token(pin, PinUvPermissions::CREDENTIAL_MANAGEMENT, None)?;
+        Self::run_delete_all(
+            || Self::enumerate_credentials(&token, |request| self.send_ctap_checked(request)),
+            |credential_id| self.send_delete_credential(credential_id, &token),
+        )
+    }
+
+    fn assert_credential(
+        &self,
+        rp_id: &str,
+        credential_id: &[u8],
+        pin: &str,
+    ) -> Result<AssertionCheck, FidoError> {
+        Self::validate_pin(pin)?;
 
-        match response {
-            CtapResponse::Success => Ok(()),
-            CtapResponse::Error(code) => Err(crate::fido::cbor::ctap_error_to_fido_error(code)),
-            _ => Err(FidoError::CommunicationError(
-                "非預期的回應格式".to_string(),
-            )),
+        if rp_id.is_empty() || credential_id.is_empty() {
+            return Err(FidoError::CommunicationError(
+                "RP ID 與憑證 ID 不可為空".to_string(),
+            ));
         }
+
+        use crate::fido::cbor::{client_data_hash, decode_assertion, encode_get_assertion};
+        let cbor_err = |e: crate::error::CborError| FidoError::CborError(e.to_string());
+
+        let credential = self
+            .list_credentials(pin)?
+            .into_iter()
+            .find(|c| c.rp_id == rp_id && c.credential_id == credential_id)
+            .ok_or(FidoError::CredentialNotFound)?;
+        // 僅支援 ES256 憑證的簽章驗證
+        let public_key = credential.public_key.ok_or(FidoError::NotSupported)?;
+
+        let challenge = Self::random_challenge()?;
+        let hash = client_data_hash("webauthn.get", &challenge, rp_id);
+        let token =
+            self.acquire_pin_uv_auth_token(pin, PinUvPermissions::GET_ASSERTION, Some(rp_id))?;
+        let encoded =
+            encode_get_assertion(rp_id, &hash, credential_id, Some(&token)).map_err(cbor_err)?;
+        let payload = self.send_ctap_checked(&encoded)?;
+        let assertion = decode_assertion(&payload, rp_id).map_err(cbor_err)?;
+
+        Ok(AssertionCheck {
+            rp_id: rp_id.to_string(),
+            credential_id: crate::util::hex_encode(credential_id),
+            sign_count: assertion.sign_count,
+            user_verified: assertion.flags & AUTH_DATA_FLAG_UV != 0,
+            signature_valid: Self::assertion_signature_valid(&public_key, &assertion, &hash),
+        })
     }
 
     // === 6.4: FIDO 裝置資訊與組態 ===
 
     fn get_info(&self) -> Result<FidoDeviceInfo, FidoError> {
         use crate::fido::cbor::{CborCodec, CborCodecImpl};
-        use crate::fido::types::{CtapCommand, CtapResponse};
+        use crate::fido::types::CtapCommand;
 
         let codec = CborCodecImpl::new();
         let cmd = CtapCommand::GetInfo;
@@ -258,35 +879,39 @@ impl FidoModule for FidoModuleImpl {
             .encode_ctap_command(&cmd)
             .map_err(|e| FidoError::CborError(e.to_string()))?;
 
-        let response_bytes = self.send_ctap_command(&encoded)?;
+        let payload = self.send_ctap_checked(&encoded)?;
+        crate::fido::cbor::decode_get_info(&payload)
+            .map_err(|e| FidoError::CborError(e.to_string()))
+    }
 
-        let response = codec
-            .decode_ctap_response(&response_bytes)
-            .map_err(|e| FidoError::CborError(e.to_string()))?;
+    fn firmware_status(&self) -> Result<FirmwareStatus, FidoError> {
+        let info = self.cached_info(false)?;
+        crate::firmware::status(&DeviceType::PicoFido, &info.firmware_version).ok_or_else(|| {
+            FidoError::CommunicationError(format!("無法解析韌體版本: {}", info.firmware_version))
+        })
+    }
 
-        match response {
-            CtapResponse::GetInfo(info) => Ok(info),
-            CtapResponse::Error(code) => Err(crate::fido::cbor::ctap_error_to_fido_error(code)),
-            _ => Err(FidoError::CommunicationError(
-                "非預期的回應格式".to_string(),
-            )),
-        }
+    fn ping(&self) -> Result<PingResult, FidoError> {
+        Ok(Self::ping_with(|| self.cached_info(true)))
     }
 
-    fn set_min_pin_length(&self, pin: &str, length: u8) -> Result<(), FidoError> {
+    fn set_min_pin_length(&self, pin: &str, params: &SetMinPinLengthParams) -> Result<(), FidoError> {
         Self::validate_pin(pin)?;
+        self.ensure_pin_change_not_required()?;
 
-        if length < 4 || length > 63 {
-            return Err(FidoError::PinLengthInvalid);
+        if let Some(length) = params.new_min_pin_length {
+            if !(4..=63).contains(&length) {
+                return Err(FidoError::PinLengthInvalid);
+            }
         }
 
-        use crate::fido::cbor::{CborCodec, CborCodecImpl};
-        use crate::fido::types::{AuthConfigSubCommand, CtapCommand, CtapResponse};
+        use crate::fido::cbor::{encode_set_min_pin_length, CborCodec, CborCodecImpl};
+        use crate::fido::types::CtapResponse;
 
+        let token =
+            self.acquire_pin_uv_auth_token(pin, PinUvPermissions::AUTHENTICATOR_CONFIG, None)?;
         let codec = CborCodecImpl::new();
-        let cmd = CtapCommand::AuthenticatorConfig(AuthConfigSubCommand::SetMinPinLength);
-        let encoded = codec
-            .encode_ctap_command(&cmd)
+        let encoded = encode_set_min_pin_length(params, Some(&token))
             .map_err(|e| FidoError::CborError(e.to_string()))?;
 
         let response_bytes = self.send_ctap_command(&encoded)?;
@@ -304,18 +929,26 @@ impl FidoModule for FidoModuleImpl {
         }
     }
 
+    fn get_min_pin_length(&self) -> Result<MinPinLengthInfo, FidoError> {
+        // 設定後通常會立即讀回，略過快取並更新之
+        let info = self.cached_info(true)?;
+        Ok(MinPinLengthInfo {
+            min_pin_length: info.min_pin_length.unwrap_or(CTAP_DEFAULT_MIN_PIN_LENGTH),
+            force_pin_change: info.force_pin_change,
+        })
+    }
+
     fn toggle_enterprise_attestation(&self, pin: &str, _enable: bool) -> Result<(), FidoError> {
         Self::validate_pin(pin)?;
+        self.ensure_pin_change_not_required()?;
 
-        use crate::fido::cbor::{CborCodec, CborCodecImpl};
-        use crate::fido::types::{AuthConfigSubCommand, CtapCommand, CtapResponse};
+        use crate::fido::cbor::{encode_enable_enterprise_attestation, CborCodec, CborCodecImpl};
+        use crate::fido::types::CtapResponse;
 
+        let token =
+            self.acquire_pin_uv_auth_token(pin, PinUvPermissions::AUTHENTICATOR_CONFIG, None)?;
         let codec = CborCodecImpl::new();
-        let cmd = CtapCommand::AuthenticatorConfig(
-            AuthConfigSubCommand::EnableEnterpriseAttestation,
-        );
-        let encoded = codec
-            .encode_ctap_command(&cmd)
+        let encoded = encode_enable_enterprise_attestation(Some(&token))
             .map_err(|e| FidoError::CborError(e.to_string()))?;
 
         let response_bytes = self.send_ctap_command(&encoded)?;
@@ -333,56 +966,76 @@ impl FidoModule for FidoModuleImpl {
         }
     }
 
-    fn set_led_config(&self, _config: &LedConfig) -> Result<(), FidoError> {
-        use crate::fido::cbor::{CborCodec, CborCodecImpl};
-        use crate::fido::types::{CtapCommand, CtapResponse};
+    fn toggle_always_uv(&self, pin: &str, enable: bool) -> Result<(), FidoError> {
+        Self::validate_pin(pin)?;
+        self.ensure_pin_change_not_required()?;
 
-        let codec = CborCodecImpl::new();
-        // LED config is sent as a vendor-specific CTAP command;
-        // we use AuthenticatorConfig as the transport envelope.
-        let cmd = CtapCommand::AuthenticatorConfig(
-            crate::fido::types::AuthConfigSubCommand::SetMinPinLength, // vendor extension placeholder
-        );
-        let encoded = codec
-            .encode_ctap_command(&cmd)
+        // toggleAlwaysUv 只能切換，先依 GetInfo 的 alwaysUv 判斷是否需要送出
+        match self.get_info()?.options.get("alwaysUv") {
+            None => return Err(FidoError::NotSupported),
+            Some(&current) if current == enable => return Ok(()),
+            Some(_) => {}
+        }
+
+        let token =
+            self.acquire_pin_uv_auth_token(pin, PinUvPermissions::AUTHENTICATOR_CONFIG, None)?;
+        let encoded = crate::fido::cbor::encode_toggle_always_uv(Some(&token))
             .map_err(|e| FidoError::CborError(e.to_string()))?;
+        self.send_ctap_checked(&encoded)?;
+        Ok(())
+    }
 
-        let response_bytes = self.send_ctap_command(&encoded)?;
+    fn get_key_agreement(&self, protocol: u8) -> Result<KeyAgreementInfo, FidoError> {
+        if !(1..=2).contains(&protocol) {
+            return Err(FidoError::NotSupported);
+        }
 
-        let response = codec
-            .decode_ctap_response(&response_bytes)
+        let encoded = crate::fido::cbor::encode_get_key_agreement(protocol)
             .map_err(|e| FidoError::CborError(e.to_string()))?;
+        let payload = self.send_ctap_checked(&encoded)?;
+        crate::fido::cbor::decode_key_agreement(&payload, protocol)
+            .map_err(|e| FidoError::CborError(e.to_string()))
+    }
 
-        match response {
-            CtapResponse::Success => Ok(()),
-            CtapResponse::Error(code) => Err(crate::fido::cbor::ctap_error_to_fido_error(code)),
-            _ => Err(FidoError::CommunicationError(
-                "非預期的回應格式".to_string(),
-            )),
-        }
+    fn self_test_registration(&self) -> Result<SelfTestResult, FidoError> {
+        use crate::fido::cbor::{
+            client_data_hash, decode_get_assertion, decode_make_credential,
+            encode_get_assertion, encode_make_credential,
+        };
+        let cbor_err = |e: crate::error::CborError| FidoError::CborError(e.to_string());
+
+        let challenge = Self::random_challenge()?;
+        let create_hash = client_data_hash("webauthn.create", &challenge, SELF_TEST_RP_ID);
+        let encoded = encode_make_credential(&create_hash, SELF_TEST_RP_ID, &challenge[..16])
+            .map_err(cbor_err)?;
+        let payload = self.send_ctap_checked(&encoded)?;
+        let (attestation_format, credential_id) =
+            decode_make_credential(&payload, SELF_TEST_RP_ID).map_err(cbor_err)?;
+
+        let get_hash = client_data_hash("webauthn.get", &challenge, SELF_TEST_RP_ID);
+        let encoded = encode_get_assertion(SELF_TEST_RP_ID, &get_hash, &credential_id, None)
+            .map_err(cbor_err)?;
+        let payload = self.send_ctap_checked(&encoded)?;
+        let sign_count = decode_get_assertion(&payload, SELF_TEST_RP_ID).map_err(cbor_err)?;
+
+        Ok(SelfTestResult {
+            rp_id: SELF_TEST_RP_ID.to_string(),
+            attestation_format,
+            credential_id: crate::util::hex_encode(&credential_id),
+            sign_count,
+        })
     }
 
-    // === 6.5: FIDO OATH 管理 ===
-
-    fn list_oath_credentials(&self) -> Result<Vec<OathCredential>, FidoError> {
-        use crate::fido::cbor::{CborCodec, CborCodecImpl};
-        use crate::fido::types::CtapCommand;
-
-        let codec = CborCodecImpl::new();
-        // OATH 使用 vendor-specific Selection 指令作為列舉入口
-        let cmd = CtapCommand::Selection;
-        let encoded = codec
-            .encode_ctap_command(&cmd)
-            .map_err(|e| FidoError::CborError(e.to_string()))?;
-
-        let response_bytes = self.send_ctap_command(&encoded)?;
+    fn set_led_config(&self, _config: &LedConfig) -> Result<(), FidoError> {
+        // LED 設定尚無對應的 CTAP/vendor 指令，不送出任何請求
+        Err(FidoError::NotSupported)
+    }
 
-        let _response = codec
-            .decode_ctap_response(&response_bytes)
-            .map_err(|e| FidoError::CborError(e.to_string()))?;
+    // === 6.5: FIDO OATH 管理 ===
 
-        // 實際裝置會回傳 OATH 憑證列表，此處解析回應
-        Ok(vec![])
+    fn list_oath_credentials(&self) -> Result<Vec<OathCredential>, FidoError> {
+        let data = self.send_oath_command(&oath::list_command())?;
+        Ok(oath::parse_list_response(&data))
     }
 
     fn add_oath_credential(&self, credential: &OathCredentialParams) -> Result<(), FidoError> {
@@ -403,28 +1056,8 @@ impl FidoModule for FidoModuleImpl {
             ));
         }
 
-        use crate::fido::cbor::{CborCodec, CborCodecImpl};
-        use crate::fido::types::{CtapCommand, CtapResponse};
-
-        let codec = CborCodecImpl::new();
-        let cmd = CtapCommand::Selection;
-        let encoded = codec
-            .encode_ctap_command(&cmd)
-            .map_err(|e| FidoError::CborError(e.to_string()))?;
-
-        let response_bytes = self.send_ctap_command(&encoded)?;
-
-        let response = codec
-            .decode_ctap_response(&response_bytes)
-            .map_err(|e| FidoError::CborError(e.to_string()))?;
-
-        match response {
-            CtapResponse::Success => Ok(()),
-            CtapResponse::Error(code) => Err(crate::fido::cbor::ctap_error_to_fido_error(code)),
-            _ => Err(FidoError::CommunicationError(
-                "非預期的回應格式".to_string(),
-            )),
-        }
+        self.send_oath_command(&oath::put_command(credential)?)?;
+        Ok(())
     }
 
     fn calculate_oath(&self, credential_id: &str) -> Result<String, FidoError> {
@@ -434,23 +1067,21 @@ impl FidoModule for FidoModuleImpl {
             ));
         }
 
-        use crate::fido::cbor::{CborCodec, CborCodecImpl};
-        use crate::fido::types::CtapCommand;
-
-        let codec = CborCodecImpl::new();
-        let cmd = CtapCommand::Selection;
-        let encoded = codec
-            .encode_ctap_command(&cmd)
-            .map_err(|e| FidoError::CborError(e.to_string()))?;
-
-        let response_bytes = self.send_ctap_command(&encoded)?;
-
-        let _response = codec
-            .decode_ctap_response(&response_bytes)
-            .map_err(|e| FidoError::CborError(e.to_string()))?;
-
-        // 實際裝置會回傳計算後的 OTP 碼
-        Ok(String::new())
+        // LIST 提供類型與週期，用來決定 challenge
+        let credential = self
+            .list_oath_credentials()?
+            .into_iter()
+            .find(|c| c.id == credential_id)
+            .ok_or(FidoError::CredentialNotFound)?;
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let challenge = oath::challenge_for(&credential, now);
+        let data = self.send_oath_command(&oath::calculate_command(credential_id, &challenge))?;
+        oath::parse_calculate_response(&data).ok_or(FidoError::CommunicationError(
+            "非預期的回應格式".to_string(),
+        ))
     }
 
     fn delete_oath_credential(&self, credential_id: &str) -> Result<(), FidoError> {
@@ -460,57 +1091,29 @@ impl FidoModule for FidoModuleImpl {
             ));
         }
 
-        use crate::fido::cbor::{CborCodec, CborCodecImpl};
-        use crate::fido::types::{CtapCommand, CtapResponse};
-
-        let codec = CborCodecImpl::new();
-        let cmd = CtapCommand::Selection;
-        let encoded = codec
-            .encode_ctap_command(&cmd)
-            .map_err(|e| FidoError::CborError(e.to_string()))?;
-
-        let response_bytes = self.send_ctap_command(&encoded)?;
-
-        let response = codec
-            .decode_ctap_response(&response_bytes)
-            .map_err(|e| FidoError::CborError(e.to_string()))?;
+        self.send_oath_command(&oath::delete_command(credential_id))?;
+        Ok(())
+    }
 
-        match response {
-            CtapResponse::Success => Ok(()),
-            CtapResponse::Error(code) => Err(crate::fido::cbor::ctap_error_to_fido_error(code)),
-            _ => Err(FidoError::CommunicationError(
-                "非預期的回應格式".to_string(),
-            )),
-        }
+    fn oath_slot_info(&self) -> Result<OathSlotInfo, FidoError> {
+        // YKOATH 不回報容量，以 LIST 的項目數計算使用量
+        let used = self.list_oath_credentials()?.len();
+        Ok(oath::slot_info(used))
     }
 
     // === 6.6: FIDO 備份與重設 ===
 
     fn get_backup_words(&self, pin: &str) -> Result<Vec<String>, FidoError> {
         Self::validate_pin(pin)?;
+        self.ensure_pin_change_not_required()?;
 
-        use crate::fido::cbor::{CborCodec, CborCodecImpl};
-        use crate::fido::types::CtapCommand;
-
-        let codec = CborCodecImpl::new();
-        // 備份助記詞透過 vendor-specific Selection 指令取得
-        let cmd = CtapCommand::Selection;
-        let encoded = codec
-            .encode_ctap_command(&cmd)
-            .map_err(|e| FidoError::CborError(e.to_string()))?;
-
-        let response_bytes = self.send_ctap_command(&encoded)?;
-
-        let _response = codec
-            .decode_ctap_response(&response_bytes)
-            .map_err(|e| FidoError::CborError(e.to_string()))?;
-
-        // 實際裝置會回傳 24 個助記詞
-        Ok(vec![])
+        // 讀取備份助記詞尚無對應的 CTAP/vendor 指令
+        Err(FidoError::NotSupported)
     }
 
     fn restore_from_words(&self, pin: &str, words: &[String]) -> Result<(), FidoError> {
         Self::validate_pin(pin)?;
+        self.ensure_pin_change_not_required()?;
 
         if words.len() != 24 {
             return Err(FidoError::CommunicationError(
@@ -527,59 +1130,144 @@ impl FidoModule for FidoModuleImpl {
             }
         }
 
+        // 以助記詞還原尚無對應的 CTAP/vendor 指令，不可回報成功
+        Err(FidoError::NotSupported)
+    }
+
+    fn reset_device(&self) -> Result<(), FidoError> {
         use crate::fido::cbor::{CborCodec, CborCodecImpl};
         use crate::fido::types::{CtapCommand, CtapResponse};
 
         let codec = CborCodecImpl::new();
-        let cmd = CtapCommand::Selection;
+        let cmd = CtapCommand::Reset;
         let encoded = codec
             .encode_ctap_command(&cmd)
             .map_err(|e| FidoError::CborError(e.to_string()))?;
 
         let response_bytes = self.send_ctap_command(&encoded)?;
 
+        // 重設僅在插入後約 10 秒內且觸碰裝置時被接受，逾時錯誤需提示使用者重新插入
+        if let Some(&status) = response_bytes.first() {
+            if status != 0x00 {
+                return Err(crate::fido::cbor::reset_error_to_fido_error(status));
+            }
+        }
+
         let response = codec
             .decode_ctap_response(&response_bytes)
             .map_err(|e| FidoError::CborError(e.to_string()))?;
 
         match response {
             CtapResponse::Success => Ok(()),
-            CtapResponse::Error(code) => Err(crate::fido::cbor::ctap_error_to_fido_error(code)),
+            CtapResponse::Error(code) => Err(crate::fido::cbor::reset_error_to_fido_error(code)),
             _ => Err(FidoError::CommunicationError(
                 "非預期的回應格式".to_string(),
             )),
         }
     }
 
-    fn reset_device(&self) -> Result<(), FidoError> {
-        use crate::fido::cbor::{CborCodec, CborCodecImpl};
-        use crate::fido::types::{CtapCommand, CtapResponse};
+    fn reset_prepare(&self, pin: Option<&str>) -> Result<ResetPreparation, FidoError> {
+        // 憑證數僅供提示，查詢失敗不影響重設流程
+        let credentials_to_lose = match pin {
+            Some(pin) => self
+                .get_creds_metadata(pin)
+                .inspect_err(|e| log::warn!("getCredsMetadata before reset failed: {e}"))
+                .ok()
+                .map(|m| m.existing_resident_credentials_count),
+            None => None,
+        };
 
-        let codec = CborCodecImpl::new();
-        let cmd = CtapCommand::Reset;
-        let encoded = codec
-            .encode_ctap_command(&cmd)
-            .map_err(|e| FidoError::CborError(e.to_string()))?;
+        let token = Self::new_reset_token();
+        if let Ok(mut pending) = self.pending_reset.lock() {
+            *pending = Some((token.clone(), Instant::now()));
+        }
+        Ok(ResetPreparation {
+            token,
+            credentials_to_lose,
+            expires_in_secs: RESET_TOKEN_TTL.as_secs(),
+        })
+    }
 
-        let response_bytes = self.send_ctap_command(&encoded)?;
+    fn reset_confirm(&self, token: &str) -> Result<(), FidoError> {
+        // 確認碼只能使用一次，不論是否相符都會被清除
+        let pending = self.pending_reset.lock().ok().and_then(|mut p| p.take());
+        Self::check_reset_token(pending, token, Instant::now())?;
+        self.reset_device()
+    }
 
-        let response = codec
-            .decode_ctap_response(&response_bytes)
-            .map_err(|e| FidoError::CborError(e.to_string()))?;
+    // === largeBlobs ===
 
-        match response {
-            CtapResponse::Success => Ok(()),
-            CtapResponse::Error(code) => Err(crate::fido::cbor::ctap_error_to_fido_error(code)),
-            _ => Err(FidoError::CommunicationError(
-                "非預期的回應格式".to_string(),
-            )),
+    fn read_large_blob(&self) -> Result<Vec<u8>, FidoError> {
+        use crate::fido::large_blob::{
+            get_request, parse_get_response, verify_integrity, DEFAULT_FRAGMENT_SIZE,
+        };
+
+        self.require_large_blobs()?;
+
+        // 依 offset 逐段讀取，直到回傳長度小於請求長度
+        let mut blob = Vec::new();
+        loop {
+            let request = get_request(blob.len(), DEFAULT_FRAGMENT_SIZE)
+                .map_err(|e| FidoError::CborError(e.to_string()))?;
+            let payload = self.send_ctap_checked(&request)?;
+            let fragment = parse_get_response(&payload)
+                .map_err(|e| FidoError::CborError(e.to_string()))?;
+            let done = fragment.len() < DEFAULT_FRAGMENT_SIZE;
+            blob.extend_from_slice(&fragment);
+            if done {
+                break;
+            }
+        }
+
+        Ok(verify_integrity(&blob)?.to_vec())
+    }
+
+    fn write_large_blob(&self, pin: &str, data: &[u8]) -> Result<(), FidoError> {
+        use crate::fido::large_blob::{append_integrity, set_requests, DEFAULT_FRAGMENT_SIZE};
+
+        Self::validate_pin(pin)?;
+
+        // data 為序列化後的 largeBlob CBOR 陣列
+        match serde_cbor::from_slice::<serde_cbor::Value>(data) {
+            Ok(serde_cbor::Value::Array(_)) => {}
+            _ => {
+                return Err(FidoError::CborError(
+                    "largeBlob 資料必須為 CBOR 陣列".to_string(),
+                ))
+            }
+        }
+
+        self.require_large_blobs()?;
+
+        let token = self.acquire_pin_uv_auth_token(pin, PinUvPermissions::LARGE_BLOB_WRITE, None)?;
+        let blob = append_integrity(data);
+        let requests = set_requests(&blob, DEFAULT_FRAGMENT_SIZE, Some(&token))
+            .map_err(|e| FidoError::CborError(e.to_string()))?;
+        for request in requests {
+            self.send_ctap_checked(&request)?;
         }
+        Ok(())
+    }
+
+    // === 進階：原始 CTAP 指令 ===
+
+    fn transmit_cbor(&self, command_byte: u8, cbor_params: &[u8]) -> Result<Vec<u8>, FidoError> {
+        use crate::fido::ctaphid::{cbor_request, frame_message, CTAPHID_CBOR};
+
+        let payload = cbor_request(command_byte, cbor_params);
+        // 先確認訊息可被 CTAPHID 承載，避免傳送到一半才失敗
+        frame_message(0, CTAPHID_CBOR, &payload)?;
+        // 回傳含狀態碼的原始回應，由呼叫端自行解讀
+        self.send_ctap_command(&payload)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::fido::ctaphid;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
 
     // === PIN 驗證測試 ===
 
@@ -663,160 +1351,534 @@ mod tests {
     }
 
     #[test]
-    fn test_set_pin_valid_pin_hits_device_communication() {
-        // Valid PIN passes validation but fails at device communication (stub)
+    fn test_set_pin_valid_pin_hits_device_communication() {
+        // Valid PIN passes validation but fails at device communication (stub)
+        let module = FidoModuleImpl::new("test".to_string());
+        let result = module.set_pin("1234");
+        assert!(matches!(result, Err(FidoError::CommunicationError(_))));
+    }
+
+    // === change_pin 驗證邏輯測試 ===
+
+    #[test]
+    fn test_change_pin_rejects_short_new_pin() {
+        let module = FidoModuleImpl::new("test".to_string());
+        let result = module.change_pin("old_pin", "ab");
+        assert!(matches!(result, Err(FidoError::PinLengthInvalid)));
+    }
+
+    #[test]
+    fn test_change_pin_rejects_long_new_pin() {
+        let module = FidoModuleImpl::new("test".to_string());
+        let pin = "x".repeat(64);
+        let result = module.change_pin("old_pin", &pin);
+        assert!(matches!(result, Err(FidoError::PinLengthInvalid)));
+    }
+
+    #[test]
+    fn test_change_pin_valid_pins_hits_device_communication() {
+        let module = FidoModuleImpl::new("test".to_string());
+        let result = module.change_pin("old_pin_1234", "new_pin_5678");
+        assert!(matches!(result, Err(FidoError::CommunicationError(_))));
+    }
+
+    // === get_pin_retries 測試 ===
+
+    #[test]
+    fn test_get_pin_retries_hits_device_communication() {
+        let module = FidoModuleImpl::new("test".to_string());
+        let result = module.get_pin_retries();
+        assert!(matches!(result, Err(FidoError::CommunicationError(_))));
+    }
+
+    // === 6.3: 憑證管理測試 ===
+
+    #[test]
+    fn test_get_creds_metadata_validates_pin() {
+        let module = FidoModuleImpl::new("test".to_string());
+        assert!(matches!(
+            module.get_creds_metadata("ab"),
+            Err(FidoError::PinLengthInvalid)
+        ));
+        assert!(matches!(
+            module.get_creds_metadata("1234"),
+            Err(FidoError::CommunicationError(_))
+        ));
+    }
+
+    #[test]
+    fn test_list_credentials_validates_pin() {
+        let module = FidoModuleImpl::new("test".to_string());
+        assert!(matches!(
+            module.list_credentials("ab"),
+            Err(FidoError::PinLengthInvalid)
+        ));
+    }
+
+    #[test]
+    fn test_list_credentials_valid_pin_hits_device() {
+        let module = FidoModuleImpl::new("test".to_string());
+        assert!(matches!(
+            module.list_credentials("1234"),
+            Err(FidoError::CommunicationError(_))
+        ));
+    }
+
+    #[test]
+    fn test_delete_credential_validates_pin() {
+        let module = FidoModuleImpl::new("test".to_string());
+        assert!(matches!(
+            module.delete_credential("ab", &[1, 2]),
+            Err(FidoError::PinLengthInvalid)
+        ));
+    }
+
+    #[test]
+    fn test_delete_credential_rejects_empty_id() {
+        let module = FidoModuleImpl::new("test".to_string());
+        assert!(matches!(
+            module.delete_credential("1234", &[]),
+            Err(FidoError::CommunicationError(_))
+        ));
+    }
+
+    #[test]
+    fn test_delete_credential_valid_hits_device() {
+        let module = FidoModuleImpl::new("test".to_string());
+        assert!(matches!(
+            module.delete_credential("1234", &[1, 2, 3]),
+            Err(FidoError::CommunicationError(_))
+        ));
+    }
+
+    fn credential(id: u8) -> FidoCredential {
+        FidoCredential {
+            credential_id: vec![id],
+            rp_id: "example.com".to_string(),
+            rp_name: None,
+            user_name: None,
+            user_display_name: None,
+            creation_time: None,
+            cred_protect: None,
+            public_key: None,
+        }
+    }
+
+    // === 憑證列舉測試 ===
+
+    fn test_token() -> PinUvAuthToken {
+        PinUvAuthToken::new(PinUvAuthProtocol::Two, crate::secret::Zeroizing::new(vec![0x3C; 32]))
+    }
+
+    fn rp_response(rp_id: &str, total: Option<i128>) -> Vec<u8> {
+        use serde_cbor::Value;
+        use sha2::{Digest, Sha256};
+
+        let rp = [(Value::Text("id".into()), Value::Text(rp_id.into()))].into_iter().collect();
+        let mut map = std::collections::BTreeMap::new();
+        map.insert(Value::Integer(0x03), Value::Map(rp));
+        map.insert(Value::Integer(0x04), Value::Bytes(Sha256::digest(rp_id).to_vec()));
+        if let Some(total) = total {
+            map.insert(Value::Integer(0x05), Value::Integer(total));
+        }
+        serde_cbor::to_vec(&Value::Map(map)).unwrap()
+    }
+
+    fn credential_response(id: u8, total: Option<i128>) -> Vec<u8> {
+        use serde_cbor::Value;
+
+        let desc = [(Value::Text("id".into()), Value::Bytes(vec![id]))].into_iter().collect();
+        let mut map = std::collections::BTreeMap::new();
+        map.insert(Value::Integer(0x07), Value::Map(desc));
+        if let Some(total) = total {
+            map.insert(Value::Integer(0x09), Value::Integer(total));
+        }
+        serde_cbor::to_vec(&Value::Map(map)).unwrap()
+    }
+
+    /// 已送出請求的 credentialManagement 子指令碼
+    type SentSubCommands = std::rc::Rc<std::cell::RefCell<Vec<u8>>>;
+
+    /// 依序回放 `responses`，並記錄每個請求的子指令碼
+    fn replay(
+        responses: Vec<Result<Vec<u8>, FidoError>>,
+    ) -> (impl FnMut(&[u8]) -> Result<Vec<u8>, FidoError>, SentSubCommands) {
+        let sent = SentSubCommands::default();
+        let log = sent.clone();
+        let mut responses = responses.into_iter();
+        let send = move |request: &[u8]| {
+            assert_eq!(request[0], 0x0A);
+            // map(n) 之後的第一組為 0x01: subCommand
+            log.borrow_mut().push(request[3]);
+            responses.next().expect("多送出了請求")
+        };
+        (send, sent)
+    }
+
+    #[test]
+    fn test_enumerate_credentials_walks_rps_then_credentials() {
+        let (send, sent) = replay(vec![
+            Ok(rp_response("a.example", Some(2))),
+            Ok(rp_response("b.example", None)),
+            Ok(credential_response(1, Some(2))),
+            Ok(credential_response(2, None)),
+            Ok(credential_response(3, Some(1))),
+        ]);
+        let credentials = FidoModuleImpl::enumerate_credentials(&test_token(), send).unwrap();
+
+        let summary: Vec<_> =
+            credentials.iter().map(|c| (c.rp_id.as_str(), c.credential_id[0])).collect();
+        assert_eq!(summary, vec![("a.example", 1), ("a.example", 2), ("b.example", 3)]);
+        // RPsBegin, RPsNext, CredentialsBegin, CredentialsNext, CredentialsBegin
+        assert_eq!(*sent.borrow(), vec![0x02, 0x03, 0x04, 0x05, 0x04]);
+    }
+
+    #[test]
+    fn test_enumerate_credentials_without_credentials_is_empty() {
+        let (send, sent) = replay(vec![Err(FidoError::CtapError(CTAP2_ERR_NO_CREDENTIALS))]);
+        assert!(FidoModuleImpl::enumerate_credentials(&test_token(), send).unwrap().is_empty());
+        assert_eq!(*sent.borrow(), vec![0x02]);
+    }
+
+    #[test]
+    fn test_enumerate_credentials_propagates_other_errors() {
+        let responses = vec![Ok(rp_response("a.example", None)), Err(FidoError::PinInvalid(0))];
+        let (send, _) = replay(responses);
+        assert!(matches!(
+            FidoModuleImpl::enumerate_credentials(&test_token(), send),
+            Err(FidoError::PinInvalid(0))
+        ));
+    }
+
+    #[test]
+    fn test_run_delete_all_enumerates_then_deletes_each() {
+        let mut steps = Vec::new();
+        let deleted = FidoModuleImpl::run_delete_all(
+            || Ok(vec![credential(1), credential(2), credential(3)]),
+            |id| {
+                steps.push(id.to_vec());
+                Ok(())
+            },
+        )
+        .unwrap();
+        assert_eq!(deleted, 3);
+        assert_eq!(steps, vec![vec![1], vec![2], vec![3]]);
+    }
+
+    #[test]
+    fn test_run_delete_all_reports_partial_failure() {
+        let mut attempts = 0;
+        let result = FidoModuleImpl::run_delete_all(
+            || Ok(vec![credential(1), credential(2), credential(3)]),
+            |id| {
+                attempts += 1;
+                if id == [2] {
+                    return Err(FidoError::CtapError(0x2E));
+                }
+                Ok(())
+            },
+        );
+        match result {
+            Err(FidoError::BulkDeleteAborted { deleted, total, error }) => {
+                assert_eq!((deleted, total), (1, 3));
+                assert!(matches!(*error, FidoError::CtapError(0x2E)));
+            }
+            other => panic!("unexpected result: {other:?}"),
+        }
+        // 失敗後不再嘗試刪除其餘憑證
+        assert_eq!(attempts, 2);
+    }
+
+    #[test]
+    fn test_run_delete_all_list_failure_deletes_nothing() {
+        let result = FidoModuleImpl::run_delete_all(
+            || Err(FidoError::NotSupported),
+            |_| panic!("列舉失敗時不應刪除"),
+        );
+        assert!(matches!(result, Err(FidoError::NotSupported)));
+        assert_eq!(FidoModuleImpl::run_delete_all(|| Ok(vec![]), |_| Ok(())).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_delete_all_credentials_validates_pin() {
+        let module = FidoModuleImpl::new("test".to_string());
+        assert!(matches!(
+            module.delete_all_credentials("12"),
+            Err(FidoError::PinLengthInvalid)
+        ));
+    }
+
+    #[test]
+    fn test_assert_credential_validates_input_then_hits_device() {
+        let module = FidoModuleImpl::new("test".to_string());
+        assert!(matches!(
+            module.assert_credential("example.com", &[1], "ab"),
+            Err(FidoError::PinLengthInvalid)
+        ));
+        assert!(matches!(
+            module.assert_credential("example.com", &[], "1234"),
+            Err(FidoError::CommunicationError(_))
+        ));
+        assert!(matches!(
+            module.assert_credential("example.com", &[1, 2, 3], "1234"),
+            Err(FidoError::CommunicationError(_))
+        ));
+    }
+
+    #[test]
+    fn test_assertion_signature_covers_auth_data_and_client_data_hash() {
+        use crate::fido::cbor::AssertionResponse;
+        use crate::util::hex_decode;
+
+        let public_key = hex_decode(
+            "0420b003d2f297be2c5e2c83a7e9f9a5b9eff49111acf4fddbcc0301480e359de6\
+             dc809c49652aeb6d63329abf5a52155c766345c28fed3024741c8ed01589d28b",
+        )
+        .unwrap();
+        let mut hash = [0u8; 32];
+        hash.copy_from_slice(
+            &hex_decode("255e6e8baea1fcaa46da36633ec0b59e7ca901c0a25fcc32cf1db81d508ecdb3").unwrap(),
+        );
+        let assertion = AssertionResponse {
+            auth_data: hex_decode(
+                "a379a6f6eeafb9a55e378c118034e2751e682fab9f2d30ab13d2125586ce19470500000007",
+            )
+            .unwrap(),
+            signature: hex_decode(
+                "3045022100bb9612546571e2e6674cab965a162c6ad83cbddda8192a00e235a07868fdab3a\
+                 02207b36f63f171a20520eb851e8d6df09934a94b2c41c1fe9bae9c50570c0c79940",
+            )
+            .unwrap(),
+            flags: 0x05,
+            sign_count: 7,
+        };
+        assert!(FidoModuleImpl::assertion_signature_valid(&public_key, &assertion, &hash));
+
+        // clientDataHash 不同（例如 challenge 被替換）時驗證失敗
+        let mut other_hash = hash;
+        other_hash[0] ^= 0x01;
+        assert!(!FidoModuleImpl::assertion_signature_valid(&public_key, &assertion, &other_hash));
+
+        // 公鑰不在曲線上、公鑰或簽章截斷時驗證失敗
+        let mut bad_key = public_key.clone();
+        bad_key[64] ^= 0x01;
+        assert!(!FidoModuleImpl::assertion_signature_valid(&bad_key, &assertion, &hash));
+        assert!(!FidoModuleImpl::assertion_signature_valid(&public_key[..33], &assertion, &hash));
+        let mut truncated = assertion.clone();
+        truncated.signature.pop();
+        assert!(!FidoModuleImpl::assertion_signature_valid(&public_key, &truncated, &hash));
+    }
+
+    // === 6.4: 裝置資訊與組態測試 ===
+
+    #[test]
+    fn test_get_info_hits_device() {
+        let module = FidoModuleImpl::new("test".to_string());
+        assert!(matches!(
+            module.get_info(),
+            Err(FidoError::CommunicationError(_))
+        ));
+    }
+
+    #[test]
+    fn test_get_min_pin_length_hits_device() {
         let module = FidoModuleImpl::new("test".to_string());
-        let result = module.set_pin("1234");
-        assert!(matches!(result, Err(FidoError::CommunicationError(_))));
+        assert!(matches!(
+            module.get_min_pin_length(),
+            Err(FidoError::CommunicationError(_))
+        ));
     }
 
-    // === change_pin 驗證邏輯測試 ===
+    #[test]
+    fn test_ping_reports_firmware_from_get_info() {
+        use serde_cbor::Value;
+
+        let payload = serde_cbor::to_vec(&Value::Map(
+            [(Value::Integer(0x0E), Value::Integer(0x0604))].into_iter().collect(),
+        ))
+        .unwrap();
+        let result = FidoModuleImpl::ping_with(|| {
+            crate::fido::cbor::decode_get_info(&payload).map_err(|e| FidoError::CborError(e.to_string()))
+        });
+        assert_eq!(
+            result,
+            PingResult { reachable: true, firmware: Some("6.4".to_string()), error: None }
+        );
+    }
 
     #[test]
-    fn test_change_pin_rejects_short_new_pin() {
+    fn test_ping_unreachable_is_not_an_error() {
+        let result = FidoModuleImpl::ping_with(|| Err(FidoError::Timeout));
+        assert!(!result.reachable && result.firmware.is_none());
+        assert!(result.error.is_some());
+
         let module = FidoModuleImpl::new("test".to_string());
-        let result = module.change_pin("old_pin", "ab");
-        assert!(matches!(result, Err(FidoError::PinLengthInvalid)));
+        assert!(!module.ping().unwrap().reachable);
     }
 
     #[test]
-    fn test_change_pin_rejects_long_new_pin() {
-        let module = FidoModuleImpl::new("test".to_string());
-        let pin = "x".repeat(64);
-        let result = module.change_pin("old_pin", &pin);
-        assert!(matches!(result, Err(FidoError::PinLengthInvalid)));
+    fn test_forced_pin_change_blocks_pin_operations() {
+        use serde_cbor::Value;
+
+        let payload = serde_cbor::to_vec(&Value::Map(
+            [(Value::Integer(0x0C), Value::Bool(true))].into_iter().collect(),
+        ))
+        .unwrap();
+        let mut info = crate::fido::cbor::decode_get_info(&payload).unwrap();
+        assert!(matches!(
+            FidoModuleImpl::check_forced_pin_change(&info),
+            Err(FidoError::PinChangeRequired)
+        ));
+        info.force_pin_change = false;
+        assert!(FidoModuleImpl::check_forced_pin_change(&info).is_ok());
     }
 
     #[test]
-    fn test_change_pin_valid_pins_hits_device_communication() {
+    fn test_firmware_status_hits_device() {
         let module = FidoModuleImpl::new("test".to_string());
-        let result = module.change_pin("old_pin_1234", "new_pin_5678");
-        assert!(matches!(result, Err(FidoError::CommunicationError(_))));
+        assert!(matches!(
+            module.firmware_status(),
+            Err(FidoError::CommunicationError(_))
+        ));
     }
 
-    // === get_pin_retries 測試 ===
-
     #[test]
-    fn test_get_pin_retries_hits_device_communication() {
+    fn test_cached_info_does_not_cache_errors() {
         let module = FidoModuleImpl::new("test".to_string());
-        let result = module.get_pin_retries();
-        assert!(matches!(result, Err(FidoError::CommunicationError(_))));
+        assert!(matches!(module.cached_info(false), Err(FidoError::CommunicationError(_))));
+        assert!(matches!(module.cached_info(false), Err(FidoError::CommunicationError(_))));
     }
 
-    // === 6.3: 憑證管理測試 ===
+    fn min_pin_length(length: u8) -> SetMinPinLengthParams {
+        SetMinPinLengthParams {
+            new_min_pin_length: Some(length),
+            ..Default::default()
+        }
+    }
 
     #[test]
-    fn test_list_credentials_validates_pin() {
+    fn test_set_min_pin_length_validates_pin() {
         let module = FidoModuleImpl::new("test".to_string());
         assert!(matches!(
-            module.list_credentials("ab"),
+            module.set_min_pin_length("ab", &min_pin_length(6)),
             Err(FidoError::PinLengthInvalid)
         ));
     }
 
     #[test]
-    fn test_list_credentials_valid_pin_hits_device() {
+    fn test_set_min_pin_length_rejects_too_small() {
         let module = FidoModuleImpl::new("test".to_string());
         assert!(matches!(
-            module.list_credentials("1234"),
-            Err(FidoError::CommunicationError(_))
+            module.set_min_pin_length("1234", &min_pin_length(3)),
+            Err(FidoError::PinLengthInvalid)
         ));
     }
 
     #[test]
-    fn test_delete_credential_validates_pin() {
+    fn test_set_min_pin_length_rejects_too_large() {
         let module = FidoModuleImpl::new("test".to_string());
         assert!(matches!(
-            module.delete_credential("ab", &[1, 2]),
+            module.set_min_pin_length("1234", &min_pin_length(64)),
             Err(FidoError::PinLengthInvalid)
         ));
     }
 
     #[test]
-    fn test_delete_credential_rejects_empty_id() {
+    fn test_set_min_pin_length_valid_hits_device() {
         let module = FidoModuleImpl::new("test".to_string());
         assert!(matches!(
-            module.delete_credential("1234", &[]),
+            module.set_min_pin_length("1234", &min_pin_length(6)),
             Err(FidoError::CommunicationError(_))
         ));
     }
 
     #[test]
-    fn test_delete_credential_valid_hits_device() {
+    fn test_set_min_pin_length_without_length_hits_device() {
         let module = FidoModuleImpl::new("test".to_string());
+        let params = SetMinPinLengthParams {
+            min_pin_length_rp_ids: vec!["example.com".to_string()],
+            force_change_pin: true,
+            ..Default::default()
+        };
         assert!(matches!(
-            module.delete_credential("1234", &[1, 2, 3]),
+            module.set_min_pin_length("1234", &params),
             Err(FidoError::CommunicationError(_))
         ));
     }
 
-    // === 6.4: 裝置資訊與組態測試 ===
-
     #[test]
-    fn test_get_info_hits_device() {
+    fn test_toggle_enterprise_attestation_validates_pin() {
         let module = FidoModuleImpl::new("test".to_string());
         assert!(matches!(
-            module.get_info(),
-            Err(FidoError::CommunicationError(_))
+            module.toggle_enterprise_attestation("ab", true),
+            Err(FidoError::PinLengthInvalid)
         ));
     }
 
     #[test]
-    fn test_set_min_pin_length_validates_pin() {
+    fn test_toggle_enterprise_attestation_valid_hits_device() {
         let module = FidoModuleImpl::new("test".to_string());
         assert!(matches!(
-            module.set_min_pin_length("ab", 6),
-            Err(FidoError::PinLengthInvalid)
+            module.toggle_enterprise_attestation("1234", true),
+            Err(FidoError::CommunicationError(_))
         ));
     }
 
     #[test]
-    fn test_set_min_pin_length_rejects_too_small() {
+    fn test_toggle_always_uv_validates_pin() {
         let module = FidoModuleImpl::new("test".to_string());
         assert!(matches!(
-            module.set_min_pin_length("1234", 3),
+            module.toggle_always_uv("ab", true),
             Err(FidoError::PinLengthInvalid)
         ));
     }
 
     #[test]
-    fn test_set_min_pin_length_rejects_too_large() {
+    fn test_toggle_always_uv_valid_hits_device() {
         let module = FidoModuleImpl::new("test".to_string());
         assert!(matches!(
-            module.set_min_pin_length("1234", 64),
-            Err(FidoError::PinLengthInvalid)
+            module.toggle_always_uv("1234", true),
+            Err(FidoError::CommunicationError(_))
         ));
     }
 
     #[test]
-    fn test_set_min_pin_length_valid_hits_device() {
+    fn test_get_key_agreement_rejects_unknown_protocol() {
         let module = FidoModuleImpl::new("test".to_string());
         assert!(matches!(
-            module.set_min_pin_length("1234", 6),
-            Err(FidoError::CommunicationError(_))
+            module.get_key_agreement(3),
+            Err(FidoError::NotSupported)
         ));
     }
 
     #[test]
-    fn test_toggle_enterprise_attestation_validates_pin() {
+    fn test_get_key_agreement_hits_device() {
         let module = FidoModuleImpl::new("test".to_string());
         assert!(matches!(
-            module.toggle_enterprise_attestation("ab", true),
-            Err(FidoError::PinLengthInvalid)
+            module.get_key_agreement(2),
+            Err(FidoError::CommunicationError(_))
         ));
     }
 
     #[test]
-    fn test_toggle_enterprise_attestation_valid_hits_device() {
+    fn test_self_test_registration_hits_device() {
         let module = FidoModuleImpl::new("test".to_string());
         assert!(matches!(
-            module.toggle_enterprise_attestation("1234", true),
+            module.self_test_registration(),
             Err(FidoError::CommunicationError(_))
         ));
     }
 
     #[test]
-    fn test_set_led_config_hits_device() {
+    fn test_random_challenge_varies() {
+        assert_ne!(
+            FidoModuleImpl::random_challenge().unwrap(),
+            FidoModuleImpl::random_challenge().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_set_led_config_not_supported() {
         let module = FidoModuleImpl::new("test".to_string());
         let config = LedConfig {
             gpio: Some(25),
@@ -826,7 +1888,7 @@ mod tests {
         };
         assert!(matches!(
             module.set_led_config(&config),
-            Err(FidoError::CommunicationError(_))
+            Err(FidoError::NotSupported)
         ));
     }
 
@@ -841,6 +1903,40 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn test_oath_slot_info_hits_device() {
+        let module = FidoModuleImpl::new("test".to_string());
+        assert!(matches!(
+            module.oath_slot_info(),
+            Err(FidoError::CommunicationError(_))
+        ));
+    }
+
+    #[test]
+    fn test_oath_aid_defaults_to_ykoath() {
+        let module = FidoModuleImpl::new("test".to_string());
+        assert_eq!(module.oath_aid(), oath::YKOATH_AID);
+        assert_eq!(module.oath_select_command(), oath::select_command(oath::YKOATH_AID));
+    }
+
+    #[test]
+    fn test_custom_oath_aid_used_in_select() {
+        let module = FidoModuleImpl::new("test".to_string());
+        let custom = vec![0xA0, 0x00, 0x00, 0x05, 0x27, 0x21, 0x01, 0x01];
+        module.set_oath_aid(custom.clone()).unwrap();
+        let apdu = module.oath_select_command();
+        assert_eq!(&apdu[..5], &[0x00, 0xA4, 0x04, 0x00, 0x08]);
+        assert_eq!(&apdu[5..], custom.as_slice());
+    }
+
+    #[test]
+    fn test_set_oath_aid_rejects_invalid_length() {
+        let module = FidoModuleImpl::new("test".to_string());
+        assert!(matches!(module.set_oath_aid(vec![0xA0; 4]), Err(FidoError::InvalidAid(4))));
+        assert!(matches!(module.set_oath_aid(vec![0xA0; 17]), Err(FidoError::InvalidAid(17))));
+        assert_eq!(module.oath_aid(), oath::YKOATH_AID);
+    }
+
     #[test]
     fn test_add_oath_credential_rejects_empty_secret() {
         let module = FidoModuleImpl::new("test".to_string());
@@ -961,11 +2057,11 @@ mod tests {
     }
 
     #[test]
-    fn test_get_backup_words_valid_hits_device() {
+    fn test_get_backup_words_not_supported() {
         let module = FidoModuleImpl::new("test".to_string());
         assert!(matches!(
             module.get_backup_words("1234"),
-            Err(FidoError::CommunicationError(_))
+            Err(FidoError::NotSupported)
         ));
     }
 
@@ -1001,12 +2097,12 @@ mod tests {
     }
 
     #[test]
-    fn test_restore_from_words_valid_hits_device() {
+    fn test_restore_from_words_not_supported() {
         let module = FidoModuleImpl::new("test".to_string());
         let words: Vec<String> = (0..24).map(|i| format!("word{}", i)).collect();
         assert!(matches!(
             module.restore_from_words("1234", &words),
-            Err(FidoError::CommunicationError(_))
+            Err(FidoError::NotSupported)
         ));
     }
 
@@ -1018,4 +2114,428 @@ mod tests {
             Err(FidoError::CommunicationError(_))
         ));
     }
+
+    #[test]
+    fn test_reset_confirm_requires_prepare() {
+        let module = FidoModuleImpl::new("test".to_string());
+        assert!(matches!(
+            module.reset_confirm("deadbeef"),
+            Err(FidoError::ResetTokenInvalid)
+        ));
+    }
+
+    #[test]
+    fn test_reset_prepare_then_confirm_hits_device() {
+        let module = FidoModuleImpl::new("test".to_string());
+        let prep = module.reset_prepare(None).unwrap();
+        assert_eq!(prep.credentials_to_lose, None);
+        assert_eq!(prep.expires_in_secs, RESET_TOKEN_TTL.as_secs());
+        assert!(matches!(
+            module.reset_confirm(&prep.token),
+            Err(FidoError::CommunicationError(_))
+        ));
+        // 確認碼已被使用
+        assert!(matches!(
+            module.reset_confirm(&prep.token),
+            Err(FidoError::ResetTokenInvalid)
+        ));
+    }
+
+    #[test]
+    fn test_reset_prepare_tolerates_metadata_failure() {
+        let module = FidoModuleImpl::new("test".to_string());
+        let prep = module.reset_prepare(Some("1234")).unwrap();
+        assert_eq!(prep.credentials_to_lose, None);
+    }
+
+    #[test]
+    fn test_reset_confirm_wrong_token_consumes_pending() {
+        let module = FidoModuleImpl::new("test".to_string());
+        let prep = module.reset_prepare(None).unwrap();
+        assert!(matches!(
+            module.reset_confirm("wrong"),
+            Err(FidoError::ResetTokenInvalid)
+        ));
+        assert!(matches!(
+            module.reset_confirm(&prep.token),
+            Err(FidoError::ResetTokenInvalid)
+        ));
+    }
+
+    #[test]
+    fn test_reset_token_invalidated_by_device_change() {
+        let module = FidoModuleImpl::new("test".to_string());
+        let prep = module.reset_prepare(None).unwrap();
+        module.set_device_path("other");
+        assert!(matches!(
+            module.reset_confirm(&prep.token),
+            Err(FidoError::ResetTokenInvalid)
+        ));
+    }
+
+    #[test]
+    fn test_check_reset_token_expiry() {
+        let issued = Instant::now();
+        let pending = || Some(("abc".to_string(), issued));
+        assert!(FidoModuleImpl::check_reset_token(pending(), "abc", issued).is_ok());
+        assert!(FidoModuleImpl::check_reset_token(pending(), "abc", issued + RESET_TOKEN_TTL).is_ok());
+        assert!(matches!(
+            FidoModuleImpl::check_reset_token(
+                pending(),
+                "abc",
+                issued + RESET_TOKEN_TTL + Duration::from_millis(1)
+            ),
+            Err(FidoError::ResetTokenExpired)
+        ));
+        assert!(matches!(
+            FidoModuleImpl::check_reset_token(pending(), "abd", issued),
+            Err(FidoError::ResetTokenInvalid)
+        ));
+    }
+
+    #[test]
+    fn test_reset_tokens_are_unique() {
+        let a = FidoModuleImpl::new_reset_token();
+        let b = FidoModuleImpl::new_reset_token();
+        assert_eq!(a.len(), 16);
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn test_advanced_mode_defaults_off() {
+        let module = FidoModuleImpl::new("test".to_string());
+        assert!(!module.advanced_mode());
+        module.set_advanced_mode(true);
+        assert!(module.advanced_mode());
+    }
+
+    fn info_with_options(options: &[(&str, bool)]) -> FidoDeviceInfo {
+        use serde_cbor::Value;
+
+        let options = options
+            .iter()
+            .map(|&(name, enabled)| (Value::Text(name.to_string()), Value::Bool(enabled)))
+            .collect();
+        let payload = serde_cbor::to_vec(&Value::Map(
+            [(Value::Integer(0x04), Value::Map(options))].into_iter().collect(),
+        ))
+        .unwrap();
+        crate::fido::cbor::decode_get_info(&payload).unwrap()
+    }
+
+    #[test]
+    fn test_token_subcommand_prefers_uv_when_supported() {
+        let info = info_with_options(&[("uv", true), ("pinUvAuthToken", true), ("clientPin", true)]);
+        assert_eq!(
+            FidoModuleImpl::token_subcommand(&info, UvMethod::Uv),
+            ClientPinSubCommand::GetPinUvAuthTokenUsingUvWithPermissions
+        );
+        assert_eq!(
+            FidoModuleImpl::token_subcommand(&info, UvMethod::Pin),
+            ClientPinSubCommand::GetPinUvAuthTokenUsingPinWithPermissions
+        );
+    }
+
+    #[test]
+    fn test_token_subcommand_falls_back_to_pin() {
+        // uv 未設定（false）或不支援 pinUvAuthToken 時不能以 UV 取得 token
+        let uv_not_enrolled = info_with_options(&[("uv", false), ("pinUvAuthToken", true)]);
+        assert_eq!(
+            FidoModuleImpl::token_subcommand(&uv_not_enrolled, UvMethod::Uv),
+            ClientPinSubCommand::GetPinUvAuthTokenUsingPinWithPermissions
+        );
+        let ctap20 = info_with_options(&[("uv", true), ("clientPin", true)]);
+        assert_eq!(
+            FidoModuleImpl::token_subcommand(&ctap20, UvMethod::Uv),
+            ClientPinSubCommand::GetPinToken
+        );
+    }
+
+    #[test]
+    fn test_uv_method_defaults_to_pin() {
+        let module = FidoModuleImpl::new("test".to_string());
+        assert_eq!(module.uv_method(), UvMethod::Pin);
+        module.set_uv_method(UvMethod::Uv);
+        assert_eq!(module.uv_method(), UvMethod::Uv);
+    }
+
+    #[test]
+    fn test_transmit_cbor_hits_device() {
+        let module = FidoModuleImpl::new("test".to_string());
+        assert!(matches!(
+            module.transmit_cbor(0x41, &[0xA0]),
+            Err(FidoError::CommunicationError(_))
+        ));
+    }
+
+    #[test]
+    fn test_transmit_cbor_rejects_oversized_params() {
+        let module = FidoModuleImpl::new(String::new());
+        let params = vec![0u8; crate::fido::ctaphid::MAX_MESSAGE_SIZE];
+        let err = module.transmit_cbor(0x41, &params).unwrap_err();
+        assert!(matches!(err, FidoError::CommunicationError(ref m) if m.contains("CTAPHID")));
+    }
+
+    #[test]
+    fn test_read_large_blob_hits_device() {
+        let module = FidoModuleImpl::new("test".to_string());
+        assert!(matches!(
+            module.read_large_blob(),
+            Err(FidoError::CommunicationError(_))
+        ));
+    }
+
+    #[test]
+    fn test_write_large_blob_rejects_non_array() {
+        let module = FidoModuleImpl::new("test".to_string());
+        assert!(matches!(
+            module.write_large_blob("1234", &[0xA0]),
+            Err(FidoError::CborError(_))
+        ));
+    }
+
+    #[test]
+    fn test_write_large_blob_validates_pin() {
+        let module = FidoModuleImpl::new("test".to_string());
+        assert!(matches!(
+            module.write_large_blob("ab", &[0x80]),
+            Err(FidoError::PinLengthInvalid)
+        ));
+    }
+
+    #[test]
+    fn test_write_large_blob_hits_device() {
+        let module = FidoModuleImpl::new("test".to_string());
+        assert!(matches!(
+            module.write_large_blob("1234", &[0x80]),
+            Err(FidoError::CommunicationError(_))
+        ));
+    }
+
+    #[test]
+    fn test_read_timeout_default_and_setter() {
+        let module = FidoModuleImpl::new("test".to_string());
+        assert_eq!(module.read_timeout(), DEFAULT_READ_TIMEOUT);
+        module.set_read_timeout(Duration::from_secs(3));
+        assert_eq!(module.read_timeout(), Duration::from_secs(3));
+    }
+
+    #[test]
+    fn test_for_device_keeps_settings_and_active_path() {
+        let module = FidoModuleImpl::new("active".to_string());
+        module.set_read_timeout(Duration::from_secs(3));
+        module.set_uv_method(UvMethod::Uv);
+        module.set_oath_aid(vec![0xA0, 0x00, 0x00, 0x05, 0x27]).unwrap();
+
+        let other = module.for_device("other");
+        assert_eq!(other.get_device_path(), "other");
+        assert_eq!(module.get_device_path(), "active");
+        assert_eq!(other.read_timeout(), Duration::from_secs(3));
+        assert_eq!(other.uv_method(), UvMethod::Uv);
+        assert_eq!(other.oath_aid(), module.oath_aid());
+        assert!(Arc::ptr_eq(&other.device_locks, &module.device_locks));
+    }
+
+    // === CTAPHID 並行保護測試 ===
+
+    /// 記錄寫入 / 讀取事件至共用紀錄的傳輸層；每次讀取前稍作延遲以放大交錯機會
+    struct LoggingTransport {
+        tag: char,
+        cid: u32,
+        log: Arc<Mutex<Vec<char>>>,
+        written: AtomicUsize,
+    }
+
+    impl HidTransport for LoggingTransport {
+        fn write_report(&self, _report: &[u8]) -> Result<(), FidoError> {
+            self.written.fetch_add(1, Ordering::SeqCst);
+            self.log.lock().unwrap().push(self.tag);
+            std::thread::sleep(Duration::from_millis(1));
+            Ok(())
+        }
+
+        fn read_report(&self, buf: &mut [u8], _timeout: Duration) -> Result<usize, FidoError> {
+            std::thread::sleep(Duration::from_millis(2));
+            self.log.lock().unwrap().push(self.tag.to_ascii_uppercase());
+            let frames = ctaphid::frame_message(self.cid, ctaphid::CTAPHID_CBOR, &[0x00])?;
+            buf[..frames[0].len()].copy_from_slice(&frames[0]);
+            Ok(frames[0].len())
+        }
+    }
+
+    #[test]
+    fn test_transact_overlapping_calls_do_not_interleave() {
+        let module = Arc::new(FidoModuleImpl::new("hid0".to_string()));
+        let log = Arc::new(Mutex::new(Vec::new()));
+        // 超過一個封包的請求，確保每個交換含多次寫入
+        let payload = vec![0x04; 100];
+
+        let handles: Vec<_> = ['a', 'b', 'c']
+            .into_iter()
+            .enumerate()
+            .map(|(i, tag)| {
+                let (module, log, payload) =
+                    (Arc::clone(&module), Arc::clone(&log), payload.clone());
+                std::thread::spawn(move || {
+                    let dev = LoggingTransport {
+                        tag,
+                        cid: i as u32 + 1,
+                        log,
+                        written: AtomicUsize::new(0),
+                    };
+                    let (cmd, data) = module
+                        .transact(&dev, dev.cid, ctaphid::CTAPHID_CBOR, &payload)
+                        .unwrap();
+                    assert_eq!((cmd, data), (ctaphid::CTAPHID_CBOR, vec![0x00]));
+                    assert_eq!(dev.written.load(Ordering::SeqCst), 2);
+                })
+            })
+            .collect();
+        for h in handles {
+            h.join().unwrap();
+        }
+
+        // 每個交換（2 次寫入 + 1 次讀取）必須連續出現
+        let log = log.lock().unwrap();
+        assert_eq!(log.len(), 9);
+        for exchange in log.chunks(3) {
+            let tag = exchange[0];
+            assert_eq!(exchange, [tag, tag, tag.to_ascii_uppercase()], "interleaved: {log:?}");
+        }
+    }
+
+    #[test]
+    fn test_transact_releases_lock_after_failure() {
+        let module = FidoModuleImpl::new("hid0".to_string());
+        let too_long = vec![0u8; ctaphid::MAX_MESSAGE_SIZE + 1];
+        let dev = LoggingTransport {
+            tag: 'a',
+            cid: 1,
+            log: Default::default(),
+            written: AtomicUsize::new(0),
+        };
+        assert!(module.transact(&dev, 1, 0x10, &too_long).is_err());
+        assert!(module.transact(&dev, 1, 0x10, &[0x04]).is_ok());
+        // 回應已讀取完畢，不再有等待中的 channel
+        assert!(module.pending_cid.lock().unwrap().is_none());
+    }
+
+    /// 模擬認證器：回應 CTAPHID_INIT 配置 channel，CBOR 請求回傳固定回應
+    struct AuthenticatorTransport {
+        cid: u32,
+        requests: Mutex<Vec<(u32, u8)>>,
+        pending: Mutex<Option<[u8; ctaphid::HID_REPORT_SIZE]>>,
+    }
+
+    impl HidTransport for AuthenticatorTransport {
+        fn write_report(&self, report: &[u8]) -> Result<(), FidoError> {
+            let cid = u32::from_be_bytes([report[0], report[1], report[2], report[3]]);
+            let cmd = report[4] & 0x7F;
+            self.requests.lock().unwrap().push((cid, cmd));
+            let frames = if cmd == ctaphid::CTAPHID_INIT {
+                let init = [&report[7..15], &self.cid.to_be_bytes()[..], &[0x02, 5, 2, 0, 0x05]];
+                ctaphid::frame_message(cid, cmd, &init.concat())?
+            } else {
+                ctaphid::frame_message(cid, cmd, &[0x00, 0xA0])?
+            };
+            *self.pending.lock().unwrap() = Some(frames[0]);
+            Ok(())
+        }
+
+        fn read_report(&self, buf: &mut [u8], _timeout: Duration) -> Result<usize, FidoError> {
+            match self.pending.lock().unwrap().take() {
+                Some(frame) => {
+                    buf[..frame.len()].copy_from_slice(&frame);
+                    Ok(frame.len())
+                }
+                None => Ok(0),
+            }
+        }
+    }
+
+    #[test]
+    fn test_ctap_exchange_allocates_channel_before_cbor() {
+        let module = FidoModuleImpl::new("hid0".to_string());
+        let dev = AuthenticatorTransport {
+            cid: 0x0102_0304,
+            requests: Mutex::new(Vec::new()),
+            pending: Mutex::new(None),
+        };
+        assert_eq!(module.ctap_exchange(&dev, &[0x04]).unwrap(), vec![0x00, 0xA0]);
+        assert_eq!(
+            *dev.requests.lock().unwrap(),
+            vec![
+                (ctaphid::BROADCAST_CID, ctaphid::CTAPHID_INIT),
+                (0x0102_0304, ctaphid::CTAPHID_CBOR),
+            ]
+        );
+    }
+
+    /// 模擬 OATH applet：記錄 CTAPHID_MSG 收到的 APDU，SELECT 回 9000，其他指令回傳固定 LIST 資料
+    struct OathTransport {
+        apdus: Mutex<Vec<Vec<u8>>>,
+        pending: Mutex<Option<[u8; ctaphid::HID_REPORT_SIZE]>>,
+    }
+
+    impl HidTransport for OathTransport {
+        fn write_report(&self, report: &[u8]) -> Result<(), FidoError> {
+            let cid = u32::from_be_bytes([report[0], report[1], report[2], report[3]]);
+            let cmd = report[4] & 0x7F;
+            let len = usize::from(u16::from_be_bytes([report[5], report[6]]));
+            let payload = &report[7..7 + len];
+            let response = if cmd == ctaphid::CTAPHID_INIT {
+                [payload, &0x0A0B_0C0Du32.to_be_bytes()[..], &[0x02, 5, 2, 0, 0x05]].concat()
+            } else {
+                self.apdus.lock().unwrap().push(payload.to_vec());
+                if payload[1] == 0xA4 {
+                    vec![0x90, 0x00]
+                } else {
+                    [&[oath::TAG_NAME_LIST, 6, 0x21][..], b"A:one", &[0x90, 0x00]].concat()
+                }
+            };
+            *self.pending.lock().unwrap() = Some(ctaphid::frame_message(cid, cmd, &response)?[0]);
+            Ok(())
+        }
+
+        fn read_report(&self, buf: &mut [u8], _timeout: Duration) -> Result<usize, FidoError> {
+            match self.pending.lock().unwrap().take() {
+                Some(frame) => {
+                    buf[..frame.len()].copy_from_slice(&frame);
+                    Ok(frame.len())
+                }
+                None => Ok(0),
+            }
+        }
+    }
+
+    #[test]
+    fn test_oath_exchange_selects_configured_aid() {
+        let module = FidoModuleImpl::new("hid0".to_string());
+        let custom = vec![0xA0, 0x00, 0x00, 0x05, 0x27, 0x21, 0x01, 0x01];
+        module.set_oath_aid(custom.clone()).unwrap();
+        let dev = OathTransport { apdus: Mutex::new(Vec::new()), pending: Mutex::new(None) };
+
+        let data = module.oath_exchange(&dev, &oath::list_command()).unwrap();
+        assert_eq!(oath::parse_list_response(&data)[0].id, "A:one");
+        assert_eq!(
+            *dev.apdus.lock().unwrap(),
+            vec![oath::select_command(&custom), oath::list_command()]
+        );
+    }
+
+    #[test]
+    fn test_cancel_without_pending_request_is_noop() {
+        let module = FidoModuleImpl::new("test".to_string());
+        assert!(module.cancel().is_ok());
+    }
+
+    #[test]
+    fn test_cancel_pending_request_requires_device() {
+        let module = FidoModuleImpl::new("test".to_string());
+        *module.pending_cid.lock().unwrap() = Some(7);
+        assert!(matches!(
+            module.cancel(),
+            Err(FidoError::CommunicationError(_))
+        ));
+    }
 }
@@ -0,0 +1,492 @@
+use crate::error::FidoError;
+use crate::fido::types::{
+    OathAlgorithm, OathCredential, OathCredentialParams, OathSlotInfo, OathType,
+    DEFAULT_OATH_DIGITS,
+};
+
+/// YKOATH LIST 回應中每筆憑證的 TLV tag
+pub const TAG_NAME_LIST: u8 = 0x72;
+/// YKOATH CALCULATE 回應中截斷後 OTP 的 TLV tag（位數(1) + 值(4)）
+pub const TAG_TRUNCATED_RESPONSE: u8 = 0x76;
+/// 憑證名稱的 TLV tag
+pub const TAG_NAME: u8 = 0x71;
+/// 密鑰的 TLV tag（類型/演算法(1) + 位數(1) + 密鑰）
+pub const TAG_KEY: u8 = 0x73;
+/// CALCULATE challenge 的 TLV tag
+pub const TAG_CHALLENGE: u8 = 0x74;
+/// HOTP 初始計數器的 TLV tag（4 位元組 big-endian）
+pub const TAG_IMF: u8 = 0x7A;
+
+/// YKOATH 指令碼
+pub const INS_PUT: u8 = 0x01;
+pub const INS_DELETE: u8 = 0x02;
+pub const INS_LIST: u8 = 0xA1;
+pub const INS_CALCULATE: u8 = 0xA2;
+pub const INS_SEND_REMAINING: u8 = 0xA5;
+
+/// YKOATH 憑證名稱長度上限（位元組）
+const MAX_NAME_LEN: usize = 64;
+/// 密鑰長度上限（HMAC 區塊大小內，較長的密鑰需由呼叫端先雜湊）
+const MAX_SECRET_LEN: usize = 64;
+
+/// YKOATH applet 預設 AID（Pico-FIDO 與相容韌體）
+pub const YKOATH_AID: &[u8] = &[0xA0, 0x00, 0x00, 0x05, 0x27, 0x21, 0x01];
+
+/// 未在名稱中指定時的 TOTP 週期（秒）
+const DEFAULT_TOTP_PERIOD: u32 = 30;
+
+/// 韌體可儲存的 OATH 憑證上限（YKOATH 不回報容量）
+pub const MAX_OATH_CREDENTIALS: u32 = 255;
+
+/// 建立選擇 OATH applet 的 SELECT APDU（by DF name）
+pub fn select_command(aid: &[u8]) -> Vec<u8> {
+    let mut apdu = vec![0x00, 0xA4, 0x04, 0x00, aid.len() as u8];
+    apdu.extend_from_slice(aid);
+    apdu
+}
+
+/// 建立短格式 TLV（值長度不得超過 255 位元組）
+fn tlv(tag: u8, value: &[u8]) -> Vec<u8> {
+    let mut out = vec![tag, value.len() as u8];
+    out.extend_from_slice(value);
+    out
+}
+
+/// 建立 CLA=00、P1=00 的 YKOATH APDU
+fn ykoath_apdu(ins: u8, p2: u8, data: &[u8]) -> Vec<u8> {
+    let mut apdu = vec![0x00, ins, 0x00, p2, data.len() as u8];
+    apdu.extend_from_slice(data);
+    apdu
+}
+
+/// 建立 LIST APDU
+pub fn list_command() -> Vec<u8> {
+    vec![0x00, INS_LIST, 0x00, 0x00]
+}
+
+/// 建立 SEND REMAINING APDU（回應狀態碼為 61xx 時取得剩餘資料）
+pub fn send_remaining_command() -> Vec<u8> {
+    vec![0x00, INS_SEND_REMAINING, 0x00, 0x00]
+}
+
+/// 依參數組出裝置上的憑證名稱 `[period/][issuer:]account`，預設週期不寫入名稱
+pub fn credential_name(params: &OathCredentialParams) -> String {
+    let label = if params.issuer.is_empty() {
+        params.account.clone()
+    } else {
+        format!("{}:{}", params.issuer, params.account)
+    };
+    match (&params.oath_type, params.period) {
+        (OathType::Totp, Some(period)) if period != DEFAULT_TOTP_PERIOD => {
+            format!("{period}/{label}")
+        }
+        _ => label,
+    }
+}
+
+/// 建立 PUT APDU；HOTP 指定非零計數器時一併寫入 IMF
+pub fn put_command(params: &OathCredentialParams) -> Result<Vec<u8>, FidoError> {
+    let name = credential_name(params);
+    if name.len() > MAX_NAME_LEN {
+        return Err(FidoError::CommunicationError("OATH 憑證名稱過長".to_string()));
+    }
+    if params.secret.len() > MAX_SECRET_LEN {
+        return Err(FidoError::CommunicationError("OATH 密鑰過長".to_string()));
+    }
+    let type_byte = match params.oath_type {
+        OathType::Hotp => 0x10,
+        OathType::Totp => 0x20,
+    };
+    // 參數未指定演算法，一律使用 SHA1（0x01）
+    let mut key = vec![type_byte | 0x01, params.digits];
+    key.extend_from_slice(&params.secret);
+
+    let mut data = tlv(TAG_NAME, name.as_bytes());
+    data.extend(tlv(TAG_KEY, &key));
+    if let (OathType::Hotp, Some(counter)) = (&params.oath_type, params.counter) {
+        if counter > 0 {
+            let counter = u32::try_from(counter).map_err(|_| {
+                FidoError::CommunicationError("HOTP 計數器超出範圍".to_string())
+            })?;
+            data.extend(tlv(TAG_IMF, &counter.to_be_bytes()));
+        }
+    }
+    Ok(ykoath_apdu(INS_PUT, 0x00, &data))
+}
+
+/// 建立 DELETE APDU
+pub fn delete_command(name: &str) -> Vec<u8> {
+    ykoath_apdu(INS_DELETE, 0x00, &tlv(TAG_NAME, name.as_bytes()))
+}
+
+/// 建立 CALCULATE APDU（P2=01 要求截斷回應）
+pub fn calculate_command(name: &str, challenge: &[u8]) -> Vec<u8> {
+    let mut data = tlv(TAG_NAME, name.as_bytes());
+    data.extend(tlv(TAG_CHALLENGE, challenge));
+    ykoath_apdu(INS_CALCULATE, 0x01, &data)
+}
+
+/// TOTP challenge：UNIX 時間除以週期後的 8 位元組 big-endian 值
+pub fn totp_challenge(unix_secs: u64, period: u32) -> [u8; 8] {
+    (unix_secs / u64::from(period.max(1))).to_be_bytes()
+}
+
+/// 由憑證資訊決定 CALCULATE 的 challenge；HOTP 使用空 challenge
+pub fn challenge_for(credential: &OathCredential, unix_secs: u64) -> Vec<u8> {
+    match credential.oath_type {
+        OathType::Totp => {
+            totp_challenge(unix_secs, credential.period.unwrap_or(DEFAULT_TOTP_PERIOD)).to_vec()
+        }
+        OathType::Hotp => Vec::new(),
+    }
+}
+
+/// 解析 CALCULATE 截斷回應為補零後的 OTP 字串
+pub fn parse_calculate_response(data: &[u8]) -> Option<String> {
+    let [TAG_TRUNCATED_RESPONSE, len, value @ ..] = data else {
+        return None;
+    };
+    let value = value.get(..usize::from(*len))?;
+    let digits = digits_from_truncated_response(value)?;
+    let truncated = u32::from_be_bytes([value[1], value[2], value[3], value[4]]) & 0x7FFF_FFFF;
+    let code = truncated % 10u32.pow(u32::from(digits));
+    Some(format!("{code:0width$}", width = usize::from(digits)))
+}
+
+/// 拆出回應資料與狀態碼（SW1 SW2）
+pub fn split_status(response: &[u8]) -> Result<(&[u8], u16), FidoError> {
+    match response {
+        [data @ .., sw1, sw2] => Ok((data, u16::from_be_bytes([*sw1, *sw2]))),
+        _ => Err(FidoError::CommunicationError("OATH 回應過短".to_string())),
+    }
+}
+
+/// 將 YKOATH 狀態碼轉換為錯誤
+pub fn check_status(sw: u16) -> Result<(), FidoError> {
+    match sw {
+        0x9000 => Ok(()),
+        0x6A82 => Err(FidoError::CredentialNotFound),
+        0x6A84 => Err(FidoError::CommunicationError("OATH 儲存空間不足".to_string())),
+        0x6982 => Err(FidoError::CommunicationError("OATH applet 需要先驗證密碼".to_string())),
+        0x6A80 | 0x6700 => Err(FidoError::CommunicationError("OATH 參數錯誤".to_string())),
+        _ => Err(FidoError::CommunicationError(format!("OATH 狀態碼: {sw:04X}"))),
+    }
+}
+
+/// 解析 YKOATH 類型位元組的高四位元（0x10=HOTP, 0x20=TOTP）
+fn oath_type_from_byte(byte: u8) -> Option<OathType> {
+    match byte & 0xF0 {
+        0x10 => Some(OathType::Hotp),
+        0x20 => Some(OathType::Totp),
+        _ => None,
+    }
+}
+
+/// 解析單筆 LIST 項目：類型/演算法(1) + 名稱 `[period/][issuer:]account`
+/// 位數不在 LIST 中回報，先以預設值填入
+pub fn parse_list_entry(value: &[u8]) -> Option<OathCredential> {
+    let (&type_byte, name) = value.split_first()?;
+    let oath_type = oath_type_from_byte(type_byte)?;
+    let algorithm = OathAlgorithm::from_type_byte(type_byte)?;
+    let id = String::from_utf8(name.to_vec()).ok()?;
+
+    let (period, label) = match (&oath_type, id.split_once('/')) {
+        (OathType::Totp, Some((p, rest))) if p.parse::<u32>().is_ok() => {
+            (p.parse().ok(), rest)
+        }
+        (OathType::Totp, _) => (Some(DEFAULT_TOTP_PERIOD), id.as_str()),
+        (OathType::Hotp, _) => (None, id.as_str()),
+    };
+    let (issuer, account) = match label.split_once(':') {
+        Some((issuer, account)) => (Some(issuer.to_string()), account.to_string()),
+        None => (None, label.to_string()),
+    };
+
+    Some(OathCredential {
+        id: id.clone(),
+        issuer,
+        account,
+        oath_type,
+        period,
+        digits: DEFAULT_OATH_DIGITS,
+        algorithm,
+    })
+}
+
+/// 逐一取出 LIST 回應中的憑證項目內容，遇到長度不足的項目即停止
+fn list_entries(data: &[u8]) -> impl Iterator<Item = &[u8]> {
+    let mut rest = data;
+    std::iter::from_fn(move || loop {
+        let [tag, len, tail @ ..] = rest else {
+            return None;
+        };
+        let len = usize::from(*len);
+        if tail.len() < len {
+            return None;
+        }
+        let (value, next) = tail.split_at(len);
+        rest = next;
+        if *tag == TAG_NAME_LIST {
+            return Some(value);
+        }
+    })
+}
+
+/// 解析 LIST 回應資料，略過無法辨識或長度不足的項目
+pub fn parse_list_response(data: &[u8]) -> Vec<OathCredential> {
+    list_entries(data).filter_map(parse_list_entry).collect()
+}
+
+/// 依已使用的憑證數計算槽位資訊
+pub fn slot_info(used: usize) -> OathSlotInfo {
+    OathSlotInfo {
+        used: u32::try_from(used).unwrap_or(u32::MAX),
+        total: MAX_OATH_CREDENTIALS,
+    }
+}
+
+/// 由 LIST 回應計算槽位使用量；無法辨識類型的項目仍佔用一個槽位
+pub fn parse_slot_info(data: &[u8]) -> OathSlotInfo {
+    slot_info(list_entries(data).count())
+}
+
+/// 由 CALCULATE 截斷回應的值取得 OTP 位數
+pub fn digits_from_truncated_response(value: &[u8]) -> Option<u8> {
+    match value {
+        [digits, _, _, _, _] if (6..=8).contains(digits) => Some(*digits),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(type_byte: u8, name: &str) -> Vec<u8> {
+        let mut value = vec![type_byte];
+        value.extend_from_slice(name.as_bytes());
+        value
+    }
+
+    // === SELECT 測試 ===
+
+    #[test]
+    fn test_select_command_default_aid() {
+        assert_eq!(
+            select_command(YKOATH_AID),
+            vec![0x00, 0xA4, 0x04, 0x00, 0x07, 0xA0, 0x00, 0x00, 0x05, 0x27, 0x21, 0x01]
+        );
+    }
+
+    fn params(
+        oath_type: OathType,
+        period: Option<u32>,
+        counter: Option<u64>,
+    ) -> OathCredentialParams {
+        OathCredentialParams {
+            secret: vec![0x11; 10],
+            issuer: "Example".to_string(),
+            account: "alice".to_string(),
+            oath_type,
+            digits: 6,
+            period,
+            counter,
+        }
+    }
+
+    // === APDU 建構測試 ===
+
+    #[test]
+    fn test_credential_name_includes_non_default_period() {
+        assert_eq!(credential_name(&params(OathType::Totp, Some(30), None)), "Example:alice");
+        assert_eq!(credential_name(&params(OathType::Totp, Some(60), None)), "60/Example:alice");
+        assert_eq!(credential_name(&params(OathType::Hotp, Some(60), None)), "Example:alice");
+        let mut no_issuer = params(OathType::Totp, None, None);
+        no_issuer.issuer.clear();
+        assert_eq!(credential_name(&no_issuer), "alice");
+    }
+
+    #[test]
+    fn test_put_command_layout() {
+        let apdu = put_command(&params(OathType::Totp, Some(30), None)).unwrap();
+        assert_eq!(&apdu[..4], &[0x00, INS_PUT, 0x00, 0x00]);
+        assert_eq!(usize::from(apdu[4]), apdu.len() - 5);
+        let name = b"Example:alice";
+        assert_eq!(&apdu[5..7], &[TAG_NAME, name.len() as u8]);
+        assert_eq!(&apdu[7..7 + name.len()], name);
+        let key = &apdu[7 + name.len()..];
+        assert_eq!(&key[..4], &[TAG_KEY, 12, 0x21, 6]);
+        assert_eq!(&key[4..], &[0x11; 10]);
+    }
+
+    #[test]
+    fn test_put_command_hotp_counter() {
+        let apdu = put_command(&params(OathType::Hotp, None, Some(5))).unwrap();
+        assert_eq!(&apdu[apdu.len() - 6..], &[TAG_IMF, 4, 0, 0, 0, 5]);
+        let apdu = put_command(&params(OathType::Hotp, None, Some(0))).unwrap();
+        assert!(!apdu.contains(&TAG_IMF));
+        assert!(put_command(&params(OathType::Hotp, None, Some(u64::MAX))).is_err());
+    }
+
+    #[test]
+    fn test_put_command_rejects_long_secret() {
+        let mut long = params(OathType::Totp, None, None);
+        long.secret = vec![0; MAX_SECRET_LEN + 1];
+        assert!(matches!(put_command(&long), Err(FidoError::CommunicationError(_))));
+    }
+
+    #[test]
+    fn test_delete_and_calculate_commands() {
+        assert_eq!(
+            delete_command("a:b"),
+            vec![0x00, INS_DELETE, 0x00, 0x00, 5, TAG_NAME, 3, b'a', b':', b'b']
+        );
+        assert_eq!(
+            calculate_command("ab", &[0x01, 0x02]),
+            vec![
+                0x00, INS_CALCULATE, 0x00, 0x01, 8, TAG_NAME, 2, b'a', b'b', TAG_CHALLENGE, 2, 1, 2
+            ]
+        );
+    }
+
+    #[test]
+    fn test_challenge_for_totp_and_hotp() {
+        assert_eq!(totp_challenge(59, 30), [0, 0, 0, 0, 0, 0, 0, 1]);
+        let totp = parse_list_entry(&entry(0x21, "60/Example:alice")).unwrap();
+        assert_eq!(challenge_for(&totp, 120), vec![0, 0, 0, 0, 0, 0, 0, 2]);
+        let hotp = parse_list_entry(&entry(0x11, "Example:bob")).unwrap();
+        assert!(challenge_for(&hotp, 120).is_empty());
+    }
+
+    // === CALCULATE 回應與狀態碼測試 ===
+
+    #[test]
+    fn test_parse_calculate_response() {
+        // RFC 4226 範例：截斷值 0x4C93CF18 -> 6 位數 755224
+        let data = [TAG_TRUNCATED_RESPONSE, 5, 6, 0x4C, 0x93, 0xCF, 0x18];
+        assert_eq!(parse_calculate_response(&data).as_deref(), Some("755224"));
+        let padded = [TAG_TRUNCATED_RESPONSE, 5, 8, 0x00, 0x00, 0x00, 0x07];
+        assert_eq!(parse_calculate_response(&padded).as_deref(), Some("00000007"));
+        assert!(parse_calculate_response(&[TAG_TRUNCATED_RESPONSE, 5, 6, 0x00]).is_none());
+        assert!(parse_calculate_response(&[TAG_NAME, 1, 0]).is_none());
+    }
+
+    #[test]
+    fn test_split_and_check_status() {
+        assert_eq!(split_status(&[0x01, 0x90, 0x00]).unwrap(), (&[0x01][..], 0x9000));
+        assert!(split_status(&[0x90]).is_err());
+        assert!(check_status(0x9000).is_ok());
+        assert!(matches!(check_status(0x6A82), Err(FidoError::CredentialNotFound)));
+        assert!(matches!(check_status(0x6A84), Err(FidoError::CommunicationError(_))));
+    }
+
+    // === LIST 項目解析測試 ===
+
+    #[test]
+    fn test_parse_list_entry_algorithms() {
+        for (byte, algorithm) in [
+            (0x21, OathAlgorithm::Sha1),
+            (0x22, OathAlgorithm::Sha256),
+            (0x23, OathAlgorithm::Sha512),
+        ] {
+            let cred = parse_list_entry(&entry(byte, "Example:alice")).unwrap();
+            assert_eq!(cred.algorithm, algorithm);
+            assert_eq!(cred.digits, DEFAULT_OATH_DIGITS);
+        }
+    }
+
+    #[test]
+    fn test_parse_list_entry_unknown_algorithm() {
+        assert!(parse_list_entry(&entry(0x24, "Example:alice")).is_none());
+        assert!(parse_list_entry(&entry(0x30, "Example:alice")).is_none());
+        assert!(parse_list_entry(&[]).is_none());
+    }
+
+    #[test]
+    fn test_parse_list_entry_totp_period_and_issuer() {
+        let cred = parse_list_entry(&entry(0x21, "60/Example:alice")).unwrap();
+        assert!(matches!(cred.oath_type, OathType::Totp));
+        assert_eq!(cred.period, Some(60));
+        assert_eq!(cred.issuer.as_deref(), Some("Example"));
+        assert_eq!(cred.account, "alice");
+        assert_eq!(cred.id, "60/Example:alice");
+
+        let cred = parse_list_entry(&entry(0x21, "alice")).unwrap();
+        assert_eq!(cred.period, Some(DEFAULT_TOTP_PERIOD));
+        assert!(cred.issuer.is_none());
+    }
+
+    #[test]
+    fn test_parse_list_entry_hotp_has_no_period() {
+        let cred = parse_list_entry(&entry(0x12, "Example:bob")).unwrap();
+        assert!(matches!(cred.oath_type, OathType::Hotp));
+        assert_eq!(cred.period, None);
+        assert_eq!(cred.algorithm, OathAlgorithm::Sha256);
+    }
+
+    // === LIST 回應解析測試 ===
+
+    #[test]
+    fn test_parse_list_response_multiple_entries() {
+        let mut data = Vec::new();
+        for (byte, name) in [(0x21u8, "A:one"), (0x13, "B:two")] {
+            let value = entry(byte, name);
+            data.push(TAG_NAME_LIST);
+            data.push(value.len() as u8);
+            data.extend(value);
+        }
+        let creds = parse_list_response(&data);
+        assert_eq!(creds.len(), 2);
+        assert_eq!(creds[1].algorithm, OathAlgorithm::Sha512);
+    }
+
+    #[test]
+    fn test_parse_list_response_truncated() {
+        let value = entry(0x21, "A:one");
+        let mut data = vec![TAG_NAME_LIST, value.len() as u8];
+        data.extend(&value[..3]);
+        assert!(parse_list_response(&data).is_empty());
+    }
+
+    // === 槽位使用量測試 ===
+
+    #[test]
+    fn test_parse_slot_info_counts_every_entry() {
+        let mut data = Vec::new();
+        for (byte, name) in [(0x21u8, "A:one"), (0x30, "B:unknown"), (0x12, "C:three")] {
+            let value = entry(byte, name);
+            data.push(TAG_NAME_LIST);
+            data.push(value.len() as u8);
+            data.extend(value);
+        }
+        // 非 LIST 項目不計入
+        data.extend([0x71, 0x01, 0x00]);
+        let info = parse_slot_info(&data);
+        assert_eq!(info, OathSlotInfo { used: 3, total: MAX_OATH_CREDENTIALS });
+        assert_eq!(info.remaining(), MAX_OATH_CREDENTIALS - 3);
+        assert_eq!(parse_list_response(&data).len(), 2);
+    }
+
+    #[test]
+    fn test_parse_slot_info_empty_and_truncated() {
+        assert_eq!(parse_slot_info(&[]).used, 0);
+        let value = entry(0x21, "A:one");
+        let mut data = vec![TAG_NAME_LIST, value.len() as u8];
+        data.extend(&value);
+        data.extend([TAG_NAME_LIST, 0x10, 0x21]);
+        assert_eq!(parse_slot_info(&data).used, 1);
+    }
+
+    #[test]
+    fn test_slot_info_remaining_saturates() {
+        assert_eq!(slot_info(300).remaining(), 0);
+    }
+
+    #[test]
+    fn test_digits_from_truncated_response() {
+        assert_eq!(digits_from_truncated_response(&[8, 0, 0, 0, 1]), Some(8));
+        assert_eq!(digits_from_truncated_response(&[6, 0, 0, 0, 1]), Some(6));
+        assert_eq!(digits_from_truncated_response(&[4, 0, 0, 0, 1]), None);
+        assert_eq!(digits_from_truncated_response(&[6, 0]), None);
+    }
+}
@@ -0,0 +1,339 @@
+use aes::cipher::block_padding::NoPadding;
+use aes::cipher::{BlockDecryptMut, BlockEncryptMut, KeyIvInit};
+use hkdf::Hkdf;
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+
+use crate::error::FidoError;
+use crate::fido::types::PinUvAuthParams;
+use crate::secret::Zeroizing;
+
+const AES_BLOCK_SIZE: usize = 16;
+/// newPinEnc 明文的長度：PIN 以 0x00 補滿 64 位元組
+const PADDED_PIN_LEN: usize = 64;
+
+type Aes256CbcEnc = cbc::Encryptor<aes::Aes256>;
+type Aes256CbcDec = cbc::Decryptor<aes::Aes256>;
+
+/// HMAC-SHA-256
+fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(key).expect("HMAC 接受任意長度的金鑰");
+    mac.update(message);
+    mac.finalize().into_bytes().into()
+}
+
+// === pinUvAuthProtocol ===
+
+/// CTAP 2.1 §6.5 的 PIN/UV 驗證協定
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PinUvAuthProtocol {
+    /// 協定 1：SHA-256(Z) 為共享密鑰，AES-CBC 使用全零 IV，驗證值取 HMAC 前 16 位元組
+    One,
+    /// 協定 2：以 HKDF 分別導出 HMAC 與 AES 金鑰，AES-CBC 使用隨機 IV，驗證值為完整 HMAC
+    Two,
+}
+
+impl PinUvAuthProtocol {
+    /// 依 GetInfo 的 pinUvAuthProtocols 選擇協定：支援 2 時優先使用，未回報時使用 1
+    pub fn preferred(supported: &[u8]) -> Self {
+        if supported.contains(&2) {
+            PinUvAuthProtocol::Two
+        } else {
+            PinUvAuthProtocol::One
+        }
+    }
+
+    pub fn version(self) -> u8 {
+        match self {
+            PinUvAuthProtocol::One => 1,
+            PinUvAuthProtocol::Two => 2,
+        }
+    }
+
+    /// 由 ECDH 的 Z 導出共享密鑰
+    pub fn shared_secret(self, z: &[u8; 32]) -> SharedSecret {
+        let (hmac_key, aes_key) = match self {
+            PinUvAuthProtocol::One => {
+                let key: [u8; 32] = Sha256::digest(z).into();
+                (key, key)
+            }
+            PinUvAuthProtocol::Two => {
+                let hkdf = Hkdf::<Sha256>::new(Some(&[0u8; 32]), z);
+                let derive = |info: &[u8]| {
+                    let mut key = [0u8; 32];
+                    hkdf.expand(info, &mut key).expect("32 位元組在 HKDF 輸出上限內");
+                    key
+                };
+                (derive(b"CTAP2 HMAC key"), derive(b"CTAP2 AES key"))
+            }
+        };
+        SharedSecret { protocol: self, hmac_key, aes_key }
+    }
+
+    /// 以 `key`（共享密鑰的 HMAC 金鑰或 pinUvAuthToken）計算 pinUvAuthParam
+    pub fn authenticate(self, key: &[u8], message: &[u8]) -> Vec<u8> {
+        let mac = hmac_sha256(key, message);
+        match self {
+            PinUvAuthProtocol::One => mac[..16].to_vec(),
+            PinUvAuthProtocol::Two => mac.to_vec(),
+        }
+    }
+}
+
+/// 與認證器協商出的共享密鑰；drop 時清除
+pub struct SharedSecret {
+    protocol: PinUvAuthProtocol,
+    hmac_key: [u8; 32],
+    aes_key: [u8; 32],
+}
+
+impl Drop for SharedSecret {
+    fn drop(&mut self) {
+        self.hmac_key = [0; 32];
+        self.aes_key = [0; 32];
+        std::hint::black_box((&self.hmac_key, &self.aes_key));
+    }
+}
+
+impl SharedSecret {
+    pub fn protocol(&self) -> PinUvAuthProtocol {
+        self.protocol
+    }
+
+    /// 加密區塊對齊的資料；協定 2 的輸出為 IV || 密文
+    pub fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>, FidoError> {
+        let iv = match self.protocol {
+            PinUvAuthProtocol::One => [0u8; AES_BLOCK_SIZE],
+            PinUvAuthProtocol::Two => random_bytes()?,
+        };
+        self.encrypt_with_iv(&iv, plaintext)
+    }
+
+    fn encrypt_with_iv(
+        &self,
+        iv: &[u8; AES_BLOCK_SIZE],
+        plaintext: &[u8],
+    ) -> Result<Vec<u8>, FidoError> {
+        if !plaintext.len().is_multiple_of(AES_BLOCK_SIZE) {
+            return Err(FidoError::CommunicationError("加密資料長度未對齊 AES 區塊".to_string()));
+        }
+        let ciphertext = Aes256CbcEnc::new(&self.aes_key.into(), iv.into())
+            .encrypt_padded_vec_mut::<NoPadding>(plaintext);
+        Ok(match self.protocol {
+            PinUvAuthProtocol::One => ciphertext,
+            PinUvAuthProtocol::Two => [iv.as_slice(), &ciphertext].concat(),
+        })
+    }
+
+    /// 解密認證器回傳的資料（例如加密的 pinUvAuthToken）
+    pub fn decrypt(&self, ciphertext: &[u8]) -> Result<Zeroizing<Vec<u8>>, FidoError> {
+        let (iv, body) = match self.protocol {
+            PinUvAuthProtocol::One => ([0u8; AES_BLOCK_SIZE], ciphertext),
+            PinUvAuthProtocol::Two if ciphertext.len() >= AES_BLOCK_SIZE => {
+                let (iv, body) = ciphertext.split_at(AES_BLOCK_SIZE);
+                let mut block = [0u8; AES_BLOCK_SIZE];
+                block.copy_from_slice(iv);
+                (block, body)
+            }
+            PinUvAuthProtocol::Two => ([0u8; AES_BLOCK_SIZE], &[][..]),
+        };
+        if body.is_empty() || !body.len().is_multiple_of(AES_BLOCK_SIZE) {
+            return Err(FidoError::CommunicationError("認證器回傳的加密資料長度無效".to_string()));
+        }
+        Aes256CbcDec::new(&self.aes_key.into(), &iv.into())
+            .decrypt_padded_vec_mut::<NoPadding>(body)
+            .map(Zeroizing::new)
+            .map_err(|_| FidoError::CommunicationError("認證器回傳的加密資料長度無效".to_string()))
+    }
+
+    /// 以共享密鑰計算 pinUvAuthParam（setPIN / changePIN 使用）
+    pub fn authenticate(&self, message: &[u8]) -> Vec<u8> {
+        self.protocol.authenticate(&self.hmac_key, message)
+    }
+}
+
+/// 以 PIN 取得的 pinUvAuthToken；drop 時清除
+#[derive(Debug)]
+pub struct PinUvAuthToken {
+    protocol: PinUvAuthProtocol,
+    token: Zeroizing<Vec<u8>>,
+}
+
+impl PinUvAuthToken {
+    pub fn new(protocol: PinUvAuthProtocol, token: Zeroizing<Vec<u8>>) -> Self {
+        Self { protocol, token }
+    }
+
+    /// 對 `message` 計算 pinUvAuthParam，連同協定版本一起回傳
+    pub fn authenticate(&self, message: &[u8]) -> PinUvAuthParams {
+        PinUvAuthParams {
+            protocol: self.protocol.version(),
+            param: self.protocol.authenticate(&self.token, message),
+        }
+    }
+}
+
+/// pinHashEnc 的明文：LEFT(SHA-256(PIN), 16)
+pub fn pin_hash(pin: &str) -> Zeroizing<Vec<u8>> {
+    Zeroizing::new(Sha256::digest(pin.as_bytes())[..16].to_vec())
+}
+
+/// newPinEnc 的明文：PIN 以 0x00 補滿 64 位元組；呼叫前須以 `validate_pin` 確認長度不超過 63
+pub fn padded_pin(pin: &str) -> Zeroizing<Vec<u8>> {
+    // 預先配置完整容量，避免擴充時在舊緩衝區留下 PIN
+    let mut padded = Zeroizing::new(Vec::with_capacity(PADDED_PIN_LEN));
+    padded.extend_from_slice(pin.as_bytes());
+    padded.resize(PADDED_PIN_LEN, 0);
+    padded
+}
+
+/// 取得密碼學安全的亂數
+pub fn random_bytes<const N: usize>() -> Result<[u8; N], FidoError> {
+    let mut bytes = [0u8; N];
+    getrandom::fill(&mut bytes)
+        .map_err(|e| FidoError::CommunicationError(format!("無法取得亂數: {e}")))?;
+    Ok(bytes)
+}
+
+/// 平台端的暫時 ECDH 金鑰；私鑰 drop 時由 `p256` 清除
+pub struct EphemeralKey {
+    secret: p256::SecretKey,
+    public_key: Vec<u8>,
+}
+
+impl EphemeralKey {
+    /// 產生新的 P-256 金鑰對；亂數落在 [1, n) 之外時重新產生
+    pub fn generate() -> Result<Self, FidoError> {
+        use p256::elliptic_curve::sec1::ToEncodedPoint;
+
+        loop {
+            let bytes = Zeroizing::new(random_bytes::<32>()?.to_vec());
+            if let Ok(secret) = p256::SecretKey::from_slice(&bytes) {
+                let public_key = secret.public_key().to_encoded_point(false).as_bytes().to_vec();
+                return Ok(Self { secret, public_key });
+            }
+        }
+    }
+
+    /// 未壓縮公鑰的 x 與 y 座標
+    pub fn coordinates(&self) -> (&[u8], &[u8]) {
+        (&self.public_key[1..33], &self.public_key[33..65])
+    }
+
+    /// 與認證器的 keyAgreement（未壓縮 SEC1 點）協商共享密鑰
+    pub fn agree(
+        &self,
+        protocol: PinUvAuthProtocol,
+        peer_public_key: &[u8],
+    ) -> Result<SharedSecret, FidoError> {
+        let peer = p256::PublicKey::from_sec1_bytes(peer_public_key).map_err(|_| {
+            FidoError::CommunicationError("認證器回傳的 keyAgreement 無效".to_string())
+        })?;
+        let z = p256::ecdh::diffie_hellman(self.secret.to_nonzero_scalar(), peer.as_affine());
+        Ok(protocol.shared_secret(&(*z.raw_secret_bytes()).into()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::hex_encode;
+
+    // === pinUvAuthProtocol 測試 ===
+
+    #[test]
+    fn test_preferred_protocol() {
+        assert_eq!(PinUvAuthProtocol::preferred(&[1, 2]), PinUvAuthProtocol::Two);
+        assert_eq!(PinUvAuthProtocol::preferred(&[1]), PinUvAuthProtocol::One);
+        assert_eq!(PinUvAuthProtocol::preferred(&[]), PinUvAuthProtocol::One);
+    }
+
+    #[test]
+    fn test_protocol_one_uses_zero_iv_and_truncated_mac() {
+        let secret = PinUvAuthProtocol::One.shared_secret(&[0x11; 32]);
+        let plaintext = [0x22; 16];
+        let ciphertext = secret.encrypt(&plaintext).unwrap();
+        assert_eq!(ciphertext.len(), 16);
+        assert_eq!(ciphertext, secret.encrypt(&plaintext).unwrap());
+        assert_eq!(secret.decrypt(&ciphertext).unwrap().as_slice(), plaintext);
+        assert_eq!(secret.authenticate(b"message").len(), 16);
+    }
+
+    #[test]
+    fn test_protocol_two_prepends_iv_and_uses_full_mac() {
+        let secret = PinUvAuthProtocol::Two.shared_secret(&[0x11; 32]);
+        let plaintext = [0x22; 32];
+        let ciphertext = secret.encrypt_with_iv(&[0x33; 16], &plaintext).unwrap();
+        assert_eq!(ciphertext.len(), 48);
+        assert_eq!(ciphertext[..16], [0x33; 16]);
+        assert_eq!(secret.decrypt(&ciphertext).unwrap().as_slice(), plaintext);
+        assert_eq!(secret.authenticate(b"message").len(), 32);
+    }
+
+    #[test]
+    fn test_protocol_keys_differ_by_version() {
+        let z = [0x44; 32];
+        let one = PinUvAuthProtocol::One.shared_secret(&z);
+        let two = PinUvAuthProtocol::Two.shared_secret(&z);
+        assert_eq!(one.hmac_key, one.aes_key);
+        assert_ne!(two.hmac_key, two.aes_key);
+        assert_ne!(one.hmac_key, two.hmac_key);
+    }
+
+    #[test]
+    fn test_decrypt_rejects_unaligned_data() {
+        let secret = PinUvAuthProtocol::Two.shared_secret(&[0x11; 32]);
+        assert!(secret.decrypt(&[0u8; 16]).is_err());
+        assert!(secret.decrypt(&[0u8; 40]).is_err());
+        assert!(secret.encrypt(&[0u8; 15]).is_err());
+    }
+
+    #[test]
+    fn test_token_authenticate_reports_protocol() {
+        let token = PinUvAuthToken::new(PinUvAuthProtocol::Two, Zeroizing::new(vec![0x55; 32]));
+        let auth = token.authenticate(b"message");
+        assert_eq!(auth.protocol, 2);
+        assert_eq!(auth.param, hmac_sha256(&[0x55; 32], b"message").to_vec());
+    }
+
+    #[test]
+    fn test_pin_hash_is_left_16_of_sha256() {
+        let hash = pin_hash("1234");
+        assert_eq!(
+            hex_encode(&hash),
+            "03ac674216f3e15c761ee1a5e255f067"
+        );
+    }
+
+    #[test]
+    fn test_padded_pin_is_zero_filled_to_64_bytes() {
+        let padded = padded_pin("1234");
+        assert_eq!(padded.len(), 64);
+        assert_eq!(&padded[..4], b"1234");
+        assert!(padded[4..].iter().all(|&b| b == 0));
+        assert_eq!(padded_pin(&"x".repeat(63))[63], 0);
+    }
+
+    #[test]
+    fn test_ephemeral_keys_agree() {
+        let platform = EphemeralKey::generate().unwrap();
+        let authenticator = EphemeralKey::generate().unwrap();
+        let a = platform.agree(PinUvAuthProtocol::Two, &authenticator.public_key).unwrap();
+        let b = authenticator.agree(PinUvAuthProtocol::Two, &platform.public_key).unwrap();
+        assert_eq!(a.hmac_key, b.hmac_key);
+        assert_eq!(a.aes_key, b.aes_key);
+        let (x, y) = platform.coordinates();
+        assert_eq!([&[0x04][..], x, y].concat(), platform.public_key);
+    }
+
+    #[test]
+    fn test_agree_rejects_point_off_curve() {
+        let platform = EphemeralKey::generate().unwrap();
+        let mut peer = EphemeralKey::generate().unwrap().public_key.clone();
+        peer[64] ^= 0x01;
+        assert!(matches!(
+            platform.agree(PinUvAuthProtocol::Two, &peer),
+            Err(FidoError::CommunicationError(_))
+        ));
+    }
+}
@@ -9,11 +9,102 @@ pub struct FidoDeviceInfo {
     pub versions: Vec<String>,
     pub extensions: Vec<String>,
     pub aaguid: String,
+    /// 依 AAGUID 查得的型號名稱（未知的 AAGUID 為 None）
+    #[serde(default)]
+    pub model_name: Option<String>,
     pub firmware_version: String,
     pub serial_number: Option<String>,
     pub pin_set: bool,
     pub pin_retries: u8,
     pub options: HashMap<String, bool>,
+    #[serde(default)]
+    pub capabilities: FidoCapabilities,
+    /// 目前的 PIN 最小長度（GetInfo 0x0D，裝置未回報時為 None）
+    #[serde(default)]
+    pub min_pin_length: Option<u8>,
+    /// 是否要求下次使用前變更 PIN（GetInfo 0x0C）
+    #[serde(default)]
+    pub force_pin_change: bool,
+    /// 支援的 pinUvAuthProtocol 版本，依裝置偏好排序（GetInfo 0x06）
+    #[serde(default)]
+    pub pin_uv_auth_protocols: Vec<u8>,
+}
+
+impl FidoDeviceInfo {
+    /// option 是否存在且為 true
+    pub fn option_enabled(&self, name: &str) -> bool {
+        self.options.get(name).copied().unwrap_or(false)
+    }
+
+    /// 是否支援指定的 extension
+    pub fn supports_extension(&self, name: &str) -> bool {
+        self.extensions.iter().any(|ext| ext == name)
+    }
+
+    /// 是否支援 authenticatorCredentialManagement（含 CTAP 2.1 預覽版）
+    pub fn supports_cred_mgmt(&self) -> bool {
+        self.option_enabled("credMgmt") || self.option_enabled("credentialMgmtPreview")
+    }
+
+    /// 是否支援 authenticatorConfig
+    pub fn supports_authenticator_config(&self) -> bool {
+        self.option_enabled("authnrCfg")
+    }
+
+    /// 是否支援 authenticatorLargeBlobs
+    pub fn supports_large_blobs(&self) -> bool {
+        self.option_enabled("largeBlobs")
+    }
+
+    /// 是否支援 CTAP 2.1 的 getPinUvAuthToken（含 permissions 參數）
+    pub fn supports_pin_uv_auth_token(&self) -> bool {
+        self.option_enabled("pinUvAuthToken")
+    }
+
+    /// 是否具備已設定的內建使用者驗證（指紋等），可用來取得 pinUvAuthToken
+    pub fn supports_builtin_uv(&self) -> bool {
+        self.option_enabled("uv") && self.supports_pin_uv_auth_token()
+    }
+
+    /// 是否支援 hmac-secret extension
+    pub fn supports_hmac_secret(&self) -> bool {
+        self.supports_extension("hmac-secret")
+    }
+
+    /// 是否支援 credProtect extension
+    pub fn supports_cred_protect(&self) -> bool {
+        self.supports_extension("credProtect")
+    }
+
+    /// 依 options 與 extensions 計算功能旗標
+    pub fn compute_capabilities(&self) -> FidoCapabilities {
+        FidoCapabilities {
+            client_pin: self.options.contains_key("clientPin"),
+            cred_mgmt: self.supports_cred_mgmt(),
+            authenticator_config: self.supports_authenticator_config(),
+            large_blobs: self.supports_large_blobs(),
+            hmac_secret: self.supports_hmac_secret(),
+            cred_protect: self.supports_cred_protect(),
+        }
+    }
+}
+
+/// 目前的 PIN 最小長度設定
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct MinPinLengthInfo {
+    pub min_pin_length: u8,
+    pub force_pin_change: bool,
+}
+
+/// 認證器功能旗標（供前端判斷是否顯示對應功能）
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct FidoCapabilities {
+    pub client_pin: bool,
+    pub cred_mgmt: bool,
+    pub authenticator_config: bool,
+    pub large_blobs: bool,
+    pub hmac_secret: bool,
+    pub cred_protect: bool,
 }
 
 // === FIDO 憑證 ===
@@ -27,6 +118,89 @@ pub struct FidoCredential {
     pub user_name: Option<String>,
     pub user_display_name: Option<String>,
     pub creation_time: Option<u64>,
+    /// credProtect 等級：1=userVerificationOptional, 2=...WithCredentialIDList, 3=Required
+    #[serde(default)]
+    pub cred_protect: Option<u8>,
+    /// 憑證公鑰（未壓縮 SEC1 點，僅 ES256 憑證會帶入）
+    #[serde(default)]
+    pub public_key: Option<Vec<u8>>,
+}
+
+/// credentialManagement getCredsMetadata 回應：常駐憑證使用量
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CredsMetadata {
+    pub existing_resident_credentials_count: u32,
+    pub max_possible_remaining_resident_credentials_count: u32,
+}
+
+impl CredsMetadata {
+    /// 常駐憑證總容量（已使用 + 剩餘）
+    pub fn total_slots(&self) -> u32 {
+        self.existing_resident_credentials_count
+            .saturating_add(self.max_possible_remaining_resident_credentials_count)
+    }
+}
+
+/// OATH 憑證槽使用量
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct OathSlotInfo {
+    pub used: u32,
+    pub total: u32,
+}
+
+impl OathSlotInfo {
+    /// 剩餘可新增的憑證數
+    pub fn remaining(&self) -> u32 {
+        self.total.saturating_sub(self.used)
+    }
+}
+
+/// 測試註冊結果：以固定的測試 RP 建立非駐留憑證並完成一次簽署
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SelfTestResult {
+    pub rp_id: String,
+    /// attestation 格式（如 "packed"、"none"）
+    pub attestation_format: String,
+    /// 測試憑證 ID（hex）
+    pub credential_id: String,
+    /// GetAssertion 回傳的簽署計數
+    pub sign_count: u32,
+}
+
+/// 以既有憑證執行 GetAssertion 並驗證簽章的結果
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AssertionCheck {
+    pub rp_id: String,
+    /// 憑證 ID（hex）
+    pub credential_id: String,
+    pub sign_count: u32,
+    /// authenticatorData 是否設定 UV 旗標
+    pub user_verified: bool,
+    /// 簽章是否能以憑證公鑰驗證
+    pub signature_valid: bool,
+}
+
+/// 重設準備階段的結果；`token` 需在有效期限內交給 `reset_confirm` 才會實際重設
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ResetPreparation {
+    pub token: String,
+    /// 重設後將遺失的常駐憑證數；未提供 PIN 或查詢失敗時為 None
+    pub credentials_to_lose: Option<u32>,
+    pub expires_in_secs: u64,
+}
+
+/// clientPIN getKeyAgreement 回傳的認證器 COSE 公鑰（座標以 hex 表示）
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct KeyAgreementInfo {
+    pub pin_uv_auth_protocol: u8,
+    /// COSE kty（2 = EC2）
+    pub kty: i64,
+    /// COSE alg（-25 = ECDH-ES+HKDF-256）
+    pub alg: i64,
+    /// COSE crv（1 = P-256）
+    pub crv: i64,
+    pub x: String,
+    pub y: String,
 }
 
 // === OATH 相關 ===
@@ -38,6 +212,34 @@ pub enum OathType {
     Hotp,
 }
 
+/// OATH HMAC 演算法
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum OathAlgorithm {
+    #[default]
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+impl OathAlgorithm {
+    /// 由 YKOATH 類型位元組的低四位元解析（0x01=SHA1, 0x02=SHA256, 0x03=SHA512）
+    pub fn from_type_byte(byte: u8) -> Option<Self> {
+        match byte & 0x0F {
+            0x01 => Some(OathAlgorithm::Sha1),
+            0x02 => Some(OathAlgorithm::Sha256),
+            0x03 => Some(OathAlgorithm::Sha512),
+            _ => None,
+        }
+    }
+}
+
+/// OTP 預設位數
+pub const DEFAULT_OATH_DIGITS: u8 = 6;
+
+fn default_oath_digits() -> u8 {
+    DEFAULT_OATH_DIGITS
+}
+
 /// OATH 憑證資訊
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OathCredential {
@@ -46,6 +248,10 @@ pub struct OathCredential {
     pub account: String,
     pub oath_type: OathType,
     pub period: Option<u32>,
+    #[serde(default = "default_oath_digits")]
+    pub digits: u8,
+    #[serde(default)]
+    pub algorithm: OathAlgorithm,
 }
 
 /// OATH 憑證新增參數
@@ -63,13 +269,25 @@ pub struct OathCredentialParams {
 // === CTAP 協定 ===
 
 /// ClientPin 子指令
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub enum ClientPinSubCommand {
     GetRetries,
     GetKeyAgreement,
     SetPin,
     ChangePin,
     GetPinToken,
+    GetPinUvAuthTokenUsingUvWithPermissions,
+    GetPinUvAuthTokenUsingPinWithPermissions,
+}
+
+/// 取得 pinUvAuthToken 時偏好的使用者驗證方式
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum UvMethod {
+    /// 輸入 PIN
+    #[default]
+    Pin,
+    /// 裝置內建驗證（指紋等）；裝置不支援時改用 PIN
+    Uv,
 }
 
 /// 憑證管理子指令
@@ -83,6 +301,13 @@ pub enum CredMgmtSubCommand {
     DeleteCredential,
 }
 
+impl CredMgmtSubCommand {
+    /// 憑證管理只需要 cm 權限
+    pub fn permissions(&self) -> PinUvPermissions {
+        PinUvPermissions::CREDENTIAL_MANAGEMENT
+    }
+}
+
 /// 認證器組態子指令
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum AuthConfigSubCommand {
@@ -91,6 +316,82 @@ pub enum AuthConfigSubCommand {
     SetMinPinLength,
 }
 
+impl AuthConfigSubCommand {
+    /// 認證器組態只需要 acfg 權限
+    pub fn permissions(&self) -> PinUvPermissions {
+        PinUvPermissions::AUTHENTICATOR_CONFIG
+    }
+}
+
+/// pinUvAuthToken 的權限位元（CTAP 2.1 §6.5.5.7），可用 `|` 組合
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PinUvPermissions(pub u8);
+
+impl PinUvPermissions {
+    /// mc：makeCredential
+    pub const MAKE_CREDENTIAL: Self = Self(0x01);
+    /// ga：getAssertion
+    pub const GET_ASSERTION: Self = Self(0x02);
+    /// cm：credentialManagement
+    pub const CREDENTIAL_MANAGEMENT: Self = Self(0x04);
+    /// be：bioEnrollment
+    pub const BIO_ENROLLMENT: Self = Self(0x08);
+    /// lbw：largeBlobWrite
+    pub const LARGE_BLOB_WRITE: Self = Self(0x10);
+    /// acfg：authenticatorConfig
+    pub const AUTHENTICATOR_CONFIG: Self = Self(0x20);
+
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl std::ops::BitOr for PinUvPermissions {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+/// CTAPHID_KEEPALIVE 回報的裝置狀態
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum KeepaliveStatus {
+    /// 裝置處理中
+    Processing,
+    /// 等待使用者觸碰
+    UpNeeded,
+    Unknown(u8),
+}
+
+impl From<u8> for KeepaliveStatus {
+    fn from(value: u8) -> Self {
+        match value {
+            1 => KeepaliveStatus::Processing,
+            2 => KeepaliveStatus::UpNeeded,
+            other => KeepaliveStatus::Unknown(other),
+        }
+    }
+}
+
+/// authenticatorConfig setMinPINLength 參數
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct SetMinPinLengthParams {
+    /// 新的最小 PIN 長度（key 0x01）；`None` 時不變更長度
+    pub new_min_pin_length: Option<u8>,
+    /// 可讀取最小 PIN 長度的 RP ID 列表（key 0x02）
+    pub min_pin_length_rp_ids: Vec<String>,
+    /// 是否要求使用者於下次使用前變更 PIN（key 0x03）
+    pub force_change_pin: bool,
+}
+
+/// pinUvAuthProtocol 與 pinUvAuthParam
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PinUvAuthParams {
+    pub protocol: u8,
+    pub param: Vec<u8>,
+}
+
 /// CTAP 指令列舉
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum CtapCommand {
@@ -0,0 +1,82 @@
+use crate::types::{DeviceType, FirmwareStatus, Version};
+
+/// 各裝置建議的最低韌體版本；新韌體補上重要功能或修正時在此調整
+pub const MINIMUM_RECOMMENDED: &[(DeviceType, Version)] = &[
+    (DeviceType::PicoHsm, Version::new(5, 0)),
+    (DeviceType::PicoFido, Version::new(5, 0)),
+];
+
+/// 取得指定裝置類型的建議最低版本
+pub fn minimum_recommended(device_type: &DeviceType) -> Version {
+    MINIMUM_RECOMMENDED
+        .iter()
+        .find(|(kind, _)| kind == device_type)
+        .map(|&(_, version)| version)
+        .unwrap_or(Version::new(0, 0))
+}
+
+/// 比較目前版本與建議最低版本；版本字串無法解析時回傳 None
+pub fn status(device_type: &DeviceType, current: &str) -> Option<FirmwareStatus> {
+    Some(status_against(Version::parse(current)?, minimum_recommended(device_type)))
+}
+
+/// 以指定的最低版本比較
+pub fn status_against(current: Version, minimum_recommended: Version) -> FirmwareStatus {
+    FirmwareStatus {
+        current,
+        minimum_recommended,
+        up_to_date: current >= minimum_recommended,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // === 版本解析測試 ===
+
+    #[test]
+    fn test_version_parse_formats() {
+        assert_eq!(Version::parse("5.2"), Some(Version::new(5, 2)));
+        assert_eq!(Version::parse("v6.0"), Some(Version::new(6, 0)));
+        assert_eq!(Version::parse(" 4.10.1 "), Some(Version::new(4, 10)));
+        assert_eq!(Version::parse("unknown"), None);
+        assert_eq!(Version::parse("5"), None);
+        assert_eq!(Version::parse("5.x"), None);
+    }
+
+    #[test]
+    fn test_version_display() {
+        assert_eq!(Version::new(5, 12).to_string(), "5.12");
+    }
+
+    // === 版本比較測試 ===
+
+    #[test]
+    fn test_status_against_sample_versions() {
+        let minimum = Version::new(5, 2);
+        for (current, up_to_date) in [
+            (Version::new(4, 9), false),
+            (Version::new(5, 0), false),
+            (Version::new(5, 1), false),
+            (Version::new(5, 2), true),
+            (Version::new(5, 10), true),
+            (Version::new(6, 0), true),
+        ] {
+            let status = status_against(current, minimum);
+            assert_eq!(status.up_to_date, up_to_date, "{current}");
+            assert_eq!(status.minimum_recommended, minimum);
+        }
+    }
+
+    #[test]
+    fn test_status_uses_table_per_device() {
+        for (kind, minimum) in MINIMUM_RECOMMENDED {
+            let status = status(kind, &minimum.to_string()).unwrap();
+            assert!(status.up_to_date);
+            assert_eq!(status.current, *minimum);
+        }
+        assert!(status(&DeviceType::PicoHsm, "1.0").is_some_and(|s| !s.up_to_date));
+        assert!(status(&DeviceType::PicoFido, "unknown").is_none());
+    }
+}
@@ -1,6 +1,13 @@
 use crate::error::{ApduError, HsmError};
 use crate::hsm::types::{ApduCommand, ApduResponse};
 
+/// 標準 APDU 單次可攜帶的最大資料長度
+const SHORT_MAX_DATA: usize = 255;
+/// 標準 APDU 可要求的最大回應長度（Le=0x00）
+const SHORT_MAX_LE: u16 = 256;
+/// CLA 的指令鏈接位元（ISO 7816-4 §5.1.1，非最後一段時設定）
+const CLA_CHAINING: u8 = 0x10;
+
 /// APDU 編解碼器 trait
 pub trait ApduCodec {
     /// 將 ApduCommand 編碼為位元組序列（支援標準與擴充 APDU）
@@ -102,6 +109,8 @@ impl ApduCodec for ApduCodecImpl {
             (0x6A, 0x82) => Some(HsmError::KeyNotFound(0)),
             // 參考資料未找到
             (0x6A, 0x88) => Some(HsmError::KeyNotFound(0)),
+            // 指令或類別不支援：韌體沒有此功能
+            (0x6D, 0x00) | (0x6E, 0x00) => Some(HsmError::NotSupported),
             // 其他錯誤
             _ => Some(HsmError::StatusError(sw1, sw2)),
         }
@@ -109,6 +118,34 @@ impl ApduCodec for ApduCodecImpl {
 }
 
 impl ApduCodecImpl {
+    /// 以標準 APDU 與指令鏈接編碼：資料依 255 位元組分段，除最後一段外設定 CLA 鏈接位元，
+    /// Le 只附在最後一段且上限為 256，較長的回應由 61 XX (GET RESPONSE) 取回
+    pub fn encode_chained(&self, cmd: &ApduCommand) -> Vec<Vec<u8>> {
+        let le = cmd.le.map(|le| if le == 0 { SHORT_MAX_LE } else { le.min(SHORT_MAX_LE) });
+        let data = cmd.data.as_deref().unwrap_or_default();
+        let chunks: Vec<&[u8]> = if data.is_empty() {
+            vec![&[]]
+        } else {
+            data.chunks(SHORT_MAX_DATA).collect()
+        };
+        let last = chunks.len() - 1;
+        chunks
+            .into_iter()
+            .enumerate()
+            .map(|(i, chunk)| {
+                let segment = crate::secret::Zeroizing::new(ApduCommand {
+                    cla: if i < last { cmd.cla | CLA_CHAINING } else { cmd.cla },
+                    ins: cmd.ins,
+                    p1: cmd.p1,
+                    p2: cmd.p2,
+                    data: Some(chunk.to_vec()),
+                    le: if i == last { le } else { None },
+                });
+                self.encode_apdu(&segment)
+            })
+            .collect()
+    }
+
     /// 擴充 APDU 編碼（data > 255 bytes 或 Le > 256）
     fn encode_extended(&self, cmd: &ApduCommand, has_data: bool, has_le: bool) -> Vec<u8> {
         let mut buf = vec![cmd.cla, cmd.ins, cmd.p1, cmd.p2];
@@ -152,6 +189,77 @@ impl ApduCodecImpl {
     }
 }
 
+/// 將擴充 APDU 位元組還原為 ApduCommand；標準 APDU 或格式不符時回傳 None
+pub fn parse_extended(raw: &[u8]) -> Option<ApduCommand> {
+    let (&[cla, ins, p1, p2, 0x00, hi, lo], rest) = raw.split_first_chunk::<7>()? else {
+        return None;
+    };
+    let command = |data: Option<Vec<u8>>, le: Option<u16>| {
+        Some(ApduCommand { cla, ins, p1, p2, data, le })
+    };
+    let len = u16::from_be_bytes([hi, lo]);
+    match rest.len() {
+        // Extended Case 2
+        0 => command(None, Some(len)),
+        // Extended Case 3 / Case 4
+        n if n == len as usize && len > 0 => command(Some(rest.to_vec()), None),
+        n if n == len as usize + 2 && len > 0 => {
+            let (data, le) = rest.split_at(len as usize);
+            command(Some(data.to_vec()), Some(u16::from_be_bytes([le[0], le[1]])))
+        }
+        _ => None,
+    }
+}
+
+/// 取出 ATR 的歷史位元組（ISO 7816-3 §8.2）；ATR 不完整時回傳 None
+pub fn atr_historical_bytes(atr: &[u8]) -> Option<&[u8]> {
+    let t0 = *atr.get(1)?;
+    let count = (t0 & 0x0F) as usize;
+    let mut pos = 2;
+    let mut indicator = t0 >> 4;
+    loop {
+        // TAi、TBi、TCi、TDi 是否存在由上一個 Y 的各位元決定
+        pos += (indicator & 0x07).count_ones() as usize;
+        if indicator & 0x08 == 0 {
+            break;
+        }
+        indicator = *atr.get(pos)? >> 4;
+        pos += 1;
+    }
+    atr.get(pos..pos + count)
+}
+
+/// 由歷史位元組中的 card capabilities（compact-TLV tag 7）判斷卡片是否支援擴充 APDU；
+/// 第三個位元組 b7 為「支援擴充 Lc/Le」。沒有相關資訊時回傳 None
+pub fn extended_length_from_atr(atr: &[u8]) -> Option<bool> {
+    let historical = atr_historical_bytes(atr)?;
+    let objects = match historical.split_first()? {
+        (0x80, rest) => rest,
+        // 類別 0x00 的最後三個位元組為狀態資訊，不屬於 compact-TLV
+        (0x00, rest) => rest.get(..rest.len().checked_sub(3)?)?,
+        _ => return None,
+    };
+    let mut pos = 0;
+    while let Some(&header) = objects.get(pos) {
+        let len = (header & 0x0F) as usize;
+        let value = objects.get(pos + 1..pos + 1 + len)?;
+        if header >> 4 == 0x7 {
+            return value.get(2).map(|b| b & 0x40 != 0);
+        }
+        pos += 1 + len;
+    }
+    None
+}
+
+/// 卡片不支援擴充 APDU 時，將擴充 APDU 改為鏈接的標準 APDU；不需轉換時回傳 None
+pub fn short_apdu_fallback(raw: &[u8], extended_supported: bool) -> Option<Vec<Vec<u8>>> {
+    if extended_supported {
+        return None;
+    }
+    let cmd = crate::secret::Zeroizing::new(parse_extended(raw)?);
+    Some(ApduCodecImpl::new().encode_chained(&cmd))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -292,6 +400,94 @@ mod tests {
         assert_eq!(encoded[6], 0xF4); // Le low
     }
 
+    // === 指令鏈接與擴充 APDU 支援測試 ===
+
+    /// SmartCard-HSM 的 ATR，card capabilities 為 73 80 21 40（支援擴充 Lc/Le）
+    const SC_HSM_ATR: [u8; 24] = [
+        0x3B, 0xFE, 0x18, 0x00, 0x00, 0x81, 0x31, 0xFE, 0x45, 0x80, 0x31, 0x81, 0x54, 0x48,
+        0x53, 0x4D, 0x31, 0x73, 0x80, 0x21, 0x40, 0x81, 0x07, 0xFA,
+    ];
+
+    #[test]
+    fn test_encode_chained_splits_data_and_keeps_le_on_last() {
+        let data: Vec<u8> = (0..600).map(|i| i as u8).collect();
+        let cmd = ApduCommand {
+            cla: 0x80,
+            ins: 0x74,
+            p1: 0x01,
+            p2: 0x00,
+            data: Some(data.clone()),
+            le: Some(1024),
+        };
+        let segments = codec().encode_chained(&cmd);
+        assert_eq!(segments.len(), 3);
+        assert_eq!(&segments[0][..5], &[0x90, 0x74, 0x01, 0x00, 0xFF]);
+        assert_eq!(&segments[1][..5], &[0x90, 0x74, 0x01, 0x00, 0xFF]);
+        assert_eq!(&segments[2][..5], &[0x80, 0x74, 0x01, 0x00, 90]);
+        assert_eq!(segments[0].len(), 5 + 255);
+        // 最後一段的 Le 以 0x00 要求 256 位元組
+        assert_eq!(segments[2].len(), 5 + 90 + 1);
+        assert_eq!(segments[2].last(), Some(&0x00));
+        let joined: Vec<u8> =
+            segments.iter().flat_map(|s| s[5..5 + s[4] as usize].to_vec()).collect();
+        assert_eq!(joined, data);
+    }
+
+    #[test]
+    fn test_encode_chained_large_le_only_becomes_single_short_apdu() {
+        let cmd = ApduCommand {
+            cla: 0x00,
+            ins: 0xB1,
+            p1: 0xCE,
+            p2: 0x01,
+            data: None,
+            le: Some(0),
+        };
+        assert_eq!(codec().encode_chained(&cmd), vec![vec![0x00, 0xB1, 0xCE, 0x01, 0x00]]);
+    }
+
+    #[test]
+    fn test_parse_extended_round_trips_encode() {
+        let cases = [(None, Some(500)), (Some(vec![0x5A; 300]), None), (Some(vec![0x5A; 300]), Some(0))];
+        for (data, le) in cases {
+            let cmd = ApduCommand { cla: 0x00, ins: 0xDA, p1: 0x01, p2: 0x02, data, le };
+            let parsed = parse_extended(&codec().encode_apdu(&cmd)).unwrap();
+            assert_eq!((parsed.data, parsed.le), (cmd.data, cmd.le));
+        }
+        assert!(parse_extended(&[0x00, 0xA4, 0x04, 0x00, 0x02, 0xA0, 0x00]).is_none());
+        assert!(parse_extended(&[0x00, 0xDA, 0x01, 0x02, 0x00, 0x01, 0x2C, 0x5A]).is_none());
+    }
+
+    #[test]
+    fn test_extended_length_from_atr() {
+        assert_eq!(atr_historical_bytes(&SC_HSM_ATR).map(<[u8]>::len), Some(14));
+        assert_eq!(extended_length_from_atr(&SC_HSM_ATR), Some(true));
+
+        let mut short_only = SC_HSM_ATR;
+        short_only[20] = 0x00;
+        assert_eq!(extended_length_from_atr(&short_only), Some(false));
+
+        // 沒有 card capabilities 或 ATR 被截斷
+        assert_eq!(extended_length_from_atr(&[0x3B, 0x02, 0x80, 0x31]), None);
+        assert_eq!(extended_length_from_atr(&SC_HSM_ATR[..10]), None);
+    }
+
+    #[test]
+    fn test_short_apdu_fallback_decision() {
+        let extended = codec().encode_apdu(&ApduCommand {
+            cla: 0x00,
+            ins: 0xD6,
+            p1: 0x00,
+            p2: 0x00,
+            data: Some(vec![0xAB; 300]),
+            le: None,
+        });
+        assert!(short_apdu_fallback(&extended, true).is_none());
+        assert_eq!(short_apdu_fallback(&extended, false).map(|s| s.len()), Some(2));
+        // 標準 APDU 不需轉換
+        assert!(short_apdu_fallback(&[0x00, 0xB0, 0x00, 0x00, 0x10], false).is_none());
+    }
+
     // === decode_apdu_response 測試 ===
 
     #[test]
@@ -380,6 +576,18 @@ mod tests {
         assert!(matches!(err, HsmError::KeyNotFound(_)));
     }
 
+    #[test]
+    fn test_status_ins_not_supported() {
+        let err = codec().status_to_error(0x6D, 0x00).unwrap();
+        assert!(matches!(err, HsmError::NotSupported));
+    }
+
+    #[test]
+    fn test_status_cla_not_supported() {
+        let err = codec().status_to_error(0x6E, 0x00).unwrap();
+        assert!(matches!(err, HsmError::NotSupported));
+    }
+
     #[test]
     fn test_status_unknown_error() {
         let err = codec().status_to_error(0x6F, 0x00).unwrap();
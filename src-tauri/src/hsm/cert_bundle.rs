@@ -0,0 +1,205 @@
+use crate::error::HsmError;
+use crate::hsm::types::CertBundleFormat;
+use crate::util::{base64_decode, base64_encode};
+
+/// PKCS#7 signedData OID (1.2.840.113549.1.7.2)
+const OID_SIGNED_DATA: &[u8] = &[0x2A, 0x86, 0x48, 0x86, 0xF7, 0x0D, 0x01, 0x07, 0x02];
+/// PKCS#7 data OID (1.2.840.113549.1.7.1)
+const OID_DATA: &[u8] = &[0x2A, 0x86, 0x48, 0x86, 0xF7, 0x0D, 0x01, 0x07, 0x01];
+
+/// 依指定格式將憑證鏈（DER，EE 在前）打包
+pub fn bundle(certs: &[Vec<u8>], format: CertBundleFormat) -> Vec<u8> {
+    match format {
+        CertBundleFormat::Pkcs7 => pkcs7_bundle(certs),
+        CertBundleFormat::Pem => pem_bundle(certs).into_bytes(),
+    }
+}
+
+/// 打包為 degenerate PKCS#7 SignedData（僅含憑證，無簽章），即 .p7b 格式
+pub fn pkcs7_bundle(certs: &[Vec<u8>]) -> Vec<u8> {
+    let cert_bytes: Vec<u8> = certs.concat();
+
+    let mut signed_data = der_tlv(0x02, &[0x01]); // version
+    signed_data.extend(der_tlv(0x31, &[])); // digestAlgorithms
+    signed_data.extend(der_tlv(0x30, &der_tlv(0x06, OID_DATA))); // contentInfo
+    signed_data.extend(der_tlv(0xA0, &cert_bytes)); // [0] IMPLICIT certificates
+    signed_data.extend(der_tlv(0x31, &[])); // signerInfos
+
+    let mut content_info = der_tlv(0x06, OID_SIGNED_DATA);
+    content_info.extend(der_tlv(0xA0, &der_tlv(0x30, &signed_data)));
+    der_tlv(0x30, &content_info)
+}
+
+/// 打包為串接的 PEM 憑證
+pub fn pem_bundle(certs: &[Vec<u8>]) -> String {
+    let mut out = String::new();
+    for cert in certs {
+        out.push_str("-----BEGIN CERTIFICATE-----\n");
+        let encoded = base64_encode(cert);
+        for line in encoded.as_bytes().chunks(64) {
+            out.push_str(&String::from_utf8_lossy(line));
+            out.push('\n');
+        }
+        out.push_str("-----END CERTIFICATE-----\n");
+    }
+    out
+}
+
+/// 自動判斷匯入的憑證格式並回傳 DER：以 `-----BEGIN` 開頭視為 PEM 並取第一個區塊，
+/// 以 SEQUENCE (0x30) 開頭視為 DER，其餘回傳格式錯誤
+pub fn decode_certificate(data: &[u8]) -> Result<Vec<u8>, HsmError> {
+    let invalid = |reason: &str| HsmError::InvalidCertificate(reason.to_string());
+    let start = data.iter().position(|b| !b.is_ascii_whitespace()).unwrap_or(data.len());
+    let trimmed = &data[start..];
+
+    if trimmed.starts_with(b"-----BEGIN") {
+        let text = std::str::from_utf8(trimmed).map_err(|_| invalid("PEM 含非 ASCII 字元"))?;
+        let mut body = String::new();
+        let mut ended = false;
+        for line in text.lines().skip(1) {
+            if line.starts_with("-----END") {
+                ended = true;
+                break;
+            }
+            body.push_str(line);
+        }
+        if !ended {
+            return Err(invalid("PEM 缺少 END 標記"));
+        }
+        let der = base64_decode(&body).map_err(|e| invalid(&e.to_string()))?;
+        return match der.first() {
+            Some(0x30) => Ok(der),
+            _ => Err(invalid("PEM 內容不是 DER 憑證")),
+        };
+    }
+
+    match data.first() {
+        Some(0x30) => Ok(data.to_vec()),
+        _ => Err(invalid("需為 PEM 或 DER 格式")),
+    }
+}
+
+/// 組出 DER TLV（definite length）
+fn der_tlv(tag: u8, value: &[u8]) -> Vec<u8> {
+    let mut out = vec![tag];
+    let len = value.len();
+    if len < 0x80 {
+        out.push(len as u8);
+    } else {
+        let len_bytes: Vec<u8> = len
+            .to_be_bytes()
+            .into_iter()
+            .skip_while(|&b| b == 0)
+            .collect();
+        out.push(0x80 | len_bytes.len() as u8);
+        out.extend(len_bytes);
+    }
+    out.extend_from_slice(value);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 兩張最小的 DER 結構作為範例憑證
+    fn sample_ee() -> Vec<u8> {
+        vec![0x30, 0x03, 0x02, 0x01, 0x01]
+    }
+
+    fn sample_ca() -> Vec<u8> {
+        vec![0x30, 0x03, 0x02, 0x01, 0x02]
+    }
+
+    // === DER 編碼測試 ===
+
+    #[test]
+    fn test_der_tlv_short_and_long_length() {
+        assert_eq!(der_tlv(0x04, &[0xAA]), vec![0x04, 0x01, 0xAA]);
+        let long = der_tlv(0x04, &[0u8; 0x80]);
+        assert_eq!(&long[..3], &[0x04, 0x81, 0x80]);
+        let longer = der_tlv(0x04, &[0u8; 0x0123]);
+        assert_eq!(&longer[..4], &[0x04, 0x82, 0x01, 0x23]);
+    }
+
+    // === 憑證格式判斷測試 ===
+
+    #[test]
+    fn test_decode_certificate_pem() {
+        let pem = pem_bundle(&[sample_ee(), sample_ca()]);
+        assert_eq!(decode_certificate(pem.as_bytes()).unwrap(), sample_ee());
+        let crlf = format!("\r\n{}", pem.replace('\n', "\r\n"));
+        assert_eq!(decode_certificate(crlf.as_bytes()).unwrap(), sample_ee());
+    }
+
+    #[test]
+    fn test_decode_certificate_der_passes_through() {
+        assert_eq!(decode_certificate(&sample_ee()).unwrap(), sample_ee());
+    }
+
+    #[test]
+    fn test_decode_certificate_rejects_unknown_format() {
+        for data in [
+            &b"hello"[..],
+            &[0x7F, 0x21, 0x00],
+            b"-----BEGIN CERTIFICATE-----\nMAMCAQE=\n",
+            b"-----BEGIN CERTIFICATE-----\nMAM*AQE=\n-----END CERTIFICATE-----\n",
+            // 解碼後不是 SEQUENCE
+            b"-----BEGIN CERTIFICATE-----\nBAEA\n-----END CERTIFICATE-----\n",
+        ] {
+            assert!(matches!(decode_certificate(data), Err(HsmError::InvalidCertificate(_))));
+        }
+    }
+
+    // === 憑證鏈打包測試 ===
+
+    #[test]
+    fn test_pkcs7_bundle_two_certs() {
+        let bundle = pkcs7_bundle(&[sample_ee(), sample_ca()]);
+        let expected: Vec<u8> = [
+            &[0x30, 0x2F][..],
+            &[0x06, 0x09],
+            OID_SIGNED_DATA,
+            &[0xA0, 0x22, 0x30, 0x20],
+            &[0x02, 0x01, 0x01],
+            &[0x31, 0x00],
+            &[0x30, 0x0B, 0x06, 0x09],
+            OID_DATA,
+            &[0xA0, 0x0A],
+            &sample_ee(),
+            &sample_ca(),
+            &[0x31, 0x00],
+        ]
+        .concat();
+        assert_eq!(bundle, expected);
+    }
+
+    #[test]
+    fn test_pem_bundle_two_certs() {
+        let pem = pem_bundle(&[sample_ee(), sample_ca()]);
+        assert_eq!(
+            pem,
+            "-----BEGIN CERTIFICATE-----\nMAMCAQE=\n-----END CERTIFICATE-----\n\
+             -----BEGIN CERTIFICATE-----\nMAMCAQI=\n-----END CERTIFICATE-----\n"
+        );
+    }
+
+    #[test]
+    fn test_pem_bundle_wraps_lines_at_64() {
+        let pem = pem_bundle(&[vec![0u8; 60]]);
+        let lines: Vec<&str> = pem.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[1].len(), 64);
+        assert_eq!(lines[2], "A".repeat(16));
+    }
+
+    #[test]
+    fn test_bundle_dispatches_format() {
+        let certs = [sample_ee()];
+        assert_eq!(bundle(&certs, CertBundleFormat::Pkcs7), pkcs7_bundle(&certs));
+        assert_eq!(
+            bundle(&certs, CertBundleFormat::Pem),
+            pem_bundle(&certs).into_bytes()
+        );
+    }
+}
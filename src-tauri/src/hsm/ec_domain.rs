@@ -0,0 +1,246 @@
+use crate::hsm::tlv::push_tlv;
+use crate::util::hex_decode;
+
+/// id-TA-ECDSA-SHA-256 (0.4.0.127.0.7.2.2.2.2.3)：SC-HSM 以此 OID 標示 EC 公鑰範本
+const OID_TA_ECDSA_SHA256: &[u8] = &[0x04, 0x00, 0x7F, 0x00, 0x07, 0x02, 0x02, 0x02, 0x02, 0x03];
+
+/// CV 請求的 Certificate Profile Identifier（固定為 0）
+const CPI: u8 = 0x00;
+
+/// 曲線的 domain parameters（十六進位、big-endian，長度皆補齊至欄位大小）；所有曲線的 cofactor 皆為 1
+struct EcDomain {
+    name: &'static str,
+    prime: &'static str,
+    a: &'static str,
+    b: &'static str,
+    /// 未壓縮基點（04 || x || y）
+    generator: &'static str,
+    order: &'static str,
+}
+
+const EC_DOMAINS: &[EcDomain] = &[
+    EcDomain {
+        name: "secp256r1",
+        prime: "ffffffff00000001000000000000000000000000ffffffffffffffffffffffff",
+        a: "ffffffff00000001000000000000000000000000fffffffffffffffffffffffc",
+        b: "5ac635d8aa3a93e7b3ebbd55769886bc651d06b0cc53b0f63bce3c3e27d2604b",
+        generator: "046b17d1f2e12c4247f8bce6e563a440f277037d812deb33a0f4a13945d898c2\
+            964fe342e2fe1a7f9b8ee7eb4a7c0f9e162bce33576b315ececbb6406837bf51\
+            f5",
+        order: "ffffffff00000000ffffffffffffffffbce6faada7179e84f3b9cac2fc632551",
+    },
+    EcDomain {
+        name: "secp384r1",
+        prime: "fffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffe\
+            ffffffff0000000000000000ffffffff",
+        a: "fffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffe\
+            ffffffff0000000000000000fffffffc",
+        b: "b3312fa7e23ee7e4988e056be3f82d19181d9c6efe8141120314088f5013875a\
+            c656398d8a2ed19d2a85c8edd3ec2aef",
+        generator: "04aa87ca22be8b05378eb1c71ef320ad746e1d3b628ba79b9859f741e082542a\
+            385502f25dbf55296c3a545e3872760ab73617de4a96262c6f5d9e98bf9292dc\
+            29f8f41dbd289a147ce9da3113b5f0b8c00a60b1ce1d7e819d7a431d7c90ea0e\
+            5f",
+        order: "ffffffffffffffffffffffffffffffffffffffffffffffffc7634d81f4372ddf\
+            581a0db248b0a77aecec196accc52973",
+    },
+    EcDomain {
+        name: "secp521r1",
+        prime: "01ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff\
+            ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff\
+            ffff",
+        a: "01ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff\
+            ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff\
+            fffc",
+        b: "0051953eb9618e1c9a1f929a21a0b68540eea2da725b99b315f3b8b489918ef1\
+            09e156193951ec7e937b1652c0bd3bb1bf073573df883d2c34f1ef451fd46b50\
+            3f00",
+        generator: "0400c6858e06b70404e9cd9e3ecb662395b4429c648139053fb521f828af606b\
+            4d3dbaa14b5e77efe75928fe1dc127a2ffa8de3348b3c1856a429bf97e7e31c2\
+            e5bd66011839296a789a3bc0045c8a5fb42c7d1bd998f54449579b446817afbd\
+            17273e662c97ee72995ef42640c550b9013fad0761353c7086a272c24088be94\
+            769fd16650",
+        order: "01ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff\
+            fffa51868783bf2f966b7fcc0148f709a5d03bb5c9b8899c47aebb6fb71e9138\
+            6409",
+    },
+    EcDomain {
+        name: "brainpoolP256r1",
+        prime: "a9fb57dba1eea9bc3e660a909d838d726e3bf623d52620282013481d1f6e5377",
+        a: "7d5a0975fc2c3057eef67530417affe7fb8055c126dc5c6ce94a4b44f330b5d9",
+        b: "26dc5c6ce94a4b44f330b5d9bbd77cbf958416295cf7e1ce6bccdc18ff8c07b6",
+        generator: "048bd2aeb9cb7e57cb2c4b482ffc81b7afb9de27e1e3bd23c23a4453bd9ace32\
+            62547ef835c3dac4fd97f8461a14611dc9c27745132ded8e545c1d54c72f0469\
+            97",
+        order: "a9fb57dba1eea9bc3e660a909d838d718c397aa3b561a6f7901e0e82974856a7",
+    },
+    EcDomain {
+        name: "brainpoolP384r1",
+        prime: "8cb91e82a3386d280f5d6f7e50e641df152f7109ed5456b412b1da197fb71123\
+            acd3a729901d1a71874700133107ec53",
+        a: "7bc382c63d8c150c3c72080ace05afa0c2bea28e4fb22787139165efba91f90f\
+            8aa5814a503ad4eb04a8c7dd22ce2826",
+        b: "04a8c7dd22ce28268b39b55416f0447c2fb77de107dcd2a62e880ea53eeb62d5\
+            7cb4390295dbc9943ab78696fa504c11",
+        generator: "041d1c64f068cf45ffa2a63a81b7c13f6b8847a3e77ef14fe3db7fcafe0cbd10\
+            e8e826e03436d646aaef87b2e247d4af1e8abe1d7520f9c2a45cb1eb8e95cfd5\
+            5262b70b29feec5864e19c054ff99129280e4646217791811142820341263c53\
+            15",
+        order: "8cb91e82a3386d280f5d6f7e50e641df152f7109ed5456b31f166e6cac0425a7\
+            cf3ab6af6b7fc3103b883202e9046565",
+    },
+    EcDomain {
+        name: "brainpoolP512r1",
+        prime: "aadd9db8dbe9c48b3fd4e6ae33c9fc07cb308db3b3c9d20ed6639cca70330871\
+            7d4d9b009bc66842aecda12ae6a380e62881ff2f2d82c68528aa6056583a48f3",
+        a: "7830a3318b603b89e2327145ac234cc594cbdd8d3df91610a83441caea9863bc\
+            2ded5d5aa8253aa10a2ef1c98b9ac8b57f1117a72bf2c7b9e7c1ac4d77fc94ca",
+        b: "3df91610a83441caea9863bc2ded5d5aa8253aa10a2ef1c98b9ac8b57f1117a7\
+            2bf2c7b9e7c1ac4d77fc94cadc083e67984050b75ebae5dd2809bd638016f723",
+        generator: "0481aee4bdd82ed9645a21322e9c4c6a9385ed9f70b5d916c1b43b62eef4d009\
+            8eff3b1f78e2d0d48d50d1687b93b97d5f7c6d5047406a5e688b352209bcb9f8\
+            227dde385d566332ecc0eabfa9cf7822fdf209f70024a57b1aa000c55b881f81\
+            11b2dcde494a5f485e5bca4bd88a2763aed1ca2b2fa8f0540678cd1e0f3ad808\
+            92",
+        order: "aadd9db8dbe9c48b3fd4e6ae33c9fc07cb308db3b3c9d20ed6639cca70330870\
+            553e5c414ca92619418661197fac10471db1d381085ddaddb58796829ca90069",
+    },
+];
+
+/// 附加兩位元組 tag（5F29、7F49、5F20）的 TLV
+fn push_tlv2(out: &mut Vec<u8>, tag: [u8; 2], value: &[u8]) {
+    out.push(tag[0]);
+    push_tlv(out, tag[1], value);
+}
+
+/// 曲線的 domain parameters TLV：81 p、82 a、83 b、84 G、85 n、87 h；未知曲線回傳 None
+pub fn domain_parameters(curve: &str) -> Option<Vec<u8>> {
+    let domain = EC_DOMAINS.iter().find(|d| d.name == curve)?;
+    let mut out = Vec::new();
+    for (tag, hex) in [
+        (0x81, domain.prime),
+        (0x82, domain.a),
+        (0x83, domain.b),
+        (0x84, domain.generator),
+        (0x85, domain.order),
+    ] {
+        push_tlv(&mut out, tag, &hex_decode(hex).ok()?);
+    }
+    push_tlv(&mut out, 0x87, &[0x01]);
+    Some(out)
+}
+
+/// 由 domain parameters 的質數 p 反查曲線名稱
+pub fn curve_for_prime(prime: &[u8]) -> Option<&'static str> {
+    EC_DOMAINS
+        .iter()
+        .find(|d| hex_decode(d.prime).is_ok_and(|p| p == prime))
+        .map(|d| d.name)
+}
+
+/// GENERATE ASYMMETRIC KEY PAIR 的 EC 資料欄位（CV 請求範本）：
+/// 5F29 CPI、7F49 公鑰範本（OID + domain parameters）、5F20 標籤（空白時省略）
+pub fn generate_request(curve: &str, label: &str) -> Option<Vec<u8>> {
+    let mut template = Vec::new();
+    push_tlv(&mut template, 0x06, OID_TA_ECDSA_SHA256);
+    template.extend_from_slice(&domain_parameters(curve)?);
+
+    let mut data = Vec::new();
+    push_tlv2(&mut data, [0x5F, 0x29], &[CPI]);
+    push_tlv2(&mut data, [0x7F, 0x49], &template);
+    if !label.is_empty() {
+        push_tlv2(&mut data, [0x5F, 0x20], label.as_bytes());
+    }
+    Some(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hsm::tlv::{find_tag, TlvReader};
+
+    /// 取出 7F49 範本內容（跳過 5F29）
+    fn public_key_template(data: &[u8]) -> &[u8] {
+        assert_eq!(&data[..4], &[0x5F, 0x29, 0x01, CPI]);
+        assert_eq!(&data[4..6], &[0x7F, 0x49]);
+        let rest = &data[5..];
+        let tlv = TlvReader::new(rest).next().unwrap().unwrap();
+        tlv.value
+    }
+
+    /// (曲線, 欄位位元組數, 質數開頭位元組)
+    const EXPECTED: &[(&str, usize, &[u8])] = &[
+        ("secp256r1", 32, &[0xFF, 0xFF, 0xFF, 0xFF, 0x00, 0x00, 0x00, 0x01]),
+        ("secp384r1", 48, &[0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]),
+        ("secp521r1", 66, &[0x01, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]),
+        ("brainpoolP256r1", 32, &[0xA9, 0xFB, 0x57, 0xDB, 0xA1, 0xEE, 0xA9, 0xBC]),
+        ("brainpoolP384r1", 48, &[0x8C, 0xB9, 0x1E, 0x82, 0xA3, 0x38, 0x6D, 0x28]),
+        ("brainpoolP512r1", 64, &[0xAA, 0xDD, 0x9D, 0xB8, 0xDB, 0xE9, 0xC4, 0x8B]),
+    ];
+
+    // === EC domain parameters 測試 ===
+
+    #[test]
+    fn test_domain_parameters_per_curve() {
+        for &(curve, size, prime_prefix) in EXPECTED {
+            let params = domain_parameters(curve).unwrap();
+            let prime = find_tag(&params, 0x81).unwrap();
+            assert_eq!(prime.len(), size, "{curve}");
+            assert!(prime.starts_with(prime_prefix), "{curve}");
+            for tag in [0x82, 0x83, 0x85] {
+                assert_eq!(find_tag(&params, tag).unwrap().len(), size, "{curve} tag {tag:02X}");
+            }
+            let generator = find_tag(&params, 0x84).unwrap();
+            assert_eq!(generator.len(), 2 * size + 1, "{curve}");
+            assert_eq!(generator[0], 0x04, "{curve}");
+            assert_eq!(find_tag(&params, 0x87), Some(&[0x01][..]), "{curve}");
+        }
+    }
+
+    #[test]
+    fn test_domain_parameters_known_values() {
+        let params = domain_parameters("secp256r1").unwrap();
+        assert_eq!(
+            find_tag(&params, 0x83).unwrap(),
+            hex_decode("5ac635d8aa3a93e7b3ebbd55769886bc651d06b0cc53b0f63bce3c3e27d2604b").unwrap()
+        );
+        // secp521r1 的質數為 2^521 - 1
+        let params = domain_parameters("secp521r1").unwrap();
+        let prime = find_tag(&params, 0x81).unwrap();
+        assert_eq!(prime[0], 0x01);
+        assert!(prime[1..].iter().all(|&b| b == 0xFF));
+    }
+
+    #[test]
+    fn test_curve_for_prime_round_trip() {
+        for &(curve, ..) in EXPECTED {
+            let params = domain_parameters(curve).unwrap();
+            assert_eq!(curve_for_prime(find_tag(&params, 0x81).unwrap()), Some(curve));
+        }
+        assert_eq!(curve_for_prime(&[0xFF; 32]), None);
+    }
+
+    #[test]
+    fn test_domain_parameters_unknown_curve() {
+        assert_eq!(domain_parameters("secp256k1"), None);
+        assert_eq!(generate_request("p999", "x"), None);
+    }
+
+    #[test]
+    fn test_generate_request_layout() {
+        let data = generate_request("brainpoolP512r1", "backup").unwrap();
+        let template = public_key_template(&data);
+        assert_eq!(find_tag(template, 0x06), Some(OID_TA_ECDSA_SHA256));
+        // 長範本使用 82 兩位元組長度
+        assert_eq!(data[6], 0x82);
+        let end = 5 + 4 + template.len();
+        assert_eq!(&data[end..], &[&[0x5F, 0x20, 0x06][..], b"backup"].concat()[..]);
+    }
+
+    #[test]
+    fn test_generate_request_omits_empty_label() {
+        let data = generate_request("secp256r1", "").unwrap();
+        let template = public_key_template(&data);
+        assert_eq!(data.len(), 5 + 3 + template.len());
+    }
+}
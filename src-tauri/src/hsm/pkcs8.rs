@@ -0,0 +1,284 @@
+use crate::error::HsmError;
+use crate::hsm::tlv::{Tlv, TlvReader};
+use crate::secret::Zeroize;
+
+/// rsaEncryption OID (1.2.840.113549.1.1.1)
+const OID_RSA_ENCRYPTION: &[u8] = &[0x2A, 0x86, 0x48, 0x86, 0xF7, 0x0D, 0x01, 0x01, 0x01];
+/// id-ecPublicKey OID (1.2.840.10045.2.1)
+const OID_EC_PUBLIC_KEY: &[u8] = &[0x2A, 0x86, 0x48, 0xCE, 0x3D, 0x02, 0x01];
+
+/// RSA 私鑰元件（CRT 形式，整數皆已去除前導零）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RsaPrivateKey {
+    pub modulus: Vec<u8>,
+    pub public_exponent: Vec<u8>,
+    pub prime1: Vec<u8>,
+    pub prime2: Vec<u8>,
+    pub exponent1: Vec<u8>,
+    pub exponent2: Vec<u8>,
+    pub coefficient: Vec<u8>,
+}
+
+/// EC 私鑰元件
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EcPrivateKey {
+    /// 曲線 OID（DER 內容，不含 tag 與長度）
+    pub curve_oid: Vec<u8>,
+    pub private_key: Vec<u8>,
+    /// 未壓縮公鑰點（PKCS#8 未附帶時為 None）
+    pub public_key: Option<Vec<u8>>,
+}
+
+/// 由 PKCS#8 PrivateKeyInfo 解析出的私鑰
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PrivateKey {
+    Rsa(RsaPrivateKey),
+    Ec(EcPrivateKey),
+}
+
+impl Zeroize for PrivateKey {
+    fn zeroize(&mut self) {
+        match self {
+            PrivateKey::Rsa(key) => {
+                for part in [
+                    &mut key.prime1,
+                    &mut key.prime2,
+                    &mut key.exponent1,
+                    &mut key.exponent2,
+                    &mut key.coefficient,
+                ] {
+                    part.zeroize();
+                }
+            }
+            PrivateKey::Ec(key) => key.private_key.zeroize(),
+        }
+    }
+}
+
+fn invalid(what: &str) -> HsmError {
+    HsmError::InvalidPrivateKey(what.to_string())
+}
+
+/// 讀取結構內所有 TLV；任何元素不完整皆視為格式錯誤
+fn elements(data: &[u8]) -> Result<std::vec::IntoIter<Tlv<'_>>, HsmError> {
+    TlvReader::new(data)
+        .collect::<Result<Vec<_>, _>>()
+        .map(Vec::into_iter)
+        .map_err(|t| HsmError::InvalidPrivateKey(format!("DER 資料不完整: tag 0x{:02X}", t.tag)))
+}
+
+/// 取出下一個元素並檢查 tag
+fn expect<'a>(
+    items: &mut impl Iterator<Item = Tlv<'a>>, tag: u8, what: &str,
+) -> Result<&'a [u8], HsmError> {
+    match items.next() {
+        Some(tlv) if tlv.tag == tag => Ok(tlv.value),
+        _ => Err(invalid(what)),
+    }
+}
+
+/// 取出 INTEGER 並去除 DER 的前導零
+fn unsigned_integer<'a>(
+    items: &mut impl Iterator<Item = Tlv<'a>>, what: &str,
+) -> Result<Vec<u8>, HsmError> {
+    let value = expect(items, 0x02, what)?;
+    let start = value.iter().position(|&b| b != 0).unwrap_or(value.len());
+    if start == value.len() {
+        return Err(invalid(what));
+    }
+    Ok(value[start..].to_vec())
+}
+
+/// 解析 PKCS#8 PrivateKeyInfo（RFC 5208），支援 RSA 與 EC 私鑰
+pub fn parse(der: &[u8]) -> Result<PrivateKey, HsmError> {
+    let mut outer = elements(der)?;
+    let info = expect(&mut outer, 0x30, "PrivateKeyInfo")?;
+    if outer.next().is_some() {
+        return Err(invalid("PrivateKeyInfo 後有多餘資料"));
+    }
+
+    let mut fields = elements(info)?;
+    expect(&mut fields, 0x02, "version")?;
+    let mut algorithm = elements(expect(&mut fields, 0x30, "privateKeyAlgorithm")?)?;
+    let private_key = expect(&mut fields, 0x04, "privateKey")?;
+
+    match expect(&mut algorithm, 0x06, "algorithm")? {
+        OID_RSA_ENCRYPTION => parse_rsa(private_key).map(PrivateKey::Rsa),
+        OID_EC_PUBLIC_KEY => {
+            let curve = algorithm.next().filter(|p| p.tag == 0x06).map(|p| p.value);
+            parse_ec(private_key, curve).map(PrivateKey::Ec)
+        }
+        _ => Err(HsmError::NotSupported),
+    }
+}
+
+/// 解析 RSAPrivateKey（RFC 8017 A.1.2）；多質數金鑰不支援
+fn parse_rsa(data: &[u8]) -> Result<RsaPrivateKey, HsmError> {
+    let mut outer = elements(data)?;
+    let mut fields = elements(expect(&mut outer, 0x30, "RSAPrivateKey")?)?;
+    if expect(&mut fields, 0x02, "RSA version")? != [0x00] {
+        return Err(HsmError::NotSupported);
+    }
+    let modulus = unsigned_integer(&mut fields, "modulus")?;
+    let public_exponent = unsigned_integer(&mut fields, "publicExponent")?;
+    // 裝置以 CRT 元件運算，不需要 d
+    expect(&mut fields, 0x02, "privateExponent")?;
+    Ok(RsaPrivateKey {
+        modulus,
+        public_exponent,
+        prime1: unsigned_integer(&mut fields, "prime1")?,
+        prime2: unsigned_integer(&mut fields, "prime2")?,
+        exponent1: unsigned_integer(&mut fields, "exponent1")?,
+        exponent2: unsigned_integer(&mut fields, "exponent2")?,
+        coefficient: unsigned_integer(&mut fields, "coefficient")?,
+    })
+}
+
+/// 解析 ECPrivateKey（RFC 5915）；曲線優先取自 AlgorithmIdentifier，否則取 [0] parameters
+fn parse_ec(data: &[u8], curve: Option<&[u8]>) -> Result<EcPrivateKey, HsmError> {
+    let mut outer = elements(data)?;
+    let mut fields = elements(expect(&mut outer, 0x30, "ECPrivateKey")?)?;
+    if expect(&mut fields, 0x02, "EC version")? != [0x01] {
+        return Err(invalid("EC version"));
+    }
+    let private_key = expect(&mut fields, 0x04, "privateKey")?.to_vec();
+
+    let mut curve_oid = curve.map(<[u8]>::to_vec);
+    let mut public_key = None;
+    for tlv in fields {
+        match tlv.tag {
+            0xA0 if curve_oid.is_none() => {
+                curve_oid = Some(expect(&mut elements(tlv.value)?, 0x06, "namedCurve")?.to_vec());
+            }
+            0xA1 => {
+                // BIT STRING 第一個位元組為未使用位元數，公鑰點必須為 0
+                match expect(&mut elements(tlv.value)?, 0x03, "publicKey")? {
+                    [0x00, point @ ..] if !point.is_empty() => public_key = Some(point.to_vec()),
+                    _ => return Err(invalid("publicKey")),
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(EcPrivateKey {
+        curve_oid: curve_oid.ok_or_else(|| invalid("namedCurve"))?,
+        private_key,
+        public_key,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 組出短長度的 DER 元素（測試資料皆小於 128 位元組）
+    fn der(tag: u8, parts: &[&[u8]]) -> Vec<u8> {
+        let value = parts.concat();
+        [&[tag, value.len() as u8][..], &value].concat()
+    }
+
+    fn private_key_info(algorithm: &[u8], private_key: &[u8]) -> Vec<u8> {
+        der(0x30, &[&der(0x02, &[&[0x00]]), &der(0x30, &[algorithm]), &der(0x04, &[private_key])])
+    }
+
+    fn sample_rsa() -> Vec<u8> {
+        let ints: Vec<Vec<u8>> = [
+            &[0x00][..],
+            &[0x00, 0xC3, 0x01],
+            &[0x01, 0x00, 0x01],
+            &[0x55],
+            &[0x00, 0xE1],
+            &[0x0B],
+            &[0x21],
+            &[0x03],
+            &[0x07],
+        ]
+        .iter()
+        .map(|v| der(0x02, &[v]))
+        .collect();
+        let refs: Vec<&[u8]> = ints.iter().map(Vec::as_slice).collect();
+        let rsa_key = der(0x30, &refs);
+        private_key_info(&[&der(0x06, &[OID_RSA_ENCRYPTION])[..], &[0x05, 0x00]].concat(), &rsa_key)
+    }
+
+    const P256_OID: &[u8] = &[0x2A, 0x86, 0x48, 0xCE, 0x3D, 0x03, 0x01, 0x07];
+
+    // === PKCS#8 解析測試 ===
+
+    #[test]
+    fn test_parse_rsa_strips_leading_zeros() {
+        let PrivateKey::Rsa(key) = parse(&sample_rsa()).unwrap() else {
+            panic!("expected RSA key");
+        };
+        assert_eq!(key.modulus, vec![0xC3, 0x01]);
+        assert_eq!(key.public_exponent, vec![0x01, 0x00, 0x01]);
+        assert_eq!(key.prime1, vec![0xE1]);
+        assert_eq!(key.prime2, vec![0x0B]);
+        assert_eq!(key.exponent1, vec![0x21]);
+        assert_eq!(key.exponent2, vec![0x03]);
+        assert_eq!(key.coefficient, vec![0x07]);
+    }
+
+    #[test]
+    fn test_parse_ec_with_curve_in_algorithm() {
+        let point = [0x04, 0x11, 0x22];
+        let ec_key = der(
+            0x30,
+            &[
+                &der(0x02, &[&[0x01]]),
+                &der(0x04, &[&[0xAA; 32]]),
+                &der(0xA1, &[&der(0x03, &[&[0x00], &point])]),
+            ],
+        );
+        let algorithm = [der(0x06, &[OID_EC_PUBLIC_KEY]), der(0x06, &[P256_OID])].concat();
+        let parsed = parse(&private_key_info(&algorithm, &ec_key)).unwrap();
+        assert_eq!(
+            parsed,
+            PrivateKey::Ec(EcPrivateKey {
+                curve_oid: P256_OID.to_vec(),
+                private_key: vec![0xAA; 32],
+                public_key: Some(point.to_vec()),
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_ec_curve_from_private_key_parameters() {
+        let ec_key = der(
+            0x30,
+            &[
+                &der(0x02, &[&[0x01]]),
+                &der(0x04, &[&[0xBB; 32]]),
+                &der(0xA0, &[&der(0x06, &[P256_OID])]),
+            ],
+        );
+        let PrivateKey::Ec(key) = parse(&private_key_info(&der(0x06, &[OID_EC_PUBLIC_KEY]), &ec_key)).unwrap()
+        else {
+            panic!("expected EC key");
+        };
+        assert_eq!(key.curve_oid, P256_OID);
+        assert_eq!(key.public_key, None);
+    }
+
+    #[test]
+    fn test_parse_rejects_truncated_and_unknown_algorithm() {
+        let der_data = sample_rsa();
+        assert!(matches!(
+            parse(&der_data[..der_data.len() - 1]),
+            Err(HsmError::InvalidPrivateKey(_))
+        ));
+        // Ed25519 (1.3.101.112)
+        let ed25519 = private_key_info(&der(0x06, &[&[0x2B, 0x65, 0x70]]), &[0x04, 0x00]);
+        assert!(matches!(parse(&ed25519), Err(HsmError::NotSupported)));
+    }
+
+    #[test]
+    fn test_private_key_zeroize_clears_secret_parts() {
+        let mut key = parse(&sample_rsa()).unwrap();
+        key.zeroize();
+        let PrivateKey::Rsa(rsa) = key else { panic!("expected RSA key") };
+        assert!(rsa.prime1.is_empty() && rsa.coefficient.is_empty());
+        assert_eq!(rsa.modulus, vec![0xC3, 0x01]);
+    }
+}
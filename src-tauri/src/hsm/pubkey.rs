@@ -0,0 +1,216 @@
+use crate::hsm::tlv::{self, Tlv, TlvReader};
+
+/// rsaEncryption OID (1.2.840.113549.1.1.1)
+const OID_RSA_ENCRYPTION: &[u8] = &[0x2A, 0x86, 0x48, 0x86, 0xF7, 0x0D, 0x01, 0x01, 0x01];
+/// id-ecPublicKey OID (1.2.840.10045.2.1)
+const OID_EC_PUBLIC_KEY: &[u8] = &[0x2A, 0x86, 0x48, 0xCE, 0x3D, 0x02, 0x01];
+
+/// 用於比對的公鑰（RSA 只比對 modulus，已去除前導零）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PublicKey {
+    Rsa { modulus: Vec<u8> },
+    Ec { point: Vec<u8> },
+}
+
+fn strip_leading_zeros(value: &[u8]) -> Vec<u8> {
+    let start = value.iter().position(|&b| b != 0).unwrap_or(value.len());
+    value[start..].to_vec()
+}
+
+/// 讀取結構內所有 TLV；任何元素不完整即視為無法解析
+fn elements(data: &[u8]) -> Option<std::vec::IntoIter<Tlv<'_>>> {
+    TlvReader::new(data).collect::<Result<Vec<_>, _>>().ok().map(Vec::into_iter)
+}
+
+fn expect<'a>(items: &mut impl Iterator<Item = Tlv<'a>>, tag: u8) -> Option<&'a [u8]> {
+    items.next().filter(|tlv| tlv.tag == tag).map(|tlv| tlv.value)
+}
+
+/// EC 公鑰所屬曲線的識別：X.509 以 namedCurve OID 標示，CV 格式只帶 domain parameters 的質數 p
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EcCurveId {
+    Oid(Vec<u8>),
+    Prime(Vec<u8>),
+}
+
+/// X.509 憑證的 SubjectPublicKeyInfo 欄位
+struct SubjectPublicKeyInfo<'a> {
+    algorithm: &'a [u8],
+    /// RSA 的參數為 NULL，EC 為 namedCurve OID
+    curve: Option<&'a [u8]>,
+    key: &'a [u8],
+}
+
+/// 由 X.509 憑證（RFC 5280）取出 SubjectPublicKeyInfo
+fn subject_public_key_info(der: &[u8]) -> Option<SubjectPublicKeyInfo<'_>> {
+    let mut certificate = elements(expect(&mut elements(der)?, 0x30)?)?;
+    let mut tbs = elements(expect(&mut certificate, 0x30)?)?.peekable();
+    // version [0] 為選用欄位
+    tbs.next_if(|tlv| tlv.tag == 0xA0);
+    expect(&mut tbs, 0x02)?; // serialNumber
+    for _ in 0..4 {
+        // signature、issuer、validity、subject
+        expect(&mut tbs, 0x30)?;
+    }
+
+    let mut spki = elements(expect(&mut tbs, 0x30)?)?;
+    let mut algorithm = elements(expect(&mut spki, 0x30)?)?;
+    let oid = expect(&mut algorithm, 0x06)?;
+    let curve = algorithm.next().filter(|tlv| tlv.tag == 0x06).map(|tlv| tlv.value);
+    // BIT STRING 第一個位元組為未使用位元數，公鑰必須為 0
+    let key = match expect(&mut spki, 0x03)? {
+        [0x00, key @ ..] if !key.is_empty() => key,
+        _ => return None,
+    };
+    Some(SubjectPublicKeyInfo { algorithm: oid, curve, key })
+}
+
+/// 由 X.509 憑證的 SubjectPublicKeyInfo 取出公鑰
+pub fn from_certificate(der: &[u8]) -> Option<PublicKey> {
+    let SubjectPublicKeyInfo { algorithm, key, .. } = subject_public_key_info(der)?;
+    match algorithm {
+        OID_RSA_ENCRYPTION => {
+            let mut fields = elements(expect(&mut elements(key)?, 0x30)?)?;
+            let modulus = strip_leading_zeros(expect(&mut fields, 0x02)?);
+            (!modulus.is_empty()).then_some(PublicKey::Rsa { modulus })
+        }
+        OID_EC_PUBLIC_KEY => Some(PublicKey::Ec { point: key.to_vec() }),
+        _ => None,
+    }
+}
+
+/// 由 CV 憑證或金鑰產生時的 CV 請求（BSI TR-03110）的 7F49 取出公鑰；
+/// EC 的 domain 參數同樣使用 tag 81，因此先找公鑰點 86 再視為 RSA
+pub fn from_cv_certificate(data: &[u8]) -> Option<PublicKey> {
+    let key = tlv::find_cv_tag(data, 0x7F49)?;
+    if let Some(point) = tlv::find_cv_tag(key, 0x86).filter(|p| !p.is_empty()) {
+        return Some(PublicKey::Ec { point: point.to_vec() });
+    }
+    let modulus = strip_leading_zeros(tlv::find_cv_tag(key, 0x81)?);
+    (!modulus.is_empty()).then_some(PublicKey::Rsa { modulus })
+}
+
+/// 依開頭 tag 判斷為 X.509（30）或 CV 格式（7F21、67 包裝的請求）
+pub fn from_any(data: &[u8]) -> Option<PublicKey> {
+    match data.first()? {
+        0x30 => from_certificate(data),
+        _ => from_cv_certificate(data),
+    }
+}
+
+/// 取出 EC 公鑰的曲線識別；RSA 公鑰或無法解析時回傳 None
+pub fn ec_curve(data: &[u8]) -> Option<EcCurveId> {
+    if data.first()? == &0x30 {
+        let spki = subject_public_key_info(data)?;
+        return match (spki.algorithm, spki.curve) {
+            (OID_EC_PUBLIC_KEY, Some(curve)) => Some(EcCurveId::Oid(curve.to_vec())),
+            _ => None,
+        };
+    }
+    let key = tlv::find_cv_tag(data, 0x7F49)?;
+    tlv::find_cv_tag(key, 0x86).filter(|p| !p.is_empty())?;
+    tlv::find_cv_tag(key, 0x81).map(|prime| EcCurveId::Prime(prime.to_vec()))
+}
+
+/// 比對裝置上既有的憑證或請求與新憑證的公鑰；任一方無法解析時回傳 None
+pub fn matches(stored: &[u8], certificate: &[u8]) -> Option<bool> {
+    Some(from_any(stored)? == from_any(certificate)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 組出短長度的 DER 元素（測試資料皆小於 128 位元組）
+    fn der(tag: u8, parts: &[&[u8]]) -> Vec<u8> {
+        let value = parts.concat();
+        [&[tag, value.len() as u8][..], &value].concat()
+    }
+
+    fn x509(algorithm: &[u8], key: &[u8]) -> Vec<u8> {
+        let name = der(0x30, &[&der(0x31, &[&[0x00; 4]])]);
+        let tbs = der(
+            0x30,
+            &[
+                &der(0xA0, &[&der(0x02, &[&[0x02]])]),
+                &der(0x02, &[&[0x01, 0x23]]),
+                &der(0x30, &[&der(0x06, &[&[0x2A, 0x03]])]),
+                &name,
+                &der(0x30, &[&der(0x17, &[b"260101000000Z"])]),
+                &name,
+                &der(0x30, &[&der(0x30, &[algorithm]), &der(0x03, &[&[0x00], key])]),
+            ],
+        );
+        der(0x30, &[&tbs, &der(0x30, &[&der(0x06, &[&[0x2A, 0x03]])]), &der(0x03, &[&[0x00, 0xAA]])])
+    }
+
+    fn rsa_certificate(modulus: &[u8]) -> Vec<u8> {
+        let key = der(0x30, &[&der(0x02, &[modulus]), &der(0x02, &[&[0x01, 0x00, 0x01]])]);
+        x509(&[&der(0x06, &[OID_RSA_ENCRYPTION])[..], &[0x05, 0x00]].concat(), &key)
+    }
+
+    fn ec_certificate(point: &[u8]) -> Vec<u8> {
+        let p256 = [0x2A, 0x86, 0x48, 0xCE, 0x3D, 0x03, 0x01, 0x07];
+        x509(&[der(0x06, &[OID_EC_PUBLIC_KEY]), der(0x06, &[&p256])].concat(), point)
+    }
+
+    /// 組出 CV 請求：7F21 { 7F4E { 5F29, 7F49 { ... }, 5F20 }, 5F37 }
+    fn cv_request(public_key: &[u8]) -> Vec<u8> {
+        let body = [&[0x5F, 0x29, 0x01, 0x00][..], &[0x7F, 0x49, public_key.len() as u8], public_key]
+            .concat();
+        let body = [&[0x7F, 0x4E, body.len() as u8][..], &body, &[0x5F, 0x37, 0x01, 0xEE]].concat();
+        [&[0x7F, 0x21, body.len() as u8][..], &body].concat()
+    }
+
+    // === 公鑰比對測試 ===
+
+    #[test]
+    fn test_rsa_certificate_matches_cv_request() {
+        let modulus = [0xC3, 0x01, 0x7F];
+        let request = cv_request(&[der(0x81, &[&modulus]), der(0x82, &[&[0x01, 0x00, 0x01]])].concat());
+        let certificate = rsa_certificate(&[&[0x00][..], &modulus].concat());
+        assert_eq!(from_certificate(&certificate), Some(PublicKey::Rsa { modulus: modulus.to_vec() }));
+        assert_eq!(matches(&request, &certificate), Some(true));
+        assert_eq!(matches(&request, &rsa_certificate(&[0x00, 0xC3, 0x01, 0x7E])), Some(false));
+    }
+
+    #[test]
+    fn test_ec_certificate_matches_point_not_domain_parameter() {
+        let point = [0x04, 0x11, 0x22];
+        // 81 為曲線質數 p，不可被誤判為 RSA modulus
+        let request = cv_request(&[der(0x81, &[&[0xFF; 4]]), der(0x86, &[&point])].concat());
+        assert_eq!(from_cv_certificate(&request), Some(PublicKey::Ec { point: point.to_vec() }));
+        assert_eq!(matches(&request, &ec_certificate(&point)), Some(true));
+        assert_eq!(matches(&request, &ec_certificate(&[0x04, 0x11, 0x23])), Some(false));
+    }
+
+    #[test]
+    fn test_ec_curve_from_certificate_and_cv_request() {
+        let p256 = [0x2A, 0x86, 0x48, 0xCE, 0x3D, 0x03, 0x01, 0x07];
+        assert_eq!(ec_curve(&ec_certificate(&[0x04, 0x11])), Some(EcCurveId::Oid(p256.to_vec())));
+        let request = cv_request(&[der(0x81, &[&[0xFF; 4]]), der(0x86, &[&[0x04, 0x11]])].concat());
+        assert_eq!(ec_curve(&request), Some(EcCurveId::Prime(vec![0xFF; 4])));
+    }
+
+    #[test]
+    fn test_ec_curve_ignores_rsa_keys() {
+        assert_eq!(ec_curve(&rsa_certificate(&[0x00, 0xC3, 0x01])), None);
+        let request = cv_request(&der(0x81, &[&[0xC3, 0x01]]));
+        assert_eq!(ec_curve(&request), None);
+        assert_eq!(ec_curve(&[]), None);
+    }
+
+    #[test]
+    fn test_rsa_and_ec_keys_never_match() {
+        let point = [0x04, 0x11, 0x22];
+        assert_eq!(matches(&ec_certificate(&point), &rsa_certificate(&point)), Some(false));
+    }
+
+    #[test]
+    fn test_unparseable_data_cannot_be_compared() {
+        let certificate = ec_certificate(&[0x04, 0x11, 0x22]);
+        assert_eq!(from_certificate(&certificate[..certificate.len() - 1]), None);
+        assert_eq!(matches(&[0x30, 0x00], &certificate), None);
+        assert_eq!(matches(&certificate, &[]), None);
+    }
+}
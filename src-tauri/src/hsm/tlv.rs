@@ -0,0 +1,259 @@
+use crate::error::HsmError;
+
+/// 單一 BER-TLV 元素（僅支援單位元組 tag）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tlv<'a> {
+    pub tag: u8,
+    pub value: &'a [u8],
+}
+
+impl Tlv<'_> {
+    /// tag 的 bit 6 表示 constructed（內含其他 TLV）
+    pub fn is_constructed(&self) -> bool {
+        self.tag & 0x20 != 0
+    }
+}
+
+/// TLV 長度欄位或內容超出緩衝區
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Truncated<'a> {
+    pub tag: u8,
+    /// 該 tag 實際可取得的內容（長度欄位不完整時為空）
+    pub available: &'a [u8],
+}
+
+impl From<Truncated<'_>> for HsmError {
+    fn from(t: Truncated<'_>) -> Self {
+        HsmError::CommunicationError(format!("TLV 資料不完整: tag 0x{:02X}", t.tag))
+    }
+}
+
+/// 逐一讀取 TLV 並檢查邊界；遇到不完整的元素回傳 `Truncated` 後停止
+pub struct TlvReader<'a> {
+    rest: &'a [u8],
+}
+
+impl<'a> TlvReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { rest: data }
+    }
+
+    /// 解析長度欄位（短格式、0x81 xx、0x82 xx xx），回傳 (長度, 長度欄位位元組數)
+    fn read_length(bytes: &[u8]) -> Option<(usize, usize)> {
+        match bytes {
+            [len, ..] if *len < 0x80 => Some((usize::from(*len), 1)),
+            [0x81, len, ..] => Some((usize::from(*len), 2)),
+            [0x82, hi, lo, ..] => Some((usize::from(u16::from_be_bytes([*hi, *lo])), 3)),
+            _ => None,
+        }
+    }
+}
+
+impl<'a> Iterator for TlvReader<'a> {
+    type Item = Result<Tlv<'a>, Truncated<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (&tag, after_tag) = std::mem::take(&mut self.rest).split_first()?;
+        let Some((len, len_size)) = Self::read_length(after_tag) else {
+            return Some(Err(Truncated { tag, available: &[] }));
+        };
+        let body = &after_tag[len_size..];
+        if body.len() < len {
+            return Some(Err(Truncated { tag, available: body }));
+        }
+        let (value, next) = body.split_at(len);
+        self.rest = next;
+        Some(Ok(Tlv { tag, value }))
+    }
+}
+
+/// 深度優先搜尋指定 tag 的內容，會進入 constructed 元素；
+/// 不完整的 constructed 元素以實際取得的部分繼續搜尋
+pub fn find_tag(data: &[u8], wanted: u8) -> Option<&[u8]> {
+    for item in TlvReader::new(data) {
+        match item {
+            Ok(tlv) if tlv.tag == wanted => return Some(tlv.value),
+            Ok(tlv) if tlv.is_constructed() => {
+                if let Some(found) = find_tag(tlv.value, wanted) {
+                    return Some(found);
+                }
+            }
+            Ok(_) => {}
+            Err(t) if t.tag & 0x20 != 0 => return find_tag(t.available, wanted),
+            Err(_) => return None,
+        }
+    }
+    None
+}
+
+/// 深度優先搜尋 CV 憑證（BSI TR-03110）中的 tag，支援兩位元組 tag（7F21、5F20 等）；
+/// 任何元素不完整即停止搜尋
+pub fn find_cv_tag(data: &[u8], wanted: u16) -> Option<&[u8]> {
+    let mut rest = data;
+    while let Some(&first) = rest.first() {
+        let (tag, tag_len) = match rest {
+            [_, second, ..] if first & 0x1F == 0x1F => (u16::from_be_bytes([first, *second]), 2),
+            _ => (u16::from(first), 1),
+        };
+        let (len, len_size) = TlvReader::read_length(rest.get(tag_len..)?)?;
+        let start = tag_len + len_size;
+        let value = rest.get(start..start + len)?;
+        if tag == wanted {
+            return Some(value);
+        }
+        if first & 0x20 != 0 {
+            if let Some(found) = find_cv_tag(value, wanted) {
+                return Some(found);
+            }
+        }
+        rest = &rest[start + len..];
+    }
+    None
+}
+
+/// 將 TLV 附加到 `out`，長度欄位採與 `TlvReader` 相同的格式（內容最長 0xFFFF 位元組）
+pub fn push_tlv(out: &mut Vec<u8>, tag: u8, value: &[u8]) {
+    out.push(tag);
+    match value.len() {
+        len @ 0..=0x7F => out.push(len as u8),
+        len @ 0x80..=0xFF => out.extend_from_slice(&[0x81, len as u8]),
+        len => {
+            let len = u16::try_from(len).expect("TLV value exceeds 0xFFFF bytes");
+            out.push(0x82);
+            out.extend_from_slice(&len.to_be_bytes());
+        }
+    }
+    out.extend_from_slice(value);
+}
+
+/// 將 ENUMERATE OBJECTS 回應切成 (前綴, ID) 組；長度為奇數時捨棄最後的殘缺位元組
+pub fn fid_entries(data: &[u8]) -> impl Iterator<Item = (u8, u8)> + '_ {
+    if !data.len().is_multiple_of(2) {
+        log::warn!("object list has odd length {}, ignoring trailing byte", data.len());
+    }
+    data.chunks_exact(2).map(|fid| (fid[0], fid[1]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // === TlvReader 測試 ===
+
+    #[test]
+    fn test_reader_walks_sequential_tlvs() {
+        let data = [0x81, 0x01, 0xAA, 0x82, 0x02, 0xBB, 0xCC];
+        let items: Vec<Tlv> = TlvReader::new(&data).map(Result::unwrap).collect();
+        assert_eq!(
+            items,
+            vec![
+                Tlv { tag: 0x81, value: &[0xAA] },
+                Tlv { tag: 0x82, value: &[0xBB, 0xCC] },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_reader_long_form_lengths() {
+        let mut data = vec![0x04, 0x81, 0x80];
+        data.extend([0x11; 0x80]);
+        data.extend([0x05, 0x82, 0x01, 0x00]);
+        data.extend([0x22; 0x100]);
+        let items: Vec<Tlv> = TlvReader::new(&data).map(Result::unwrap).collect();
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].value.len(), 0x80);
+        assert_eq!(items[1].value.len(), 0x100);
+    }
+
+    #[test]
+    fn test_reader_truncated_value() {
+        let data = [0x81, 0x01, 0xAA, 0x85, 0x05, 0x00, 0x01];
+        let mut reader = TlvReader::new(&data);
+        assert!(reader.next().unwrap().is_ok());
+        assert_eq!(
+            reader.next(),
+            Some(Err(Truncated { tag: 0x85, available: &[0x00, 0x01] }))
+        );
+        assert_eq!(reader.next(), None);
+    }
+
+    #[test]
+    fn test_reader_truncated_length_field() {
+        let mut reader = TlvReader::new(&[0x85]);
+        assert_eq!(reader.next(), Some(Err(Truncated { tag: 0x85, available: &[] })));
+        let mut reader = TlvReader::new(&[0x85, 0x82, 0x01]);
+        assert!(matches!(reader.next(), Some(Err(_))));
+        assert_eq!(reader.next(), None);
+    }
+
+    #[test]
+    fn test_truncated_converts_to_communication_error() {
+        let err: HsmError = Truncated { tag: 0x85, available: &[] }.into();
+        assert!(matches!(err, HsmError::CommunicationError(_)));
+    }
+
+    // === find_tag 測試 ===
+
+    #[test]
+    fn test_find_tag_nested() {
+        let data = [0x6F, 0x07, 0x82, 0x01, 0x38, 0x85, 0x02, 0x01, 0x02];
+        assert_eq!(find_tag(&data, 0x85), Some(&[0x01, 0x02][..]));
+    }
+
+    #[test]
+    fn test_find_tag_ignores_tag_byte_inside_values() {
+        // 0x85 出現在 0x82 的內容中，不應被當成 tag
+        let data = [0x82, 0x03, 0x85, 0x05, 0x00];
+        assert_eq!(find_tag(&data, 0x85), None);
+    }
+
+    #[test]
+    fn test_find_tag_truncated_primitive() {
+        let data = [0x85, 0x05, 0x00, 0x01];
+        assert_eq!(find_tag(&data, 0x85), None);
+    }
+
+    // === CV 憑證 tag 測試 ===
+
+    #[test]
+    fn test_find_cv_tag_two_byte_tags() {
+        // 7F21 { 7F4E { 5F29 00, 42 "CA", 5F20 "DEV" }, 5F37 sig }
+        let data = [
+            0x7F, 0x21, 0x15, 0x7F, 0x4E, 0x0E, 0x5F, 0x29, 0x01, 0x00, 0x42, 0x02, b'C', b'A',
+            0x5F, 0x20, 0x03, b'D', b'E', b'V', 0x5F, 0x37, 0x01, 0xAA,
+        ];
+        assert_eq!(find_cv_tag(&data, 0x5F20), Some(&b"DEV"[..]));
+        assert_eq!(find_cv_tag(&data, 0x42), Some(&b"CA"[..]));
+        assert_eq!(find_cv_tag(&data, 0x5F37), Some(&[0xAA][..]));
+        assert_eq!(find_cv_tag(&data, 0x5F4C), None);
+    }
+
+    #[test]
+    fn test_find_cv_tag_truncated() {
+        let data = [0x7F, 0x21, 0x08, 0x5F, 0x20, 0x05, b'D'];
+        assert_eq!(find_cv_tag(&data, 0x5F20), None);
+    }
+
+    // === push_tlv 測試 ===
+
+    #[test]
+    fn test_push_tlv_round_trips_through_reader() {
+        let mut data = Vec::new();
+        push_tlv(&mut data, 0x81, &[0xAA]);
+        push_tlv(&mut data, 0x82, &[0x11; 0x80]);
+        push_tlv(&mut data, 0x83, &[0x22; 0x100]);
+        assert_eq!(&data[..3], &[0x81, 0x01, 0xAA]);
+        assert_eq!(&data[3..6], &[0x82, 0x81, 0x80]);
+        let lengths: Vec<usize> = TlvReader::new(&data).map(|t| t.unwrap().value.len()).collect();
+        assert_eq!(lengths, vec![1, 0x80, 0x100]);
+    }
+
+    // === fid_entries 測試 ===
+
+    #[test]
+    fn test_fid_entries_pairs_and_trailing_byte() {
+        let fids: Vec<(u8, u8)> = fid_entries(&[0xCC, 0x01, 0xCE, 0x02, 0xCC]).collect();
+        assert_eq!(fids, vec![(0xCC, 0x01), (0xCE, 0x02)]);
+        assert_eq!(fid_entries(&[]).count(), 0);
+    }
+}
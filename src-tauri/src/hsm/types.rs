@@ -11,6 +11,162 @@ pub struct HsmDeviceInfo {
     pub used_memory: u64,
     pub total_memory: u64,
     pub file_count: u32,
+    /// INITIALIZE (nc=0) 回報的可用 RAM (heap)，不支援時為 0
+    #[serde(default)]
+    pub available_ram: u64,
+    /// 初始化時設定的 DKEK 份額總數，未設定 DKEK 時為 None
+    #[serde(default)]
+    pub dkek_shares_total: Option<u8>,
+    /// 尚未匯入的 DKEK 份額數，未設定 DKEK 時為 None
+    #[serde(default)]
+    pub dkek_shares_missing: Option<u8>,
+    /// SELECT FCI 回報的生命週期狀態（tag 0x8A），未提供時為 None
+    #[serde(default)]
+    pub life_cycle: Option<LifeCycleState>,
+    /// SELECT FCI 回報的初始化選項（tag 0x85）
+    #[serde(default)]
+    pub features: DeviceFeatures,
+}
+
+/// ISO 7816-4 生命週期狀態位元組（LCS）
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum LifeCycleState {
+    Creation,
+    Initialisation,
+    Activated,
+    Deactivated,
+    Terminated,
+    Unknown(u8),
+}
+
+impl From<u8> for LifeCycleState {
+    fn from(value: u8) -> Self {
+        match value {
+            0x01 => LifeCycleState::Creation,
+            0x03 => LifeCycleState::Initialisation,
+            0x05 | 0x07 => LifeCycleState::Activated,
+            0x04 | 0x06 => LifeCycleState::Deactivated,
+            0x0C..=0x0F => LifeCycleState::Terminated,
+            other => LifeCycleState::Unknown(other),
+        }
+    }
+}
+
+/// 不帶資料的 VERIFY 回報的 PIN 狀態（查詢不消耗重試次數）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PinStatus {
+    /// 9000：本次連線已驗證
+    Verified,
+    /// 63Cx：尚未驗證，剩餘 x 次
+    Retries(u8),
+    /// 6983：已鎖定
+    Blocked,
+    /// 6A88：PIN 尚未設定（裝置未初始化）
+    NotInitialized,
+}
+
+impl PinStatus {
+    /// 剩餘重試次數；已驗證時不回報次數
+    pub fn retries(self) -> Option<u8> {
+        match self {
+            PinStatus::Retries(n) => Some(n),
+            PinStatus::Blocked => Some(0),
+            PinStatus::Verified | PinStatus::NotInitialized => None,
+        }
+    }
+}
+
+// 韌體裝置選項位元（sc_hsm.h 的 HSM_OPT_*），SELECT 選項欄與 DYNOPS 共用同一組定義
+pub const OPT_RRC: u16 = 0x0001;
+pub const OPT_TRANSPORT_PIN: u16 = 0x0002;
+pub const OPT_SESSION_PIN: u16 = 0x0004;
+pub const OPT_REPLACE_PKA: u16 = 0x0008;
+pub const OPT_COMBINED_AUTH: u16 = 0x0010;
+pub const OPT_RRC_RESET_ONLY: u16 = 0x0020;
+pub const OPT_BOOTSEL_BUTTON: u16 = 0x0100;
+pub const OPT_KEY_COUNTER_ALL: u16 = 0x0200;
+pub const OPT_SECURE_LOCK: u16 = 0x0400;
+
+/// SELECT 回應 tag 0x85 的 16 位元選項欄，記錄 INITIALIZE 時啟用的裝置功能
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct DeviceFeatures {
+    /// 允許以 SO-PIN 重設 User PIN 重試次數（RESET RETRY COUNTER）
+    pub reset_retry_counter: bool,
+    /// 使用傳輸 PIN，首次使用前須變更
+    pub transport_pin: bool,
+    /// 支援 session PIN
+    pub session_pin: bool,
+    /// 以公鑰認證取代 User PIN
+    pub replace_pka: bool,
+    /// PIN 與公鑰認證合併驗證
+    pub combined_auth: bool,
+    /// RESET RETRY COUNTER 僅能重設次數，不能設定新 PIN
+    pub rrc_reset_only: bool,
+    /// 以 BOOTSEL 按鈕確認操作
+    pub bootsel_button: bool,
+    /// 所有金鑰皆啟用使用計數器
+    pub key_counter_all: bool,
+    /// 已啟用安全鎖
+    pub secure_lock: bool,
+    /// 完整的 16 位元選項值，包含尚未具名的位元
+    pub raw: u16,
+}
+
+impl DeviceFeatures {
+    /// 由 SELECT 選項欄建立功能狀態
+    pub fn from_bits(bits: u16) -> Self {
+        Self {
+            reset_retry_counter: bits & OPT_RRC != 0,
+            transport_pin: bits & OPT_TRANSPORT_PIN != 0,
+            session_pin: bits & OPT_SESSION_PIN != 0,
+            replace_pka: bits & OPT_REPLACE_PKA != 0,
+            combined_auth: bits & OPT_COMBINED_AUTH != 0,
+            rrc_reset_only: bits & OPT_RRC_RESET_ONLY != 0,
+            bootsel_button: bits & OPT_BOOTSEL_BUTTON != 0,
+            key_counter_all: bits & OPT_KEY_COUNTER_ALL != 0,
+            secure_lock: bits & OPT_SECURE_LOCK != 0,
+            raw: bits,
+        }
+    }
+}
+
+/// CMD_MEMORY 回報的記憶體使用量
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct MemInfo {
+    pub free: u32,
+    pub used: u32,
+    pub total: u32,
+    pub file_count: u32,
+}
+
+/// 韌體支援的金鑰演算法與參數
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SupportedAlgos {
+    pub rsa_bits: Vec<u16>,
+    pub ec_curves: Vec<String>,
+    pub aes_bits: Vec<u16>,
+}
+
+/// 診斷報告（`debug_device_raw` 的結構化版本）
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DebugReport {
+    pub select_response: Vec<u8>,
+    pub parsed_version: String,
+    pub options: u16,
+    pub init_response: Option<Vec<u8>>,
+    pub memory: Option<MemInfo>,
+}
+
+/// INITIALIZE 的額外選項；欄位為 `None` 時沿用裝置預設值
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct InitializeOptions {
+    /// User PIN 重試次數上限 (tag 0x91)
+    pub pin_retry_limit: Option<u8>,
+    /// 金鑰網域數量 (tag 0x97)
+    pub key_domains: Option<u8>,
+    /// 裝置韌體允許的 DKEK 份額數上限；未指定時為 `DEFAULT_MAX_DKEK_SHARES`
+    #[serde(default)]
+    pub max_dkek_shares: Option<u8>,
 }
 
 // === HSM 金鑰相關 ===
@@ -23,6 +179,55 @@ pub enum HsmKeyType {
     Aes,
 }
 
+/// 產生／匯入私鑰時資料欄位開頭的演算法位元組
+const ALGORITHM_RSA: u8 = 0x30;
+const ALGORITHM_EC: u8 = 0x31;
+/// GENERATE SECRET KEY 的 P2 演算法參考：(金鑰長度, 演算法 ID)
+pub const AES_ALGORITHM_REFS: &[(u16, u8)] = &[(128, 0xB0), (192, 0xB1), (256, 0xB2)];
+/// PKCS#15 PrKD 記錄的金鑰類型 tag：privateRSAKey (SEQUENCE)、privateECKey ([0])、
+/// 秘密金鑰 SKD 的 aesKey ([8])
+const PRKD_TAG_RSA: u8 = 0x30;
+const PRKD_TAG_EC: u8 = 0xA0;
+const PRKD_TAG_AES: u8 = 0xA8;
+/// 裝置未提供曲線資訊時使用的曲線名稱
+const UNKNOWN_CURVE: &str = "unknown";
+
+impl HsmKeyType {
+    /// 裝置上的演算法位元組：RSA／EC 為私鑰資料的開頭，AES 為依長度而定的 P2 演算法參考
+    pub fn to_algorithm_byte(&self, key_size: u16) -> Option<u8> {
+        match self {
+            HsmKeyType::Rsa => Some(ALGORITHM_RSA),
+            HsmKeyType::Ec { .. } => Some(ALGORITHM_EC),
+            HsmKeyType::Aes => AES_ALGORITHM_REFS
+                .iter()
+                .find(|&&(size, _)| size == key_size)
+                .map(|&(_, algorithm)| algorithm),
+        }
+    }
+
+    /// `to_algorithm_byte` 的反向對應；演算法位元組不含曲線，EC 曲線由呼叫端提供
+    pub fn from_algorithm_byte(byte: u8, curve: &str) -> Option<Self> {
+        match byte {
+            ALGORITHM_RSA => Some(HsmKeyType::Rsa),
+            ALGORITHM_EC => Some(HsmKeyType::Ec { curve: curve.to_string() }),
+            _ if AES_ALGORITHM_REFS.iter().any(|&(_, algorithm)| algorithm == byte) => {
+                Some(HsmKeyType::Aes)
+            }
+            _ => None,
+        }
+    }
+
+    /// 由 PrKD 記錄的金鑰類型 tag 判斷；PrKD 不含曲線，EC 曲線標示為 unknown
+    pub fn from_prkd_tag(tag: u8) -> Option<Self> {
+        match tag {
+            PRKD_TAG_RSA => Some(HsmKeyType::Rsa),
+            PRKD_TAG_EC => Some(HsmKeyType::Ec { curve: UNKNOWN_CURVE.to_string() }),
+            PRKD_TAG_AES => Some(HsmKeyType::Aes),
+            _ => None,
+        }
+    }
+}
+
 /// HSM 金鑰資訊
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HsmKeyInfo {
@@ -34,6 +239,45 @@ pub struct HsmKeyInfo {
     pub usage: Vec<String>,
 }
 
+/// AES 金鑰預定用途
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum AesMode {
+    /// CBC 加解密
+    #[default]
+    Cbc,
+    /// CMAC 訊息鑑別
+    Cmac,
+    /// 金鑰衍生
+    Derive,
+}
+
+impl AesMode {
+    /// 對應的 `HsmKeyInfo::usage` 標示
+    pub fn usage(&self) -> Vec<String> {
+        let usage: &[&str] = match self {
+            AesMode::Cbc => &["encrypt", "decrypt"],
+            AesMode::Cmac => &["mac"],
+            AesMode::Derive => &["derive"],
+        };
+        usage.iter().map(|u| u.to_string()).collect()
+    }
+}
+
+/// 金鑰產生規格（用於批次產生）；`id` 為 `None` 時自動配置最小的空位
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum KeySpec {
+    Rsa { bits: u16, id: Option<u8>, label: String },
+    Ec { curve: String, id: Option<u8>, label: String },
+    Aes {
+        bits: u16,
+        id: Option<u8>,
+        #[serde(default)]
+        label: String,
+        #[serde(default)]
+        mode: AesMode,
+    },
+}
+
 /// 金鑰物件類型（用於刪除操作）
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum KeyObjectType {
@@ -56,6 +300,41 @@ pub struct HsmCertInfo {
     pub key_id: Option<u8>,
 }
 
+// === HSM 物件列舉 ===
+
+/// 裝置上的物件類別（依韌體的 FID 前綴判斷）
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum ObjectClass {
+    /// 0xCC：RSA/EC 私鑰或 AES 秘密金鑰
+    Key,
+    /// 0xC4：私鑰描述 (PrKD)
+    KeyDescription,
+    /// 0xC8：憑證描述 (CD)
+    CertificateDescription,
+    /// 0xC9：資料物件描述 (DCOD)
+    DataDescription,
+    /// 0xCA
+    CaCertificate,
+    /// 0xCD
+    Data,
+    /// 0xCE：終端憑證
+    Certificate,
+    /// 0xCF
+    ProtectedData,
+    Unknown,
+}
+
+/// ENUMERATE OBJECTS 列出的單一物件
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ObjectRef {
+    /// 完整 FID（前綴 + ID）
+    pub fid: u16,
+    pub id: u8,
+    pub class: ObjectClass,
+    /// 相同 ID 的金鑰已不存在（殘留的私鑰描述、憑證描述或終端憑證）
+    pub orphaned: bool,
+}
+
 // === DKEK 備份相關 ===
 
 /// DKEK 份額狀態
@@ -67,20 +346,141 @@ pub struct DkekStatus {
     pub key_check_value: Option<String>,
 }
 
+/// 目前 key domain 的 DKEK 份額匯入進度，每匯入一份即以 `"dkek-progress"` 事件送出
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DkekProgress {
+    /// 本程式在此 key domain 已匯入的份額數（換 key domain 或裝置重設後重新計算）
+    pub imported_this_session: u8,
+    pub status: DkekStatus,
+}
+
+/// 憑證鏈匯出格式
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum CertBundleFormat {
+    /// PKCS#7 (.p7b) DER
+    #[default]
+    Pkcs7,
+    /// 串接的 PEM
+    Pem,
+}
+
+/// SO-PIN 與 DKEK 輪替流程的步驟
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum RotationStep {
+    ChangeSoPin,
+    CreateDkekShare,
+}
+
+/// 金鑰與憑證合併匯入流程的步驟
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum ImportStep {
+    UnwrapKey,
+    ImportCertificate,
+}
+
+/// 包裝金鑰 blob 標頭記錄的金鑰類型
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum WrappedKeyType {
+    Rsa,
+    RsaCrt,
+    Ec,
+    Aes,
+    Unknown(u8),
+}
+
+impl From<u8> for WrappedKeyType {
+    fn from(value: u8) -> Self {
+        match value {
+            5 => WrappedKeyType::Rsa,
+            6 => WrappedKeyType::RsaCrt,
+            12 => WrappedKeyType::Ec,
+            15 => WrappedKeyType::Aes,
+            other => WrappedKeyType::Unknown(other),
+        }
+    }
+}
+
+impl WrappedKeyType {
+    /// 解包後應還原成的物件類別：AES 為秘密金鑰，其餘（含未知類型）為私鑰
+    pub fn object_class(&self) -> KeyObjectType {
+        match self {
+            WrappedKeyType::Aes => KeyObjectType::SecretKey,
+            _ => KeyObjectType::PrivateKey,
+        }
+    }
+}
+
+/// WRAP KEY 結果：包裝後的 blob 與其標頭資訊
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct WrappedKey {
+    pub blob: Vec<u8>,
+    pub key_type: WrappedKeyType,
+    /// 產生此 blob 的 DKEK 檢查值（十六進位）
+    pub key_check_value: String,
+}
+
+/// UNWRAP KEY 完成後的確認資訊
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct UnwrapStatus {
+    pub key_ref: u8,
+    pub key_type: WrappedKeyType,
+    pub key_check_value: String,
+}
+
 // === HSM 裝置選項 ===
 
-/// HSM 裝置選項狀態
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// HSM 裝置選項狀態（CMD_DYNOPS 動態選項位元欄）
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct HsmOptions {
     pub press_to_confirm: bool,
     pub key_usage_counter: bool,
+    /// 完整的 16 位元選項值，包含尚未具名的位元
+    pub raw: u16,
+}
+
+impl HsmOptions {
+    /// 由 DYNOPS 位元欄建立選項狀態
+    pub fn from_bits(bits: u16) -> Self {
+        Self {
+            press_to_confirm: bits & HsmOptionType::PressToConfirm.mask() != 0,
+            key_usage_counter: bits & HsmOptionType::KeyUsageCounter.mask() != 0,
+            raw: bits,
+        }
+    }
 }
 
 /// HSM 選項類型（用於設定操作）
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum HsmOptionType {
+    /// `OPT_BOOTSEL_BUTTON`: 使用金鑰前需按 BOOTSEL 按鈕確認
     PressToConfirm,
+    /// `OPT_KEY_COUNTER_ALL`: 所有金鑰皆啟用使用計數器
     KeyUsageCounter,
+    /// 以位元位置 (0-15) 指定其他韌體選項
+    Bit(u8),
+}
+
+impl HsmOptionType {
+    /// 此選項在 DYNOPS 位元欄中的遮罩；超出 16 位元的 `Bit` 回傳 0
+    pub fn mask(&self) -> u16 {
+        match self {
+            HsmOptionType::PressToConfirm => OPT_BOOTSEL_BUTTON,
+            HsmOptionType::KeyUsageCounter => OPT_KEY_COUNTER_ALL,
+            HsmOptionType::Bit(bit) => 1u16.checked_shl(*bit as u32).unwrap_or(0),
+        }
+    }
+}
+
+/// PC/SC 連線共用模式
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum CardShareMode {
+    /// 所有操作皆以共用模式連線
+    #[default]
+    Shared,
+    /// 所有操作皆以獨佔模式連線
+    Exclusive,
+    /// 僅破壞性操作（初始化、DKEK 匯入、金鑰包裝/還原）以獨佔模式連線
+    ExclusiveForDestructive,
 }
 
 // === APDU 協定 ===
@@ -96,6 +496,44 @@ pub struct ApduCommand {
     pub le: Option<u16>,
 }
 
+/// 資料欄位含 PIN、密碼或金鑰內容的指令：VERIFY、CHANGE REFERENCE DATA、RESET RETRY COUNTER、
+/// INITIALIZE、KEY DOMAIN（DKEK 份額密碼）、UNWRAP KEY（包裝的金鑰或明文私鑰）
+const SECRET_DATA_INS: &[u8] = &[0x20, 0x24, 0x2C, 0x50, 0x52, 0x74];
+
+impl ApduCommand {
+    /// 資料欄位是否含不可寫入日誌的機密內容
+    pub fn has_secret_data(&self) -> bool {
+        SECRET_DATA_INS.contains(&self.ins)
+    }
+}
+
+/// 供追蹤與除錯輸出的文字格式，例如 `00 A4 04 00 Lc=2 Data=A000 Le=256`；
+/// `has_secret_data` 的指令不輸出資料內容
+impl std::fmt::Display for ApduCommand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:02X} {:02X} {:02X} {:02X}", self.cla, self.ins, self.p1, self.p2)?;
+        if let Some(data) = self.data.as_deref().filter(|d| !d.is_empty()) {
+            write!(f, " Lc={}", data.len())?;
+            if self.has_secret_data() {
+                f.write_str(" Data=<redacted>")?;
+            } else {
+                write!(f, " Data={}", crate::util::hex_encode_upper(data))?;
+            }
+        }
+        if let Some(le) = self.le {
+            write!(f, " Le={le}")?;
+        }
+        Ok(())
+    }
+}
+
+/// 含 PIN 的指令以 `Zeroizing<ApduCommand>` 持有，drop 時清除資料欄位
+impl crate::secret::Zeroize for ApduCommand {
+    fn zeroize(&mut self) {
+        self.data.zeroize();
+    }
+}
+
 /// APDU 回應結構
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct ApduResponse {
@@ -103,3 +541,57 @@ pub struct ApduResponse {
     pub sw1: u8,
     pub sw2: u8,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command(ins: u8, data: Option<Vec<u8>>, le: Option<u16>) -> ApduCommand {
+        ApduCommand { cla: 0x00, ins, p1: 0x04, p2: 0x00, data, le }
+    }
+
+    // === ApduCommand 文字格式測試 ===
+
+    #[test]
+    fn test_display_case1_header_only() {
+        assert_eq!(command(0xA4, None, None).to_string(), "00 A4 04 00");
+        // 空資料等同沒有資料
+        assert_eq!(command(0xA4, Some(vec![]), None).to_string(), "00 A4 04 00");
+    }
+
+    #[test]
+    fn test_display_case2_le_only() {
+        assert_eq!(command(0xB0, None, Some(256)).to_string(), "00 B0 04 00 Le=256");
+    }
+
+    #[test]
+    fn test_display_case3_data_hex_dumped() {
+        let cmd = command(0xA4, Some(vec![0xE8, 0x2B, 0x06]), None);
+        assert_eq!(cmd.to_string(), "00 A4 04 00 Lc=3 Data=E82B06");
+    }
+
+    #[test]
+    fn test_display_case4_data_and_le() {
+        let cmd = ApduCommand { cla: 0x80, p1: 0x02, p2: 0x0F, ..command(0x62, Some(vec![0xA0, 0x00]), Some(10)) };
+        assert_eq!(cmd.to_string(), "80 62 02 0F Lc=2 Data=A000 Le=10");
+    }
+
+    #[test]
+    fn test_display_redacts_pin_data() {
+        let verify = ApduCommand { p1: 0x00, p2: 0x81, ..command(0x20, Some(b"648219".to_vec()), None) };
+        assert_eq!(verify.to_string(), "00 20 00 81 Lc=6 Data=<redacted>");
+    }
+
+    #[test]
+    fn test_display_redacts_key_import() {
+        // UNWRAP KEY 的明文私鑰匯入（P2=0x90）含 RSA 質數或 EC 純量
+        let import = ApduCommand {
+            cla: 0x80,
+            p1: 0x03,
+            p2: 0x90,
+            ..command(0x74, Some(vec![0x31, 0x04, 0x0A, 0x0B, 0x0C, 0x0D]), None)
+        };
+        assert!(import.has_secret_data());
+        assert_eq!(import.to_string(), "80 74 03 90 Lc=6 Data=<redacted>");
+    }
+}
@@ -0,0 +1,113 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// 以裝置路徑為鍵的短期快取，避免 UI 切換分頁時重複讀取裝置資訊
+pub struct InfoCache<T> {
+    ttl: Duration,
+    entries: Mutex<HashMap<String, (T, Instant)>>,
+}
+
+impl<T: Clone> InfoCache<T> {
+    pub fn new(ttl: Duration) -> Self {
+        Self { ttl, entries: Mutex::new(HashMap::new()) }
+    }
+
+    /// 快取未過期且未要求重新讀取時直接回傳；否則呼叫 `fetch` 並更新快取（失敗不寫入）
+    pub fn get_or_fetch<E>(
+        &self,
+        path: &str,
+        refresh: bool,
+        fetch: impl FnOnce() -> Result<T, E>,
+    ) -> Result<T, E> {
+        self.get_or_fetch_at(path, refresh, Instant::now(), fetch)
+    }
+
+    fn get_or_fetch_at<E>(
+        &self,
+        path: &str,
+        refresh: bool,
+        now: Instant,
+        fetch: impl FnOnce() -> Result<T, E>,
+    ) -> Result<T, E> {
+        if !refresh {
+            if let Some(value) = self.lookup(path, now) {
+                return Ok(value);
+            }
+        }
+        let value = fetch()?;
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.insert(path.to_string(), (value.clone(), now));
+        }
+        Ok(value)
+    }
+
+    fn lookup(&self, path: &str, now: Instant) -> Option<T> {
+        let entries = self.entries.lock().ok()?;
+        let (value, fetched_at) = entries.get(path)?;
+        (now.saturating_duration_since(*fetched_at) < self.ttl).then(|| value.clone())
+    }
+
+    /// 清除所有快取（裝置插拔或切換時呼叫）
+    pub fn invalidate_all(&self) {
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    // === 快取測試 ===
+
+    fn counting_fetch(calls: &Cell<u32>) -> impl FnOnce() -> Result<u32, ()> + '_ {
+        move || {
+            calls.set(calls.get() + 1);
+            Ok(calls.get())
+        }
+    }
+
+    #[test]
+    fn test_cache_hit_within_ttl() {
+        let cache = InfoCache::new(Duration::from_secs(10));
+        let calls = Cell::new(0);
+        let t0 = Instant::now();
+        assert_eq!(cache.get_or_fetch_at("dev0", false, t0, counting_fetch(&calls)), Ok(1));
+        let later = t0 + Duration::from_secs(5);
+        assert_eq!(cache.get_or_fetch_at("dev0", false, later, counting_fetch(&calls)), Ok(1));
+        assert_eq!(calls.get(), 1);
+    }
+
+    #[test]
+    fn test_cache_miss_after_ttl_or_other_device() {
+        let cache = InfoCache::new(Duration::from_secs(10));
+        let calls = Cell::new(0);
+        let t0 = Instant::now();
+        cache.get_or_fetch_at("dev0", false, t0, counting_fetch(&calls)).unwrap();
+        assert_eq!(cache.get_or_fetch_at("dev1", false, t0, counting_fetch(&calls)), Ok(2));
+        let expired = t0 + Duration::from_secs(10);
+        assert_eq!(cache.get_or_fetch_at("dev0", false, expired, counting_fetch(&calls)), Ok(3));
+    }
+
+    #[test]
+    fn test_forced_refresh_and_invalidate() {
+        let cache = InfoCache::new(Duration::from_secs(10));
+        let calls = Cell::new(0);
+        let t0 = Instant::now();
+        cache.get_or_fetch_at("dev0", false, t0, counting_fetch(&calls)).unwrap();
+        assert_eq!(cache.get_or_fetch_at("dev0", true, t0, counting_fetch(&calls)), Ok(2));
+        cache.invalidate_all();
+        assert_eq!(cache.get_or_fetch_at("dev0", false, t0, counting_fetch(&calls)), Ok(3));
+    }
+
+    #[test]
+    fn test_fetch_error_is_not_cached() {
+        let cache = InfoCache::new(Duration::from_secs(10));
+        assert_eq!(cache.get_or_fetch("dev0", false, || Err::<u32, _>("busy")), Err("busy"));
+        assert_eq!(cache.get_or_fetch("dev0", false, || Ok::<_, &str>(7)), Ok(7));
+        assert_eq!(cache.get_or_fetch("dev0", false, || Err::<u32, _>("busy")), Ok(7));
+    }
+}
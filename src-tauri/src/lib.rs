@@ -1,26 +1,48 @@
 pub mod commands;
+pub mod device_lock;
 pub mod device_manager;
+pub mod diagnostics;
 pub mod error;
 pub mod fido;
+pub mod firmware;
 pub mod hsm;
+pub mod info_cache;
+pub mod logging;
+pub mod pcsc_context;
+pub mod secret;
 pub mod types;
+pub mod util;
 
 use std::sync::Arc;
 
-use crate::commands::device::{check_scard_service, list_all_readers, open_device, scan_devices};
+use tauri::{Emitter, Listener};
+
+use crate::commands::device::{
+    check_scard_service, close_device, get_device_id, identify_device, list_all_readers, open_device,
+    run_self_test, scan_devices, set_hsm_reader_patterns, set_scan_scope,
+};
 use crate::commands::fido::{
-    fido_add_oath, fido_calculate_oath, fido_change_pin, fido_delete_credential,
-    fido_delete_oath, fido_get_backup_words, fido_get_info, fido_list_credentials,
-    fido_list_oath, fido_reset_device, fido_restore_from_words, fido_set_led_config,
-    fido_set_min_pin_length, fido_set_pin, fido_toggle_enterprise_attestation,
+    fido_add_oath, fido_assert_credential, fido_calculate_oath, fido_cancel, fido_change_pin, fido_delete_all_credentials,
+    fido_delete_credential,
+    fido_delete_oath, fido_get_backup_words, fido_firmware_status, fido_get_creds_metadata, fido_get_info, fido_get_key_agreement, fido_get_min_pin_length,
+    fido_list_credentials, fido_ping, fido_list_oath, fido_oath_slot_info, fido_read_large_blob, fido_reset_confirm,
+    fido_reset_prepare,
+    fido_restore_from_words, fido_self_test_registration, fido_set_advanced_mode, fido_set_led_config, fido_set_min_pin_length, fido_set_oath_aid,
+    fido_set_pin, fido_set_read_timeout, fido_set_uv_method, fido_toggle_always_uv, fido_toggle_enterprise_attestation, fido_transmit_cbor,
+    fido_write_large_blob,
 };
 use crate::commands::hsm::{
     hsm_change_pin, hsm_change_so_pin, hsm_create_dkek_share, hsm_debug_device_raw,
-    hsm_delete_key, hsm_disable_secure_lock, hsm_enable_secure_lock,
-    hsm_export_certificate, hsm_generate_aes_key, hsm_generate_ec_key,
-    hsm_generate_rsa_key, hsm_get_device_info, hsm_get_options, hsm_import_certificate,
-    hsm_import_dkek_share, hsm_initialize, hsm_list_certificates, hsm_list_keys,
-    hsm_set_datetime, hsm_set_led_config, hsm_set_option, hsm_unblock_pin,
+    hsm_debug_device_raw_structured,
+    hsm_delete_key, hsm_delete_key_pair, hsm_set_key_label, hsm_list_all_objects, hsm_delete_orphaned_objects, hsm_disable_secure_lock, hsm_enable_secure_lock,
+    hsm_export_certificate, hsm_export_certificate_chain, hsm_generate_aes_key, hsm_generate_ec_key,
+    hsm_generate_keys, hsm_generate_rsa_key, hsm_firmware_status, hsm_get_device_info, hsm_get_memory, hsm_get_options,
+    hsm_get_supported_algorithms, hsm_read_fault_log, hsm_clear_fault_log,
+    hsm_get_pin_retries, hsm_get_so_pin_retries, hsm_import_certificate, hsm_ping,
+    hsm_import_dkek_share, hsm_import_key_with_cert, hsm_import_private_key, hsm_initialize, hsm_list_certificates, hsm_list_keys,
+    hsm_factory_reset, hsm_next_free_key_id, hsm_rotate_security,
+    hsm_set_applet_aid, hsm_set_datetime, hsm_set_led_config, hsm_set_option, hsm_set_share_mode,
+    hsm_set_transmit_timeout, hsm_unblock_pin,
     hsm_unwrap_key, hsm_verify_pin, hsm_wrap_key,
 };
 use crate::device_manager::{start_device_polling, DeviceManagerImpl};
@@ -29,12 +51,19 @@ use crate::hsm::HsmModuleImpl;
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    // Set RUST_LOG=debug to trace device exchanges
+    logging::init();
+
     let device_manager = Arc::new(DeviceManagerImpl::new());
     let fido_module = Arc::new(FidoModuleImpl::new(String::new()));
     let hsm_module = Arc::new(HsmModuleImpl::new(String::new()));
 
     // Clone for the polling background task
     let dm_for_polling = Arc::clone(&device_manager);
+    let fido_for_events = Arc::clone(&fido_module);
+    let hsm_for_events = Arc::clone(&hsm_module);
+    let fido_for_cache = Arc::clone(&fido_module);
+    let hsm_for_cache = Arc::clone(&hsm_module);
 
     tauri::Builder::default()
         .plugin(tauri_plugin_shell::init())
@@ -44,55 +73,127 @@ pub fn run() {
         .invoke_handler(tauri::generate_handler![
             // Device management
             scan_devices,
+            set_scan_scope,
+            set_hsm_reader_patterns,
             open_device,
+            identify_device,
+            get_device_id,
+            close_device,
             list_all_readers,
             check_scard_service,
+            run_self_test,
             // FIDO commands
             fido_get_info,
+            fido_ping,
+            fido_firmware_status,
+            fido_get_min_pin_length,
             fido_set_pin,
             fido_change_pin,
+            fido_get_creds_metadata,
             fido_list_credentials,
             fido_delete_credential,
+            fido_delete_all_credentials,
+            fido_assert_credential,
             fido_list_oath,
+            fido_oath_slot_info,
             fido_calculate_oath,
             fido_add_oath,
             fido_delete_oath,
             fido_get_backup_words,
             fido_restore_from_words,
-            fido_reset_device,
+            fido_reset_prepare,
+            fido_reset_confirm,
             fido_set_min_pin_length,
             fido_toggle_enterprise_attestation,
+            fido_toggle_always_uv,
+            fido_get_key_agreement,
+            fido_self_test_registration,
             fido_set_led_config,
+            fido_read_large_blob,
+            fido_write_large_blob,
+            fido_set_advanced_mode,
+            fido_set_uv_method,
+            fido_set_read_timeout,
+            fido_set_oath_aid,
+            fido_cancel,
+            fido_transmit_cbor,
             // HSM commands
             hsm_initialize,
             hsm_verify_pin,
             hsm_change_pin,
             hsm_change_so_pin,
             hsm_unblock_pin,
+            hsm_get_pin_retries,
+            hsm_ping,
+            hsm_get_so_pin_retries,
             hsm_list_keys,
+            hsm_next_free_key_id,
             hsm_generate_rsa_key,
             hsm_generate_ec_key,
             hsm_generate_aes_key,
+            hsm_generate_keys,
             hsm_delete_key,
+            hsm_delete_key_pair,
+            hsm_set_key_label,
+            hsm_list_all_objects,
+            hsm_delete_orphaned_objects,
             hsm_list_certificates,
             hsm_import_certificate,
             hsm_export_certificate,
+            hsm_export_certificate_chain,
             hsm_create_dkek_share,
+            hsm_factory_reset,
+            hsm_rotate_security,
             hsm_import_dkek_share,
             hsm_wrap_key,
             hsm_unwrap_key,
+            hsm_import_key_with_cert,
+            hsm_import_private_key,
             hsm_get_options,
             hsm_set_option,
             hsm_set_datetime,
             hsm_get_device_info,
+            hsm_firmware_status,
+            hsm_get_memory,
+            hsm_read_fault_log,
+            hsm_clear_fault_log,
+            hsm_get_supported_algorithms,
             hsm_enable_secure_lock,
             hsm_disable_secure_lock,
             hsm_set_led_config,
+            hsm_set_share_mode,
+            hsm_set_transmit_timeout,
+            hsm_set_applet_aid,
             hsm_debug_device_raw,
+            hsm_debug_device_raw_structured,
         ])
         .setup(move |app| {
             // Start background device polling for hot-plug detection
             start_device_polling(app.handle().clone(), dm_for_polling);
+
+            // Device list changed: cached device info may belong to an unplugged device
+            app.handle().listen("device-changed", move |_| {
+                fido_for_cache.invalidate_info_cache();
+                hsm_for_cache.invalidate_info_cache();
+            });
+
+            // Forward CTAPHID keep-alive status to the frontend
+            let handle = app.handle().clone();
+            fido_for_events.set_keepalive_handler(Box::new(move |status| {
+                let _ = handle.emit("fido-keepalive", status);
+            }));
+
+            // Ask the frontend to prompt for a touch when press-to-confirm is pending
+            let handle = app.handle().clone();
+            hsm_for_events.set_presence_handler(Box::new(move || {
+                let _ = handle.emit("hsm-user-presence-required", ());
+            }));
+
+            // Report cumulative DKEK share import progress for the current key domain
+            let handle = app.handle().clone();
+            hsm_for_events.set_dkek_progress_handler(Box::new(move |progress| {
+                let _ = handle.emit("dkek-progress", progress);
+            }));
             Ok(())
         })
         .run(tauri::generate_context!())
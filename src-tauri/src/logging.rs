@@ -0,0 +1,56 @@
+use std::io::Write;
+
+/// 將 `log` 記錄輸出至 stderr 的簡易 logger，層級由 `RUST_LOG` 決定（預設關閉）
+struct StderrLogger;
+
+impl log::Log for StderrLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.enabled(record.metadata()) {
+            let _ = writeln!(
+                std::io::stderr(),
+                "[{} {}] {}",
+                record.level(),
+                record.target(),
+                record.args()
+            );
+        }
+    }
+
+    fn flush(&self) {
+        let _ = std::io::stderr().flush();
+    }
+}
+
+static LOGGER: StderrLogger = StderrLogger;
+
+/// 解析 `RUST_LOG` 的層級（僅支援單一層級，例如 `debug`）
+fn parse_level(value: Option<&str>) -> log::LevelFilter {
+    value
+        .and_then(|v| v.trim().parse().ok())
+        .unwrap_or(log::LevelFilter::Off)
+}
+
+/// 安裝 logger；若已有其他 logger 則保留原本的設定
+pub fn init() {
+    let level = parse_level(std::env::var("RUST_LOG").ok().as_deref());
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(level);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_level() {
+        assert_eq!(parse_level(Some("debug")), log::LevelFilter::Debug);
+        assert_eq!(parse_level(Some("WARN")), log::LevelFilter::Warn);
+        assert_eq!(parse_level(Some("nonsense")), log::LevelFilter::Off);
+        assert_eq!(parse_level(None), log::LevelFilter::Off);
+    }
+}
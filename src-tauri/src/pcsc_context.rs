@@ -0,0 +1,177 @@
+use std::sync::{Mutex, OnceLock};
+
+/// 可共用的 PC/SC context 抽象，便於以假物件測試重建邏輯
+pub trait ContextHandle: Clone {
+    fn establish() -> Result<Self, pcsc::Error>;
+    fn is_valid(&self) -> bool;
+}
+
+impl ContextHandle for pcsc::Context {
+    fn establish() -> Result<Self, pcsc::Error> {
+        pcsc::Context::establish(pcsc::Scope::User)
+    }
+
+    fn is_valid(&self) -> bool {
+        pcsc::Context::is_valid(self).is_ok()
+    }
+}
+
+/// 延遲建立、跨模組共用的 PC/SC context；失效時於下次取用重新建立
+pub struct SharedContext<C: ContextHandle = pcsc::Context> {
+    slot: Mutex<Option<C>>,
+}
+
+impl<C: ContextHandle> Default for SharedContext<C> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C: ContextHandle> SharedContext<C> {
+    pub const fn new() -> Self {
+        Self {
+            slot: Mutex::new(None),
+        }
+    }
+
+    /// 取得共用 context，尚未建立或已失效時重新建立
+    pub fn get(&self) -> Result<C, pcsc::Error> {
+        let mut slot = self.slot.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(ctx) = slot.as_ref().filter(|ctx| ctx.is_valid()) {
+            return Ok(ctx.clone());
+        }
+        let ctx = C::establish()?;
+        *slot = Some(ctx.clone());
+        Ok(ctx)
+    }
+
+    /// 捨棄目前的 context，下次 `get` 時重新建立
+    pub fn invalidate(&self) {
+        if let Ok(mut slot) = self.slot.lock() {
+            *slot = None;
+        }
+    }
+
+    /// 依 PC/SC 錯誤判斷 context 是否已不可用，必要時捨棄
+    pub fn report_error(&self, err: &pcsc::Error) {
+        if requires_reestablish(err) {
+            self.invalidate();
+        }
+    }
+}
+
+/// 服務重啟或 handle 失效時，既有 context 無法再使用
+fn requires_reestablish(err: &pcsc::Error) -> bool {
+    matches!(
+        err,
+        pcsc::Error::InvalidHandle | pcsc::Error::NoService | pcsc::Error::ServiceStopped
+    )
+}
+
+/// DeviceManagerImpl 與 HsmModuleImpl 共用的 PC/SC context
+pub fn shared() -> &'static SharedContext {
+    static SHARED: OnceLock<SharedContext> = OnceLock::new();
+    SHARED.get_or_init(SharedContext::new)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    thread_local! {
+        static ESTABLISHED: Cell<u32> = const { Cell::new(0) };
+        static FAIL_ESTABLISH: Cell<bool> = const { Cell::new(false) };
+    }
+
+    /// 假 context：記錄建立次數，並可由測試標記為失效
+    #[derive(Clone)]
+    struct FakeContext {
+        id: u32,
+        valid: Rc<Cell<bool>>,
+    }
+
+    impl ContextHandle for FakeContext {
+        fn establish() -> Result<Self, pcsc::Error> {
+            if FAIL_ESTABLISH.with(Cell::get) {
+                return Err(pcsc::Error::NoService);
+            }
+            let id = ESTABLISHED.with(|n| {
+                n.set(n.get() + 1);
+                n.get()
+            });
+            Ok(FakeContext {
+                id,
+                valid: Rc::new(Cell::new(true)),
+            })
+        }
+
+        fn is_valid(&self) -> bool {
+            self.valid.get()
+        }
+    }
+
+    fn reset_counters() {
+        ESTABLISHED.with(|n| n.set(0));
+        FAIL_ESTABLISH.with(|f| f.set(false));
+    }
+
+    // === 共用 context 測試 ===
+
+    #[test]
+    fn test_context_is_reused() {
+        reset_counters();
+        let shared = SharedContext::<FakeContext>::new();
+        let first = shared.get().unwrap();
+        let second = shared.get().unwrap();
+        assert_eq!(first.id, second.id);
+        assert_eq!(ESTABLISHED.with(Cell::get), 1);
+    }
+
+    #[test]
+    fn test_context_reestablished_after_invalidate() {
+        reset_counters();
+        let shared = SharedContext::<FakeContext>::new();
+        let first = shared.get().unwrap();
+        shared.invalidate();
+        let second = shared.get().unwrap();
+        assert_ne!(first.id, second.id);
+        assert_eq!(ESTABLISHED.with(Cell::get), 2);
+    }
+
+    #[test]
+    fn test_context_reestablished_when_no_longer_valid() {
+        reset_counters();
+        let shared = SharedContext::<FakeContext>::new();
+        let first = shared.get().unwrap();
+        first.valid.set(false);
+        let second = shared.get().unwrap();
+        assert_ne!(first.id, second.id);
+    }
+
+    #[test]
+    fn test_report_error_only_invalidates_on_service_errors() {
+        reset_counters();
+        let shared = SharedContext::<FakeContext>::new();
+        shared.get().unwrap();
+        shared.report_error(&pcsc::Error::NoSmartcard);
+        shared.get().unwrap();
+        assert_eq!(ESTABLISHED.with(Cell::get), 1);
+
+        shared.report_error(&pcsc::Error::ServiceStopped);
+        shared.get().unwrap();
+        assert_eq!(ESTABLISHED.with(Cell::get), 2);
+    }
+
+    #[test]
+    fn test_establish_failure_is_not_cached() {
+        reset_counters();
+        let shared = SharedContext::<FakeContext>::new();
+        FAIL_ESTABLISH.with(|f| f.set(true));
+        assert!(matches!(shared.get(), Err(pcsc::Error::NoService)));
+        FAIL_ESTABLISH.with(|f| f.set(false));
+        assert!(shared.get().is_ok());
+        assert_eq!(ESTABLISHED.with(Cell::get), 1);
+    }
+}
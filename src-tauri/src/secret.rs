@@ -0,0 +1,154 @@
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+
+use serde::{Deserialize, Deserializer};
+
+/// 可將內容清為零的型別（PIN、含 PIN 的 APDU 資料等）
+pub trait Zeroize {
+    fn zeroize(&mut self);
+}
+
+impl Zeroize for Vec<u8> {
+    fn zeroize(&mut self) {
+        // 連同未使用的容量一起清除，避免先前較長的內容殘留
+        let capacity = self.capacity();
+        self.fill(0);
+        self.resize(capacity, 0);
+        std::hint::black_box(self.as_slice());
+        self.clear();
+    }
+}
+
+impl Zeroize for String {
+    fn zeroize(&mut self) {
+        std::mem::take(self).into_bytes().zeroize();
+    }
+}
+
+impl<T: Zeroize> Zeroize for Option<T> {
+    fn zeroize(&mut self) {
+        if let Some(inner) = self {
+            inner.zeroize();
+        }
+    }
+}
+
+/// 在 drop 時自動清除內容的包裝型別
+pub struct Zeroizing<T: Zeroize>(T);
+
+impl<T: Zeroize> Zeroizing<T> {
+    pub fn new(value: T) -> Self {
+        Self(value)
+    }
+}
+
+impl<T: Zeroize> Deref for Zeroizing<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: Zeroize> DerefMut for Zeroizing<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+impl<T: Zeroize + AsRef<[u8]>> AsRef<[u8]> for Zeroizing<T> {
+    fn as_ref(&self) -> &[u8] {
+        self.0.as_ref()
+    }
+}
+
+impl<T: Zeroize> Drop for Zeroizing<T> {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+/// 內容不會出現在除錯輸出中
+impl<T: Zeroize> fmt::Debug for Zeroizing<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Zeroizing(<redacted>)")
+    }
+}
+
+/// 由前端傳入的敏感指令參數（PIN、私鑰）可直接反序列化為 `Zeroizing`
+impl<'de, T: Zeroize + Deserialize<'de>> Deserialize<'de> for Zeroizing<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        T::deserialize(deserializer).map(Zeroizing::new)
+    }
+}
+
+/// PIN 等敏感字串
+pub type SecretString = Zeroizing<String>;
+
+impl From<&str> for SecretString {
+    fn from(value: &str) -> Self {
+        Zeroizing::new(value.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    // === Zeroizing 測試 ===
+
+    /// 記錄 zeroize 被呼叫時的內容，用來觀察 drop 時的清除
+    struct Observed {
+        data: Vec<u8>,
+        cleared: Rc<Cell<bool>>,
+    }
+
+    impl Zeroize for Observed {
+        fn zeroize(&mut self) {
+            self.data.zeroize();
+            self.cleared.set(self.data.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_zeroizing_clears_on_drop() {
+        let cleared = Rc::new(Cell::new(false));
+        let secret = Zeroizing::new(Observed {
+            data: b"123456".to_vec(),
+            cleared: Rc::clone(&cleared),
+        });
+        assert_eq!(secret.data, b"123456");
+        assert!(!cleared.get());
+        drop(secret);
+        assert!(cleared.get());
+    }
+
+    #[test]
+    fn test_option_zeroize_clears_inner() {
+        let mut data = Some(b"654321".to_vec());
+        data.zeroize();
+        assert_eq!(data, Some(Vec::new()));
+    }
+
+    #[test]
+    fn test_string_zeroize_empties() {
+        let mut pin = String::from("123456");
+        pin.zeroize();
+        assert!(pin.is_empty());
+    }
+
+    #[test]
+    fn test_secret_string_deserializes_and_redacts() {
+        let pin: SecretString = serde_json::from_str("\"123456\"").unwrap();
+        assert_eq!(pin.as_str(), "123456");
+        assert_eq!(format!("{pin:?}"), "Zeroizing(<redacted>)");
+    }
+
+    #[test]
+    fn test_zeroizing_bytes_deserialize() {
+        let der: Zeroizing<Vec<u8>> = serde_json::from_str("[48, 0]").unwrap();
+        assert_eq!(der.as_slice(), &[0x30, 0x00]);
+    }
+}
@@ -1,5 +1,7 @@
 use serde::{Deserialize, Serialize};
 
+use crate::hsm::types::DeviceFeatures;
+
 /// 裝置類型列舉
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum DeviceType {
@@ -7,6 +9,40 @@ pub enum DeviceType {
     PicoHsm,
 }
 
+/// 裝置可用狀態
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum DeviceAvailability {
+    /// 可正常連線
+    #[default]
+    Available,
+    /// 已被其他程式佔用（例如 ssh-agent、瀏覽器）
+    Busy,
+    /// 卡片無回應、已移除或被重設
+    Unresponsive,
+}
+
+/// 裝置掃描範圍
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ScanScope {
+    /// HID 與 CCID 皆掃描
+    #[default]
+    Both,
+    /// 僅掃描 HID (Pico-FIDO)
+    HidOnly,
+    /// 僅掃描 CCID (Pico-HSM)
+    CcidOnly,
+}
+
+impl ScanScope {
+    pub fn includes_hid(self) -> bool {
+        matches!(self, ScanScope::Both | ScanScope::HidOnly)
+    }
+
+    pub fn includes_ccid(self) -> bool {
+        matches!(self, ScanScope::Both | ScanScope::CcidOnly)
+    }
+}
+
 /// 裝置基本資訊（共用於 FIDO 與 HSM）
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DeviceInfo {
@@ -14,6 +50,33 @@ pub struct DeviceInfo {
     pub serial: String,
     pub firmware_version: String,
     pub path: String,
+    #[serde(default)]
+    pub availability: DeviceAvailability,
+    /// 裝置類型是否經 USB VID/PID 或 ATR 確認；false 表示僅由讀卡機名稱推斷
+    #[serde(default)]
+    pub verified: bool,
+    /// 掃描時順帶取得的能力摘要（不需額外開啟裝置）；無法取得時為 None
+    #[serde(default)]
+    pub capabilities: Option<DeviceCapabilities>,
+}
+
+/// 開啟裝置後一次取得的識別與能力摘要
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DeviceCapabilities {
+    pub device_type: DeviceType,
+    pub path: String,
+    pub firmware_version: String,
+    /// FIDO：是否已設定 PIN
+    pub pin_set: Option<bool>,
+    /// FIDO：是否支援憑證管理
+    pub cred_mgmt: Option<bool>,
+    /// HSM：是否已初始化
+    pub initialized: Option<bool>,
+    /// HSM：可用記憶體（位元組）
+    pub free_memory: Option<u64>,
+    /// HSM：SELECT 選項欄記錄的裝置功能
+    #[serde(default)]
+    pub features: Option<DeviceFeatures>,
 }
 
 /// LED 組態設定（共用於 FIDO 與 HSM）
@@ -24,3 +87,60 @@ pub struct LedConfig {
     pub dimmable: Option<bool>,
     pub color: Option<String>,
 }
+
+/// 韌體版本（主版本.次版本）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Version {
+    pub major: u8,
+    pub minor: u8,
+}
+
+impl Version {
+    pub const fn new(major: u8, minor: u8) -> Self {
+        Self { major, minor }
+    }
+
+    /// 解析 "5.2"、"v5.2" 或 "5.2.1" 形式的版本字串（修訂號忽略）；無法解析時回傳 None
+    pub fn parse(s: &str) -> Option<Self> {
+        let s = s.trim();
+        let s = s.strip_prefix(['v', 'V']).unwrap_or(s);
+        let mut parts = s.split('.');
+        let major = parts.next()?.parse().ok()?;
+        let minor = parts.next()?.parse().ok()?;
+        Some(Self { major, minor })
+    }
+}
+
+impl std::fmt::Display for Version {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
+    }
+}
+
+/// 韌體是否達建議的最低版本
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FirmwareStatus {
+    pub current: Version,
+    pub minimum_recommended: Version,
+    pub up_to_date: bool,
+}
+
+/// 連線測試結果（僅做唯讀交換，不需要 PIN）
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PingResult {
+    pub reachable: bool,
+    /// 裝置回報的韌體版本（未回報時為 None）
+    pub firmware: Option<String>,
+    /// 無法連線時的錯誤訊息
+    pub error: Option<String>,
+}
+
+impl PingResult {
+    /// 由一次交換的結果建立；通訊錯誤回報為無法連線，而不是指令失敗
+    pub fn from_outcome<E: std::fmt::Display>(outcome: Result<Option<String>, E>) -> Self {
+        match outcome {
+            Ok(firmware) => Self { reachable: true, firmware, error: None },
+            Err(e) => Self { reachable: false, firmware: None, error: Some(e.to_string()) },
+        }
+    }
+}
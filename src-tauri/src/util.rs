@@ -0,0 +1,186 @@
+use crate::error::EncodingError;
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// ISO 7816-5 applet AID 的長度範圍（位元組）
+const AID_LEN_RANGE: std::ops::RangeInclusive<usize> = 5..=16;
+
+// === AID ===
+
+/// 檢查 SELECT 使用的 applet AID 長度，無效時回傳實際長度
+pub fn validate_aid(aid: &[u8]) -> Result<(), usize> {
+    if AID_LEN_RANGE.contains(&aid.len()) {
+        Ok(())
+    } else {
+        Err(aid.len())
+    }
+}
+
+// === Hex ===
+
+/// 將位元組陣列轉換為大寫十六進位字串
+pub fn hex_encode_upper(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02X}")).collect()
+}
+
+/// 將位元組陣列轉換為小寫十六進位字串
+pub fn hex_encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// 將十六進位字串（大小寫皆可）轉換為位元組陣列；長度須為偶數
+pub fn hex_decode(hex: &str) -> Result<Vec<u8>, EncodingError> {
+    if !hex.len().is_multiple_of(2) {
+        return Err(EncodingError::OddHexLength(hex.len()));
+    }
+    hex.as_bytes()
+        .chunks_exact(2)
+        .enumerate()
+        .map(|(i, pair)| Ok(hex_nibble(pair[0], i * 2)? << 4 | hex_nibble(pair[1], i * 2 + 1)?))
+        .collect()
+}
+
+fn hex_nibble(c: u8, position: usize) -> Result<u8, EncodingError> {
+    char::from(c)
+        .to_digit(16)
+        .map(|d| d as u8)
+        .ok_or(EncodingError::InvalidHexChar { position, ch: char::from(c) })
+}
+
+// === Base64 ===
+
+/// 標準 Base64 編碼（含 `=` 補齊）
+pub fn base64_encode(data: &[u8]) -> String {
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let b = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let n = u32::from_be_bytes([0, b[0], b[1], b[2]]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64_ALPHABET[(n >> (18 - 6 * i) & 0x3F) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// 標準 Base64 解碼；忽略空白字元（PEM 換行），補齊可省略
+pub fn base64_decode(text: &str) -> Result<Vec<u8>, EncodingError> {
+    let mut sextets = Vec::with_capacity(text.len());
+    let mut padding = 0;
+    for (position, ch) in text.chars().enumerate() {
+        match ch {
+            c if c.is_ascii_whitespace() => {}
+            '=' => padding += 1,
+            // 補齊之後不應再出現資料字元
+            c if padding == 0 => {
+                let value = BASE64_ALPHABET
+                    .iter()
+                    .position(|&a| char::from(a) == c)
+                    .ok_or(EncodingError::InvalidBase64Char { position, ch: c })?;
+                sextets.push(value as u32);
+            }
+            c => return Err(EncodingError::InvalidBase64Char { position, ch: c }),
+        }
+    }
+    if sextets.len() % 4 == 1 || padding > 2 {
+        return Err(EncodingError::InvalidBase64Length(sextets.len() + padding));
+    }
+
+    let mut out = Vec::with_capacity(sextets.len() * 3 / 4);
+    for group in sextets.chunks(4) {
+        let n = group
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &s)| n | s << (18 - 6 * i));
+        let bytes = n.to_be_bytes();
+        out.extend_from_slice(&bytes[1..group.len()]);
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // === AID 測試 ===
+
+    #[test]
+    fn test_validate_aid_length() {
+        assert!(validate_aid(&[0xA0; 5]).is_ok());
+        assert!(validate_aid(&[0xA0; 16]).is_ok());
+        assert_eq!(validate_aid(&[0xA0; 4]), Err(4));
+        assert_eq!(validate_aid(&[0xA0; 17]), Err(17));
+    }
+
+    // === Hex 測試 ===
+
+    #[test]
+    fn test_hex_round_trip() {
+        let bytes = [0x01, 0x23, 0x45, 0x67, 0x89, 0xAB, 0xCD, 0xEF];
+        assert_eq!(hex_encode_upper(&bytes), "0123456789ABCDEF");
+        assert_eq!(hex_encode(&bytes), "0123456789abcdef");
+        assert_eq!(hex_decode("0123456789abcdef").unwrap(), bytes);
+        assert_eq!(hex_decode("0123456789ABCDEF").unwrap(), bytes);
+    }
+
+    #[test]
+    fn test_hex_decode_empty() {
+        assert_eq!(hex_decode("").unwrap(), Vec::<u8>::new());
+        assert_eq!(hex_encode(&[]), "");
+    }
+
+    #[test]
+    fn test_hex_decode_odd_length() {
+        assert!(matches!(hex_decode("ABC"), Err(EncodingError::OddHexLength(3))));
+    }
+
+    #[test]
+    fn test_hex_decode_invalid_char() {
+        assert!(matches!(
+            hex_decode("0G"),
+            Err(EncodingError::InvalidHexChar { position: 1, ch: 'G' })
+        ));
+        assert!(matches!(
+            hex_decode("+1"),
+            Err(EncodingError::InvalidHexChar { position: 0, .. })
+        ));
+    }
+
+    // === Base64 測試 ===
+
+    #[test]
+    fn test_base64_encode_padding() {
+        assert_eq!(base64_encode(b""), "");
+        assert_eq!(base64_encode(b"f"), "Zg==");
+        assert_eq!(base64_encode(b"fo"), "Zm8=");
+        assert_eq!(base64_encode(b"foo"), "Zm9v");
+        assert_eq!(base64_encode(b"foobar"), "Zm9vYmFy");
+    }
+
+    #[test]
+    fn test_base64_decode_round_trip() {
+        for data in [&b""[..], b"f", b"fo", b"foo", b"foobar", &[0xFF, 0x00, 0xFE]] {
+            assert_eq!(base64_decode(&base64_encode(data)).unwrap(), data);
+        }
+        assert_eq!(base64_decode("Zm9v\nYmFy\n").unwrap(), b"foobar");
+        assert_eq!(base64_decode("Zg").unwrap(), b"f");
+    }
+
+    #[test]
+    fn test_base64_decode_invalid() {
+        assert!(matches!(
+            base64_decode("Zm9*"),
+            Err(EncodingError::InvalidBase64Char { position: 3, ch: '*' })
+        ));
+        assert!(matches!(base64_decode("Zg==Zg"), Err(EncodingError::InvalidBase64Char { .. })));
+        assert!(matches!(base64_decode("Z"), Err(EncodingError::InvalidBase64Length(1))));
+    }
+}
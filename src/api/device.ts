@@ -11,6 +11,11 @@ export function openDevice(path: string): Promise<void> {
   return safeInvoke<void>('open_device', { path });
 }
 
+/** 取得不隨插拔變動的裝置識別碼（FIDO: `fido:<AAGUID>[:<序號>]`，HSM: `hsm:<CHR>`） */
+export function getDeviceId(path: string): Promise<string> {
+  return safeInvoke<string>('get_device_id', { path });
+}
+
 /** 診斷用：列出所有 PC/SC 讀卡機及其 ATR */
 export function listAllReaders(): Promise<string[]> {
   return safeInvoke<string[]>('list_all_readers');
@@ -15,35 +15,111 @@ const ERROR_MESSAGES: Record<string, string> = {
   // FIDO 錯誤
   PinInvalid: 'PIN 碼錯誤',
   PinLocked: 'PIN 已鎖定，需要重設裝置',
+  PinAuthBlocked: '連續 PIN 錯誤次數過多，請拔除並重新插入裝置後再試',
+  PinChangeRequired: '裝置要求先變更 PIN，請完成變更後再進行其他操作',
   PinTooShort: 'PIN 長度不足（最少 4 位元組）',
   PinTooLong: 'PIN 長度超過上限（最多 63 位元組）',
   CredentialNotFound: '找不到指定的憑證',
+  BulkDeleteAborted: '刪除憑證途中失敗，部分憑證已被刪除',
   NotSupported: '裝置不支援此功能',
+  ResetTokenInvalid: '重設確認碼無效，請重新開始重設流程',
+  ResetTokenExpired: '重設確認碼已過期，請重新開始重設流程',
+  InvalidAid: 'AID 長度無效（需 5-16 位元組）',
 
   // HSM 錯誤
   AuthenticationFailed: '驗證失敗，請確認 PIN 碼正確',
   SoPinInvalid: 'SO-PIN 錯誤',
   SoPinLocked: 'SO-PIN 已鎖定，裝置需要重新初始化',
   NotInitialized: '裝置尚未初始化',
+  DeviceNotInitialized: '裝置尚未初始化，請先執行初始化',
   KeyNotFound: '找不到指定的金鑰',
   CertificateNotFound: '找不到指定的憑證',
+  InvalidCertificate: '憑證格式無效，請使用 PEM 或 DER 檔案',
+  CertificateKeyMismatch: '憑證的公鑰與該 ID 的金鑰不符',
+  CertificateKeyUnverified: '無法確認憑證的公鑰與該 ID 的金鑰相符，確認無誤後請以強制模式匯入',
   InsufficientMemory: '裝置記憶體不足',
   DkekNotInitialized: '尚未初始化 DKEK，請先匯入 DKEK 份額',
+  InvalidDkekShares: 'DKEK 份額數超出裝置允許的範圍',
+  FactoryResetNotConfirmed: '未確認清除裝置，已取消重設',
+  CardReset: '卡片已重設，請重試',
 };
 
+/** 裝置管理錯誤（domain 為 Device）的使用者可讀訊息，依 DeviceError 變體名稱對應 */
+const DEVICE_ERROR_MESSAGES: Record<string, string> = {
+  NotFound: ERROR_MESSAGES.DeviceNotFound,
+  ConnectionLost: ERROR_MESSAGES.DeviceDisconnected,
+  Timeout: ERROR_MESSAGES.ConnectionTimeout,
+  OpenFailed: '裝置開啟失敗，請確認裝置未被其他程式佔用',
+  DeviceBusy: ERROR_MESSAGES.DeviceBusy,
+  UnsupportedDevice: '不支援的裝置類型',
+};
+
+/**
+ * 後端指令層統一錯誤：`domain` 標示來源模組，`kind` 為錯誤變體名稱，
+ * `message` 為後端的錯誤說明，`error` 為該模組的原始錯誤（保留重試次數等欄位）
+ */
+export interface AppError {
+  domain: 'Device' | 'Fido' | 'Hsm' | 'Cbor' | 'Apdu';
+  kind: string;
+  message: string;
+  error: unknown;
+}
+
+export function isAppError(error: unknown): error is AppError {
+  return (
+    !!error &&
+    typeof error === 'object' &&
+    'domain' in error &&
+    'kind' in error &&
+    'message' in error
+  );
+}
+
+/** 取得錯誤的變體名稱；非後端結構化錯誤時為 undefined */
+export function errorKind(error: unknown): string | undefined {
+  return isAppError(error) ? error.kind : undefined;
+}
+
+/** 將 invoke 錯誤轉為文字；後端錯誤使用其 `message` */
+export function errorText(error: unknown): string {
+  if (typeof error === 'string') return error;
+  if (error instanceof Error) return error.message;
+  if (isAppError(error)) return error.message;
+  if (error && typeof error === 'object') return JSON.stringify(error);
+  return String(error);
+}
+
+/** 取得帶資料變體的內容，例如 `{ PinInvalid: 2 }` 的 `2` */
+function variantData(error: AppError): unknown {
+  const inner = error.error;
+  if (!inner || typeof inner !== 'object') return undefined;
+  return (inner as Record<string, unknown>)[error.kind];
+}
+
 /** 將 invoke 錯誤轉換為使用者可讀訊息 */
 export function toUserMessage(error: unknown): string {
-  const raw = String(error);
+  if (!isAppError(error)) {
+    return errorText(error) || '發生未知錯誤';
+  }
+
+  const { domain, kind } = error;
+  if (domain === 'Device' && kind in DEVICE_ERROR_MESSAGES) {
+    return DEVICE_ERROR_MESSAGES[kind];
+  }
 
-  // 嘗試匹配已知錯誤類型
-  for (const [key, message] of Object.entries(ERROR_MESSAGES)) {
-    if (raw.includes(key)) {
-      return message;
+  // PIN 錯誤附帶剩餘次數
+  if (kind === 'PinInvalid') {
+    const retries = variantData(error);
+    if (typeof retries === 'number') {
+      return `${ERROR_MESSAGES.PinInvalid}，剩餘 ${retries} 次`;
     }
   }
 
-  // 回傳原始錯誤訊息
-  return raw || '發生未知錯誤';
+  // 依變體名稱完全比對已知錯誤類型，其餘使用後端的錯誤說明
+  if (Object.prototype.hasOwnProperty.call(ERROR_MESSAGES, kind)) {
+    return ERROR_MESSAGES[kind];
+  }
+  return error.message || '發生未知錯誤';
 }
 
 /** 封裝 invoke 呼叫，統一錯誤處理 */
@@ -1,16 +1,30 @@
 import { safeInvoke } from './errors';
 import type {
+  AssertionCheck,
   FidoDeviceInfo,
   FidoCredential,
+  FirmwareStatus,
   OathCredential,
   OathCredentialParams,
+  OathSlotInfo,
   LedConfig,
+  MinPinLengthInfo,
+  PingResult,
+  ResetPreparation,
 } from '../types';
 
 // --- 裝置資訊 ---
 
-export function fidoGetInfo(path: string): Promise<FidoDeviceInfo> {
-  return safeInvoke<FidoDeviceInfo>('fido_get_info', { path });
+export function fidoGetInfo(path: string, refresh = false): Promise<FidoDeviceInfo> {
+  return safeInvoke<FidoDeviceInfo>('fido_get_info', { path, refresh });
+}
+
+export function fidoFirmwareStatus(path: string): Promise<FirmwareStatus> {
+  return safeInvoke<FirmwareStatus>('fido_firmware_status', { path });
+}
+
+export function fidoPing(path: string): Promise<PingResult> {
+  return safeInvoke<PingResult>('fido_ping', { path });
 }
 
 // --- PIN 管理 ---
@@ -37,8 +51,26 @@ export function fidoDeleteCredential(path: string, pin: string, credentialId: nu
   return safeInvoke<void>('fido_delete_credential', { path, pin, credentialId });
 }
 
+/** 刪除所有可發現憑證（不影響 PIN 設定），回傳刪除數量 */
+export function fidoDeleteAllCredentials(path: string, pin: string): Promise<number> {
+  return safeInvoke<number>('fido_delete_all_credentials', { path, pin });
+}
+
+export function fidoAssertCredential(
+  path: string,
+  rpId: string,
+  credentialId: number[],
+  pin: string,
+): Promise<AssertionCheck> {
+  return safeInvoke<AssertionCheck>('fido_assert_credential', { path, rpId, credentialId, pin });
+}
+
 // --- 組態設定 ---
 
+export function fidoGetMinPinLength(path: string): Promise<MinPinLengthInfo> {
+  return safeInvoke<MinPinLengthInfo>('fido_get_min_pin_length', { path });
+}
+
 export function fidoSetMinPinLength(path: string, pin: string, length: number): Promise<void> {
   return safeInvoke<void>('fido_set_min_pin_length', { path, pin, length });
 }
@@ -57,6 +89,10 @@ export function fidoListOath(path: string): Promise<OathCredential[]> {
   return safeInvoke<OathCredential[]>('fido_list_oath', { path });
 }
 
+export function fidoOathSlotInfo(path: string): Promise<OathSlotInfo> {
+  return safeInvoke<OathSlotInfo>('fido_oath_slot_info', { path });
+}
+
 export function fidoAddOath(path: string, credential: OathCredentialParams): Promise<void> {
   return safeInvoke<void>('fido_add_oath', { path, credential });
 }
@@ -79,6 +115,10 @@ export function fidoRestoreFromWords(path: string, pin: string, words: string[])
   return safeInvoke<void>('fido_restore_from_words', { path, pin, words });
 }
 
-export function fidoReset(path: string): Promise<void> {
-  return safeInvoke<void>('fido_reset', { path });
+export function fidoResetPrepare(path: string, pin?: string): Promise<ResetPreparation> {
+  return safeInvoke<ResetPreparation>('fido_reset_prepare', { path, pin });
+}
+
+export function fidoResetConfirm(path: string, token: string): Promise<void> {
+  return safeInvoke<void>('fido_reset_confirm', { path, token });
 }
@@ -4,8 +4,11 @@ import type {
   HsmKeyInfo,
   HsmCertInfo,
   DkekStatus,
+  FirmwareStatus,
   HsmOptions,
   LedConfig,
+  ObjectRef,
+  PingResult,
 } from '../types';
 
 // --- 初始化 ---
@@ -28,10 +31,27 @@ export function hsmChangeSoPin(path: string, oldSoPin: string, newSoPin: string)
   return safeInvoke<void>('hsm_change_so_pin', { path, oldSoPin, newSoPin });
 }
 
+/** 以目前的 SO-PIN 重新初始化裝置並清除所有金鑰與憑證；confirmation 須為 "ERASE" */
+export function hsmFactoryReset(
+  path: string,
+  soPin: string,
+  newPin: string,
+  newSoPin: string,
+  dkekShares: number,
+  confirmation: string,
+): Promise<HsmDeviceInfo> {
+  return safeInvoke<HsmDeviceInfo>('hsm_factory_reset', { path, soPin, newPin, newSoPin, dkekShares, confirmation });
+}
+
 export function hsmUnblockPin(path: string, soPin: string, newPin: string): Promise<void> {
   return safeInvoke<void>('hsm_unblock_pin', { path, soPin, newPin });
 }
 
+/** User PIN 剩餘重試次數（不消耗次數）；本次連線已驗證時為 null */
+export function hsmGetPinRetries(path: string): Promise<number | null> {
+  return safeInvoke<number | null>('hsm_get_pin_retries', { path });
+}
+
 // --- 金鑰管理 ---
 
 export function hsmListKeys(path: string, pin: string): Promise<HsmKeyInfo[]> {
@@ -60,6 +80,18 @@ export function hsmDeleteKey(path: string, pin: string, id: number, keyType: str
   return safeInvoke<void>('hsm_delete_key', { path, pin, id, keyType });
 }
 
+export function hsmSetKeyLabel(path: string, pin: string, id: number, label: string): Promise<void> {
+  return safeInvoke<void>('hsm_set_key_label', { path, pin, id, label });
+}
+
+export function hsmListAllObjects(path: string, pin: string): Promise<ObjectRef[]> {
+  return safeInvoke<ObjectRef[]>('hsm_list_all_objects', { path, pin });
+}
+
+export function hsmDeleteOrphanedObjects(path: string, pin: string): Promise<ObjectRef[]> {
+  return safeInvoke<ObjectRef[]>('hsm_delete_orphaned_objects', { path, pin });
+}
+
 // --- 憑證管理 ---
 
 export function hsmListCertificates(path: string, pin: string): Promise<HsmCertInfo[]> {
@@ -67,9 +99,9 @@ export function hsmListCertificates(path: string, pin: string): Promise<HsmCertI
 }
 
 export function hsmImportCertificate(
-  path: string, pin: string, id: number, certData: number[],
+  path: string, pin: string, id: number, certData: number[], force = false,
 ): Promise<void> {
-  return safeInvoke<void>('hsm_import_certificate', { path, pin, id, certData });
+  return safeInvoke<void>('hsm_import_certificate', { path, pin, id, certData, force });
 }
 
 export function hsmExportCertificate(path: string, id: number): Promise<number[]> {
@@ -112,8 +144,25 @@ export function hsmSetDatetime(path: string): Promise<void> {
   return safeInvoke<void>('hsm_set_datetime', { path });
 }
 
-export function hsmGetDeviceInfo(path: string): Promise<HsmDeviceInfo> {
-  return safeInvoke<HsmDeviceInfo>('hsm_get_device_info', { path });
+export function hsmGetDeviceInfo(path: string, refresh = false): Promise<HsmDeviceInfo> {
+  return safeInvoke<HsmDeviceInfo>('hsm_get_device_info', { path, refresh });
+}
+
+export function hsmFirmwareStatus(path: string): Promise<FirmwareStatus> {
+  return safeInvoke<FirmwareStatus>('hsm_firmware_status', { path });
+}
+
+export function hsmPing(path: string): Promise<PingResult> {
+  return safeInvoke<PingResult>('hsm_ping', { path });
+}
+
+/** 韌體故障紀錄（每筆一行）；韌體未提供時拋出 NotSupported */
+export function hsmReadFaultLog(path: string): Promise<string[]> {
+  return safeInvoke<string[]>('hsm_read_fault_log', { path });
+}
+
+export function hsmClearFaultLog(path: string): Promise<void> {
+  return safeInvoke<void>('hsm_clear_fault_log', { path });
 }
 
 // --- 安全鎖 ---
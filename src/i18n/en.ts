@@ -105,6 +105,18 @@ const en: Translations = {
     diagRawData: '🔍 Raw Device Data',
     diagReading: 'Reading…',
     diagHint: 'Show raw hex response of SELECT / INIT / CMD_MEMORY',
+    lifeCycle: 'Life Cycle',
+    deviceFeatures: 'Enabled Features',
+    noFeatures: 'None',
+    featResetRetryCounter: 'PIN reset by SO-PIN',
+    featTransportPin: 'Transport PIN',
+    featSessionPin: 'Session PIN',
+    featReplacePka: 'Public key auth',
+    featCombinedAuth: 'Combined auth',
+    featRrcResetOnly: 'Retry reset only',
+    featBootselButton: 'BOOTSEL button confirm',
+    featKeyCounterAll: 'Usage counter on all keys',
+    featSecureLock: 'Secure lock',
   },
   hsmInit: {
     title: 'Device Initialization',
@@ -125,7 +137,7 @@ const en: Translations = {
     dkekNotice: 'Initialization complete. You set {n} DKEK shares. Go to "Backup" tab to import all DKEK shares to enable key backup.',
     pinError: 'PIN must be 6-16 characters',
     soPinError: 'SO-PIN must be 16 hex characters',
-    dkekError: 'DKEK shares must be 0-255',
+    dkekError: 'DKEK shares must be 0-16',
   },
   hsmPin: {
     lockWarning: 'After 3 wrong PIN attempts, PIN will be locked. Use SO-PIN to unblock. After 15 wrong SO-PIN attempts, the device will be permanently locked and require re-initialization.',
@@ -271,6 +283,7 @@ const en: Translations = {
     firmwareVersion: 'Firmware Version',
     serialNumber: 'Serial Number',
     aaguid: 'AAGUID',
+    model: 'Model',
     pinStatus: 'PIN Status',
     pinSet: 'PIN Set',
     retriesLeft: 'Retries Left',
@@ -387,6 +400,7 @@ const en: Translations = {
     resetBtn: 'Reset Device',
     resetConfirmTitle: 'Reset Device',
     resetConfirmMsg: 'This will erase all data including PIN, credentials, and settings. This cannot be undone. Continue?',
+    resetCredentialCount: '{n} discoverable credentials on this device will be permanently lost.',
     resetConfirmBtn: 'Confirm Reset',
     resetSuccess: 'Device reset',
     resetFailed: 'Reset failed',
@@ -106,6 +106,18 @@ export interface Translations {
     diagRawData: string;
     diagReading: string;
     diagHint: string;
+    lifeCycle: string;
+    deviceFeatures: string;
+    noFeatures: string;
+    featResetRetryCounter: string;
+    featTransportPin: string;
+    featSessionPin: string;
+    featReplacePka: string;
+    featCombinedAuth: string;
+    featRrcResetOnly: string;
+    featBootselButton: string;
+    featKeyCounterAll: string;
+    featSecureLock: string;
   };
   // HSM Init
   hsmInit: {
@@ -279,6 +291,7 @@ export interface Translations {
     firmwareVersion: string;
     serialNumber: string;
     aaguid: string;
+    model: string;
     pinStatus: string;
     pinSet: string;
     retriesLeft: string;
@@ -400,6 +413,7 @@ export interface Translations {
     resetBtn: string;
     resetConfirmTitle: string;
     resetConfirmMsg: string;
+    resetCredentialCount: string;
     resetConfirmBtn: string;
     resetSuccess: string;
     resetFailed: string;
@@ -105,6 +105,18 @@ const zhCN: Translations = {
     diagRawData: '🔍 设备原始数据',
     diagReading: '读取中…',
     diagHint: '显示 SELECT / INIT / CMD_MEMORY 的原始 hex 响应',
+    lifeCycle: '生命周期',
+    deviceFeatures: '已启用功能',
+    noFeatures: '无',
+    featResetRetryCounter: 'SO-PIN 重置 PIN',
+    featTransportPin: '传输 PIN',
+    featSessionPin: 'Session PIN',
+    featReplacePka: '公钥认证',
+    featCombinedAuth: '合并认证',
+    featRrcResetOnly: '仅重置重试次数',
+    featBootselButton: 'BOOTSEL 按钮确认',
+    featKeyCounterAll: '所有密钥使用计数',
+    featSecureLock: '安全锁',
   },
   hsmInit: {
     title: '设备初始化',
@@ -125,7 +137,7 @@ const zhCN: Translations = {
     dkekNotice: '初始化完成。您已设置 {n} 份 DKEK 份额，请前往「备份还原」页面依次导入所有 DKEK 份额，以启用密钥备份功能。',
     pinError: 'PIN 长度须为 6-16 字符',
     soPinError: 'SO-PIN 须为 16 个十六进制字符',
-    dkekError: 'DKEK 份数须为 0-255 的整数',
+    dkekError: 'DKEK 份数须为 0-16 的整数',
   },
   hsmPin: {
     lockWarning: '若 PIN 输入错误达 3 次，PIN 将被锁定，请使用 SO-PIN 解锁。若 SO-PIN 输入错误达 15 次，设备将完全锁定且需要重新初始化。',
@@ -271,6 +283,7 @@ const zhCN: Translations = {
     firmwareVersion: '固件版本',
     serialNumber: '序列号',
     aaguid: 'AAGUID',
+    model: '型号',
     pinStatus: 'PIN 状态',
     pinSet: 'PIN 已设置',
     retriesLeft: '剩余重试次数',
@@ -387,6 +400,7 @@ const zhCN: Translations = {
     resetBtn: '重置设备',
     resetConfirmTitle: '重置设备',
     resetConfirmMsg: '此操作将清除设备上的所有数据，包括 PIN、凭证及设置。此操作无法恢复，确定要继续吗？',
+    resetCredentialCount: '设备上的 {n} 个可发现凭证将永久丢失。',
     resetConfirmBtn: '确认重置',
     resetSuccess: '设备已重置',
     resetFailed: '重置失败',
@@ -105,6 +105,18 @@ const zhTW: Translations = {
     diagRawData: '🔍 裝置原始資料',
     diagReading: '讀取中…',
     diagHint: '顯示 SELECT / INIT / CMD_MEMORY 的原始 hex 回應',
+    lifeCycle: '生命週期',
+    deviceFeatures: '已啟用功能',
+    noFeatures: '無',
+    featResetRetryCounter: 'SO-PIN 重設 PIN',
+    featTransportPin: '傳輸 PIN',
+    featSessionPin: 'Session PIN',
+    featReplacePka: '公鑰認證',
+    featCombinedAuth: '合併認證',
+    featRrcResetOnly: '僅重設重試次數',
+    featBootselButton: 'BOOTSEL 按鈕確認',
+    featKeyCounterAll: '所有金鑰使用計數',
+    featSecureLock: '安全鎖',
   },
   hsmInit: {
     title: '裝置初始化',
@@ -125,7 +137,7 @@ const zhTW: Translations = {
     dkekNotice: '初始化完成。您已設定 {n} 份 DKEK 份額，請前往「備份還原」頁面依序匯入所有 DKEK 份額，以啟用金鑰備份功能。',
     pinError: 'PIN 長度須為 6-16 字元',
     soPinError: 'SO-PIN 須為 16 個十六進位字元',
-    dkekError: 'DKEK 份數須為 0-255 的整數',
+    dkekError: 'DKEK 份數須為 0-16 的整數',
   },
   hsmPin: {
     lockWarning: '若 PIN 輸入錯誤達 3 次，PIN 將被鎖定，請使用 SO-PIN 解鎖。若 SO-PIN 輸入錯誤達 15 次，裝置將完全鎖定且需要重新初始化。',
@@ -271,6 +283,7 @@ const zhTW: Translations = {
     firmwareVersion: '韌體版本',
     serialNumber: '序號',
     aaguid: 'AAGUID',
+    model: '型號',
     pinStatus: 'PIN 狀態',
     pinSet: 'PIN 已設定',
     retriesLeft: '剩餘重試次數',
@@ -387,6 +400,7 @@ const zhTW: Translations = {
     resetBtn: '重設裝置',
     resetConfirmTitle: '重設裝置',
     resetConfirmMsg: '此操作將清除裝置上的所有資料，包括 PIN、憑證及設定。此操作無法復原，確定要繼續嗎？',
+    resetCredentialCount: '裝置上的 {n} 筆可發現憑證將永久遺失。',
     resetConfirmBtn: '確認重設',
     resetSuccess: '裝置已重設',
     resetFailed: '重設失敗',
@@ -1,9 +1,10 @@
 import { useState } from 'react';
 import { useDeviceStore } from '../../store/deviceStore';
 import { useI18n } from '../../i18n';
-import { fidoGetBackupWords, fidoRestoreFromWords, fidoReset } from '../../api/fido';
+import { fidoGetBackupWords, fidoRestoreFromWords, fidoResetPrepare, fidoResetConfirm } from '../../api/fido';
 import Notification from '../../components/Notification';
 import ConfirmDialog from '../../components/ConfirmDialog';
+import type { ResetPreparation } from '../../types';
 
 const styles = {
   container: { maxWidth: 560 },
@@ -82,8 +83,8 @@ export default function FidoBackup() {
   const [submitting, setSubmitting] = useState(false);
   const [notification, setNotification] = useState<{ message: string; type: 'success' | 'error' } | null>(null);
 
-  // Reset
-  const [resetDialogOpen, setResetDialogOpen] = useState(false);
+  // Reset：先取得確認碼，確認對話框送出時才實際重設
+  const [resetPreparation, setResetPreparation] = useState<ResetPreparation | null>(null);
 
   // Backup words
   const [backupWords, setBackupWords] = useState<string[] | null>(null);
@@ -91,12 +92,25 @@ export default function FidoBackup() {
   // Restore
   const [restoreInput, setRestoreInput] = useState('');
 
-  const handleReset = async () => {
+  const handleResetPrepare = async () => {
     if (!devicePath) return;
-    setResetDialogOpen(false);
     setSubmitting(true);
     try {
-      await fidoReset(devicePath);
+      setResetPreparation(await fidoResetPrepare(devicePath, pin || undefined));
+    } catch (e) {
+      setNotification({ message: `${t.fidoBackup.resetFailed}：${e}`, type: 'error' });
+    } finally {
+      setSubmitting(false);
+    }
+  };
+
+  const handleReset = async () => {
+    if (!devicePath || !resetPreparation) return;
+    const { token } = resetPreparation;
+    setResetPreparation(null);
+    setSubmitting(true);
+    try {
+      await fidoResetConfirm(devicePath, token);
       setNotification({ message: t.fidoBackup.resetSuccess, type: 'success' });
     } catch (e) {
       setNotification({ message: `${t.fidoBackup.resetFailed}：${e}`, type: 'error' });
@@ -147,12 +161,16 @@ export default function FidoBackup() {
         onClose={() => setNotification(null)}
       />
       <ConfirmDialog
-        open={resetDialogOpen}
+        open={!!resetPreparation}
         title={t.fidoBackup.resetConfirmTitle}
-        message={t.fidoBackup.resetConfirmMsg}
+        message={
+          resetPreparation?.credentialsToLose != null
+            ? `${t.fidoBackup.resetCredentialCount.replace('{n}', String(resetPreparation.credentialsToLose))} ${t.fidoBackup.resetConfirmMsg}`
+            : t.fidoBackup.resetConfirmMsg
+        }
         confirmLabel={t.fidoBackup.resetConfirmBtn}
         onConfirm={handleReset}
-        onCancel={() => setResetDialogOpen(false)}
+        onCancel={() => setResetPreparation(null)}
         destructive
       />
 
@@ -179,7 +197,7 @@ export default function FidoBackup() {
         <div style={styles.sectionTitle}>{t.fidoBackup.resetDevice}</div>
         <button
           style={{ ...styles.btn, ...styles.btnDanger, ...(submitting ? styles.btnDisabled : {}) }}
-          onClick={() => setResetDialogOpen(true)}
+          onClick={handleResetPrepare}
           disabled={submitting}
         >
           {t.fidoBackup.resetBtn}
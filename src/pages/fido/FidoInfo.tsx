@@ -117,6 +117,12 @@ export default function FidoInfo() {
           <span style={styles.label}>{t.fidoInfo.aaguid}</span>
           <span style={{ ...styles.value, ...styles.mono }}>{info.aaguid}</span>
         </div>
+        {info.modelName && (
+          <div style={styles.row}>
+            <span style={styles.label}>{t.fidoInfo.model}</span>
+            <span style={styles.value}>{info.modelName}</span>
+          </div>
+        )}
       </div>
 
       {/* PIN 狀態 */}
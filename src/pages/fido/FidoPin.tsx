@@ -148,7 +148,7 @@ export default function FidoPin() {
       await fidoSetPin(devicePath, newPin);
       setNotification({ message: t.fidoPin.setPinSuccess, type: 'success' });
       clearForm();
-      await loadInfo();
+      await loadInfo(true);
       await refreshRetries();
     } catch (e) {
       setNotification({ message: `${t.fidoPin.setPinFailed}：${e}`, type: 'error' });
@@ -2,6 +2,7 @@ import { useState } from 'react';
 import { useDeviceStore } from '../../store/deviceStore';
 import { useHsmStore } from '../../store/hsmStore';
 import { useI18n } from '../../i18n';
+import { errorText } from '../../api/errors';
 import {
   hsmListCertificates,
   hsmImportCertificate,
@@ -122,7 +123,7 @@ export default function HsmCerts() {
       useHsmStore.setState({ certificates: list });
       setLoadError('');
     } catch (e) {
-      setLoadError(String(e));
+      setLoadError(errorText(e));
     } finally {
       setLoading(false);
     }
@@ -137,7 +138,7 @@ export default function HsmCerts() {
       setUnlocked(true);
       setLoadError('');
     } catch (e) {
-      setLoadError(String(e));
+      setLoadError(errorText(e));
     } finally {
       setLoading(false);
     }
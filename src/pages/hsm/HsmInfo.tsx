@@ -2,7 +2,10 @@ import { useEffect, useState } from 'react';
 import { invoke } from '@tauri-apps/api/core';
 import { useHsmStore } from '../../store/hsmStore';
 import { useI18n } from '../../i18n';
+import type { Translations } from '../../i18n';
+import { errorKind, errorText } from '../../api/errors';
 import LoadingIndicator from '../../components/LoadingIndicator';
+import type { DeviceFeatures } from '../../types';
 
 const styles = {
   container: {
@@ -147,6 +150,22 @@ function formatBytes(bytes: number): string {
 
 type DeviceStatus = 'unknown' | 'connected' | 'initialized' | 'pin_verified';
 
+/** 列出已啟用功能的顯示名稱 */
+function enabledFeatureLabels(features: DeviceFeatures, t: Translations): string[] {
+  const labels: [boolean, string][] = [
+    [features.resetRetryCounter, t.hsmInfo.featResetRetryCounter],
+    [features.transportPin, t.hsmInfo.featTransportPin],
+    [features.sessionPin, t.hsmInfo.featSessionPin],
+    [features.replacePka, t.hsmInfo.featReplacePka],
+    [features.combinedAuth, t.hsmInfo.featCombinedAuth],
+    [features.rrcResetOnly, t.hsmInfo.featRrcResetOnly],
+    [features.bootselButton, t.hsmInfo.featBootselButton],
+    [features.keyCounterAll, t.hsmInfo.featKeyCounterAll],
+    [features.secureLock, t.hsmInfo.featSecureLock],
+  ];
+  return labels.filter(([enabled]) => enabled).map(([, label]) => label);
+}
+
 export default function HsmInfo() {
   const t = useI18n();
   const { info, loading, error, loadInfo } = useHsmStore();
@@ -181,10 +200,11 @@ export default function HsmInfo() {
       setPinResult(t.hsmInfo.pinSuccess);
       setDeviceStatus('pin_verified');
     } catch (e) {
-      const msg = String(e);
-      if (msg.includes('PinInvalid') || msg.includes('63C')) {
+      const kind = errorKind(e);
+      const msg = errorText(e);
+      if (kind === 'PinInvalid') {
         setPinResult(t.hsmInfo.pinIncorrect);
-      } else if (msg.includes('PinLocked') || msg.includes('6983')) {
+      } else if (kind === 'PinLocked') {
         setPinResult(t.hsmInfo.pinLocked);
       } else {
         setPinResult(`❌ ${msg}`);
@@ -201,7 +221,7 @@ export default function HsmInfo() {
       const result = await invoke<string[]>('hsm_debug_device_raw');
       setDiagResult(result);
     } catch (e) {
-      setDiagResult([`${t.common.error}: ${String(e)}`]);
+      setDiagResult([`${t.common.error}: ${errorText(e)}`]);
     } finally {
       setDiagLoading(false);
     }
@@ -311,6 +331,24 @@ export default function HsmInfo() {
                 {info.serialNumber || t.hsmInfo.serialPlaceholder}
               </span>
             </div>
+            {info.lifeCycle && (
+              <div style={styles.row}>
+                <span style={styles.label}>{t.hsmInfo.lifeCycle}</span>
+                <span style={styles.value}>
+                  {typeof info.lifeCycle === 'string'
+                    ? info.lifeCycle
+                    : `0x${info.lifeCycle.Unknown.toString(16).padStart(2, '0').toUpperCase()}`}
+                </span>
+              </div>
+            )}
+            {info.features && (
+              <div style={styles.row}>
+                <span style={styles.label}>{t.hsmInfo.deviceFeatures}</span>
+                <span style={styles.value}>
+                  {enabledFeatureLabels(info.features, t).join('、') || t.hsmInfo.noFeatures}
+                </span>
+              </div>
+            )}
           </div>
 
           {/* 記憶體使用量 */}
@@ -73,7 +73,7 @@ export default function HsmInit() {
     if (pin.length < 6 || pin.length > 16) errs.pin = t.hsmInit.pinError;
     if (!HEX_RE.test(soPin)) errs.soPin = t.hsmInit.soPinError;
     const shares = Number(dkekShares);
-    if (!Number.isInteger(shares) || shares < 0 || shares > 255) errs.dkekShares = t.hsmInit.dkekError;
+    if (!Number.isInteger(shares) || shares < 0 || shares > 16) errs.dkekShares = t.hsmInit.dkekError;
     return errs;
   }
 
@@ -2,6 +2,7 @@ import { useState } from 'react';
 import { useDeviceStore } from '../../store/deviceStore';
 import { useHsmStore } from '../../store/hsmStore';
 import { useI18n } from '../../i18n';
+import { errorText } from '../../api/errors';
 import {
   hsmListKeys,
   hsmGenerateRsaKey,
@@ -177,7 +178,7 @@ export default function HsmKeys() {
       useHsmStore.setState({ keys: list });
       setLoadError('');
     } catch (e) {
-      setLoadError(String(e));
+      setLoadError(errorText(e));
     } finally {
       setLoading(false);
     }
@@ -192,7 +193,7 @@ export default function HsmKeys() {
       setUnlocked(true);
       setLoadError('');
     } catch (e) {
-      setLoadError(String(e));
+      setLoadError(errorText(e));
     } finally {
       setLoading(false);
     }
@@ -385,6 +386,8 @@ export default function HsmKeys() {
                         <option value="secp384r1">secp384r1</option>
                         <option value="secp521r1">secp521r1</option>
                         <option value="brainpoolP256r1">brainpoolP256r1</option>
+                        <option value="brainpoolP384r1">brainpoolP384r1</option>
+                        <option value="brainpoolP512r1">brainpoolP512r1</option>
                       </select>
                     </div>
                   )}
@@ -1,5 +1,6 @@
 import { create } from 'zustand';
 import { invoke } from '@tauri-apps/api/core';
+import { toUserMessage } from '../api/errors';
 import type { DeviceInfo } from '../types';
 
 interface DeviceState {
@@ -36,7 +37,7 @@ export const useDeviceStore = create<DeviceState>((set, get) => ({
         selectedDevice: stillConnected ? current : null,
       });
     } catch (e) {
-      set({ error: String(e), loading: false });
+      set({ error: toUserMessage(e), loading: false });
     }
   },
 
@@ -55,7 +56,7 @@ export const useDeviceStore = create<DeviceState>((set, get) => ({
       await invoke('open_device', { path: device.path });
       set({ selectedDevice: device, loading: false });
     } catch (e) {
-      set({ error: String(e), loading: false });
+      set({ error: toUserMessage(e), loading: false });
     }
   },
 
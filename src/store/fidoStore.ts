@@ -1,5 +1,6 @@
 import { create } from 'zustand';
 import { invoke } from '@tauri-apps/api/core';
+import { errorText } from '../api/errors';
 import type { FidoDeviceInfo, FidoCredential, OathCredential } from '../types';
 
 interface FidoState {
@@ -9,7 +10,8 @@ interface FidoState {
   loading: boolean;
   error: string | null;
 
-  loadInfo: () => Promise<void>;
+  /** 讀取裝置資訊；後端有短期快取，`refresh` 為 true 時強制重新讀取 */
+  loadInfo: (refresh?: boolean) => Promise<void>;
   loadCredentials: (pin: string) => Promise<void>;
   deleteCredential: (pin: string, credentialId: number[]) => Promise<void>;
   loadOathCredentials: () => Promise<void>;
@@ -24,13 +26,13 @@ export const useFidoStore = create<FidoState>((set) => ({
   loading: false,
   error: null,
 
-  loadInfo: async () => {
+  loadInfo: async (refresh = false) => {
     set({ loading: true, error: null });
     try {
-      const info = await invoke<FidoDeviceInfo>('fido_get_info');
+      const info = await invoke<FidoDeviceInfo>('fido_get_info', { refresh });
       set({ info, loading: false });
     } catch (e) {
-      set({ error: String(e), loading: false });
+      set({ error: errorText(e), loading: false });
     }
   },
 
@@ -43,7 +45,7 @@ export const useFidoStore = create<FidoState>((set) => ({
       );
       set({ credentials, loading: false });
     } catch (e) {
-      set({ error: String(e), loading: false });
+      set({ error: errorText(e), loading: false });
     }
   },
 
@@ -58,7 +60,7 @@ export const useFidoStore = create<FidoState>((set) => ({
       );
       set({ credentials, loading: false });
     } catch (e) {
-      set({ error: String(e), loading: false });
+      set({ error: errorText(e), loading: false });
     }
   },
 
@@ -69,7 +71,7 @@ export const useFidoStore = create<FidoState>((set) => ({
         await invoke<OathCredential[]>('fido_list_oath');
       set({ oathCredentials, loading: false });
     } catch (e) {
-      set({ error: String(e), loading: false });
+      set({ error: errorText(e), loading: false });
     }
   },
 
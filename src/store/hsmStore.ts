@@ -1,5 +1,6 @@
 import { create } from 'zustand';
 import { invoke } from '@tauri-apps/api/core';
+import { errorText } from '../api/errors';
 import type {
   HsmDeviceInfo,
   HsmKeyInfo,
@@ -15,7 +16,8 @@ interface HsmState {
   loading: boolean;
   error: string | null;
 
-  loadInfo: () => Promise<void>;
+  /** 讀取裝置資訊；後端有短期快取，`refresh` 為 true 時強制重新讀取 */
+  loadInfo: (refresh?: boolean) => Promise<void>;
   loadKeys: (pin: string) => Promise<void>;
   loadCertificates: (pin: string) => Promise<void>;
   setDkekStatus: (status: DkekStatus | null) => void;
@@ -31,13 +33,13 @@ export const useHsmStore = create<HsmState>((set) => ({
   loading: false,
   error: null,
 
-  loadInfo: async () => {
+  loadInfo: async (refresh = false) => {
     set({ loading: true, error: null });
     try {
-      const info = await invoke<HsmDeviceInfo>('hsm_get_device_info');
+      const info = await invoke<HsmDeviceInfo>('hsm_get_device_info', { refresh });
       set({ info, loading: false });
     } catch (e) {
-      set({ error: String(e), loading: false });
+      set({ error: errorText(e), loading: false });
     }
   },
 
@@ -47,7 +49,7 @@ export const useHsmStore = create<HsmState>((set) => ({
       const keys = await invoke<HsmKeyInfo[]>('hsm_list_keys', { pin });
       set({ keys, loading: false });
     } catch (e) {
-      set({ error: String(e), loading: false });
+      set({ error: errorText(e), loading: false });
     }
   },
 
@@ -60,7 +62,7 @@ export const useHsmStore = create<HsmState>((set) => ({
       );
       set({ certificates, loading: false });
     } catch (e) {
-      set({ error: String(e), loading: false });
+      set({ error: errorText(e), loading: false });
     }
   },
 
@@ -11,6 +11,20 @@ export interface DeviceInfo {
   serial: string;
   firmwareVersion: string;
   path: string;
+  /** 掃描時順帶取得的能力摘要；無法取得時為 null */
+  capabilities?: DeviceCapabilities | null;
+}
+
+/** 裝置識別與能力摘要 */
+export interface DeviceCapabilities {
+  deviceType: 'PicoFido' | 'PicoHsm';
+  path: string;
+  firmwareVersion: string;
+  pinSet: boolean | null;
+  credMgmt: boolean | null;
+  initialized: boolean | null;
+  freeMemory: number | null;
+  features?: DeviceFeatures | null;
 }
 
 // === FIDO 相關 ===
@@ -20,11 +34,21 @@ export interface FidoDeviceInfo {
   versions: string[];
   extensions: string[];
   aaguid: string;
+  modelName?: string | null;
   firmwareVersion: string;
   serialNumber?: string;
   pinSet: boolean;
   pinRetries: number;
   options: Record<string, boolean>;
+  minPinLength?: number | null;
+  forcePinChange?: boolean;
+  pinUvAuthProtocols?: number[];
+}
+
+/** 目前的 PIN 最小長度設定 */
+export interface MinPinLengthInfo {
+  minPinLength: number;
+  forcePinChange: boolean;
 }
 
 /** FIDO 可發現憑證 */
@@ -35,6 +59,25 @@ export interface FidoCredential {
   userName?: string;
   userDisplayName?: string;
   creationTime?: number;
+  /** 憑證公鑰（未壓縮 P-256 點，僅 ES256 憑證提供） */
+  publicKey?: number[] | null;
+}
+
+/** 以既有憑證簽署一次 GetAssertion 並驗證簽章的結果 */
+export interface AssertionCheck {
+  rpId: string;
+  /** 憑證 ID（hex） */
+  credentialId: string;
+  signCount: number;
+  userVerified: boolean;
+  signatureValid: boolean;
+}
+
+/** 重設準備結果：確認碼需在有效期限內送回才會實際重設 */
+export interface ResetPreparation {
+  token: string;
+  credentialsToLose?: number | null;
+  expiresInSecs: number;
 }
 
 // === OATH 相關 ===
@@ -42,6 +85,9 @@ export interface FidoCredential {
 /** OATH 憑證類型 */
 export type OathType = 'Totp' | 'Hotp';
 
+/** OATH HMAC 演算法 */
+export type OathAlgorithm = 'Sha1' | 'Sha256' | 'Sha512';
+
 /** OATH 憑證資訊 */
 export interface OathCredential {
   id: string;
@@ -49,6 +95,14 @@ export interface OathCredential {
   account: string;
   oathType: OathType;
   period?: number;
+  digits: number;
+  algorithm: OathAlgorithm;
+}
+
+/** OATH 憑證槽使用量 */
+export interface OathSlotInfo {
+  used: number;
+  total: number;
 }
 
 /** OATH 憑證新增參數 */
@@ -72,6 +126,37 @@ export interface HsmDeviceInfo {
   usedMemory: number;
   totalMemory: number;
   fileCount: number;
+  /** DKEK 份額總數（未設定 DKEK 時為 null） */
+  dkekSharesTotal?: number | null;
+  /** 尚未匯入的 DKEK 份額數 */
+  dkekSharesMissing?: number | null;
+  /** SELECT FCI 回報的生命週期狀態（未提供時為 null） */
+  lifeCycle?: LifeCycleState | null;
+  /** SELECT FCI 回報的初始化選項 */
+  features?: DeviceFeatures;
+}
+
+/** ISO 7816-4 生命週期狀態（serde 列舉序列化格式） */
+export type LifeCycleState =
+  | 'Creation'
+  | 'Initialisation'
+  | 'Activated'
+  | 'Deactivated'
+  | 'Terminated'
+  | { Unknown: number };
+
+/** SELECT 選項欄解析出的裝置功能 */
+export interface DeviceFeatures {
+  resetRetryCounter: boolean;
+  transportPin: boolean;
+  sessionPin: boolean;
+  replacePka: boolean;
+  combinedAuth: boolean;
+  rrcResetOnly: boolean;
+  bootselButton: boolean;
+  keyCounterAll: boolean;
+  secureLock: boolean;
+  raw: number;
 }
 
 /** HSM 金鑰類型（serde 列舉序列化格式） */
@@ -100,6 +185,27 @@ export interface HsmCertInfo {
   keyId?: number;
 }
 
+// === HSM 物件列舉 ===
+
+export type ObjectClass =
+  | 'Key'
+  | 'KeyDescription'
+  | 'CertificateDescription'
+  | 'DataDescription'
+  | 'CaCertificate'
+  | 'Data'
+  | 'Certificate'
+  | 'ProtectedData'
+  | 'Unknown';
+
+/** 裝置上的單一物件；orphaned 表示所屬金鑰已不存在 */
+export interface ObjectRef {
+  fid: number;
+  id: number;
+  class: ObjectClass;
+  orphaned: boolean;
+}
+
 // === DKEK 備份相關 ===
 
 /** DKEK 份額狀態 */
@@ -110,6 +216,12 @@ export interface DkekStatus {
   keyCheckValue?: string;
 }
 
+/** `dkek-progress` 事件內容：目前 key domain 的份額匯入進度 */
+export interface DkekProgress {
+  importedThisSession: number;
+  status: DkekStatus;
+}
+
 // === HSM 裝置選項 ===
 
 /** HSM 裝置選項狀態 */
@@ -127,3 +239,25 @@ export interface LedConfig {
   dimmable?: boolean;
   color?: string;
 }
+
+// === 韌體版本（共用於 FIDO 與 HSM） ===
+
+/** 韌體版本（主版本.次版本） */
+export interface Version {
+  major: number;
+  minor: number;
+}
+
+/** 韌體是否達建議的最低版本 */
+export interface FirmwareStatus {
+  current: Version;
+  minimumRecommended: Version;
+  upToDate: boolean;
+}
+
+/** 連線測試結果（唯讀，不需要 PIN） */
+export interface PingResult {
+  reachable: boolean;
+  firmware: string | null;
+  error: string | null;
+}